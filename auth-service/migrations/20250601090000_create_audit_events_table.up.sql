@@ -0,0 +1,26 @@
+CREATE TABLE IF NOT EXISTS audit_events(
+   id UUID NOT NULL PRIMARY KEY,
+   event_type TEXT NOT NULL,
+   user_email TEXT,
+   actor TEXT NOT NULL,
+   ip_address TEXT,
+   user_agent TEXT,
+   outcome TEXT NOT NULL,
+   failure_reason TEXT,
+   request_id TEXT,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS audit_events_user_email_created_at_idx
+   ON audit_events (user_email, created_at);
+
+-- The audit log is append-only: reject any attempt to rewrite history
+CREATE OR REPLACE FUNCTION audit_events_reject_modification() RETURNS TRIGGER AS $$
+BEGIN
+   RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_append_only
+   BEFORE UPDATE OR DELETE ON audit_events
+   FOR EACH ROW EXECUTE FUNCTION audit_events_reject_modification();
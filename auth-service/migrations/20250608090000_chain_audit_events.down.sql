@@ -0,0 +1,4 @@
+ALTER TABLE audit_events
+   DROP COLUMN IF EXISTS sequence,
+   DROP COLUMN IF EXISTS prev_hash,
+   DROP COLUMN IF EXISTS hash;
\ No newline at end of file
@@ -0,0 +1,51 @@
+ALTER TABLE audit_events
+   ADD COLUMN sequence BIGINT,
+   ADD COLUMN prev_hash TEXT,
+   ADD COLUMN hash TEXT;
+
+-- Canonical encoding of a field, must match `AuditRecord::compute_hash`
+CREATE FUNCTION pg_temp.audit_field(value TEXT) RETURNS TEXT AS $$
+   SELECT COALESCE(octet_length(value)::TEXT || ':' || value, '-') || ';'
+$$ LANGUAGE SQL IMMUTABLE;
+
+-- Chain the events recorded so far, in the order they were written
+ALTER TABLE audit_events DISABLE TRIGGER audit_events_append_only;
+
+DO $$
+DECLARE
+   event RECORD;
+   current_sequence BIGINT := 0;
+   current_hash TEXT := repeat('0', 64);
+   next_hash TEXT;
+BEGIN
+   FOR event IN SELECT * FROM audit_events ORDER BY created_at, id LOOP
+      current_sequence := current_sequence + 1;
+      next_hash := encode(sha256(convert_to(
+         pg_temp.audit_field(current_hash)
+         || pg_temp.audit_field(current_sequence::TEXT)
+         || pg_temp.audit_field(event.id::TEXT)
+         || pg_temp.audit_field(event.event_type)
+         || pg_temp.audit_field(event.user_email)
+         || pg_temp.audit_field(event.actor)
+         || pg_temp.audit_field(event.ip_address)
+         || pg_temp.audit_field(event.user_agent)
+         || pg_temp.audit_field(event.outcome)
+         || pg_temp.audit_field(event.failure_reason)
+         || pg_temp.audit_field(event.request_id)
+         || pg_temp.audit_field((EXTRACT(EPOCH FROM event.created_at) * 1000000)::BIGINT::TEXT),
+         'UTF8')), 'hex');
+      UPDATE audit_events
+         SET sequence = current_sequence, prev_hash = current_hash, hash = next_hash
+         WHERE id = event.id;
+      current_hash := next_hash;
+   END LOOP;
+END;
+$$;
+
+ALTER TABLE audit_events ENABLE TRIGGER audit_events_append_only;
+
+ALTER TABLE audit_events
+   ALTER COLUMN sequence SET NOT NULL,
+   ALTER COLUMN prev_hash SET NOT NULL,
+   ALTER COLUMN hash SET NOT NULL,
+   ADD CONSTRAINT audit_events_sequence_key UNIQUE (sequence);
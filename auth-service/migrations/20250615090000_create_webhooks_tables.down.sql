@@ -0,0 +1,5 @@
+DROP TRIGGER IF EXISTS users_webhook_events ON users;
+DROP FUNCTION IF EXISTS users_enqueue_webhook_event();
+DROP FUNCTION IF EXISTS enqueue_webhook_event(TEXT, JSONB);
+DROP TABLE IF EXISTS webhook_deliveries;
+DROP TABLE IF EXISTS webhook_subscriptions;
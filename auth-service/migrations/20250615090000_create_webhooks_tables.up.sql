@@ -0,0 +1,67 @@
+CREATE TABLE IF NOT EXISTS webhook_subscriptions(
+   id UUID NOT NULL PRIMARY KEY,
+   url TEXT NOT NULL,
+   secret TEXT NOT NULL,
+   event_types TEXT[] NOT NULL,
+   active BOOLEAN NOT NULL DEFAULT TRUE,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Outbox of the events to deliver, one row per event and subscription
+CREATE TABLE IF NOT EXISTS webhook_deliveries(
+   id UUID NOT NULL PRIMARY KEY,
+   subscription_id UUID NOT NULL REFERENCES webhook_subscriptions(id) ON DELETE CASCADE,
+   event_id UUID NOT NULL,
+   event_type TEXT NOT NULL,
+   data JSONB NOT NULL,
+   occurred_at TIMESTAMPTZ NOT NULL,
+   status TEXT NOT NULL DEFAULT 'pending',
+   attempts INTEGER NOT NULL DEFAULT 0,
+   next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   last_attempt_at TIMESTAMPTZ,
+   last_response_status INTEGER,
+   last_error TEXT,
+   delivered_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS webhook_deliveries_due_idx
+   ON webhook_deliveries (next_attempt_at)
+   WHERE status = 'pending';
+CREATE INDEX IF NOT EXISTS webhook_deliveries_subscription_idx
+   ON webhook_deliveries (subscription_id, occurred_at);
+
+-- Queues an event for every active subscription interested in it
+CREATE OR REPLACE FUNCTION enqueue_webhook_event(p_event_type TEXT, p_data JSONB)
+RETURNS VOID AS $$
+DECLARE
+   v_event_id UUID := gen_random_uuid();
+   v_occurred_at TIMESTAMPTZ := NOW();
+BEGIN
+   INSERT INTO webhook_deliveries (id, subscription_id, event_id, event_type, data, occurred_at, next_attempt_at)
+   SELECT gen_random_uuid(), s.id, v_event_id, p_event_type, p_data, v_occurred_at, v_occurred_at
+   FROM webhook_subscriptions s
+   WHERE s.active AND p_event_type = ANY(s.event_types);
+END;
+$$ LANGUAGE plpgsql;
+
+-- Account lifecycle events are queued by the transaction changing the account,
+-- so that an event is delivered if and only if the change is committed
+CREATE OR REPLACE FUNCTION users_enqueue_webhook_event()
+RETURNS TRIGGER AS $$
+BEGIN
+   IF TG_OP = 'INSERT' THEN
+      PERFORM enqueue_webhook_event('user.created', jsonb_build_object('email', NEW.email));
+   ELSIF TG_OP = 'UPDATE' THEN
+      IF NEW.password_hash IS DISTINCT FROM OLD.password_hash THEN
+         PERFORM enqueue_webhook_event('user.password_changed', jsonb_build_object('email', NEW.email));
+      END IF;
+   ELSIF TG_OP = 'DELETE' THEN
+      PERFORM enqueue_webhook_event('user.deleted', jsonb_build_object('email', OLD.email));
+   END IF;
+   RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER users_webhook_events
+AFTER INSERT OR UPDATE OR DELETE ON users
+FOR EACH ROW EXECUTE FUNCTION users_enqueue_webhook_event();
@@ -0,0 +1,18 @@
+-- Emails are persisted here and sent in the background, so that a provider outage does not fail requests
+CREATE TABLE IF NOT EXISTS email_outbox(
+   id UUID NOT NULL PRIMARY KEY,
+   recipient TEXT NOT NULL,
+   subject TEXT NOT NULL,
+   content TEXT NOT NULL,
+   status TEXT NOT NULL DEFAULT 'pending',
+   attempts INTEGER NOT NULL DEFAULT 0,
+   next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   last_attempt_at TIMESTAMPTZ,
+   last_error TEXT,
+   sent_at TIMESTAMPTZ,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS email_outbox_due_idx
+   ON email_outbox (next_attempt_at)
+   WHERE status = 'pending';
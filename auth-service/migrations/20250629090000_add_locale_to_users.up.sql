@@ -0,0 +1,2 @@
+-- Preferred language of the emails sent to the user, as a BCP 47 tag. NULL uses the default language
+ALTER TABLE users ADD COLUMN IF NOT EXISTS locale TEXT;
@@ -0,0 +1,4 @@
+-- Verified phone number in E.164 format, and the channel the 2FA codes are sent to
+ALTER TABLE users ADD COLUMN IF NOT EXISTS phone_number TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS two_fa_channel TEXT NOT NULL DEFAULT 'email'
+   CHECK (two_fa_channel IN ('email', 'sms'));
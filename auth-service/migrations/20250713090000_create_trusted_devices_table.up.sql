@@ -0,0 +1,10 @@
+-- Browsers remembered at the end of a 2FA login, see `TrustedDevice`
+CREATE TABLE IF NOT EXISTS trusted_devices(
+   id UUID NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   user_agent TEXT,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS trusted_devices_email_idx ON trusted_devices (email);
@@ -0,0 +1,13 @@
+-- Browsers and networks the users logged in from, see `KnownDevice`.
+-- A new device keeps the session it opened until the "this wasn't me" link of its alert is used.
+CREATE TABLE IF NOT EXISTS known_devices(
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   fingerprint TEXT NOT NULL,
+   user_agent TEXT,
+   ip_prefix TEXT,
+   first_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   last_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   revocation_token_hash TEXT UNIQUE,
+   session_token_hash TEXT,
+   PRIMARY KEY (email, fingerprint)
+);
@@ -0,0 +1,12 @@
+-- Existing passwords are considered changed when the expiry policy was introduced
+ALTER TABLE users ADD COLUMN IF NOT EXISTS password_changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+-- Previous password hashes of the users, a new password must differ from the last ones
+CREATE TABLE IF NOT EXISTS password_history(
+   id BIGSERIAL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   password_hash TEXT NOT NULL,
+   replaced_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS password_history_email_idx ON password_history (email, id DESC);
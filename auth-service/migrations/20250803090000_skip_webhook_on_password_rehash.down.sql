@@ -0,0 +1,15 @@
+CREATE OR REPLACE FUNCTION users_enqueue_webhook_event()
+RETURNS TRIGGER AS $$
+BEGIN
+   IF TG_OP = 'INSERT' THEN
+      PERFORM enqueue_webhook_event('user.created', jsonb_build_object('email', NEW.email));
+   ELSIF TG_OP = 'UPDATE' THEN
+      IF NEW.password_hash IS DISTINCT FROM OLD.password_hash THEN
+         PERFORM enqueue_webhook_event('user.password_changed', jsonb_build_object('email', NEW.email));
+      END IF;
+   ELSIF TG_OP = 'DELETE' THEN
+      PERFORM enqueue_webhook_event('user.deleted', jsonb_build_object('email', OLD.email));
+   END IF;
+   RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,18 @@
+-- Upgrading the hash of the same password is not a password change: the transaction
+-- rehashing it sets `auth.password_rehash` so that `user.password_changed` is not sent
+CREATE OR REPLACE FUNCTION users_enqueue_webhook_event()
+RETURNS TRIGGER AS $$
+BEGIN
+   IF TG_OP = 'INSERT' THEN
+      PERFORM enqueue_webhook_event('user.created', jsonb_build_object('email', NEW.email));
+   ELSIF TG_OP = 'UPDATE' THEN
+      IF NEW.password_hash IS DISTINCT FROM OLD.password_hash
+         AND current_setting('auth.password_rehash', true) IS DISTINCT FROM 'on' THEN
+         PERFORM enqueue_webhook_event('user.password_changed', jsonb_build_object('email', NEW.email));
+      END IF;
+   ELSIF TG_OP = 'DELETE' THEN
+      PERFORM enqueue_webhook_event('user.deleted', jsonb_build_object('email', OLD.email));
+   END IF;
+   RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,16 @@
+-- The emails stay normalised, their original case is not kept
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_email_normalized;
+DROP TABLE IF EXISTS email_collisions;
+
+ALTER TABLE password_history
+   DROP CONSTRAINT password_history_email_fkey,
+   ADD CONSTRAINT password_history_email_fkey FOREIGN KEY (email)
+      REFERENCES users(email) ON DELETE CASCADE;
+ALTER TABLE known_devices
+   DROP CONSTRAINT known_devices_email_fkey,
+   ADD CONSTRAINT known_devices_email_fkey FOREIGN KEY (email)
+      REFERENCES users(email) ON DELETE CASCADE;
+ALTER TABLE trusted_devices
+   DROP CONSTRAINT trusted_devices_email_fkey,
+   ADD CONSTRAINT trusted_devices_email_fkey FOREIGN KEY (email)
+      REFERENCES users(email) ON DELETE CASCADE;
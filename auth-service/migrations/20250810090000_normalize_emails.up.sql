@@ -0,0 +1,55 @@
+-- Emails identify users in their canonical form, lowercased and in Unicode NFC, see `Email::parse`.
+-- Only ASCII addresses were accepted so far: they are already in NFC, and lower() matches the
+-- lowercasing of the service. normalize() would also require a UTF8 server encoding.
+
+-- Normalised emails carry over to the devices and password history of their user
+ALTER TABLE trusted_devices
+   DROP CONSTRAINT trusted_devices_email_fkey,
+   ADD CONSTRAINT trusted_devices_email_fkey FOREIGN KEY (email)
+      REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE known_devices
+   DROP CONSTRAINT known_devices_email_fkey,
+   ADD CONSTRAINT known_devices_email_fkey FOREIGN KEY (email)
+      REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE password_history
+   DROP CONSTRAINT password_history_email_fkey,
+   ADD CONSTRAINT password_history_email_fkey FOREIGN KEY (email)
+      REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+
+-- Accounts whose emails only differ by case cannot be merged automatically.
+-- They are left as they are and listed here until resolved, e.g. by deleting the unwanted ones.
+CREATE TABLE IF NOT EXISTS email_collisions(
+   email TEXT NOT NULL PRIMARY KEY,
+   normalized_email TEXT NOT NULL,
+   detected_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO email_collisions (email, normalized_email)
+SELECT email, lower(email)
+FROM users
+WHERE lower(email) IN (
+   SELECT lower(email)
+   FROM users
+   GROUP BY 1
+   HAVING COUNT(*) > 1
+)
+ON CONFLICT (email) DO NOTHING;
+
+DO $$
+DECLARE
+   collisions BIGINT;
+BEGIN
+   SELECT COUNT(DISTINCT normalized_email) INTO collisions FROM email_collisions;
+   IF collisions > 0 THEN
+      RAISE WARNING '% normalised emails are shared by several accounts, see the email_collisions table', collisions;
+   END IF;
+END $$;
+
+UPDATE users
+SET email = lower(email)
+WHERE email <> lower(email)
+  AND email NOT IN (SELECT email FROM email_collisions);
+
+-- Not checked on the colliding emails until they are resolved
+ALTER TABLE users
+   ADD CONSTRAINT users_email_normalized CHECK (email = lower(email)) NOT VALID;
@@ -0,0 +1,36 @@
+CREATE OR REPLACE FUNCTION users_enqueue_webhook_event()
+RETURNS TRIGGER AS $$
+BEGIN
+   IF TG_OP = 'INSERT' THEN
+      PERFORM enqueue_webhook_event('user.created', jsonb_build_object('email', NEW.email));
+   ELSIF TG_OP = 'UPDATE' THEN
+      IF NEW.password_hash IS DISTINCT FROM OLD.password_hash
+         AND current_setting('auth.password_rehash', true) IS DISTINCT FROM 'on' THEN
+         PERFORM enqueue_webhook_event('user.password_changed', jsonb_build_object('email', NEW.email));
+      END IF;
+   ELSIF TG_OP = 'DELETE' THEN
+      PERFORM enqueue_webhook_event('user.deleted', jsonb_build_object('email', OLD.email));
+   END IF;
+   RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE trusted_devices DROP CONSTRAINT trusted_devices_email_fkey;
+ALTER TABLE known_devices DROP CONSTRAINT known_devices_email_fkey;
+ALTER TABLE password_history DROP CONSTRAINT password_history_email_fkey;
+
+ALTER TABLE users
+   DROP CONSTRAINT users_pkey,
+   DROP CONSTRAINT users_email_key,
+   ADD PRIMARY KEY (email);
+ALTER TABLE users DROP COLUMN id;
+
+ALTER TABLE trusted_devices
+   ADD CONSTRAINT trusted_devices_email_fkey FOREIGN KEY (email)
+      REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE known_devices
+   ADD CONSTRAINT known_devices_email_fkey FOREIGN KEY (email)
+      REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE password_history
+   ADD CONSTRAINT password_history_email_fkey FOREIGN KEY (email)
+      REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
@@ -0,0 +1,47 @@
+-- Users are identified by an immutable id rather than by their email, which they can change
+-- and which no longer ends up in every JWT, see `UserId`
+-- The default fills the existing rows: updating them would fail on the unresolved email collisions,
+-- whose check constraint is enforced at every update
+ALTER TABLE users ADD COLUMN id UUID NOT NULL DEFAULT gen_random_uuid();
+
+-- The devices and password history keep referencing the email, which stays unique,
+-- but their foreign keys depend on the primary key being replaced
+ALTER TABLE trusted_devices DROP CONSTRAINT trusted_devices_email_fkey;
+ALTER TABLE known_devices DROP CONSTRAINT known_devices_email_fkey;
+ALTER TABLE password_history DROP CONSTRAINT password_history_email_fkey;
+
+ALTER TABLE users
+   DROP CONSTRAINT users_pkey,
+   ADD CONSTRAINT users_email_key UNIQUE (email),
+   ADD PRIMARY KEY (id);
+
+ALTER TABLE trusted_devices
+   ADD CONSTRAINT trusted_devices_email_fkey FOREIGN KEY (email)
+      REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE known_devices
+   ADD CONSTRAINT known_devices_email_fkey FOREIGN KEY (email)
+      REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE password_history
+   ADD CONSTRAINT password_history_email_fkey FOREIGN KEY (email)
+      REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+
+-- Webhook consumers can also follow a user across email changes
+CREATE OR REPLACE FUNCTION users_enqueue_webhook_event()
+RETURNS TRIGGER AS $$
+BEGIN
+   IF TG_OP = 'INSERT' THEN
+      PERFORM enqueue_webhook_event('user.created',
+         jsonb_build_object('user_id', NEW.id, 'email', NEW.email));
+   ELSIF TG_OP = 'UPDATE' THEN
+      IF NEW.password_hash IS DISTINCT FROM OLD.password_hash
+         AND current_setting('auth.password_rehash', true) IS DISTINCT FROM 'on' THEN
+         PERFORM enqueue_webhook_event('user.password_changed',
+            jsonb_build_object('user_id', NEW.id, 'email', NEW.email));
+      END IF;
+   ELSIF TG_OP = 'DELETE' THEN
+      PERFORM enqueue_webhook_event('user.deleted',
+         jsonb_build_object('user_id', OLD.id, 'email', OLD.email));
+   END IF;
+   RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,19 @@
+-- Email changes requested by the users, see `EmailChange`
+CREATE TABLE IF NOT EXISTS email_changes(
+   id UUID NOT NULL PRIMARY KEY,
+   user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+   old_email TEXT NOT NULL,
+   new_email TEXT NOT NULL,
+   confirm_token_hash TEXT NOT NULL UNIQUE,
+   revert_token_hash TEXT NOT NULL UNIQUE,
+   requested_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   confirm_expires_at TIMESTAMPTZ NOT NULL,
+   revert_expires_at TIMESTAMPTZ NOT NULL,
+   confirmed_at TIMESTAMPTZ,
+   reverted_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS email_changes_user_id_idx ON email_changes (user_id);
+
+-- Incremented to revoke all the JWTs of the user, e.g. when an email change is reverted
+ALTER TABLE users ADD COLUMN IF NOT EXISTS session_version INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,6 @@
+-- Whether the user can log in, see `AccountStatus`. Only a suspension has an end
+ALTER TABLE users ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'active'
+   CHECK (status IN ('active', 'disabled', 'suspended', 'pending_verification'));
+ALTER TABLE users ADD COLUMN IF NOT EXISTS suspended_until TIMESTAMPTZ;
+ALTER TABLE users ADD CONSTRAINT users_suspended_until
+   CHECK ((status = 'suspended') = (suspended_until IS NOT NULL));
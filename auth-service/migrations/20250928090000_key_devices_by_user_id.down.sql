@@ -0,0 +1,17 @@
+ALTER TABLE trusted_devices ADD COLUMN email TEXT REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+UPDATE trusted_devices SET email = users.email FROM users WHERE users.id = trusted_devices.user_id;
+ALTER TABLE trusted_devices ALTER COLUMN email SET NOT NULL;
+ALTER TABLE trusted_devices DROP COLUMN user_id;
+CREATE INDEX IF NOT EXISTS trusted_devices_email_idx ON trusted_devices (email);
+
+ALTER TABLE known_devices ADD COLUMN email TEXT REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+UPDATE known_devices SET email = users.email FROM users WHERE users.id = known_devices.user_id;
+ALTER TABLE known_devices ALTER COLUMN email SET NOT NULL;
+ALTER TABLE known_devices DROP COLUMN user_id;
+ALTER TABLE known_devices ADD PRIMARY KEY (email, fingerprint);
+
+ALTER TABLE password_history ADD COLUMN email TEXT REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+UPDATE password_history SET email = users.email FROM users WHERE users.id = password_history.user_id;
+ALTER TABLE password_history ALTER COLUMN email SET NOT NULL;
+ALTER TABLE password_history DROP COLUMN user_id;
+CREATE INDEX IF NOT EXISTS password_history_email_idx ON password_history (email, id DESC);
@@ -0,0 +1,18 @@
+-- The devices and password history reference the id of their user, so they follow them across email changes
+ALTER TABLE trusted_devices ADD COLUMN user_id UUID REFERENCES users(id) ON DELETE CASCADE;
+UPDATE trusted_devices SET user_id = users.id FROM users WHERE users.email = trusted_devices.email;
+ALTER TABLE trusted_devices ALTER COLUMN user_id SET NOT NULL;
+ALTER TABLE trusted_devices DROP COLUMN email;
+CREATE INDEX IF NOT EXISTS trusted_devices_user_id_idx ON trusted_devices (user_id);
+
+ALTER TABLE known_devices ADD COLUMN user_id UUID REFERENCES users(id) ON DELETE CASCADE;
+UPDATE known_devices SET user_id = users.id FROM users WHERE users.email = known_devices.email;
+ALTER TABLE known_devices ALTER COLUMN user_id SET NOT NULL;
+ALTER TABLE known_devices DROP COLUMN email;
+ALTER TABLE known_devices ADD PRIMARY KEY (user_id, fingerprint);
+
+ALTER TABLE password_history ADD COLUMN user_id UUID REFERENCES users(id) ON DELETE CASCADE;
+UPDATE password_history SET user_id = users.id FROM users WHERE users.email = password_history.email;
+ALTER TABLE password_history ALTER COLUMN user_id SET NOT NULL;
+ALTER TABLE password_history DROP COLUMN email;
+CREATE INDEX IF NOT EXISTS password_history_user_id_idx ON password_history (user_id, id DESC);
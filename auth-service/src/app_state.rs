@@ -1,23 +1,78 @@
+use crate::domain::audit::AuditEvent;
+use crate::domain::data_stores::AuditEventStore;
 use crate::domain::data_stores::BannedTokenStore;
+use crate::domain::data_stores::EmailChangeStore;
+use crate::domain::data_stores::EmailOutboxStore;
+use crate::domain::data_stores::KnownDeviceStore;
+use crate::domain::data_stores::PhoneVerificationStore;
+use crate::domain::data_stores::ResendPolicy;
+use crate::domain::data_stores::TrustedDeviceStore;
 use crate::domain::data_stores::TwoFACodeStore;
 use crate::domain::data_stores::UserStore;
+use crate::domain::data_stores::WebhookStore;
+use crate::domain::delivery::RetryPolicy;
+use crate::domain::email_change::EmailChangePolicy;
+use crate::domain::password::{CharacterClass, PasswordPolicy};
 use crate::domain::EmailClient;
+use crate::domain::SmsClient;
 use crate::get_postgres_pool;
+use crate::services::breached_passwords::BreachedPasswords;
+use crate::services::data_stores::hashmap_email_change_store::HashmapEmailChangeStore;
+use crate::services::data_stores::hashmap_known_device_store::HashmapKnownDeviceStore;
+use crate::services::data_stores::hashmap_phone_verification_store::HashmapPhoneVerificationStore;
+use crate::services::data_stores::hashmap_trusted_device_store::HashmapTrustedDeviceStore;
 use crate::services::data_stores::hashmap_two_fa_code_store::HashmapTwoFACodeStore;
 use crate::services::data_stores::hashmap_user_store::HashmapUserStore;
+use crate::services::data_stores::hashmap_webhook_store::HashmapWebhookStore;
 use crate::services::data_stores::hashset_banned_token_store::HashsetBannedTokenStore;
+use crate::services::data_stores::postgres_audit_event_store::PostgresAuditEventStore;
+use crate::services::data_stores::postgres_email_change_store::PostgresEmailChangeStore;
+use crate::services::data_stores::postgres_email_outbox_store::PostgresEmailOutboxStore;
+use crate::services::data_stores::postgres_known_device_store::PostgresKnownDeviceStore;
+use crate::services::data_stores::postgres_trusted_device_store::PostgresTrustedDeviceStore;
+use crate::services::data_stores::postgres_webhook_store::PostgresWebhookStore;
+use crate::services::data_stores::redis_phone_verification_store::RedisPhoneVerificationStore;
 use crate::services::data_stores::redis_two_fa_code_store::RedisTwoFACodeStore;
+use crate::services::data_stores::vec_audit_event_store::VecAuditEventStore;
+use crate::services::data_stores::vec_email_outbox_store::VecEmailOutboxStore;
+use crate::services::email_clients::file_email_client::FileEmailClient;
+use crate::services::email_clients::outbox_email_client::OutboxEmailClient;
 use crate::services::email_clients::postmark_email_client::PostmarkEmailClient;
+use crate::services::email_clients::smtp_email_client::SmtpEmailClient;
+use crate::services::email_dispatcher::EmailDispatcher;
+use crate::services::email_domain_policy::{DomainListFile, EmailDomainPolicy};
+use crate::services::password_hashing::Peppers;
+use crate::services::sms_clients::http_sms_client::HttpSmsClient;
+use crate::services::sms_clients::mock_sms_client::MockSmsClient;
+use crate::services::webhook_dispatcher::WebhookDispatcher;
 use crate::utils::constants::prod;
+use crate::utils::constants::BREACHED_PASSWORDS_DIR;
 use crate::utils::constants::DATABASE_URL;
+use crate::utils::constants::MAILBOX_PATH;
+use crate::utils::constants::PASSWORD_PEPPERS;
 use crate::utils::constants::POSTMARK_AUTH_TOKEN;
 use crate::utils::constants::REDIS_HOST_NAME;
+use crate::utils::constants::SMTP_URL;
+use crate::utils::constants::{ARGON2_ITERATIONS, ARGON2_MEMORY_KIB, ARGON2_PARALLELISM};
+use crate::utils::constants::{
+    EMAIL_DOMAIN_2FA_FILE, EMAIL_DOMAIN_ALLOWLIST_FILE, EMAIL_DOMAIN_BLOCKLIST_FILE,
+};
+use crate::utils::constants::{
+    EMAIL_PROVIDER, FILE_EMAIL_PROVIDER, POSTMARK_EMAIL_PROVIDER, SMTP_EMAIL_PROVIDER,
+};
+use crate::utils::constants::{HTTP_SMS_PROVIDER, MOCK_SMS_PROVIDER, SMS_PROVIDER};
+use crate::utils::constants::{
+    PASSWORD_HISTORY_SIZE, PASSWORD_MAX_AGE_DAYS, PASSWORD_MAX_LENGTH, PASSWORD_MIN_ENTROPY_BITS,
+    PASSWORD_MIN_LENGTH, PASSWORD_REQUIRED_CHARACTERS,
+};
+use crate::utils::constants::{SMS_API_TOKEN, SMS_API_URL, SMS_SENDER};
 use crate::Email;
 use crate::PostgresUserStore;
 use crate::RedisBannedTokenStore;
 use reqwest::Client;
 use sqlx::PgPool;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
 // Using a type alias to improve readability!
@@ -25,6 +80,14 @@ pub type UserStoreType = Arc<RwLock<dyn UserStore>>;
 pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore>>;
 pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore>>;
 pub type EmailClientType = Arc<RwLock<dyn EmailClient>>;
+pub type AuditEventStoreType = Arc<RwLock<dyn AuditEventStore>>;
+pub type WebhookStoreType = Arc<RwLock<dyn WebhookStore>>;
+pub type EmailOutboxStoreType = Arc<RwLock<dyn EmailOutboxStore>>;
+pub type SmsClientType = Arc<RwLock<dyn SmsClient>>;
+pub type PhoneVerificationStoreType = Arc<RwLock<dyn PhoneVerificationStore>>;
+pub type TrustedDeviceStoreType = Arc<RwLock<dyn TrustedDeviceStore>>;
+pub type KnownDeviceStoreType = Arc<RwLock<dyn KnownDeviceStore>>;
+pub type EmailChangeStoreType = Arc<RwLock<dyn EmailChangeStore>>;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -32,40 +95,134 @@ pub struct AppState {
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
     pub email_client: EmailClientType,
+    pub audit_event_store: AuditEventStoreType,
+    pub webhook_store: WebhookStoreType,
+    pub email_outbox_store: EmailOutboxStoreType,
+    pub sms_client: SmsClientType,
+    pub phone_verification_store: PhoneVerificationStoreType,
+    pub trusted_device_store: TrustedDeviceStoreType,
+    pub known_device_store: KnownDeviceStoreType,
+    pub email_change_store: EmailChangeStoreType,
+    /// How often the 2FA code of a login attempt can be sent again
+    pub two_fa_resend_policy: ResendPolicy,
+    /// How long a remembered device skips 2FA
+    pub trusted_device_ttl: Duration,
+    /// How long the links of an email change are valid
+    pub email_change_policy: EmailChangePolicy,
+    /// Requirements of the passwords chosen at signup
+    pub password_policy: PasswordPolicy,
+    /// Corpus new passwords are checked against, only set when `BREACHED_PASSWORDS_DIR` is
+    pub breached_passwords: Option<BreachedPasswords>,
+    /// Domains that can sign up, and those requiring 2FA
+    pub email_domain_policy: EmailDomainPolicy,
+    /// Local mailbox browsable at `/dev/mailbox`, only set with the file email provider
+    pub dev_mailbox: Option<Arc<FileEmailClient>>,
 }
 
 impl AppState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         user_store: UserStoreType,
         banned_token_store: BannedTokenStoreType,
         two_fa_code_store: TwoFACodeStoreType,
         email_client: EmailClientType,
+        audit_event_store: AuditEventStoreType,
+        webhook_store: WebhookStoreType,
+        email_outbox_store: EmailOutboxStoreType,
+        sms_client: SmsClientType,
+        phone_verification_store: PhoneVerificationStoreType,
+        trusted_device_store: TrustedDeviceStoreType,
+        known_device_store: KnownDeviceStoreType,
+        email_change_store: EmailChangeStoreType,
     ) -> Self {
         Self {
             user_store,
             banned_token_store,
             two_fa_code_store,
             email_client,
+            audit_event_store,
+            webhook_store,
+            email_outbox_store,
+            sms_client,
+            phone_verification_store,
+            trusted_device_store,
+            known_device_store,
+            email_change_store,
+            two_fa_resend_policy: two_fa_resend_policy(),
+            trusted_device_ttl: prod::trusted_devices::TTL,
+            email_change_policy: email_change_policy(),
+            password_policy: password_policy(),
+            breached_passwords: configure_breached_passwords(),
+            email_domain_policy: configure_email_domain_policy(),
+            dev_mailbox: None,
         }
     }
 
     /// Creates a new AppState with a PostgreSQL user store and a Redis banned token store / two fa code store.
     pub async fn new_ps_redis() -> Self {
         let pg_pool = configure_postgresql().await;
-        let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
+        let user_store = Arc::new(RwLock::new(PostgresUserStore::new(
+            pg_pool.clone(),
+            password_hash_params(),
+            password_peppers(),
+        )));
         let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(Arc::new(
             RwLock::new(configure_redis()),
         ))));
         let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(Arc::new(
             RwLock::new(configure_redis()),
         ))));
-        let email_client = Arc::new(RwLock::new(configure_postmark_email_client()));
+        let phone_verification_store = Arc::new(RwLock::new(RedisPhoneVerificationStore::new(
+            Arc::new(RwLock::new(configure_redis())),
+        )));
+        let audit_event_store =
+            Arc::new(RwLock::new(PostgresAuditEventStore::new(pg_pool.clone())));
+        let webhook_store = Arc::new(RwLock::new(PostgresWebhookStore::new(pg_pool.clone())));
+        let trusted_device_store = Arc::new(RwLock::new(PostgresTrustedDeviceStore::new(
+            pg_pool.clone(),
+        )));
+        let known_device_store =
+            Arc::new(RwLock::new(PostgresKnownDeviceStore::new(pg_pool.clone())));
+        let email_change_store =
+            Arc::new(RwLock::new(PostgresEmailChangeStore::new(pg_pool.clone())));
+        let email_outbox_store: EmailOutboxStoreType =
+            Arc::new(RwLock::new(PostgresEmailOutboxStore::new(pg_pool)));
+        // Emails are sent in the background by the dispatcher, see `configure_email_dispatcher`
+        let email_client = Arc::new(RwLock::new(OutboxEmailClient::new(
+            email_outbox_store.clone(),
+        )));
+        let dev_mailbox = (EMAIL_PROVIDER.as_str() == FILE_EMAIL_PROVIDER)
+            .then(|| Arc::new(FileEmailClient::new(MAILBOX_PATH.as_str())));
 
         Self {
             user_store,
             banned_token_store,
             two_fa_code_store,
             email_client,
+            audit_event_store,
+            webhook_store,
+            email_outbox_store,
+            sms_client: configure_sms_client(),
+            phone_verification_store,
+            trusted_device_store,
+            known_device_store,
+            email_change_store,
+            two_fa_resend_policy: two_fa_resend_policy(),
+            trusted_device_ttl: prod::trusted_devices::TTL,
+            email_change_policy: email_change_policy(),
+            password_policy: password_policy(),
+            breached_passwords: configure_breached_passwords(),
+            email_domain_policy: configure_email_domain_policy(),
+            dev_mailbox,
+        }
+    }
+
+    /// Appends an event to the audit log.
+    /// Failing to record an event is logged but never fails the request being audited.
+    pub async fn record_audit_event(&self, event: AuditEvent) {
+        let result = self.audit_event_store.write().await.add_event(event).await;
+        if let Err(e) = result {
+            tracing::error!(error = ?e, "Failed to record audit event");
         }
     }
 }
@@ -77,8 +234,25 @@ impl Default for AppState {
             banned_token_store: Arc::new(RwLock::new(HashsetBannedTokenStore::default())),
             two_fa_code_store: Arc::new(RwLock::new(HashmapTwoFACodeStore::default())),
             email_client: Arc::new(RwLock::new(
-                crate::services::email_clients::mock_email_client::MockEmailClient::default(),
+                crate::services::email_clients::mock_email_client::MockEmailClient,
             )),
+            audit_event_store: Arc::new(RwLock::new(VecAuditEventStore::default())),
+            webhook_store: Arc::new(RwLock::new(HashmapWebhookStore::default())),
+            email_outbox_store: Arc::new(RwLock::new(VecEmailOutboxStore::default())),
+            sms_client: Arc::new(RwLock::new(MockSmsClient)),
+            phone_verification_store: Arc::new(RwLock::new(
+                HashmapPhoneVerificationStore::default(),
+            )),
+            trusted_device_store: Arc::new(RwLock::new(HashmapTrustedDeviceStore::default())),
+            known_device_store: Arc::new(RwLock::new(HashmapKnownDeviceStore::default())),
+            email_change_store: Arc::new(RwLock::new(HashmapEmailChangeStore::default())),
+            two_fa_resend_policy: two_fa_resend_policy(),
+            trusted_device_ttl: prod::trusted_devices::TTL,
+            email_change_policy: email_change_policy(),
+            password_policy: password_policy(),
+            breached_passwords: configure_breached_passwords(),
+            email_domain_policy: configure_email_domain_policy(),
+            dev_mailbox: None,
         }
     }
 }
@@ -105,6 +279,26 @@ fn configure_redis() -> redis::Connection {
         .expect("Failed to get Redis connection")
 }
 
+// The email provider is selected with the EMAIL_PROVIDER environment variable
+fn configure_email_client() -> EmailClientType {
+    match EMAIL_PROVIDER.as_str() {
+        POSTMARK_EMAIL_PROVIDER => Arc::new(RwLock::new(configure_postmark_email_client())),
+        SMTP_EMAIL_PROVIDER => Arc::new(RwLock::new(configure_smtp_email_client())),
+        FILE_EMAIL_PROVIDER => Arc::new(RwLock::new(FileEmailClient::new(MAILBOX_PATH.as_str()))),
+        provider => panic!("Unknown email provider: {}", provider),
+    }
+}
+
+fn configure_smtp_email_client() -> SmtpEmailClient {
+    SmtpEmailClient::new(
+        &SMTP_URL,
+        Email::parse(prod::email_client::SENDER).unwrap(),
+        prod::smtp::POOL_SIZE,
+        prod::smtp::TIMEOUT,
+    )
+    .expect("Failed to configure SMTP email client")
+}
+
 fn configure_postmark_email_client() -> PostmarkEmailClient {
     let http_client = Client::builder()
         .timeout(prod::email_client::TIMEOUT)
@@ -113,8 +307,155 @@ fn configure_postmark_email_client() -> PostmarkEmailClient {
 
     PostmarkEmailClient::new(
         prod::email_client::BASE_URL.to_owned(),
-        Email::parse(&prod::email_client::SENDER.to_owned()).unwrap(),
+        Email::parse(prod::email_client::SENDER).unwrap(),
         POSTMARK_AUTH_TOKEN.to_owned(),
         http_client,
     )
 }
+
+// The SMS provider is selected with the SMS_PROVIDER environment variable.
+// With the mock provider no text message is sent, so no phone number can be verified and every code goes by email
+fn configure_sms_client() -> SmsClientType {
+    match SMS_PROVIDER.as_str() {
+        MOCK_SMS_PROVIDER => Arc::new(RwLock::new(MockSmsClient)),
+        HTTP_SMS_PROVIDER => Arc::new(RwLock::new(configure_http_sms_client())),
+        provider => panic!("Unknown SMS provider: {}", provider),
+    }
+}
+
+fn configure_http_sms_client() -> HttpSmsClient {
+    let http_client = Client::builder()
+        .timeout(prod::sms_client::TIMEOUT)
+        .build()
+        .expect("Failed to build HTTP client");
+
+    HttpSmsClient::new(
+        SMS_API_URL.to_owned(),
+        SMS_SENDER.to_owned(),
+        SMS_API_TOKEN.to_owned(),
+        http_client,
+    )
+}
+
+// Each setting of the policy defaults to the one of `PasswordPolicy::default`
+fn password_policy() -> PasswordPolicy {
+    let default = PasswordPolicy::default();
+    PasswordPolicy {
+        min_length: PASSWORD_MIN_LENGTH.unwrap_or(default.min_length),
+        max_length: PASSWORD_MAX_LENGTH.unwrap_or(default.max_length),
+        // e.g. `lowercase,uppercase,digit,symbol`
+        required_classes: match PASSWORD_REQUIRED_CHARACTERS.as_deref() {
+            Some("none") => vec![],
+            Some(classes) => classes
+                .split(',')
+                .map(|class| class.trim().parse::<CharacterClass>())
+                .collect::<Result<_, _>>()
+                .expect("Invalid PASSWORD_REQUIRED_CHARACTERS"),
+            None => default.required_classes,
+        },
+        min_entropy_bits: PASSWORD_MIN_ENTROPY_BITS.unwrap_or(default.min_entropy_bits),
+        history_size: PASSWORD_HISTORY_SIZE.unwrap_or(default.history_size),
+        // 0 disables the expiry, as leaving the variable unset does
+        max_age: PASSWORD_MAX_AGE_DAYS
+            .filter(|days| *days > 0)
+            .map(|days| Duration::from_secs(days as u64 * 24 * 60 * 60))
+            .or(default.max_age),
+    }
+}
+
+/// Costs of the new password hashes. Hashes with other costs are upgraded at the next login.
+pub fn password_hash_params() -> argon2::Params {
+    let cost = |value: Option<usize>, default: u32| {
+        value.map_or(default, |value| {
+            u32::try_from(value).expect("Argon2 costs must fit in 32 bits")
+        })
+    };
+    argon2::Params::new(
+        cost(*ARGON2_MEMORY_KIB, prod::argon2::MEMORY_KIB),
+        cost(*ARGON2_ITERATIONS, prod::argon2::ITERATIONS),
+        cost(*ARGON2_PARALLELISM, prod::argon2::PARALLELISM),
+        None,
+    )
+    .expect("Invalid Argon2 parameters")
+}
+
+/// Peppers of the password hashes, none unless `PASSWORD_PEPPER` or `PASSWORD_PEPPER_FILE` is set.
+pub fn password_peppers() -> Peppers {
+    PASSWORD_PEPPERS
+        .as_ref()
+        .map_or_else(Peppers::default, |peppers| {
+            Peppers::parse(peppers).unwrap_or_else(|e| panic!("Invalid password pepper: {}", e))
+        })
+}
+
+fn configure_breached_passwords() -> Option<BreachedPasswords> {
+    BREACHED_PASSWORDS_DIR.as_deref().map(|dir| {
+        // A missing directory would silently let every password through
+        if !std::path::Path::new(dir).is_dir() {
+            panic!("BREACHED_PASSWORDS_DIR must be a directory: {}", dir);
+        }
+        BreachedPasswords::new(dir)
+    })
+}
+
+// Each list is only enforced when its file is configured
+fn configure_email_domain_policy() -> EmailDomainPolicy {
+    let load = |path: &Option<String>| {
+        path.as_deref().map(|path| {
+            DomainListFile::load(path)
+                .unwrap_or_else(|e| panic!("Failed to load email domain list: {:#}", e))
+        })
+    };
+    EmailDomainPolicy {
+        blocklist: load(&EMAIL_DOMAIN_BLOCKLIST_FILE),
+        allowlist: load(&EMAIL_DOMAIN_ALLOWLIST_FILE),
+        require_2fa: load(&EMAIL_DOMAIN_2FA_FILE),
+    }
+}
+
+fn email_change_policy() -> EmailChangePolicy {
+    EmailChangePolicy {
+        confirm_ttl: prod::email_changes::CONFIRM_TTL,
+        revert_ttl: prod::email_changes::REVERT_TTL,
+    }
+}
+
+fn two_fa_resend_policy() -> ResendPolicy {
+    ResendPolicy {
+        cooldown: prod::two_fa::RESEND_COOLDOWN,
+        max_resends: prod::two_fa::MAX_RESENDS,
+    }
+}
+
+/// Creates the dispatcher delivering the webhooks queued in `webhook_store`.
+pub fn configure_webhook_dispatcher(webhook_store: WebhookStoreType) -> WebhookDispatcher {
+    WebhookDispatcher::new(
+        webhook_store,
+        prod::webhooks::TIMEOUT,
+        RetryPolicy {
+            max_attempts: prod::webhooks::MAX_ATTEMPTS,
+            base_delay: prod::webhooks::BASE_DELAY,
+            max_delay: prod::webhooks::MAX_DELAY,
+        },
+        prod::webhooks::POLL_INTERVAL,
+        prod::webhooks::BATCH_SIZE,
+        prod::webhooks::LEASE,
+    )
+    .expect("Failed to build webhook dispatcher")
+}
+
+/// Creates the dispatcher sending the emails queued in `email_outbox_store` through the configured provider.
+pub fn configure_email_dispatcher(email_outbox_store: EmailOutboxStoreType) -> EmailDispatcher {
+    EmailDispatcher::new(
+        email_outbox_store,
+        configure_email_client(),
+        RetryPolicy {
+            max_attempts: prod::email_outbox::MAX_ATTEMPTS,
+            base_delay: prod::email_outbox::BASE_DELAY,
+            max_delay: prod::email_outbox::MAX_DELAY,
+        },
+        prod::email_outbox::POLL_INTERVAL,
+        prod::email_outbox::BATCH_SIZE,
+        prod::email_outbox::LEASE,
+    )
+}
@@ -0,0 +1,72 @@
+//! Operations on the tamper-evident audit log.
+//!
+//! Usage:
+//!   audit_chain verify          Walk the chain stored in the database
+//!   audit_chain verify <FILE>   Verify an export produced by `audit_chain export`
+//!   audit_chain export          Print the whole chain and its head as JSON
+//!   audit_chain head            Print the chain head as JSON, to be anchored externally
+use auth_service::audit::{
+    export_audit_chain, verify_audit_chain, AuditChainExport, AuditChainVerification,
+};
+use auth_service::utils::constants::DATABASE_URL;
+use auth_service::{get_postgres_pool, AuditEventStore, PostgresAuditEventStore};
+use color_eyre::eyre::{bail, Context, Result};
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: audit_chain <verify [FILE] | export | head>";
+
+#[tokio::main]
+async fn main() -> Result<ExitCode> {
+    color_eyre::install()?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let verification = match args.as_slice() {
+        ["verify", file] => {
+            let export = std::fs::read_to_string(file).wrap_err("failed to read export")?;
+            let export: AuditChainExport =
+                serde_json::from_str(&export).wrap_err("failed to parse export")?;
+            export.verify()
+        }
+        ["verify"] => verify_audit_chain(&connect().await?).await?,
+        ["export"] => {
+            let export = export_audit_chain(&connect().await?).await?;
+            println!("{}", serde_json::to_string_pretty(&export)?);
+            return Ok(ExitCode::SUCCESS);
+        }
+        ["head"] => {
+            let head = connect().await?.get_chain_head().await?;
+            println!("{}", serde_json::to_string_pretty(&head)?);
+            return Ok(ExitCode::SUCCESS);
+        }
+        _ => bail!(USAGE),
+    };
+
+    match verification {
+        AuditChainVerification::Valid(Some(head)) => {
+            println!(
+                "Audit chain is valid up to sequence {} (hash {})",
+                head.sequence, head.hash
+            );
+            Ok(ExitCode::SUCCESS)
+        }
+        AuditChainVerification::Valid(None) => {
+            println!("Audit chain is empty");
+            Ok(ExitCode::SUCCESS)
+        }
+        AuditChainVerification::Broken(broken_link) => {
+            println!(
+                "Audit chain is broken at sequence {}: {}",
+                broken_link.sequence, broken_link.reason
+            );
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+async fn connect() -> Result<PostgresAuditEventStore> {
+    let pool = get_postgres_pool(&DATABASE_URL)
+        .await
+        .wrap_err("failed to connect to Postgres")?;
+    Ok(PostgresAuditEventStore::new(pool))
+}
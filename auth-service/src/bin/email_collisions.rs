@@ -0,0 +1,67 @@
+//! Resolves the accounts whose emails only differ by case, which the migration normalising emails
+//! listed in the `email_collisions` table. Their emails stay un-normalised until then, so their
+//! users cannot log in.
+//!
+//! Usage:
+//!   email_collisions list          Print the unresolved collisions
+//!   email_collisions keep <EMAIL>  Keep the account of EMAIL, as listed, and delete the others of its collision
+//!   email_collisions validate      Check the emails are normalised once collisions were resolved by hand
+use std::process::ExitCode;
+
+use auth_service::email_collisions::{
+    get_email_collisions, keep_colliding_account, validate_email_normalization,
+};
+use auth_service::get_postgres_pool;
+use auth_service::utils::constants::DATABASE_URL;
+use color_eyre::eyre::{bail, Context, Result};
+use sqlx::PgPool;
+
+const USAGE: &str = "Usage: email_collisions <list | keep EMAIL | validate>";
+
+#[tokio::main]
+async fn main() -> Result<ExitCode> {
+    color_eyre::install()?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let validated = match args.as_slice() {
+        ["list"] => {
+            let collisions = get_email_collisions(&connect().await?).await?;
+            for collision in &collisions {
+                println!(
+                    "{}: {}",
+                    collision.normalized_email,
+                    collision.emails.join(", ")
+                );
+            }
+            return Ok(if collisions.is_empty() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            });
+        }
+        ["keep", email] => {
+            let pool = connect().await?;
+            for deleted in keep_colliding_account(&pool, email).await? {
+                println!("Deleted {}", deleted);
+            }
+            get_email_collisions(&pool).await?.is_empty()
+        }
+        ["validate"] => validate_email_normalization(&connect().await?).await?,
+        _ => bail!(USAGE),
+    };
+
+    if validated {
+        println!("All emails are normalised");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        println!("Collisions are left, see `email_collisions list`");
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+async fn connect() -> Result<PgPool> {
+    get_postgres_pool(&DATABASE_URL)
+        .await
+        .wrap_err("failed to connect to Postgres")
+}
@@ -0,0 +1,51 @@
+//! Imports users from another system along with their password hashes, which are verified as is
+//! and upgraded to Argon2id at the first login of each user.
+//!
+//! Usage:
+//!   import_users <FILE.csv | FILE.jsonl>
+//!
+//! Each record has an `email`, a `hash` (Argon2, scrypt or PBKDF2 PHC string, or bcrypt hash)
+//! and an optional `requires_2fa`. Users whose email is taken are skipped, so an import can be run again.
+use std::path::Path;
+use std::process::ExitCode;
+
+use auth_service::app_state::{password_hash_params, password_peppers};
+use auth_service::user_import::{import_users, ImportFormat};
+use auth_service::utils::constants::DATABASE_URL;
+use auth_service::{get_postgres_pool, PostgresUserStore};
+use color_eyre::eyre::{bail, eyre, Context, Result};
+
+const USAGE: &str = "Usage: import_users <FILE.csv | FILE.jsonl>";
+
+#[tokio::main]
+async fn main() -> Result<ExitCode> {
+    color_eyre::install()?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [file] = args.as_slice() else {
+        bail!(USAGE);
+    };
+    let format = ImportFormat::from_path(Path::new(file))
+        .ok_or_else(|| eyre!("unknown file format, {}", USAGE))?;
+    let input = std::fs::read_to_string(file).wrap_err("failed to read import file")?;
+
+    let pool = get_postgres_pool(&DATABASE_URL)
+        .await
+        .wrap_err("failed to connect to Postgres")?;
+    let store = PostgresUserStore::new(pool, password_hash_params(), password_peppers());
+    let report = import_users(&store, format, &input).await;
+
+    for rejected in &report.rejected {
+        eprintln!("Line {}: {}", rejected.line, rejected.reason);
+    }
+    println!(
+        "Imported {} users, skipped {} existing, rejected {}",
+        report.imported,
+        report.existing,
+        report.rejected.len()
+    );
+    if report.rejected.is_empty() {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::FAILURE)
+    }
+}
@@ -1,8 +1,20 @@
+pub mod audit;
 pub mod data_stores;
+pub mod delivery;
 pub mod email;
+pub mod email_change;
 pub mod email_client;
+pub mod email_outbox;
 pub mod error;
+pub mod known_device;
+pub mod locale;
 pub mod password;
+pub mod phone_number;
+pub mod profile;
+pub mod sms_client;
+pub mod trusted_device;
 pub mod user;
+pub mod webhook;
 
 pub use email_client::*;
+pub use sms_client::*;
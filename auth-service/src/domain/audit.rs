@@ -0,0 +1,606 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, SubsecRound, Utc};
+use color_eyre::eyre::{eyre, Report};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::domain::data_stores::{AuditEventStore, AuditEventStoreError};
+use crate::error::AuthAPIError;
+use crate::utils::request_context::RequestContext;
+
+/// Actor recorded for actions performed through the admin API.
+pub const ADMIN_ACTOR: &str = "admin";
+/// Actor recorded when the request could not be tied to a user.
+pub const ANONYMOUS_ACTOR: &str = "anonymous";
+
+/// A security-relevant event, written once and never modified.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub id: Uuid,
+    #[serde(rename = "eventType")]
+    pub event_type: AuditEventType,
+    /// Email of the account the event is about, as provided in the request
+    #[serde(rename = "userEmail")]
+    pub user_email: Option<String>,
+    /// Who performed the action: the user themselves, `ADMIN_ACTOR` or `ANONYMOUS_ACTOR`
+    pub actor: String,
+    #[serde(rename = "ipAddress")]
+    pub ip_address: Option<String>,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    pub outcome: AuditOutcome,
+    #[serde(rename = "requestId")]
+    pub request_id: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditEvent {
+    pub fn new(
+        event_type: AuditEventType,
+        user_email: Option<&str>,
+        actor: &str,
+        outcome: AuditOutcome,
+        context: &RequestContext,
+    ) -> Self {
+        AuditEvent {
+            id: Uuid::new_v4(),
+            event_type,
+            user_email: user_email.map(str::to_owned),
+            actor: actor.to_owned(),
+            ip_address: context.ip_address.clone(),
+            user_agent: context.user_agent.clone(),
+            outcome,
+            request_id: context.request_id.clone(),
+            // Postgres stores timestamps with microsecond precision
+            created_at: Utc::now().trunc_subsecs(6),
+        }
+    }
+}
+
+/// `prev_hash` of the first record of the chain.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// An audit event as persisted: its position in the log and its link to the previous record.
+/// `hash` covers both the event and `prev_hash`, so editing, removing or reordering
+/// a record breaks every link after it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub sequence: i64,
+    #[serde(rename = "prevHash")]
+    pub prev_hash: String,
+    pub hash: String,
+    #[serde(flatten)]
+    pub event: AuditEvent,
+}
+
+impl AuditRecord {
+    /// Appends `event` to the chain whose last record is `head`.
+    pub fn chain(event: AuditEvent, head: Option<&AuditChainHead>) -> Self {
+        let (sequence, prev_hash) = match head {
+            Some(head) => (head.sequence + 1, head.hash.clone()),
+            None => (1, GENESIS_HASH.to_owned()),
+        };
+        let hash = AuditRecord::compute_hash(&prev_hash, sequence, &event);
+        AuditRecord {
+            sequence,
+            prev_hash,
+            hash,
+            event,
+        }
+    }
+
+    /// Hex encoded SHA-256 of the canonical encoding of a record.
+    /// Every field is written as `<byte length>:<value>;`, or `-;` when absent.
+    /// The encoding is mirrored by the migration chaining the pre-existing events.
+    pub fn compute_hash(prev_hash: &str, sequence: i64, event: &AuditEvent) -> String {
+        let fields = [
+            Some(prev_hash.to_owned()),
+            Some(sequence.to_string()),
+            Some(event.id.to_string()),
+            Some(event.event_type.as_str().to_owned()),
+            event.user_email.clone(),
+            Some(event.actor.clone()),
+            event.ip_address.clone(),
+            event.user_agent.clone(),
+            Some(event.outcome.as_str().to_owned()),
+            event.outcome.failure_reason().map(str::to_owned),
+            event.request_id.clone(),
+            Some(event.created_at.timestamp_micros().to_string()),
+        ];
+
+        let mut hasher = Sha256::new();
+        for field in fields {
+            match field {
+                Some(value) => hasher.update(format!("{}:{};", value.len(), value)),
+                None => hasher.update("-;"),
+            }
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    pub fn head(&self) -> AuditChainHead {
+        AuditChainHead {
+            sequence: self.sequence,
+            hash: self.hash.clone(),
+            created_at: self.event.created_at,
+        }
+    }
+}
+
+/// Latest record of the chain. Publishing it anchors the whole log up to that point.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditChainHead {
+    pub sequence: i64,
+    pub hash: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// Export of the audit log, verifiable offline and anchored by its head.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditChainExport {
+    #[serde(rename = "exportedAt")]
+    pub exported_at: DateTime<Utc>,
+    pub head: Option<AuditChainHead>,
+    pub records: Vec<AuditRecord>,
+}
+
+impl AuditChainExport {
+    /// Checks the exported records against each other and against the exported head.
+    pub fn verify(&self) -> AuditChainVerification {
+        let mut verifier = AuditChainVerifier::default();
+        for record in self.records.iter() {
+            if let Err(broken_link) = verifier.verify_next(record) {
+                return AuditChainVerification::Broken(broken_link);
+            }
+        }
+        match (&self.head, verifier.head()) {
+            (head, verified_head) if head.as_ref() == verified_head => {
+                AuditChainVerification::Valid(head.clone())
+            }
+            (head, verified_head) => AuditChainVerification::Broken(BrokenLink {
+                sequence: head.as_ref().or(verified_head).map_or(0, |h| h.sequence),
+                reason: "exported head does not match the last record".to_owned(),
+            }),
+        }
+    }
+}
+
+/// First link of the chain that does not match the records before it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrokenLink {
+    pub sequence: i64,
+    pub reason: String,
+}
+
+/// Incrementally checks that records are well chained, in order.
+#[derive(Debug, Default)]
+pub struct AuditChainVerifier {
+    head: Option<AuditChainHead>,
+}
+
+impl AuditChainVerifier {
+    pub fn verify_next(&mut self, record: &AuditRecord) -> Result<(), BrokenLink> {
+        let (expected_sequence, expected_prev_hash) = match &self.head {
+            Some(head) => (head.sequence + 1, head.hash.as_str()),
+            None => (1, GENESIS_HASH),
+        };
+        let broken = |reason: &str| BrokenLink {
+            sequence: record.sequence,
+            reason: reason.to_owned(),
+        };
+
+        if record.sequence != expected_sequence {
+            return Err(broken(&format!(
+                "expected sequence {}, a record is missing",
+                expected_sequence
+            )));
+        }
+        if record.prev_hash != expected_prev_hash {
+            return Err(broken("previous hash does not match the previous record"));
+        }
+        if record.hash
+            != AuditRecord::compute_hash(&record.prev_hash, record.sequence, &record.event)
+        {
+            return Err(broken("hash does not match the record content"));
+        }
+        self.head = Some(record.head());
+        Ok(())
+    }
+
+    /// Head of the records verified so far.
+    pub fn head(&self) -> Option<&AuditChainHead> {
+        self.head.as_ref()
+    }
+}
+
+/// Result of walking the audit chain.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuditChainVerification {
+    /// Every record is well chained, up to this head (`None` for an empty log).
+    Valid(Option<AuditChainHead>),
+    Broken(BrokenLink),
+}
+
+/// Number of records fetched at once when walking the chain.
+const AUDIT_CHAIN_PAGE_SIZE: i64 = 500;
+
+/// Reads the whole chain stored in `store`.
+pub async fn export_audit_chain(
+    store: &dyn AuditEventStore,
+) -> Result<AuditChainExport, AuditEventStoreError> {
+    let exported_at = Utc::now();
+    let mut records: Vec<AuditRecord> = vec![];
+    loop {
+        let after = records.last().map_or(0, |record| record.sequence);
+        let page = store.get_records(after, AUDIT_CHAIN_PAGE_SIZE).await?;
+        let is_last_page = (page.len() as i64) < AUDIT_CHAIN_PAGE_SIZE;
+        records.extend(page);
+        if is_last_page {
+            break;
+        }
+    }
+    Ok(AuditChainExport {
+        exported_at,
+        head: records.last().map(AuditRecord::head),
+        records,
+    })
+}
+
+/// Walks the whole chain stored in `store`, stopping at the first broken link.
+pub async fn verify_audit_chain(
+    store: &dyn AuditEventStore,
+) -> Result<AuditChainVerification, AuditEventStoreError> {
+    let mut verifier = AuditChainVerifier::default();
+    loop {
+        let after = verifier.head().map_or(0, |head| head.sequence);
+        let records = store.get_records(after, AUDIT_CHAIN_PAGE_SIZE).await?;
+        for record in records.iter() {
+            if let Err(broken_link) = verifier.verify_next(record) {
+                return Ok(AuditChainVerification::Broken(broken_link));
+            }
+        }
+        if (records.len() as i64) < AUDIT_CHAIN_PAGE_SIZE {
+            return Ok(AuditChainVerification::Valid(verifier.head().cloned()));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEventType {
+    Signup,
+    Login,
+    #[serde(rename = "verify_2fa")]
+    Verify2FA,
+    #[serde(rename = "resend_2fa")]
+    Resend2FA,
+    LoginAttemptCancel,
+    Logout,
+    AdminAuditQuery,
+    AdminWebhookSubscribe,
+    AdminWebhookUnsubscribe,
+    AdminUserStatusChange,
+    AdminUserDelete,
+    PhoneNumberVerify,
+    PhoneNumberRemove,
+    #[serde(rename = "two_fa_channel_change")]
+    TwoFAChannelChange,
+    TrustedDeviceRevoke,
+    SessionRevoke,
+    PasswordChange,
+    EmailChangeRequest,
+    EmailChangeConfirm,
+    EmailChangeRevert,
+    ProfileUpdate,
+}
+
+impl AuditEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEventType::Signup => "signup",
+            AuditEventType::Login => "login",
+            AuditEventType::Verify2FA => "verify_2fa",
+            AuditEventType::Resend2FA => "resend_2fa",
+            AuditEventType::LoginAttemptCancel => "login_attempt_cancel",
+            AuditEventType::Logout => "logout",
+            AuditEventType::AdminAuditQuery => "admin_audit_query",
+            AuditEventType::AdminWebhookSubscribe => "admin_webhook_subscribe",
+            AuditEventType::AdminWebhookUnsubscribe => "admin_webhook_unsubscribe",
+            AuditEventType::AdminUserStatusChange => "admin_user_status_change",
+            AuditEventType::AdminUserDelete => "admin_user_delete",
+            AuditEventType::PhoneNumberVerify => "phone_number_verify",
+            AuditEventType::PhoneNumberRemove => "phone_number_remove",
+            AuditEventType::TwoFAChannelChange => "two_fa_channel_change",
+            AuditEventType::TrustedDeviceRevoke => "trusted_device_revoke",
+            AuditEventType::SessionRevoke => "session_revoke",
+            AuditEventType::PasswordChange => "password_change",
+            AuditEventType::EmailChangeRequest => "email_change_request",
+            AuditEventType::EmailChangeConfirm => "email_change_confirm",
+            AuditEventType::EmailChangeRevert => "email_change_revert",
+            AuditEventType::ProfileUpdate => "profile_update",
+        }
+    }
+}
+
+impl fmt::Display for AuditEventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AuditEventType {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "signup" => Ok(AuditEventType::Signup),
+            "login" => Ok(AuditEventType::Login),
+            "verify_2fa" => Ok(AuditEventType::Verify2FA),
+            "resend_2fa" => Ok(AuditEventType::Resend2FA),
+            "login_attempt_cancel" => Ok(AuditEventType::LoginAttemptCancel),
+            "logout" => Ok(AuditEventType::Logout),
+            "admin_audit_query" => Ok(AuditEventType::AdminAuditQuery),
+            "admin_webhook_subscribe" => Ok(AuditEventType::AdminWebhookSubscribe),
+            "admin_webhook_unsubscribe" => Ok(AuditEventType::AdminWebhookUnsubscribe),
+            "admin_user_status_change" => Ok(AuditEventType::AdminUserStatusChange),
+            "admin_user_delete" => Ok(AuditEventType::AdminUserDelete),
+            "phone_number_verify" => Ok(AuditEventType::PhoneNumberVerify),
+            "phone_number_remove" => Ok(AuditEventType::PhoneNumberRemove),
+            "two_fa_channel_change" => Ok(AuditEventType::TwoFAChannelChange),
+            "trusted_device_revoke" => Ok(AuditEventType::TrustedDeviceRevoke),
+            "session_revoke" => Ok(AuditEventType::SessionRevoke),
+            "password_change" => Ok(AuditEventType::PasswordChange),
+            "email_change_request" => Ok(AuditEventType::EmailChangeRequest),
+            "email_change_confirm" => Ok(AuditEventType::EmailChangeConfirm),
+            "email_change_revert" => Ok(AuditEventType::EmailChangeRevert),
+            "profile_update" => Ok(AuditEventType::ProfileUpdate),
+            _ => Err(eyre!("Unknown audit event type: {}", s)),
+        }
+    }
+}
+
+/// Whether the audited action succeeded. Failures carry the error returned to the client.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "status", content = "reason")]
+pub enum AuditOutcome {
+    Success,
+    Failure(String),
+}
+
+impl AuditOutcome {
+    pub fn of<T>(result: &Result<T, AuthAPIError>) -> Self {
+        match result {
+            Ok(_) => AuditOutcome::Success,
+            Err(e) => AuditOutcome::Failure(e.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditOutcome::Success => "success",
+            AuditOutcome::Failure(_) => "failure",
+        }
+    }
+
+    pub fn failure_reason(&self) -> Option<&str> {
+        match self {
+            AuditOutcome::Success => None,
+            AuditOutcome::Failure(reason) => Some(reason),
+        }
+    }
+
+    pub fn parse(outcome: &str, failure_reason: Option<String>) -> color_eyre::Result<Self> {
+        match outcome {
+            "success" => Ok(AuditOutcome::Success),
+            "failure" => Ok(AuditOutcome::Failure(failure_reason.unwrap_or_default())),
+            _ => Err(eyre!("Unknown audit outcome: {}", outcome)),
+        }
+    }
+}
+
+/// Criteria used to query the audit log. Unset fields match every event.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditEventFilter {
+    #[serde(rename = "userEmail")]
+    pub user_email: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl AuditEventFilter {
+    pub fn matches(&self, event: &AuditEvent) -> bool {
+        self.user_email
+            .as_ref()
+            .is_none_or(|email| event.user_email.as_ref() == Some(email))
+            && self.from.is_none_or(|from| event.created_at >= from)
+            && self.to.is_none_or(|to| event.created_at < to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_type_round_trip() {
+        let event_types = [
+            AuditEventType::Signup,
+            AuditEventType::Login,
+            AuditEventType::Verify2FA,
+            AuditEventType::Resend2FA,
+            AuditEventType::LoginAttemptCancel,
+            AuditEventType::Logout,
+            AuditEventType::AdminAuditQuery,
+            AuditEventType::AdminWebhookSubscribe,
+            AuditEventType::AdminWebhookUnsubscribe,
+            AuditEventType::AdminUserStatusChange,
+            AuditEventType::AdminUserDelete,
+            AuditEventType::PhoneNumberVerify,
+            AuditEventType::PhoneNumberRemove,
+            AuditEventType::TwoFAChannelChange,
+            AuditEventType::TrustedDeviceRevoke,
+            AuditEventType::SessionRevoke,
+            AuditEventType::PasswordChange,
+            AuditEventType::EmailChangeRequest,
+            AuditEventType::EmailChangeConfirm,
+            AuditEventType::EmailChangeRevert,
+            AuditEventType::ProfileUpdate,
+        ];
+        for event_type in event_types {
+            assert_eq!(
+                event_type.as_str().parse::<AuditEventType>().unwrap(),
+                event_type
+            );
+            // The admin API and the export show the serde name, which the filters expect
+            assert_eq!(
+                serde_json::to_value(event_type).unwrap(),
+                event_type.as_str()
+            );
+        }
+        assert!("unknown".parse::<AuditEventType>().is_err());
+    }
+
+    #[test]
+    fn test_outcome_of_result() {
+        let ok: Result<(), AuthAPIError> = Ok(());
+        assert_eq!(AuditOutcome::of(&ok), AuditOutcome::Success);
+        let err: Result<(), AuthAPIError> = Err(AuthAPIError::AuthenticationFailure);
+        assert_eq!(
+            AuditOutcome::of(&err),
+            AuditOutcome::Failure("Authentication failure".to_owned())
+        );
+    }
+
+    fn event(user_email: &str) -> AuditEvent {
+        AuditEvent::new(
+            AuditEventType::Login,
+            Some(user_email),
+            user_email,
+            AuditOutcome::Success,
+            &RequestContext::default(),
+        )
+    }
+
+    fn chain(events: Vec<AuditEvent>) -> Vec<AuditRecord> {
+        let mut records: Vec<AuditRecord> = vec![];
+        for event in events {
+            let head = records.last().map(AuditRecord::head);
+            records.push(AuditRecord::chain(event, head.as_ref()));
+        }
+        records
+    }
+
+    fn verify(records: &[AuditRecord]) -> Result<Option<AuditChainHead>, BrokenLink> {
+        let mut verifier = AuditChainVerifier::default();
+        for record in records {
+            verifier.verify_next(record)?;
+        }
+        Ok(verifier.head().cloned())
+    }
+
+    #[test]
+    fn test_chain_links_records() {
+        let records = chain(vec![event("a@b.com"), event("c@d.com"), event("e@f.com")]);
+        assert_eq!(records[0].sequence, 1);
+        assert_eq!(records[0].prev_hash, GENESIS_HASH);
+        assert_eq!(records[1].prev_hash, records[0].hash);
+        assert_eq!(records[2].prev_hash, records[1].hash);
+        assert_eq!(verify(&records).unwrap(), Some(records[2].head()));
+        assert_eq!(verify(&[]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_verify_detects_edited_record() {
+        let mut records = chain(vec![event("a@b.com"), event("c@d.com"), event("e@f.com")]);
+        records[1].event.outcome = AuditOutcome::Failure("Authentication failure".to_owned());
+        let broken_link = verify(&records).unwrap_err();
+        assert_eq!(broken_link.sequence, 2);
+    }
+
+    #[test]
+    fn test_verify_detects_removed_record() {
+        let mut records = chain(vec![event("a@b.com"), event("c@d.com"), event("e@f.com")]);
+        records.remove(1);
+        let broken_link = verify(&records).unwrap_err();
+        assert_eq!(broken_link.sequence, 3);
+    }
+
+    #[test]
+    fn test_verify_detects_rewritten_chain() {
+        let mut records = chain(vec![event("a@b.com"), event("c@d.com"), event("e@f.com")]);
+        // Recomputing the hash of an edited record still breaks the next link
+        records[1].event.actor = "admin".to_owned();
+        records[1].hash = AuditRecord::compute_hash(&records[1].prev_hash, 2, &records[1].event);
+        let broken_link = verify(&records).unwrap_err();
+        assert_eq!(broken_link.sequence, 3);
+    }
+
+    #[test]
+    fn test_verify_export() {
+        let records = chain(vec![event("a@b.com"), event("c@d.com")]);
+        let mut export = AuditChainExport {
+            exported_at: Utc::now(),
+            head: Some(records[1].head()),
+            records,
+        };
+        assert_eq!(
+            export.verify(),
+            AuditChainVerification::Valid(export.head.clone())
+        );
+
+        // Truncating the export is detected thanks to the head
+        export.records.pop();
+        match export.verify() {
+            AuditChainVerification::Broken(broken_link) => assert_eq!(broken_link.sequence, 2),
+            verification => panic!("Expected a broken chain, got {:?}", verification),
+        }
+    }
+
+    #[test]
+    fn test_hash_fields_are_unambiguous() {
+        let mut first = event("a@b.com");
+        first.user_agent = Some("agent;".to_owned());
+        first.request_id = None;
+        let mut second = first.clone();
+        second.user_agent = Some("agent".to_owned());
+        second.request_id = Some(";".to_owned());
+        assert_ne!(
+            AuditRecord::compute_hash(GENESIS_HASH, 1, &first),
+            AuditRecord::compute_hash(GENESIS_HASH, 1, &second)
+        );
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let context = RequestContext::default();
+        let event = AuditEvent::new(
+            AuditEventType::Login,
+            Some("foo@bar.com"),
+            "foo@bar.com",
+            AuditOutcome::Success,
+            &context,
+        );
+        assert!(AuditEventFilter::default().matches(&event));
+        let filter = AuditEventFilter {
+            user_email: Some("other@bar.com".to_owned()),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&event));
+        let filter = AuditEventFilter {
+            user_email: Some("foo@bar.com".to_owned()),
+            from: Some(event.created_at - chrono::Duration::minutes(1)),
+            to: Some(event.created_at + chrono::Duration::minutes(1)),
+        };
+        assert!(filter.matches(&event));
+        let filter = AuditEventFilter {
+            from: Some(event.created_at + chrono::Duration::minutes(1)),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&event));
+    }
+}
@@ -1,21 +1,81 @@
+use chrono::{DateTime, Utc};
 use color_eyre::eyre::eyre;
 use color_eyre::eyre::Report;
 use color_eyre::eyre::Result;
+use hmac::{Hmac, Mac};
 use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
 use thiserror::Error;
 use uuid::Uuid;
 
+use crate::domain::audit::{AuditChainHead, AuditEvent, AuditEventFilter, AuditRecord};
 use crate::domain::email::Email;
+use crate::domain::email_change::EmailChange;
+use crate::domain::email_outbox::OutboxEmail;
+use crate::domain::known_device::{DeviceSighting, KnownDevice, SessionRevocation};
 use crate::domain::password::Password;
-use crate::domain::user::User;
+use crate::domain::phone_number::PhoneNumber;
+use crate::domain::profile::ProfileUpdate;
+use crate::domain::trusted_device::TrustedDevice;
+use crate::domain::user::{AccountStatus, TwoFAChannel, User, UserId};
+use crate::domain::webhook::{WebhookDelivery, WebhookSubscription};
 
 /// This module defines the data stores used in the application.
 #[async_trait::async_trait]
 pub trait UserStore: Send + Sync {
     async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
-    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
+    /// Deletes the user along with everything stored about them.
+    async fn delete_user(&mut self, user_id: &UserId) -> Result<(), UserStoreError>;
+    async fn get_user(&self, user_id: &UserId) -> Result<User, UserStoreError>;
+    /// Only for the login, whose requests carry the email, later lookups go by id
+    async fn get_user_by_email(&self, email: &Email) -> Result<User, UserStoreError>;
     async fn validate_user(&self, email: &Email, password: &Password)
         -> Result<(), UserStoreError>;
+    /// Sets the verified phone number of the user.
+    /// Removing it switches the 2FA codes back to email.
+    async fn set_phone_number(
+        &mut self,
+        user_id: &UserId,
+        phone_number: Option<PhoneNumber>,
+    ) -> Result<(), UserStoreError>;
+    async fn set_two_fa_channel(
+        &mut self,
+        user_id: &UserId,
+        channel: TwoFAChannel,
+    ) -> Result<(), UserStoreError>;
+    /// Applies the changes to the profile of the user, and returns the updated user.
+    async fn update_profile(
+        &mut self,
+        user_id: &UserId,
+        update: ProfileUpdate,
+    ) -> Result<User, UserStoreError>;
+    /// Sets the last login time of the user to now.
+    async fn record_login(&mut self, user_id: &UserId) -> Result<(), UserStoreError>;
+    /// Sets the status of the user, and increments their session version to revoke their JWTs.
+    async fn set_status(
+        &mut self,
+        user_id: &UserId,
+        status: AccountStatus,
+    ) -> Result<(), UserStoreError>;
+    /// Increments the session version of the user, which revokes all their JWTs.
+    async fn revoke_sessions(&mut self, user_id: &UserId) -> Result<(), UserStoreError>;
+    /// Replaces the email of the user, provided it is still `current_email`.
+    /// Fails with `UserAlreadyExists` if another user has `new_email`.
+    async fn update_email(
+        &mut self,
+        user_id: &UserId,
+        current_email: &Email,
+        new_email: &Email,
+    ) -> Result<(), UserStoreError>;
+    /// Replaces the password of the user, which must differ from their last `history_size` passwords,
+    /// the current one included. The replaced password is kept in the history.
+    async fn update_password(
+        &mut self,
+        user_id: &UserId,
+        password: Password,
+        history_size: usize,
+    ) -> Result<(), UserStoreError>;
 }
 
 /// This enum defines the possible errors that can occur when interacting with the user store.
@@ -27,6 +87,8 @@ pub enum UserStoreError {
     UserNotFound,
     #[error("Invalid credentials")]
     InvalidCredentials,
+    #[error("Password was used recently")]
+    PasswordReused,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
@@ -38,6 +100,7 @@ impl PartialEq for UserStoreError {
             (Self::UserAlreadyExists, Self::UserAlreadyExists)
                 | (Self::UserNotFound, Self::UserNotFound)
                 | (Self::InvalidCredentials, Self::InvalidCredentials)
+                | (Self::PasswordReused, Self::PasswordReused)
                 | (Self::UnexpectedError(_), Self::UnexpectedError(_))
         )
     }
@@ -47,6 +110,10 @@ impl From<sqlx::Error> for UserStoreError {
     fn from(err: sqlx::Error) -> Self {
         match err {
             sqlx::Error::RowNotFound => UserStoreError::UserNotFound,
+            // The email is the only unique column set by the service
+            sqlx::Error::Database(e) if e.is_unique_violation() => {
+                UserStoreError::UserAlreadyExists
+            }
             sqlx::Error::Database(e) => UserStoreError::UnexpectedError(e.into()),
             _ => UserStoreError::UnexpectedError(err.into()),
         }
@@ -54,11 +121,36 @@ impl From<sqlx::Error> for UserStoreError {
 }
 
 /// This module defines the data store for banned tokens.
+/// Tokens are identified by their hash, so that the store holds no usable JWT.
 #[async_trait::async_trait]
 pub trait BannedTokenStore: Send + Sync {
-    async fn add_banned_token(&mut self, token: &str) -> Result<(), BannedTokenStoreError>;
+    async fn add_banned_token(&mut self, token: &TokenHash) -> Result<(), BannedTokenStoreError>;
+    /// Checks the JWT `token`, which is looked up by its hash.
     async fn is_token_banned(&self, token: &str) -> Result<bool, BannedTokenStoreError>;
-    async fn remove_banned_token(&mut self, token: &str) -> Result<(), BannedTokenStoreError>;
+    async fn remove_banned_token(&mut self, token: &TokenHash)
+        -> Result<(), BannedTokenStoreError>;
+}
+
+/// SHA-256 of a JWT, identifying a session without allowing to use it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TokenHash(String);
+impl TokenHash {
+    pub fn of(token: &str) -> Self {
+        TokenHash(hex::encode(Sha256::digest(token.as_bytes())))
+    }
+
+    pub fn parse(hash: String) -> Result<Self> {
+        let is_valid = hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit());
+        is_valid
+            .then_some(TokenHash(hash))
+            .ok_or_else(|| eyre!("Invalid TokenHash"))
+    }
+}
+
+impl AsRef<str> for TokenHash {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
 }
 
 #[derive(Debug, Error)]
@@ -69,30 +161,358 @@ pub enum BannedTokenStoreError {
     UnexpectedError(#[source] Report),
 }
 
+/// Pending 2FA challenges, keyed by login attempt so that a user can log in from several devices at once.
+/// A login attempt only matches the email it was created for.
 #[async_trait::async_trait]
 pub trait TwoFACodeStore: Send + Sync {
     async fn add_code(
         &mut self,
         email: &Email,
-        code: TwoFACode,
+        code_hash: TwoFACodeHash,
         login_attempt_id: LoginAttemptId,
     ) -> Result<(), TwoFACodeStoreError>;
     async fn get_code(
         &self,
         email: &Email,
-    ) -> Result<(TwoFACode, LoginAttemptId), TwoFACodeStoreError>;
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<TwoFACodeHash, TwoFACodeStoreError>;
+    async fn remove_code(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError>;
+    /// Lists the pending login attempts of the user, the oldest first.
+    async fn get_login_attempts(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<PendingLoginAttempt>, TwoFACodeStoreError>;
+    /// Replaces the code of `login_attempt_id` with a new one about to be sent,
+    /// unless `policy` does not allow another sending yet.
+    async fn record_resend(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        code_hash: TwoFACodeHash,
+        policy: &ResendPolicy,
+    ) -> Result<(), TwoFACodeStoreError>;
+    /// Counts a wrong code given for `login_attempt_id`, which is removed once `max_attempts`
+    /// wrong codes were given. Resending a code does not reset the count.
+    async fn record_failed_attempt(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        max_attempts: u32,
+    ) -> Result<(), TwoFACodeStoreError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingLoginAttempt {
+    pub login_attempt_id: LoginAttemptId,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Limits how often a new 2FA code can be sent for a login attempt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResendPolicy {
+    /// Minimum delay since the code was last sent
+    pub cooldown: Duration,
+    pub max_resends: u32,
+}
+
+impl ResendPolicy {
+    /// Checks whether a code sent `resend_count` times again, lastly at `last_sent_at`, can be sent once more.
+    pub fn check(
+        &self,
+        resend_count: u32,
+        last_sent_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<(), TwoFACodeStoreError> {
+        if resend_count >= self.max_resends {
+            return Err(TwoFACodeStoreError::ResendLimitReached);
+        }
+        let elapsed = (now - last_sent_at).to_std().unwrap_or_default();
+        if elapsed < self.cooldown {
+            return Err(TwoFACodeStoreError::ResendTooSoon);
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Error)]
 pub enum TwoFACodeStoreError {
     #[error("Login attempt id not found")]
     LoginAttemptIdNotFound,
+    #[error("2FA code sent too recently")]
+    ResendTooSoon,
+    #[error("Too many 2FA codes sent for this login attempt")]
+    ResendLimitReached,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
 
+/// Phone numbers waiting to be verified with the code sent to them, at most one per user.
+#[async_trait::async_trait]
+pub trait PhoneVerificationStore: Send + Sync {
+    /// Replaces any pending verification of the user.
+    async fn add_verification(
+        &mut self,
+        email: &Email,
+        verification: PhoneVerification,
+    ) -> Result<(), PhoneVerificationStoreError>;
+    async fn get_verification(
+        &self,
+        email: &Email,
+    ) -> Result<PhoneVerification, PhoneVerificationStoreError>;
+    async fn remove_verification(
+        &mut self,
+        email: &Email,
+    ) -> Result<(), PhoneVerificationStoreError>;
+    /// Counts a wrong code given for the pending verification of the user,
+    /// which is removed once `max_attempts` wrong codes were given.
+    async fn record_failed_attempt(
+        &mut self,
+        email: &Email,
+        max_attempts: u32,
+    ) -> Result<(), PhoneVerificationStoreError>;
+}
+
 #[derive(Debug, Clone, PartialEq)]
+pub struct PhoneVerification {
+    pub phone_number: PhoneNumber,
+    pub code_hash: TwoFACodeHash,
+}
+
+#[derive(Debug, Error)]
+pub enum PhoneVerificationStoreError {
+    #[error("Verification not found")]
+    VerificationNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+/// Browsers remembered by the users to skip 2FA, see `TrustedDevice`.
+/// Expired devices are never returned.
+#[async_trait::async_trait]
+pub trait TrustedDeviceStore: Send + Sync {
+    async fn add_device(&mut self, device: TrustedDevice) -> Result<(), TrustedDeviceStoreError>;
+    async fn get_device(
+        &self,
+        user_id: &UserId,
+        id: &Uuid,
+    ) -> Result<TrustedDevice, TrustedDeviceStoreError>;
+    /// Lists the devices of the user, the oldest first.
+    async fn get_devices(
+        &self,
+        user_id: &UserId,
+    ) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError>;
+    async fn remove_device(
+        &mut self,
+        user_id: &UserId,
+        id: &Uuid,
+    ) -> Result<(), TrustedDeviceStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum TrustedDeviceStoreError {
+    #[error("Device not found")]
+    DeviceNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl From<sqlx::Error> for TrustedDeviceStoreError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => TrustedDeviceStoreError::DeviceNotFound,
+            _ => TrustedDeviceStoreError::UnexpectedError(err.into()),
+        }
+    }
+}
+
+/// Devices the users logged in from, see `KnownDevice`.
+#[async_trait::async_trait]
+pub trait KnownDeviceStore: Send + Sync {
+    /// Records a login from `device`.
+    /// `revocation` is kept for a new device, so that its session can be revoked from the alert.
+    async fn record_device(
+        &mut self,
+        device: KnownDevice,
+        revocation: SessionRevocation,
+    ) -> Result<DeviceSighting, KnownDeviceStoreError>;
+    /// Forgets the device whose alert carried the link, and returns its user and the session to ban.
+    /// A link can only be used once.
+    async fn revoke_session(
+        &mut self,
+        token_hash: &str,
+    ) -> Result<(UserId, TokenHash), KnownDeviceStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum KnownDeviceStoreError {
+    #[error("Session revocation not found")]
+    RevocationNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl From<sqlx::Error> for KnownDeviceStoreError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => KnownDeviceStoreError::RevocationNotFound,
+            _ => KnownDeviceStoreError::UnexpectedError(err.into()),
+        }
+    }
+}
+
+/// Email changes requested by the users, see `EmailChange`.
+#[async_trait::async_trait]
+pub trait EmailChangeStore: Send + Sync {
+    /// Adds a change, replacing the change of the user still waiting for confirmation if any.
+    async fn add_change(&mut self, change: EmailChange) -> Result<(), EmailChangeStoreError>;
+    /// Gets the change whose confirmation link carries the token, while it is pending.
+    async fn get_pending_change(
+        &self,
+        confirm_token_hash: &str,
+    ) -> Result<EmailChange, EmailChangeStoreError>;
+    /// Gets the change whose revert link carries the token, while it is revertible.
+    async fn get_revertible_change(
+        &self,
+        revert_token_hash: &str,
+    ) -> Result<EmailChange, EmailChangeStoreError>;
+    async fn mark_confirmed(&mut self, id: &Uuid) -> Result<(), EmailChangeStoreError>;
+    async fn mark_reverted(&mut self, id: &Uuid) -> Result<(), EmailChangeStoreError>;
+    /// Whether `email` is the old address of a confirmed change that can still be reverted,
+    /// which keeps it from being taken by another user in the meantime.
+    async fn is_reserved(&self, email: &Email) -> Result<bool, EmailChangeStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum EmailChangeStoreError {
+    #[error("Email change not found")]
+    ChangeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl From<sqlx::Error> for EmailChangeStoreError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => EmailChangeStoreError::ChangeNotFound,
+            _ => EmailChangeStoreError::UnexpectedError(err.into()),
+        }
+    }
+}
+
+/// Append-only store of security events.
+/// Each event is chained to the previous one when it is added, see `AuditRecord`.
+#[async_trait::async_trait]
+pub trait AuditEventStore: Send + Sync {
+    async fn add_event(&mut self, event: AuditEvent) -> Result<AuditRecord, AuditEventStoreError>;
+    /// Returns the records whose event matches `filter`, oldest first.
+    async fn get_events(
+        &self,
+        filter: &AuditEventFilter,
+    ) -> Result<Vec<AuditRecord>, AuditEventStoreError>;
+    /// Returns at most `limit` records following `after_sequence`, in chain order.
+    async fn get_records(
+        &self,
+        after_sequence: i64,
+        limit: i64,
+    ) -> Result<Vec<AuditRecord>, AuditEventStoreError>;
+    async fn get_chain_head(&self) -> Result<Option<AuditChainHead>, AuditEventStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum AuditEventStoreError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl From<sqlx::Error> for AuditEventStoreError {
+    fn from(err: sqlx::Error) -> Self {
+        AuditEventStoreError::UnexpectedError(err.into())
+    }
+}
+
+/// Webhook subscriptions and the outbox of deliveries queued for them.
+/// Events are queued by the `users` table triggers, in the transaction changing the account,
+/// so the stores only read and update the deliveries.
+#[async_trait::async_trait]
+pub trait WebhookStore: Send + Sync {
+    async fn add_subscription(
+        &mut self,
+        subscription: WebhookSubscription,
+    ) -> Result<(), WebhookStoreError>;
+    async fn get_subscriptions(&self) -> Result<Vec<WebhookSubscription>, WebhookStoreError>;
+    /// Stops queuing events for the subscription, its delivery log is kept.
+    async fn deactivate_subscription(&mut self, id: &Uuid) -> Result<(), WebhookStoreError>;
+    /// Returns the deliveries of a subscription, oldest event first.
+    async fn get_deliveries(
+        &self,
+        subscription_id: &Uuid,
+    ) -> Result<Vec<WebhookDelivery>, WebhookStoreError>;
+    /// Returns at most `limit` pending deliveries that are due, along with their subscription.
+    /// They are leased for `lease` so that they are not claimed again while being sent.
+    async fn claim_due_deliveries(
+        &mut self,
+        limit: i64,
+        lease: std::time::Duration,
+    ) -> Result<Vec<(WebhookDelivery, WebhookSubscription)>, WebhookStoreError>;
+    /// Saves the state of a delivery after an attempt, see `WebhookDelivery::record`.
+    async fn update_delivery(
+        &mut self,
+        delivery: &WebhookDelivery,
+    ) -> Result<(), WebhookStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum WebhookStoreError {
+    #[error("Subscription not found")]
+    SubscriptionNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl From<sqlx::Error> for WebhookStoreError {
+    fn from(err: sqlx::Error) -> Self {
+        WebhookStoreError::UnexpectedError(err.into())
+    }
+}
+
+/// Emails waiting to be sent by the email dispatcher, see `EmailDispatcher`.
+#[async_trait::async_trait]
+pub trait EmailOutboxStore: Send + Sync {
+    async fn add_email(&mut self, email: OutboxEmail) -> Result<(), EmailOutboxStoreError>;
+    async fn get_email(&self, id: &Uuid) -> Result<OutboxEmail, EmailOutboxStoreError>;
+    /// Returns at most `limit` pending emails that are due, oldest first.
+    /// They are leased for `lease` so that they are not claimed again while being sent.
+    async fn claim_due_emails(
+        &mut self,
+        limit: i64,
+        lease: std::time::Duration,
+    ) -> Result<Vec<OutboxEmail>, EmailOutboxStoreError>;
+    /// Saves the state of an email after an attempt, see `OutboxEmail::record`.
+    async fn update_email(&mut self, email: &OutboxEmail) -> Result<(), EmailOutboxStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum EmailOutboxStoreError {
+    #[error("Email not found")]
+    EmailNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl From<sqlx::Error> for EmailOutboxStoreError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => EmailOutboxStoreError::EmailNotFound,
+            _ => EmailOutboxStoreError::UnexpectedError(err.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct LoginAttemptId(String);
 impl LoginAttemptId {
     pub fn new() -> Self {
@@ -133,7 +553,7 @@ impl TwoFACode {
 
     pub fn parse(code: String) -> Result<Self> {
         // Ensure `code` is a valid 6-digit code
-        let is_valid = code.len() == 6 && code.chars().all(|c| c.is_digit(10));
+        let is_valid = code.len() == 6 && code.chars().all(|c| c.is_ascii_digit());
         is_valid
             .then(|| TwoFACode(code.clone()))
             .ok_or_else(|| eyre!("Invalid TwoFACode: {}", code))
@@ -151,3 +571,111 @@ impl AsRef<str> for TwoFACode {
         &self.0
     }
 }
+
+/// Keyed hash of a 2FA code, bound to its login attempt, or to the phone number it verifies.
+/// Only the hash is stored, so reading the store is not enough to complete a login.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwoFACodeHash(String);
+impl TwoFACodeHash {
+    pub fn new(secret: &str, login_attempt_id: &LoginAttemptId, code: &TwoFACode) -> Self {
+        let mac = TwoFACodeHash::mac(secret, login_attempt_id.as_ref(), code);
+        TwoFACodeHash(hex::encode(mac.finalize().into_bytes()))
+    }
+
+    pub fn for_phone_number(secret: &str, phone_number: &PhoneNumber, code: &TwoFACode) -> Self {
+        let mac = TwoFACodeHash::mac(secret, &phone_number_subject(phone_number), code);
+        TwoFACodeHash(hex::encode(mac.finalize().into_bytes()))
+    }
+
+    pub fn parse(hash: String) -> Result<Self> {
+        let is_valid = hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit());
+        is_valid
+            .then_some(TwoFACodeHash(hash))
+            .ok_or_else(|| eyre!("Invalid TwoFACodeHash"))
+    }
+
+    /// Checks in constant time whether `code` is the code of the login attempt.
+    pub fn matches(
+        &self,
+        secret: &str,
+        login_attempt_id: &LoginAttemptId,
+        code: &TwoFACode,
+    ) -> bool {
+        self.matches_subject(secret, login_attempt_id.as_ref(), code)
+    }
+
+    /// Checks in constant time whether `code` is the one sent to verify `phone_number`.
+    pub fn matches_phone_number(
+        &self,
+        secret: &str,
+        phone_number: &PhoneNumber,
+        code: &TwoFACode,
+    ) -> bool {
+        self.matches_subject(secret, &phone_number_subject(phone_number), code)
+    }
+
+    fn matches_subject(&self, secret: &str, subject: &str, code: &TwoFACode) -> bool {
+        let Ok(expected) = hex::decode(&self.0) else {
+            return false;
+        };
+        TwoFACodeHash::mac(secret, subject, code)
+            .verify_slice(&expected)
+            .is_ok()
+    }
+
+    fn mac(secret: &str, subject: &str, code: &TwoFACode) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(format!("{}.{}", subject, code.as_ref()).as_bytes());
+        mac
+    }
+}
+
+// Login attempt ids are UUIDs, so the prefix keeps both kinds of hashes apart
+fn phone_number_subject(phone_number: &PhoneNumber) -> String {
+    format!("phone:{}", phone_number.as_ref())
+}
+
+impl AsRef<str> for TwoFACodeHash {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_fa_code_hash_matches_only_its_code_and_login_attempt() {
+        let login_attempt_id = LoginAttemptId::new();
+        let code = TwoFACode::parse("123456".to_owned()).unwrap();
+        let hash = TwoFACodeHash::new("secret", &login_attempt_id, &code);
+        assert!(!hash.as_ref().contains(code.as_ref()));
+        assert!(hash.matches("secret", &login_attempt_id, &code));
+
+        let other_code = TwoFACode::parse("654321".to_owned()).unwrap();
+        assert!(!hash.matches("secret", &login_attempt_id, &other_code));
+        assert!(!hash.matches("other secret", &login_attempt_id, &code));
+        assert!(!hash.matches("secret", &LoginAttemptId::new(), &code));
+
+        let parsed = TwoFACodeHash::parse(hash.as_ref().to_owned()).unwrap();
+        assert_eq!(parsed, hash);
+        assert!(TwoFACodeHash::parse("123456".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_two_fa_code_hash_matches_only_its_code_and_phone_number() {
+        let phone_number = PhoneNumber::parse("+33612345678").unwrap();
+        let code = TwoFACode::parse("123456".to_owned()).unwrap();
+        let hash = TwoFACodeHash::for_phone_number("secret", &phone_number, &code);
+        assert!(!hash.as_ref().contains(code.as_ref()));
+        assert!(hash.matches_phone_number("secret", &phone_number, &code));
+
+        let other_code = TwoFACode::parse("654321".to_owned()).unwrap();
+        let other_phone_number = PhoneNumber::parse("+14155550123").unwrap();
+        assert!(!hash.matches_phone_number("secret", &phone_number, &other_code));
+        assert!(!hash.matches_phone_number("other secret", &phone_number, &code));
+        assert!(!hash.matches_phone_number("secret", &other_phone_number, &code));
+    }
+}
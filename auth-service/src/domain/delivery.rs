@@ -0,0 +1,83 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use color_eyre::eyre::{eyre, Report};
+use serde::{Deserialize, Serialize};
+
+/// Status of a message queued in an outbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    /// Every attempt failed, the delivery is abandoned
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for DeliveryStatus {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(DeliveryStatus::Pending),
+            "delivered" => Ok(DeliveryStatus::Delivered),
+            "failed" => Ok(DeliveryStatus::Failed),
+            _ => Err(eyre!("Unknown delivery status: {}", s)),
+        }
+    }
+}
+
+/// Exponential back-off between attempts: `base_delay * 2^(attempt - 1)`, capped at `max_delay`.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: i32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Delay before the next attempt, `None` if `attempts` already reached the maximum.
+    pub fn delay_after(&self, attempts: i32) -> Option<Duration> {
+        if attempts >= self.max_attempts {
+            return None;
+        }
+        let exponent = attempts.saturating_sub(1).clamp(0, 31) as u32;
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(exponent));
+        Some(delay.min(self.max_delay))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(10),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn test_retry_policy_backs_off_exponentially() {
+        let policy = retry_policy();
+        assert_eq!(policy.delay_after(1), Some(Duration::from_secs(10)));
+        assert_eq!(policy.delay_after(2), Some(Duration::from_secs(20)));
+        assert_eq!(policy.delay_after(3), Some(Duration::from_secs(40)));
+        assert_eq!(policy.delay_after(4), Some(Duration::from_secs(60)));
+        assert_eq!(policy.delay_after(5), None);
+    }
+}
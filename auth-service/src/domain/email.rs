@@ -1,23 +1,138 @@
+use std::borrow::Cow;
+
 use color_eyre::eyre::{eyre, Result};
+use unicode_normalization::UnicodeNormalization;
 
+/// An email address in its canonical form, which identifies a user: `Bob@Example.com` and
+/// `bob@example.com` are the same account.
+///
+/// Addresses follow RFC 5321 and its SMTPUTF8 extension, RFC 6531: the local part is a dot-atom,
+/// possibly with non-ASCII characters, or a quoted string, and the domain a DNS name, checked in
+/// its punycode form when internationalised. Address literals like `user@[192.0.2.1]` are rejected.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Email(pub String);
 
+// Longest forward path of SMTP without its angle brackets, RFC 5321 section 4.5.3.1.3
+const MAX_LENGTH: usize = 254;
+const MAX_LOCAL_PART_LENGTH: usize = 64;
+
 impl Email {
     pub fn parse(email: &str) -> Result<Self> {
-        if Email::is_valid(email) {
-            Ok(Email(email.to_string()))
-        } else {
-            Err(eyre!("Email not valid"))
+        let email = Email::normalize(email);
+        // Unlike a quoted local part, the domain cannot contain an @
+        let (local_part, domain) = email
+            .rsplit_once('@')
+            .ok_or_else(|| eyre!("Email not valid"))?;
+        let local_part = parse_local_part(local_part).ok_or_else(|| eyre!("Email not valid"))?;
+        let ascii_domain = parse_domain(domain).ok_or_else(|| eyre!("Email not valid"))?;
+        if local_part.len() > MAX_LOCAL_PART_LENGTH
+            || local_part.len() + 1 + ascii_domain.len() > MAX_LENGTH
+        {
+            return Err(eyre!("Email not valid"));
         }
+        let (domain, _) = idna::domain_to_unicode(&ascii_domain);
+        Ok(Email(format!("{}@{}", local_part, domain)))
     }
 
-    // Simple regex for email validation
     pub fn is_valid(email: &str) -> bool {
-        let re = regex::Regex::new(r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$")
-            .expect("Failed to compile regex");
-        re.is_match(email)
+        Email::parse(email).is_ok()
+    }
+
+    // Trimmed, lowercased and in Unicode NFC. The local part is lowercased too: mail servers
+    // are allowed to tell cases apart there, but none of the ones our users have do.
+    fn normalize(email: &str) -> String {
+        // Lowercasing can decompose characters, e.g. 'İ' becomes "i\u{307}"
+        email
+            .trim()
+            .nfc()
+            .collect::<String>()
+            .to_lowercase()
+            .nfc()
+            .collect()
+    }
+
+    /// The part before the `@`, only quoted when it is not a dot-atom, e.g. `"john doe"`
+    pub fn local_part(&self) -> &str {
+        self.split().0
+    }
+
+    /// The domain in Unicode, e.g. `bücher.example` rather than `xn--bcher-kva.example`
+    pub fn domain(&self) -> &str {
+        self.split().1
+    }
+
+    /// The domain with its internationalised labels in punycode, for the servers not supporting SMTPUTF8
+    pub fn ascii_domain(&self) -> Cow<'_, str> {
+        let domain = self.domain();
+        if domain.is_ascii() {
+            return Cow::Borrowed(domain);
+        }
+        idna::domain_to_ascii_strict(domain).map_or(Cow::Borrowed(domain), Cow::Owned)
+    }
+
+    fn split(&self) -> (&str, &str) {
+        self.0.rsplit_once('@').unwrap_or((&self.0, ""))
+    }
+}
+
+// Canonical form of a local part: a quoted string is unquoted if its content is a dot-atom,
+// otherwise it only keeps the quoted pairs it needs, e.g. `"a\b c"` becomes `"ab c"`
+fn parse_local_part(local_part: &str) -> Option<String> {
+    let Some(quoted) = local_part.strip_prefix('"') else {
+        return is_dot_atom(local_part).then(|| local_part.to_owned());
+    };
+    let mut content = String::new();
+    let mut chars = quoted.chars();
+    loop {
+        match chars.next()? {
+            // The closing quote must end the local part
+            '"' if chars.as_str().is_empty() => break,
+            '\\' => content.push(chars.next().filter(|c| matches!(c, ' '..='~'))?),
+            c if is_qtext(c) => content.push(c),
+            _ => return None,
+        }
     }
+    if is_dot_atom(&content) {
+        return Some(content);
+    }
+    let mut quoted = String::from('"');
+    for c in content.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    Some(quoted)
+}
+
+// Atoms separated by single dots
+fn is_dot_atom(s: &str) -> bool {
+    s.split('.')
+        .all(|atom| !atom.is_empty() && atom.chars().all(is_atext))
+}
+
+// atext of RFC 5322, extended to the non-ASCII characters by RFC 6532
+fn is_atext(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+        || "!#$%&'*+-/=?^_`{|}~".contains(c)
+        || (!c.is_ascii() && !c.is_control() && !c.is_whitespace())
+}
+
+// qtextSMTP of RFC 5321: printable ASCII but the quote and backslash, space included
+fn is_qtext(c: char) -> bool {
+    matches!(c, ' ' | '!' | '#'..='[' | ']'..='~') || (!c.is_ascii() && !c.is_control())
+}
+
+// ASCII form of a DNS name with at least two labels, the top level one not being numeric.
+// Empty labels, as in `toto..com`, and labels starting or ending with a hyphen are rejected.
+fn parse_domain(domain: &str) -> Option<String> {
+    let ascii_domain = idna::domain_to_ascii_strict(domain).ok()?;
+    let (_, top_level) = ascii_domain.rsplit_once('.')?;
+    if top_level.len() < 2 || top_level.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(ascii_domain)
 }
 
 impl AsRef<str> for Email {
@@ -52,8 +167,23 @@ mod tests {
             "@toto.com",
             "foo@.com",
             "foo@toto.c",
-            // "foo@toto..com", not handled by our regex
+            "foo@toto..com",
             "",
+            ".foo@toto.com",
+            "foo.@toto.com",
+            "fo..o@toto.com",
+            "foo bar@toto.com",
+            "foo@bar@toto.com",
+            "\"foo@toto.com",
+            "\"foo\"bar@toto.com",
+            "\"foo\\\u{e9}\"@toto.com",
+            "foo@-toto.com",
+            "foo@toto-.com",
+            "foo@to_to.com",
+            "foo@toto.com.",
+            "foo@toto.123",
+            "foo@[192.168.0.1]",
+            "foo@xn--zz.com",
         ];
         for email in test_cases.iter() {
             assert!(
@@ -64,14 +194,147 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_email_is_normalized() {
+        let test_cases = [
+            ("Bob@Example.COM", "bob@example.com"),
+            ("  bob@example.com\n", "bob@example.com"),
+            ("bob.SMITH+News@example.com", "bob.smith+news@example.com"),
+        ];
+        for (email, expected) in test_cases {
+            assert_eq!(Email::parse(email).unwrap().as_ref(), expected);
+        }
+        // Composed and decomposed forms of the same characters
+        assert_eq!(Email::normalize("Jos\u{e9}"), "jos\u{e9}");
+        assert_eq!(Email::normalize("JOSE\u{301}"), "jos\u{e9}");
+        assert_eq!(Email::normalize("\u{130}"), "i\u{307}");
+    }
+
+    #[test]
+    fn test_valid_email() {
+        let test_cases = [
+            ("o'brien@example.com", "o'brien@example.com"),
+            ("a@b.cd", "a@b.cd"),
+            ("user+tag@mail.example.co.uk", "user+tag@mail.example.co.uk"),
+            (
+                "!#$%&'*+-/=?^_`{|}~@example.com",
+                "!#$%&'*+-/=?^_`{|}~@example.com",
+            ),
+            // Internationalised domains, typed in Unicode or punycode
+            ("jose@B\u{fc}cher.example", "jose@b\u{fc}cher.example"),
+            ("jose@xn--bcher-kva.example", "jose@b\u{fc}cher.example"),
+            (
+                "\u{7528}\u{6237}@\u{4f8b}\u{5b50}.\u{5e7f}\u{544a}",
+                "\u{7528}\u{6237}@\u{4f8b}\u{5b50}.\u{5e7f}\u{544a}",
+            ),
+            ("user@example.xn--p1ai", "user@example.\u{440}\u{444}"),
+            // Quoted local parts keep their quotes only when needed
+            ("\"john\"@example.com", "john@example.com"),
+            ("\"John.Doe\"@example.com", "john.doe@example.com"),
+            ("\"john doe\"@example.com", "\"john doe\"@example.com"),
+            ("\"j\\ohn..doe\"@example.com", "\"john..doe\"@example.com"),
+            ("\"a\\\"b@c\"@example.com", "\"a\\\"b@c\"@example.com"),
+            ("\"\"@example.com", "\"\"@example.com"),
+        ];
+        for (email, expected) in test_cases {
+            match Email::parse(email) {
+                Ok(parsed) => assert_eq!(parsed.as_ref(), expected, "Failed for {}", email),
+                Err(_) => panic!("Expected {} to be valid", email),
+            }
+        }
+    }
+
+    #[test]
+    fn test_accessors() {
+        let email = Email::parse("\"Jo@hn\"@B\u{fc}cher.Example").unwrap();
+        assert_eq!(email.local_part(), "\"jo@hn\"");
+        assert_eq!(email.domain(), "b\u{fc}cher.example");
+        assert_eq!(email.ascii_domain(), "xn--bcher-kva.example");
+        let email = Email::parse("foo@toto.com").unwrap();
+        assert_eq!(email.ascii_domain(), Cow::Borrowed("toto.com"));
+    }
+
+    #[test]
+    fn test_lengths() {
+        let local_part = "a".repeat(64);
+        assert!(Email::parse(&format!("{}@example.com", local_part)).is_ok());
+        assert!(Email::parse(&format!("a{}@example.com", local_part)).is_err());
+
+        let label = "b".repeat(63);
+        assert!(Email::parse(&format!("a@{}.com", label)).is_ok());
+        assert!(Email::parse(&format!("a@b{}.com", label)).is_err());
+        // 64 + 1 + 189 characters
+        let domain = format!("{}.{}.{}.com", label, label, "c".repeat(57));
+        assert!(Email::parse(&format!("{}@{}", local_part, domain)).is_ok());
+        assert!(Email::parse(&format!("{}@c{}", local_part, domain)).is_err());
+    }
+
     quickcheck! {
         fn test_invalid_email_with_quickcheck(email: String) -> bool {
             let simple_check = email.is_empty() || !email.contains("@") || !email.contains(".");
             if simple_check {
-                Email::is_valid(&email) == false
+                !Email::is_valid(&email)
             } else {
                 true
             }
         }
     }
+
+    /// A valid email with letters of random case
+    #[derive(Debug, Clone)]
+    struct ValidEmail(String);
+
+    impl quickcheck::Arbitrary for ValidEmail {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            const LETTERS: &[char] = &['a', 'B', 'c', 'D', 'x', 'Y', 'z'];
+            const ALPHANUMERICS: &[char] = &['a', 'B', 'c', 'D', 'x', 'Y', 'z', '0', '7'];
+            let mut part = |chars: &[char], min: usize| -> String {
+                let length = min + usize::arbitrary(g) % 10;
+                (0..length).map(|_| *g.choose(chars).unwrap()).collect()
+            };
+            let local_part = part(ALPHANUMERICS, 1);
+            let domain = part(ALPHANUMERICS, 1);
+            ValidEmail(format!("{}@{}.{}", local_part, domain, part(LETTERS, 2)))
+        }
+    }
+
+    quickcheck! {
+        fn prop_parse_is_idempotent(email: String) -> bool {
+            match Email::parse(&email) {
+                Ok(parsed) => Email::parse(parsed.as_ref()).ok() == Some(parsed),
+                Err(_) => true,
+            }
+        }
+
+        fn prop_parsed_email_is_normalized(email: String) -> bool {
+            match Email::parse(&email) {
+                Ok(parsed) => {
+                    let parsed = parsed.as_ref();
+                    unicode_normalization::is_nfc(parsed)
+                        && parsed.to_lowercase() == parsed
+                        && parsed.trim() == parsed
+                }
+                Err(_) => true,
+            }
+        }
+
+        fn prop_parsed_email_is_its_parts(email: String) -> bool {
+            match Email::parse(&email) {
+                Ok(parsed) => format!("{}@{}", parsed.local_part(), parsed.domain()) == parsed.as_ref(),
+                Err(_) => true,
+            }
+        }
+
+        fn prop_normalize_is_idempotent(email: String) -> bool {
+            let normalized = Email::normalize(&email);
+            Email::normalize(&normalized) == normalized
+        }
+
+        fn prop_parse_ignores_case_and_surrounding_whitespace(email: ValidEmail) -> bool {
+            let lowercase = Email::parse(&email.0.to_lowercase()).unwrap();
+            let uppercase = Email::parse(&email.0.to_uppercase()).unwrap();
+            let padded = Email::parse(&format!(" {}\t", email.0)).unwrap();
+            Email::parse(&email.0).unwrap() == lowercase && uppercase == lowercase && padded == lowercase
+        }
+    }
 }
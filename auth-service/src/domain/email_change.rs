@@ -0,0 +1,144 @@
+use std::time::Duration;
+
+use chrono::{DateTime, SubsecRound, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::domain::email::Email;
+use crate::domain::user::UserId;
+
+/// A change of email requested by a user. It only applies once confirmed from the link sent to
+/// the new address, and can be reverted from the link sent to the old one until `revert_expires_at`.
+/// Only the hashes of the link tokens are stored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmailChange {
+    pub id: Uuid,
+    pub user_id: UserId,
+    pub old_email: Email,
+    pub new_email: Email,
+    pub confirm_token_hash: String,
+    pub revert_token_hash: String,
+    pub requested_at: DateTime<Utc>,
+    pub confirm_expires_at: DateTime<Utc>,
+    pub revert_expires_at: DateTime<Utc>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    pub reverted_at: Option<DateTime<Utc>>,
+}
+
+/// Tokens of the links of an email change, only known to the recipients of its emails.
+#[derive(Debug)]
+pub struct EmailChangeTokens {
+    pub confirm: String,
+    pub revert: String,
+}
+
+/// How long the links of an email change can be followed.
+#[derive(Debug, Clone, Copy)]
+pub struct EmailChangePolicy {
+    /// Sent to the new address
+    pub confirm_ttl: Duration,
+    /// Sent to the old address, counted from the request rather than the confirmation
+    pub revert_ttl: Duration,
+}
+
+impl EmailChange {
+    /// Returns the change along with the tokens of its links.
+    pub fn new(
+        user_id: UserId,
+        old_email: Email,
+        new_email: Email,
+        policy: &EmailChangePolicy,
+    ) -> (Self, EmailChangeTokens) {
+        let tokens = EmailChangeTokens {
+            confirm: new_token(),
+            revert: new_token(),
+        };
+        // Postgres only keeps microseconds
+        let requested_at = Utc::now().trunc_subsecs(6);
+        let expires_at = |ttl: Duration| {
+            chrono::Duration::from_std(ttl)
+                .ok()
+                .and_then(|ttl| requested_at.checked_add_signed(ttl))
+                .unwrap_or(DateTime::<Utc>::MAX_UTC)
+        };
+        let change = EmailChange {
+            id: Uuid::new_v4(),
+            user_id,
+            old_email,
+            new_email,
+            confirm_token_hash: EmailChange::hash(&tokens.confirm),
+            revert_token_hash: EmailChange::hash(&tokens.revert),
+            requested_at,
+            confirm_expires_at: expires_at(policy.confirm_ttl),
+            revert_expires_at: expires_at(policy.revert_ttl),
+            confirmed_at: None,
+            reverted_at: None,
+        };
+        (change, tokens)
+    }
+
+    pub fn hash(token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+
+    /// Whether the confirmation link can still be followed.
+    pub fn is_pending(&self, now: DateTime<Utc>) -> bool {
+        self.confirmed_at.is_none() && self.reverted_at.is_none() && now < self.confirm_expires_at
+    }
+
+    /// Whether the revert link can still be followed, before or after the confirmation.
+    pub fn is_revertible(&self, now: DateTime<Utc>) -> bool {
+        self.reverted_at.is_none() && now < self.revert_expires_at
+    }
+}
+
+fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_email_change_only_stores_the_hashes_of_its_tokens() {
+        let policy = EmailChangePolicy {
+            confirm_ttl: Duration::from_secs(60),
+            revert_ttl: Duration::from_secs(600),
+        };
+        let (change, tokens) = EmailChange::new(
+            UserId::new(),
+            Email::parse("old@example.com").unwrap(),
+            Email::parse("new@example.com").unwrap(),
+            &policy,
+        );
+        assert_ne!(tokens.confirm, tokens.revert);
+        assert_eq!(
+            change.confirm_token_hash,
+            EmailChange::hash(&tokens.confirm)
+        );
+        assert_eq!(change.revert_token_hash, EmailChange::hash(&tokens.revert));
+
+        let now = change.requested_at;
+        assert!(change.is_pending(now));
+        assert!(!change.is_pending(now + chrono::Duration::seconds(60)));
+        assert!(change.is_revertible(now + chrono::Duration::seconds(599)));
+        assert!(!change.is_revertible(now + chrono::Duration::seconds(600)));
+
+        let confirmed = EmailChange {
+            confirmed_at: Some(now),
+            ..change.clone()
+        };
+        assert!(!confirmed.is_pending(now));
+        assert!(confirmed.is_revertible(now));
+        let reverted = EmailChange {
+            reverted_at: Some(now),
+            ..change
+        };
+        assert!(!reverted.is_pending(now));
+        assert!(!reverted.is_revertible(now));
+    }
+}
@@ -1,18 +1,16 @@
 use crate::domain::email::Email;
 use color_eyre::eyre::Result;
 
+/// A rendered email, with HTML and plain text alternatives of the same content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailMessage {
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+}
+
 // This trait represents the interface all concrete email clients should implement
 #[async_trait::async_trait]
 pub trait EmailClient: Send + Sync {
-    async fn send_email(&self, recipient: &Email, subject: &str, content: &str) -> Result<()>;
-}
-
-pub fn two_fa_login_email_template(email: &Email, code: &str) -> (String, String) {
-    let subject = "Your 2FA Code";
-    let content = format!(
-        "Hello {},\n\nYour 2FA code is: {}\n\nThank you!",
-        email.as_ref(),
-        code
-    );
-    (subject.to_string(), content)
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<()>;
 }
@@ -0,0 +1,109 @@
+use chrono::{DateTime, SubsecRound, Utc};
+use uuid::Uuid;
+
+use crate::domain::delivery::DeliveryStatus;
+use crate::domain::email::Email;
+use crate::domain::email_client::EmailMessage;
+
+/// An email waiting in the outbox to be handed to the email provider.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxEmail {
+    pub id: Uuid,
+    pub recipient: Email,
+    pub message: EmailMessage,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub sent_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OutboxEmail {
+    pub fn new(recipient: Email, message: EmailMessage) -> Self {
+        // Postgres stores timestamps with microsecond precision
+        let now = Utc::now().trunc_subsecs(6);
+        OutboxEmail {
+            id: Uuid::new_v4(),
+            recipient,
+            message,
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            next_attempt_at: now,
+            last_attempt_at: None,
+            last_error: None,
+            sent_at: None,
+            created_at: now,
+        }
+    }
+
+    /// Applies the result of an attempt to send the email.
+    /// A failed attempt is retried at `next_attempt_at`, or abandoned if there is none.
+    /// The bodies, which may hold 2FA codes, are cleared once the email leaves the outbox.
+    pub fn record(
+        &mut self,
+        attempted_at: DateTime<Utc>,
+        error: Option<String>,
+        next_attempt_at: Option<DateTime<Utc>>,
+    ) {
+        self.attempts += 1;
+        self.last_attempt_at = Some(attempted_at);
+        if error.is_none() {
+            self.status = DeliveryStatus::Delivered;
+            self.sent_at = Some(attempted_at);
+        } else if let Some(next_attempt_at) = next_attempt_at {
+            self.next_attempt_at = next_attempt_at;
+        } else {
+            self.status = DeliveryStatus::Failed;
+        }
+        self.last_error = error;
+        if self.status != DeliveryStatus::Pending {
+            self.message.html_body.clear();
+            self.message.text_body.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email() -> OutboxEmail {
+        OutboxEmail::new(
+            Email::parse("foo@bar.com").unwrap(),
+            EmailMessage {
+                subject: "subject".to_owned(),
+                html_body: "<p>content</p>".to_owned(),
+                text_body: "content".to_owned(),
+            },
+        )
+    }
+
+    #[test]
+    fn test_record_attempts() {
+        let now = Utc::now();
+        let retry_at = now + chrono::Duration::seconds(10);
+
+        let mut outbox_email = email();
+        outbox_email.record(now, Some("timeout".to_owned()), Some(retry_at));
+        assert_eq!(outbox_email.status, DeliveryStatus::Pending);
+        assert_eq!(outbox_email.attempts, 1);
+        assert_eq!(outbox_email.next_attempt_at, retry_at);
+        assert_eq!(outbox_email.last_error.as_deref(), Some("timeout"));
+        assert_eq!(outbox_email.message, email().message);
+
+        outbox_email.record(now, None, None);
+        assert_eq!(outbox_email.status, DeliveryStatus::Delivered);
+        assert_eq!(outbox_email.sent_at, Some(now));
+        assert_eq!(outbox_email.last_error, None);
+        assert_eq!(outbox_email.message.html_body, "");
+        assert_eq!(outbox_email.message.text_body, "");
+
+        let mut outbox_email = email();
+        outbox_email.record(now, Some("timeout".to_owned()), None);
+        assert_eq!(outbox_email.status, DeliveryStatus::Failed);
+        assert_eq!(outbox_email.message.html_body, "");
+        assert_eq!(outbox_email.message.text_body, "");
+    }
+}
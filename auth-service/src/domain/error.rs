@@ -3,10 +3,13 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
+use chrono::{DateTime, Utc};
 use color_eyre::eyre::Report;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::domain::password::PasswordViolation;
+
 #[derive(Debug, Error)]
 pub enum AuthAPIError {
     #[error("User already exists")]
@@ -19,6 +22,20 @@ pub enum AuthAPIError {
     MissingToken,
     #[error("Invalid token")]
     InvalidToken,
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    #[error("Not found")]
+    NotFound,
+    #[error("Too many requests: {0}")]
+    TooManyRequests(String),
+    #[error("Weak password: {0:?}")]
+    WeakPassword(Vec<PasswordViolation>),
+    #[error("Account disabled")]
+    AccountDisabled,
+    #[error("Account suspended until {0}")]
+    AccountSuspended(DateTime<Utc>),
+    #[error("Account pending verification")]
+    AccountPendingVerification,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
@@ -26,12 +43,28 @@ pub enum AuthAPIError {
 #[derive(Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub error: String,
+    /// Rules of the password policy broken by a new password
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<ViolationResponse>,
+    /// End of the suspension of a suspended account
+    #[serde(
+        rename = "suspendedUntil",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub suspended_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ViolationResponse {
+    pub rule: String,
+    pub message: String,
 }
 
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
         log_error_chain(&self);
-        let (status, error_message) = match self {
+        let (status, error_message) = match &self {
             AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
             AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
             AuthAPIError::UnexpectedError(_) => {
@@ -42,9 +75,39 @@ impl IntoResponse for AuthAPIError {
             }
             AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid token"),
             AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing token"),
+            AuthAPIError::InvalidInput(message) => (StatusCode::BAD_REQUEST, message.as_str()),
+            AuthAPIError::NotFound => (StatusCode::NOT_FOUND, "Not found"),
+            AuthAPIError::TooManyRequests(message) => {
+                (StatusCode::TOO_MANY_REQUESTS, message.as_str())
+            }
+            AuthAPIError::WeakPassword(_) => (
+                StatusCode::BAD_REQUEST,
+                "Password does not meet the requirements",
+            ),
+            AuthAPIError::AccountDisabled => (StatusCode::FORBIDDEN, "Account disabled"),
+            AuthAPIError::AccountSuspended(_) => (StatusCode::LOCKED, "Account suspended"),
+            AuthAPIError::AccountPendingVerification => {
+                (StatusCode::FORBIDDEN, "Account pending verification")
+            }
+        };
+        let violations = match &self {
+            AuthAPIError::WeakPassword(violations) => violations
+                .iter()
+                .map(|violation| ViolationResponse {
+                    rule: violation.rule().to_owned(),
+                    message: violation.to_string(),
+                })
+                .collect(),
+            _ => vec![],
+        };
+        let suspended_until = match &self {
+            AuthAPIError::AccountSuspended(until) => Some(*until),
+            _ => None,
         };
         let body = Json(ErrorResponse {
             error: error_message.to_string(),
+            violations,
+            suspended_until,
         });
         (status, body).into_response()
     }
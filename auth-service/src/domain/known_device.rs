@@ -0,0 +1,139 @@
+use std::net::IpAddr;
+
+use chrono::{DateTime, SubsecRound, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::domain::data_stores::TokenHash;
+use crate::domain::user::UserId;
+
+/// A browser and network a user logged in from, fingerprinted from its user agent and IP prefix.
+/// The prefix rather than the full address keeps the fingerprint stable across DHCP leases.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnownDevice {
+    pub user_id: UserId,
+    pub fingerprint: String,
+    pub user_agent: Option<String>,
+    pub ip_prefix: Option<String>,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+impl KnownDevice {
+    pub fn new(user_id: UserId, user_agent: Option<String>, ip_address: Option<&str>) -> Self {
+        let ip_prefix = ip_address.and_then(ip_prefix);
+        let mut hasher = Sha256::new();
+        hasher.update(user_agent.as_deref().unwrap_or_default());
+        hasher.update("\n");
+        hasher.update(ip_prefix.as_deref().unwrap_or_default());
+        // Postgres only keeps microseconds
+        let now = Utc::now().trunc_subsecs(6);
+        Self {
+            user_id,
+            fingerprint: hex::encode(hasher.finalize()),
+            user_agent,
+            ip_prefix,
+            first_seen_at: now,
+            last_seen_at: now,
+        }
+    }
+}
+
+/// How a login device relates to the devices the user logged in from before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceSighting {
+    /// The first login of the user, there is nothing to compare with
+    FirstDevice,
+    Known,
+    New,
+}
+
+/// Allows the session opened from a new device to be revoked by the "this wasn't me" link of the alert.
+/// Only the hashes of the link token and of the session JWT are stored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionRevocation {
+    pub token_hash: String,
+    /// Session banned when the link is followed
+    pub session: TokenHash,
+}
+
+impl SessionRevocation {
+    /// Returns the revocation of the session of `session_token`, along with the token of its link.
+    pub fn new(session_token: &str) -> (Self, String) {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token = hex::encode(bytes);
+        let revocation = SessionRevocation {
+            token_hash: SessionRevocation::hash(&token),
+            session: TokenHash::of(session_token),
+        };
+        (revocation, token)
+    }
+
+    pub fn hash(token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+}
+
+/// The /24 network of an IPv4 address, or the /48 network of an IPv6 address.
+pub fn ip_prefix(ip_address: &str) -> Option<String> {
+    match ip_address.parse::<IpAddr>().ok()? {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            Some(format!("{}.{}.{}.0/24", a, b, c))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => ip_prefix(&ip.to_string()),
+            None => {
+                let [a, b, c, ..] = ip.segments();
+                Some(format!("{:x}:{:x}:{:x}::/48", a, b, c))
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ip_prefix() {
+        let cases = [
+            ("192.168.1.42", Some("192.168.1.0/24")),
+            ("2001:db8:abcd:12::1", Some("2001:db8:abcd::/48")),
+            ("::ffff:10.0.0.7", Some("10.0.0.0/24")),
+            ("not an ip", None),
+        ];
+        for (ip_address, expected) in cases {
+            assert_eq!(
+                ip_prefix(ip_address).as_deref(),
+                expected,
+                "Failed for {}",
+                ip_address
+            );
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_the_host_part_of_the_address() {
+        let user_id = UserId::new();
+        let firefox = Some("Firefox".to_owned());
+        let device = KnownDevice::new(user_id, firefox.clone(), Some("192.168.1.42"));
+        let same_network = KnownDevice::new(user_id, firefox.clone(), Some("192.168.1.7"));
+        assert_eq!(device.fingerprint, same_network.fingerprint);
+
+        let other_network = KnownDevice::new(user_id, firefox, Some("192.168.2.42"));
+        assert_ne!(device.fingerprint, other_network.fingerprint);
+        let other_browser =
+            KnownDevice::new(user_id, Some("Chrome".to_owned()), Some("192.168.1.42"));
+        assert_ne!(device.fingerprint, other_browser.fingerprint);
+    }
+
+    #[test]
+    fn test_session_revocation_only_stores_hashes() {
+        let (revocation, token) = SessionRevocation::new("jwt");
+        assert_ne!(revocation.token_hash, token);
+        assert_eq!(revocation.token_hash, SessionRevocation::hash(&token));
+        assert_eq!(revocation.session, TokenHash::of("jwt"));
+    }
+}
@@ -0,0 +1,102 @@
+use color_eyre::eyre::{eyre, Result};
+
+/// A BCP 47 language tag such as `fr` or `fr-CA`, normalised to lowercase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locale(String);
+
+impl Locale {
+    pub fn parse(tag: &str) -> Result<Self> {
+        if Locale::is_valid(tag) {
+            Ok(Locale(tag.to_ascii_lowercase()))
+        } else {
+            Err(eyre!("Locale not valid"))
+        }
+    }
+
+    // A 2 or 3 letters language, followed by subtags of up to 8 alphanumerics
+    fn is_valid(tag: &str) -> bool {
+        let mut subtags = tag.split('-');
+        let language = subtags.next().unwrap_or_default();
+        tag.len() <= 35
+            && (2..=3).contains(&language.len())
+            && language.chars().all(|c| c.is_ascii_alphabetic())
+            && subtags.all(|subtag| {
+                (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+            })
+    }
+
+    /// Picks the preferred locale of an `Accept-Language` header, e.g. `fr-CH, fr;q=0.9, en;q=0.8`.
+    /// Wildcards and invalid tags are ignored.
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        let mut preferred: Option<(Locale, f32)> = None;
+        for entry in header.split(',') {
+            let mut parts = entry.split(';').map(str::trim);
+            let Ok(locale) = Locale::parse(parts.next().unwrap_or_default()) else {
+                continue;
+            };
+            let quality = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok());
+            let Some(quality) = quality.filter(|q| *q > 0.0) else {
+                continue;
+            };
+            // On ties, the first entry wins
+            if preferred.as_ref().is_none_or(|(_, best)| quality > *best) {
+                preferred = Some((locale, quality));
+            }
+        }
+        preferred.map(|(locale, _)| locale)
+    }
+
+    /// The primary language subtag, e.g. `fr` for `fr-ca`
+    pub fn language(&self) -> &str {
+        self.0.split('-').next().unwrap_or(&self.0)
+    }
+}
+
+impl AsRef<str> for Locale {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        for tag in ["en", "fr-CA", "zh-Hant-TW", "es-419"] {
+            assert!(Locale::parse(tag).is_ok(), "Failed for tag: {}", tag);
+        }
+        for tag in ["", "e", "english", "fr_CA", "fr-", "fr-toolongsubtag", "*"] {
+            assert!(Locale::parse(tag).is_err(), "Failed for tag: {}", tag);
+        }
+        let locale = Locale::parse("fr-CA").unwrap();
+        assert_eq!(locale.as_ref(), "fr-ca");
+        assert_eq!(locale.language(), "fr");
+    }
+
+    #[test]
+    fn test_from_accept_language() {
+        let cases = [
+            ("fr-CH, fr;q=0.9, en;q=0.8, *;q=0.5", Some("fr-ch")),
+            ("en;q=0.5, de", Some("de")),
+            ("en, fr", Some("en")),
+            ("*, en;q=0.1", Some("en")),
+            ("fr;q=0, en;q=0.2", Some("en")),
+            ("not a locale", None),
+            ("", None),
+        ];
+        for (header, expected) in cases {
+            assert_eq!(
+                Locale::from_accept_language(header)
+                    .as_ref()
+                    .map(AsRef::as_ref),
+                expected,
+                "Failed for header: {}",
+                header
+            );
+        }
+    }
+}
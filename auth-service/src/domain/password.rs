@@ -1,21 +1,42 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use chrono::{DateTime, Utc};
+
 use color_eyre::eyre::{eyre, Result};
 use secrecy::{ExposeSecret, Secret};
+use thiserror::Error;
 
 #[derive(Debug, Clone)]
 pub struct Password(Secret<String>);
 
 impl Password {
+    /// Checks `password` against the default policy, see `PasswordPolicy::default`
     pub fn parse(password: Secret<String>) -> Result<Self> {
-        if Password::is_valid(password.expose_secret()) {
+        Password::parse_with_policy(password, &PasswordPolicy::default())
+            .map_err(|_| eyre!("Password not valid"))
+    }
+
+    /// Checks a new password, returning every rule of `policy` it breaks
+    pub fn parse_with_policy(
+        password: Secret<String>,
+        policy: &PasswordPolicy,
+    ) -> Result<Self, Vec<PasswordViolation>> {
+        let violations = policy.check(password.expose_secret());
+        if violations.is_empty() {
             Ok(Password(password))
         } else {
-            Err(eyre!("Password not valid"))
+            Err(violations)
         }
     }
 
-    // Password must be at least 8 characters long and contain at least one digit
-    pub fn is_valid(password: &str) -> bool {
-        password.len() >= 8 && password.chars().any(|c| c.is_digit(10))
+    // A password typed at login is only checked for being non-empty,
+    // the policy may have changed since the user chose it
+    pub fn parse_credential(password: Secret<String>) -> Result<Self> {
+        if password.expose_secret().is_empty() {
+            Err(eyre!("Password is empty"))
+        } else {
+            Ok(Password(password))
+        }
     }
 
     // This is to create a `Password` instance but without fulfilling the password requirements
@@ -39,6 +60,195 @@ impl AsRef<Secret<String>> for Password {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharacterClass {
+    Lowercase,
+    Uppercase,
+    Digit,
+    /// Anything else: punctuation, spaces, letters of scripts without case...
+    Symbol,
+}
+
+impl CharacterClass {
+    pub const ALL: [CharacterClass; 4] = [
+        CharacterClass::Lowercase,
+        CharacterClass::Uppercase,
+        CharacterClass::Digit,
+        CharacterClass::Symbol,
+    ];
+
+    pub fn of(c: char) -> Self {
+        if c.is_ascii_digit() {
+            CharacterClass::Digit
+        } else if c.is_lowercase() {
+            CharacterClass::Lowercase
+        } else if c.is_uppercase() {
+            CharacterClass::Uppercase
+        } else {
+            CharacterClass::Symbol
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CharacterClass::Lowercase => "lowercase",
+            CharacterClass::Uppercase => "uppercase",
+            CharacterClass::Digit => "digit",
+            CharacterClass::Symbol => "symbol",
+        }
+    }
+
+    // Number of characters an attacker has to try for each position
+    fn pool_size(&self) -> f64 {
+        match self {
+            CharacterClass::Lowercase | CharacterClass::Uppercase => 26.0,
+            CharacterClass::Digit => 10.0,
+            // Printable ASCII punctuation and space
+            CharacterClass::Symbol => 33.0,
+        }
+    }
+}
+
+impl FromStr for CharacterClass {
+    type Err = color_eyre::eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        CharacterClass::ALL
+            .into_iter()
+            .find(|class| class.as_str() == s)
+            .ok_or_else(|| eyre!("Unknown character class: {}", s))
+    }
+}
+
+impl fmt::Display for CharacterClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CharacterClass::Lowercase => write!(f, "lowercase letter"),
+            CharacterClass::Uppercase => write!(f, "uppercase letter"),
+            CharacterClass::Digit => write!(f, "digit"),
+            CharacterClass::Symbol => write!(f, "symbol"),
+        }
+    }
+}
+
+/// A rule of the password policy broken by a new password.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum PasswordViolation {
+    #[error("Password must be at least {0} characters long")]
+    TooShort(usize),
+    #[error("Password must be at most {0} characters long")]
+    TooLong(usize),
+    #[error("Password must contain at least one {0}")]
+    MissingCharacter(CharacterClass),
+    #[error("Password is too easy to guess, make it longer or mix more kinds of characters")]
+    TooGuessable,
+    #[error("Password appears in a list of breached passwords")]
+    Breached,
+    #[error("Password was used recently, choose another one")]
+    Reused,
+}
+
+impl PasswordViolation {
+    /// Stable identifier of the broken rule, for clients to map to their own messages
+    pub fn rule(&self) -> &'static str {
+        match self {
+            PasswordViolation::TooShort(_) => "min_length",
+            PasswordViolation::TooLong(_) => "max_length",
+            PasswordViolation::MissingCharacter(class) => class.as_str(),
+            PasswordViolation::TooGuessable => "entropy",
+            PasswordViolation::Breached => "breached",
+            PasswordViolation::Reused => "history",
+        }
+    }
+}
+
+/// Requirements of the passwords chosen by the users, and how long they can keep them.
+/// Lengths are counted in characters, not bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    /// Classes of characters a password must contain at least one of
+    pub required_classes: Vec<CharacterClass>,
+    /// Minimum strength according to `estimate_entropy`, 0 disables the check
+    pub min_entropy_bits: f64,
+    /// Number of last passwords, including the current one, a new password must differ from
+    pub history_size: usize,
+    /// Passwords older than this must be changed at the next login
+    pub max_age: Option<Duration>,
+}
+
+impl Default for PasswordPolicy {
+    // At least 8 characters long and containing at least one digit
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: 128,
+            required_classes: vec![CharacterClass::Digit],
+            min_entropy_bits: 0.0,
+            history_size: 0,
+            max_age: None,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    pub fn check(&self, password: &str) -> Vec<PasswordViolation> {
+        let mut violations = Vec::new();
+        let length = password.chars().count();
+        if length < self.min_length {
+            violations.push(PasswordViolation::TooShort(self.min_length));
+        }
+        if length > self.max_length {
+            violations.push(PasswordViolation::TooLong(self.max_length));
+        }
+        for class in &self.required_classes {
+            if !password.chars().any(|c| CharacterClass::of(c) == *class) {
+                violations.push(PasswordViolation::MissingCharacter(*class));
+            }
+        }
+        if estimate_entropy(password) < self.min_entropy_bits {
+            violations.push(PasswordViolation::TooGuessable);
+        }
+        violations
+    }
+
+    pub fn is_expired(&self, password_changed_at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        // A max age too long to be represented never expires
+        self.max_age
+            .and_then(|max_age| chrono::Duration::from_std(max_age).ok())
+            .and_then(|max_age| password_changed_at.checked_add_signed(max_age))
+            .is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// Rough strength of `password` in bits: each character is worth the log2 of the size of the
+/// classes of characters the password uses, except repeats and sequences like `aaaa` or `1234`
+/// which are worth a single bit.
+pub fn estimate_entropy(password: &str) -> f64 {
+    let pool_size: f64 = CharacterClass::ALL
+        .iter()
+        .filter(|class| password.chars().any(|c| CharacterClass::of(c) == **class))
+        .map(|class| class.pool_size())
+        .sum();
+    if pool_size == 0.0 {
+        return 0.0;
+    }
+    let bits_per_char = pool_size.log2();
+
+    let mut entropy = 0.0;
+    let mut previous: Option<char> = None;
+    for c in password.chars() {
+        let predictable = previous.is_some_and(|p| {
+            let distance = c as i64 - p as i64;
+            (-1..=1).contains(&distance)
+        });
+        entropy += if predictable { 1.0 } else { bits_per_char };
+        previous = Some(c);
+    }
+    entropy
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -76,10 +286,91 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_policy_reports_every_violation() {
+        let policy = PasswordPolicy {
+            min_length: 12,
+            max_length: 64,
+            required_classes: CharacterClass::ALL.to_vec(),
+            min_entropy_bits: 60.0,
+            ..PasswordPolicy::default()
+        };
+        let secret = Secret::new("abc".to_owned());
+        assert_eq!(
+            Password::parse_with_policy(secret, &policy).unwrap_err(),
+            vec![
+                PasswordViolation::TooShort(12),
+                PasswordViolation::MissingCharacter(CharacterClass::Uppercase),
+                PasswordViolation::MissingCharacter(CharacterClass::Digit),
+                PasswordViolation::MissingCharacter(CharacterClass::Symbol),
+                PasswordViolation::TooGuessable,
+            ]
+        );
+
+        let secret = Secret::new("Tr0ub4dor&3-horse".to_owned());
+        assert!(Password::parse_with_policy(secret, &policy).is_ok());
+
+        let secret = Secret::new(format!("Aa1!{}", "x".repeat(61)));
+        assert_eq!(
+            Password::parse_with_policy(secret, &policy).unwrap_err(),
+            vec![PasswordViolation::TooLong(64)]
+        );
+    }
+
+    #[test]
+    fn test_length_is_counted_in_characters() {
+        let policy = PasswordPolicy {
+            required_classes: vec![],
+            ..PasswordPolicy::default()
+        };
+        assert!(policy
+            .check("pâté")
+            .contains(&PasswordViolation::TooShort(8)));
+        assert!(policy.check("pâtéàlacrème").is_empty());
+    }
+
+    #[test]
+    fn test_entropy_penalizes_repeats_and_sequences() {
+        assert_eq!(estimate_entropy(""), 0.0);
+        assert!(estimate_entropy("aaaaaaaaaaaa") < estimate_entropy("qmzkrwpxjvtb"));
+        assert!(estimate_entropy("abcdefgh1234") < estimate_entropy("hfbdaecg3142"));
+        // Mixing classes makes each character worth more
+        assert!(estimate_entropy("qmzkrwpx") < estimate_entropy("qmZkr#px"));
+    }
+
+    #[test]
+    fn test_password_expires_after_its_max_age() {
+        let changed_at = Utc::now();
+        let day = chrono::Duration::days(1);
+        assert!(!PasswordPolicy::default().is_expired(changed_at, changed_at + day * 1000));
+
+        let policy = PasswordPolicy {
+            max_age: Some(Duration::from_secs(90 * 24 * 60 * 60)),
+            ..PasswordPolicy::default()
+        };
+        assert!(!policy.is_expired(changed_at, changed_at + day * 89));
+        assert!(policy.is_expired(changed_at, changed_at + day * 90));
+    }
+
+    #[test]
+    fn test_character_class_round_trip() {
+        for class in CharacterClass::ALL {
+            assert_eq!(class.as_str().parse::<CharacterClass>().unwrap(), class);
+        }
+        assert!("emoji".parse::<CharacterClass>().is_err());
+    }
+
+    #[test]
+    fn test_credential_is_only_checked_for_being_non_empty() {
+        assert!(Password::parse_credential(Secret::new("x".to_owned())).is_ok());
+        assert!(Password::parse_credential(Secret::new(String::new())).is_err());
+    }
+
     // Dumb tests just to familirize with the quickcheck crate
     quickcheck! {
             fn prop_valid_password_with_quickcheck(password: String) -> bool {
-                let simple_check = password.len() >= 8 && password.chars().any(|c| c.is_digit(10));
+                let length = password.chars().count();
+                let simple_check = (8..=128).contains(&length) && password.chars().any(|c| c.is_ascii_digit());
                 let secret = Secret::new(password);
                 if simple_check {
                     Password::parse(secret).is_ok()
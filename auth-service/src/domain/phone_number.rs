@@ -0,0 +1,64 @@
+use color_eyre::eyre::{eyre, Result};
+
+/// A phone number in E.164 format, e.g. `+33612345678`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PhoneNumber(String);
+
+impl PhoneNumber {
+    /// Accepts the usual separators, e.g. `+1 (555) 010-9999`, which are removed.
+    pub fn parse(phone_number: &str) -> Result<Self> {
+        let normalised: String = phone_number
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')'))
+            .collect();
+        if PhoneNumber::is_valid(&normalised) {
+            Ok(PhoneNumber(normalised))
+        } else {
+            Err(eyre!("Phone number not valid"))
+        }
+    }
+
+    // A country code never starts with 0, and E.164 numbers have at most 15 digits
+    fn is_valid(phone_number: &str) -> bool {
+        let Some(digits) = phone_number.strip_prefix('+') else {
+            return false;
+        };
+        (8..=15).contains(&digits.len())
+            && !digits.starts_with('0')
+            && digits.chars().all(|c| c.is_ascii_digit())
+    }
+}
+
+impl AsRef<str> for PhoneNumber {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let cases = [
+            ("+33612345678", "+33612345678"),
+            ("+1 (555) 010-9999", "+15550109999"),
+            ("+44 20.7946.0000", "+442079460000"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(PhoneNumber::parse(input).unwrap().as_ref(), expected);
+        }
+        for input in [
+            "",
+            "0612345678",
+            "+0612345678",
+            "+1234567",
+            "+1234567890123456",
+            "+3361234567a",
+            "++33612345678",
+        ] {
+            assert!(PhoneNumber::parse(input).is_err(), "Failed for: {}", input);
+        }
+    }
+}
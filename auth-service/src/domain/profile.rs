@@ -0,0 +1,86 @@
+use color_eyre::eyre::{eyre, Result};
+
+use super::locale::Locale;
+
+/// The name a user is greeted with, trimmed and without control characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayName(String);
+
+impl DisplayName {
+    const MAX_LENGTH: usize = 100;
+
+    pub fn parse(name: &str) -> Result<Self> {
+        let name = name.trim();
+        if name.is_empty() {
+            Err(eyre!("Display name is empty"))
+        } else if name.chars().count() > DisplayName::MAX_LENGTH {
+            Err(eyre!(
+                "Display name is longer than {} characters",
+                DisplayName::MAX_LENGTH
+            ))
+        } else if name.chars().any(char::is_control) {
+            Err(eyre!("Display name contains control characters"))
+        } else {
+            Ok(DisplayName(name.to_owned()))
+        }
+    }
+}
+
+impl AsRef<str> for DisplayName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// An IANA time zone such as `Europe/Paris`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeZone(chrono_tz::Tz);
+
+impl TimeZone {
+    pub fn parse(name: &str) -> Result<Self> {
+        name.parse::<chrono_tz::Tz>()
+            .map(TimeZone)
+            .map_err(|_| eyre!("Unknown time zone: {}", name))
+    }
+}
+
+impl AsRef<str> for TimeZone {
+    fn as_ref(&self) -> &str {
+        self.0.name()
+    }
+}
+
+/// Changes to the profile of a user. `None` leaves an attribute untouched, `Some(None)` clears it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProfileUpdate {
+    pub display_name: Option<Option<DisplayName>>,
+    pub locale: Option<Option<Locale>>,
+    pub timezone: Option<Option<TimeZone>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_display_name() {
+        assert_eq!(
+            DisplayName::parse("  Zoé Martin ").unwrap().as_ref(),
+            "Zoé Martin"
+        );
+        assert!(DisplayName::parse(&"é".repeat(100)).is_ok());
+        for name in ["", "   ", "Zoé\nMartin", &"é".repeat(101)] {
+            assert!(DisplayName::parse(name).is_err(), "Failed for {:?}", name);
+        }
+    }
+
+    #[test]
+    fn test_parse_time_zone() {
+        for name in ["Europe/Paris", "America/Argentina/Buenos_Aires", "UTC"] {
+            assert_eq!(TimeZone::parse(name).unwrap().as_ref(), name);
+        }
+        for name in ["", "Paris", "europe/paris", "+02:00"] {
+            assert!(TimeZone::parse(name).is_err(), "Failed for {:?}", name);
+        }
+    }
+}
@@ -0,0 +1,8 @@
+use crate::domain::phone_number::PhoneNumber;
+use color_eyre::eyre::Result;
+
+// This trait represents the interface all concrete SMS clients should implement
+#[async_trait::async_trait]
+pub trait SmsClient: Send + Sync {
+    async fn send_sms(&self, recipient: &PhoneNumber, content: &str) -> Result<()>;
+}
@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use chrono::{DateTime, SubsecRound, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::domain::user::UserId;
+
+/// A browser remembered by the user at the end of a 2FA login.
+/// Logging in from it skips 2FA until it expires or is revoked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrustedDevice {
+    pub id: Uuid,
+    pub user_id: UserId,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl TrustedDevice {
+    pub fn new(user_id: UserId, user_agent: Option<String>, ttl: Duration) -> Self {
+        // Postgres only keeps microseconds
+        let created_at = Utc::now().trunc_subsecs(6);
+        let ttl = chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX);
+        Self {
+            id: Uuid::new_v4(),
+            user_id,
+            user_agent,
+            created_at,
+            expires_at: created_at
+                .checked_add_signed(ttl)
+                .unwrap_or(DateTime::<Utc>::MAX_UTC),
+        }
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now
+    }
+}
+
+/// Value of the trusted device cookie: `<device id>.<hex HMAC-SHA256 of the user id and device id>`.
+/// The signature binds the cookie to its user across email changes, the device must still be in the store to be trusted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrustedDeviceToken(String);
+impl TrustedDeviceToken {
+    pub fn new(secret: &str, user_id: &UserId, device_id: &Uuid) -> Self {
+        let signature = TrustedDeviceToken::mac(secret, user_id, device_id)
+            .finalize()
+            .into_bytes();
+        TrustedDeviceToken(format!("{}.{}", device_id, hex::encode(signature)))
+    }
+
+    /// Returns the id of the device if `token` was signed for `user_id`.
+    pub fn verify(token: &str, secret: &str, user_id: &UserId) -> Option<Uuid> {
+        let (device_id, signature) = token.split_once('.')?;
+        let device_id = Uuid::parse_str(device_id).ok()?;
+        let signature = hex::decode(signature).ok()?;
+        TrustedDeviceToken::mac(secret, user_id, &device_id)
+            .verify_slice(&signature)
+            .ok()
+            .map(|_| device_id)
+    }
+
+    fn mac(secret: &str, user_id: &UserId, device_id: &Uuid) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(format!("{}.{}", user_id, device_id).as_bytes());
+        mac
+    }
+}
+
+impl AsRef<str> for TrustedDeviceToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trusted_device_token_is_bound_to_its_user() {
+        let user_id = UserId::new();
+        let device_id = Uuid::new_v4();
+        let token = TrustedDeviceToken::new("secret", &user_id, &device_id);
+        assert_eq!(
+            TrustedDeviceToken::verify(token.as_ref(), "secret", &user_id),
+            Some(device_id)
+        );
+
+        let other_user_id = UserId::new();
+        assert_eq!(
+            TrustedDeviceToken::verify(token.as_ref(), "secret", &other_user_id),
+            None
+        );
+        assert_eq!(
+            TrustedDeviceToken::verify(token.as_ref(), "other secret", &user_id),
+            None
+        );
+
+        // Another device id with the same signature
+        let (_, signature) = token.as_ref().split_once('.').unwrap();
+        let forged = format!("{}.{}", Uuid::new_v4(), signature);
+        assert_eq!(
+            TrustedDeviceToken::verify(&forged, "secret", &user_id),
+            None
+        );
+        assert_eq!(
+            TrustedDeviceToken::verify("garbage", "secret", &user_id),
+            None
+        );
+    }
+
+    #[test]
+    fn test_trusted_device_expires_after_its_ttl() {
+        let user_id = UserId::new();
+        let device = TrustedDevice::new(user_id, None, Duration::from_secs(60));
+        assert!(!device.is_expired(device.created_at));
+        assert!(device.is_expired(device.created_at + chrono::Duration::seconds(60)));
+    }
+}
@@ -1,14 +1,164 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, SubsecRound, Utc};
+use color_eyre::eyre::{eyre, Report, Result};
 use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 use super::data_stores::UserStoreError;
 use super::email::Email;
+use super::locale::Locale;
 use super::password::Password;
+use super::phone_number::PhoneNumber;
+use super::profile::{DisplayName, ProfileUpdate, TimeZone};
+
+/// Identifies a user for good, unlike their email which they can change.
+/// It is the `sub` of their JWTs, which thus don't reveal their email.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct UserId(Uuid);
+
+impl UserId {
+    pub fn new() -> Self {
+        UserId(Uuid::new_v4())
+    }
+
+    pub fn parse(id: &str) -> Result<Self> {
+        Uuid::parse_str(id)
+            .map(UserId)
+            .map_err(|_| eyre!("Invalid UserId: {}", id))
+    }
+}
+
+impl Default for UserId {
+    fn default() -> Self {
+        UserId::new()
+    }
+}
+
+impl From<Uuid> for UserId {
+    fn from(id: Uuid) -> Self {
+        UserId(id)
+    }
+}
+
+impl AsRef<Uuid> for UserId {
+    fn as_ref(&self) -> &Uuid {
+        &self.0
+    }
+}
+
+impl fmt::Display for UserId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Where the 2FA codes of a user are sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TwoFAChannel {
+    #[default]
+    Email,
+    /// Requires a verified phone number
+    Sms,
+}
+
+impl TwoFAChannel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TwoFAChannel::Email => "email",
+            TwoFAChannel::Sms => "sms",
+        }
+    }
+}
+
+impl FromStr for TwoFAChannel {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "email" => Ok(TwoFAChannel::Email),
+            "sms" => Ok(TwoFAChannel::Sms),
+            _ => Err(eyre!("Unknown 2FA channel: {}", s)),
+        }
+    }
+}
+
+/// Whether a user can log in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum AccountStatus {
+    #[default]
+    Active,
+    /// Until an admin enables the account again
+    Disabled,
+    /// Lifted on its own at `until`
+    Suspended { until: DateTime<Utc> },
+    /// The user has yet to prove they own their email
+    PendingVerification,
+}
+
+impl AccountStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountStatus::Active => "active",
+            AccountStatus::Disabled => "disabled",
+            AccountStatus::Suspended { .. } => "suspended",
+            AccountStatus::PendingVerification => "pending_verification",
+        }
+    }
+
+    /// Rebuilds a status from its stored name, and the end of the suspension for `suspended`.
+    pub fn from_parts(status: &str, suspended_until: Option<DateTime<Utc>>) -> Result<Self> {
+        match (status, suspended_until) {
+            ("active", None) => Ok(AccountStatus::Active),
+            ("disabled", None) => Ok(AccountStatus::Disabled),
+            ("suspended", Some(until)) => Ok(AccountStatus::Suspended { until }),
+            ("pending_verification", None) => Ok(AccountStatus::PendingVerification),
+            _ => Err(eyre!("Invalid account status: {}", status)),
+        }
+    }
+
+    pub fn suspended_until(&self) -> Option<DateTime<Utc>> {
+        match self {
+            AccountStatus::Suspended { until } => Some(*until),
+            _ => None,
+        }
+    }
+
+    /// The status in force at `now`, a suspension that ended being `Active`.
+    pub fn at(&self, now: DateTime<Utc>) -> AccountStatus {
+        match self {
+            AccountStatus::Suspended { until } if *until <= now => AccountStatus::Active,
+            status => *status,
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct User {
+    pub(crate) id: UserId,
     pub(crate) email: Email,
     pub(crate) password: Password,
     pub(crate) requires_2fa: bool,
+    /// Preferred language of the emails and text messages, see `Language::negotiate`
+    pub(crate) locale: Option<Locale>,
+    /// Only set once the user proved they receive text messages on it
+    pub(crate) phone_number: Option<PhoneNumber>,
+    pub(crate) two_fa_channel: TwoFAChannel,
+    /// When the password was chosen, it must be changed once older than `PasswordPolicy::max_age`
+    pub(crate) password_changed_at: DateTime<Utc>,
+    pub(crate) display_name: Option<DisplayName>,
+    pub(crate) timezone: Option<TimeZone>,
+    pub(crate) created_at: DateTime<Utc>,
+    /// When the user last completed a login, see `UserStore::record_login`
+    pub(crate) last_login_at: Option<DateTime<Utc>>,
+    pub(crate) status: AccountStatus,
+    /// Incremented on every status change and revocation of the sessions, only the JWTs carrying the current version are valid
+    pub(crate) session_version: i32,
 }
 
 impl User {
@@ -19,11 +169,48 @@ impl User {
     ) -> Result<Self, UserStoreError> {
         let email = Email::parse(&email).map_err(|_| UserStoreError::InvalidCredentials)?;
         let password = Password::parse(password).map_err(|_| UserStoreError::InvalidCredentials)?;
-        Ok(User {
+        Ok(User::from_parts(email, password, requires_2fa))
+    }
+
+    /// Creates a user from an email and a password already validated, e.g. against the configured policy
+    pub fn from_parts(email: Email, password: Password, requires_2fa: bool) -> Self {
+        // Postgres only keeps microseconds
+        let now = Utc::now().trunc_subsecs(6);
+        User {
+            id: UserId::new(),
             email,
             password,
             requires_2fa,
-        })
+            locale: None,
+            phone_number: None,
+            two_fa_channel: TwoFAChannel::Email,
+            password_changed_at: now,
+            display_name: None,
+            timezone: None,
+            created_at: now,
+            last_login_at: None,
+            status: AccountStatus::Active,
+            session_version: 0,
+        }
+    }
+
+    pub fn with_id(mut self, id: UserId) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn id(&self) -> UserId {
+        self.id
+    }
+
+    pub fn with_password_changed_at(mut self, password_changed_at: DateTime<Utc>) -> Self {
+        self.password_changed_at = password_changed_at;
+        self
+    }
+
+    pub fn with_session_version(mut self, session_version: i32) -> Self {
+        self.session_version = session_version;
+        self
     }
 
     pub(crate) fn new_with_fake_password(
@@ -33,10 +220,110 @@ impl User {
     ) -> Result<Self, UserStoreError> {
         let email = Email::parse(&email).map_err(|_| UserStoreError::InvalidCredentials)?;
         let password = Password::fake(password);
-        Ok(User {
-            email,
-            password,
-            requires_2fa,
-        })
+        Ok(User::from_parts(email, password, requires_2fa))
+    }
+
+    pub fn with_locale(mut self, locale: Option<Locale>) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    pub fn with_phone_number(
+        mut self,
+        phone_number: Option<PhoneNumber>,
+        two_fa_channel: TwoFAChannel,
+    ) -> Self {
+        self.phone_number = phone_number;
+        self.two_fa_channel = two_fa_channel;
+        self
+    }
+
+    /// Sets the profile attributes a user can change themselves, see `ProfileUpdate`.
+    pub fn with_profile(
+        mut self,
+        display_name: Option<DisplayName>,
+        timezone: Option<TimeZone>,
+    ) -> Self {
+        self.display_name = display_name;
+        self.timezone = timezone;
+        self
+    }
+
+    pub fn with_activity(
+        mut self,
+        created_at: DateTime<Utc>,
+        last_login_at: Option<DateTime<Utc>>,
+    ) -> Self {
+        self.created_at = created_at;
+        self.last_login_at = last_login_at;
+        self
+    }
+
+    pub fn with_status(mut self, status: AccountStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn status(&self) -> AccountStatus {
+        self.status
+    }
+
+    pub fn apply_profile_update(&mut self, update: ProfileUpdate) {
+        if let Some(display_name) = update.display_name {
+            self.display_name = display_name;
+        }
+        if let Some(locale) = update.locale {
+            self.locale = locale;
+        }
+        if let Some(timezone) = update.timezone {
+            self.timezone = timezone;
+        }
+    }
+
+    /// The channel the 2FA codes are actually sent to, email unless a verified phone number is set
+    pub fn effective_two_fa_channel(&self) -> TwoFAChannel {
+        match (self.two_fa_channel, &self.phone_number) {
+            (TwoFAChannel::Sms, Some(_)) => TwoFAChannel::Sms,
+            _ => TwoFAChannel::Email,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_status_round_trip() {
+        let until = Utc::now().trunc_subsecs(6);
+        let statuses = [
+            AccountStatus::Active,
+            AccountStatus::Disabled,
+            AccountStatus::Suspended { until },
+            AccountStatus::PendingVerification,
+        ];
+        for status in statuses {
+            assert_eq!(
+                AccountStatus::from_parts(status.as_str(), status.suspended_until()).unwrap(),
+                status
+            );
+        }
+        assert!(AccountStatus::from_parts("suspended", None).is_err());
+        assert!(AccountStatus::from_parts("disabled", Some(until)).is_err());
+        assert!(AccountStatus::from_parts("deleted", None).is_err());
+    }
+
+    #[test]
+    fn test_suspension_ends_on_its_own() {
+        let now = Utc::now();
+        let suspended = AccountStatus::Suspended {
+            until: now + chrono::Duration::hours(1),
+        };
+        assert_eq!(suspended.at(now), suspended);
+        assert_eq!(
+            suspended.at(now + chrono::Duration::hours(1)),
+            AccountStatus::Active
+        );
+        assert_eq!(AccountStatus::Disabled.at(now), AccountStatus::Disabled);
     }
 }
@@ -0,0 +1,277 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, SubsecRound, Utc};
+use color_eyre::eyre::{eyre, Report};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use uuid::Uuid;
+
+pub use crate::domain::delivery::{DeliveryStatus, RetryPolicy};
+
+/// Header carrying the id of the event, identical across retries
+pub const WEBHOOK_ID_HEADER: &str = "X-Webhook-Id";
+/// Header carrying the unix timestamp at which the request was signed
+pub const WEBHOOK_TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+/// Header carrying `v1=<hex HMAC-SHA256>` of `<id>.<timestamp>.<body>`
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+/// Account lifecycle events that can be subscribed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebhookEventType {
+    #[serde(rename = "user.created")]
+    UserCreated,
+    #[serde(rename = "user.password_changed")]
+    PasswordChanged,
+    #[serde(rename = "user.deleted")]
+    UserDeleted,
+}
+
+impl WebhookEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEventType::UserCreated => "user.created",
+            WebhookEventType::PasswordChanged => "user.password_changed",
+            WebhookEventType::UserDeleted => "user.deleted",
+        }
+    }
+}
+
+impl fmt::Display for WebhookEventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for WebhookEventType {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user.created" => Ok(WebhookEventType::UserCreated),
+            "user.password_changed" => Ok(WebhookEventType::PasswordChanged),
+            "user.deleted" => Ok(WebhookEventType::UserDeleted),
+            _ => Err(eyre!("Unknown webhook event type: {}", s)),
+        }
+    }
+}
+
+/// An event as sent in the body of webhook requests.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookEvent {
+    pub id: Uuid,
+    #[serde(rename = "type")]
+    pub event_type: WebhookEventType,
+    #[serde(rename = "occurredAt")]
+    pub occurred_at: DateTime<Utc>,
+    pub data: serde_json::Value,
+}
+
+impl WebhookEvent {
+    pub fn new(event_type: WebhookEventType, data: serde_json::Value) -> Self {
+        WebhookEvent {
+            id: Uuid::new_v4(),
+            event_type,
+            // Postgres stores timestamps with microsecond precision
+            occurred_at: Utc::now().trunc_subsecs(6),
+            data,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookSubscription {
+    pub id: Uuid,
+    pub url: String,
+    /// Key used to sign the payloads, only disclosed when the subscription is created
+    #[serde(skip_serializing, default)]
+    pub secret: String,
+    #[serde(rename = "eventTypes")]
+    pub event_types: Vec<WebhookEventType>,
+    pub active: bool,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl WebhookSubscription {
+    pub fn new(url: String, event_types: Vec<WebhookEventType>) -> Self {
+        let mut secret = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+        WebhookSubscription {
+            id: Uuid::new_v4(),
+            url,
+            secret: hex::encode(secret),
+            event_types,
+            active: true,
+            created_at: Utc::now().trunc_subsecs(6),
+        }
+    }
+
+    pub fn is_subscribed_to(&self, event_type: WebhookEventType) -> bool {
+        self.active && self.event_types.contains(&event_type)
+    }
+}
+
+/// One event to be sent to one subscription, along with the log of the attempts so far.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    #[serde(rename = "subscriptionId")]
+    pub subscription_id: Uuid,
+    pub event: WebhookEvent,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    #[serde(rename = "nextAttemptAt")]
+    pub next_attempt_at: DateTime<Utc>,
+    #[serde(rename = "lastAttemptAt")]
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    #[serde(rename = "lastResponseStatus")]
+    pub last_response_status: Option<i32>,
+    #[serde(rename = "lastError")]
+    pub last_error: Option<String>,
+    #[serde(rename = "deliveredAt")]
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+impl WebhookDelivery {
+    pub fn new(subscription_id: Uuid, event: WebhookEvent) -> Self {
+        WebhookDelivery {
+            id: Uuid::new_v4(),
+            subscription_id,
+            next_attempt_at: event.occurred_at,
+            event,
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            last_attempt_at: None,
+            last_response_status: None,
+            last_error: None,
+            delivered_at: None,
+        }
+    }
+
+    /// Applies the result of an attempt to the delivery.
+    pub fn record(&mut self, attempt: &DeliveryAttempt) {
+        self.attempts += 1;
+        self.last_attempt_at = Some(attempt.attempted_at);
+        self.last_response_status = attempt.response_status;
+        self.last_error = attempt.error.clone();
+        if attempt.is_success() {
+            self.status = DeliveryStatus::Delivered;
+            self.delivered_at = Some(attempt.attempted_at);
+        } else if let Some(next_attempt_at) = attempt.next_attempt_at {
+            self.next_attempt_at = next_attempt_at;
+        } else {
+            self.status = DeliveryStatus::Failed;
+        }
+    }
+}
+
+/// Outcome of one attempt to send a delivery.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeliveryAttempt {
+    pub attempted_at: DateTime<Utc>,
+    pub response_status: Option<i32>,
+    /// Why the attempt failed, `None` on success
+    pub error: Option<String>,
+    /// When to retry a failed attempt, `None` once retries are exhausted
+    pub next_attempt_at: Option<DateTime<Utc>>,
+}
+
+impl DeliveryAttempt {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Signature of a webhook request, as sent in `WEBHOOK_SIGNATURE_HEADER`.
+pub fn sign_webhook_payload(secret: &str, event_id: &Uuid, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.{}.{}", event_id, timestamp, body).as_bytes());
+    format!("v1={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_type_round_trip() {
+        for event_type in [
+            WebhookEventType::UserCreated,
+            WebhookEventType::PasswordChanged,
+            WebhookEventType::UserDeleted,
+        ] {
+            assert_eq!(
+                event_type.as_str().parse::<WebhookEventType>().unwrap(),
+                event_type
+            );
+            assert_eq!(
+                serde_json::to_value(event_type).unwrap(),
+                serde_json::json!(event_type.as_str())
+            );
+        }
+        assert!("user.unknown".parse::<WebhookEventType>().is_err());
+    }
+
+    #[test]
+    fn test_signature() {
+        let event_id = Uuid::new_v4();
+        let signature = sign_webhook_payload("secret", &event_id, 1700000000, "{}");
+        assert!(signature.starts_with("v1="));
+        assert_eq!(signature.len(), 3 + 64);
+        assert_eq!(
+            signature,
+            sign_webhook_payload("secret", &event_id, 1700000000, "{}")
+        );
+        assert_ne!(
+            signature,
+            sign_webhook_payload("other", &event_id, 1700000000, "{}")
+        );
+        assert_ne!(
+            signature,
+            sign_webhook_payload("secret", &event_id, 1700000001, "{}")
+        );
+    }
+
+    #[test]
+    fn test_delivery_records_attempts() {
+        let event = WebhookEvent::new(WebhookEventType::UserCreated, serde_json::json!({}));
+        let mut delivery = WebhookDelivery::new(Uuid::new_v4(), event);
+        let now = Utc::now();
+
+        delivery.record(&DeliveryAttempt {
+            attempted_at: now,
+            response_status: Some(500),
+            error: Some("server error".to_owned()),
+            next_attempt_at: Some(now + chrono::Duration::seconds(10)),
+        });
+        assert_eq!(delivery.status, DeliveryStatus::Pending);
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(
+            delivery.next_attempt_at,
+            now + chrono::Duration::seconds(10)
+        );
+
+        delivery.record(&DeliveryAttempt {
+            attempted_at: now,
+            response_status: Some(200),
+            error: None,
+            next_attempt_at: None,
+        });
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+        assert_eq!(delivery.delivered_at, Some(now));
+
+        let event = WebhookEvent::new(WebhookEventType::UserCreated, serde_json::json!({}));
+        let mut delivery = WebhookDelivery::new(Uuid::new_v4(), event);
+        delivery.record(&DeliveryAttempt {
+            attempted_at: now,
+            response_status: None,
+            error: Some("timeout".to_owned()),
+            next_attempt_at: None,
+        });
+        assert_eq!(delivery.status, DeliveryStatus::Failed);
+    }
+}
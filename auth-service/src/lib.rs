@@ -3,28 +3,73 @@ mod domain;
 pub mod routes;
 mod services;
 pub mod utils;
-use crate::routes::{login, logout, signup, verify_2fa, verify_token};
+use crate::routes::{
+    add_phone_number, cancel_login_attempt, change_email, change_password, confirm_email_change,
+    create_webhook, delete_phone_number, delete_user, delete_webhook, get_audit_events,
+    get_confirm_email_change, get_dev_mailbox, get_login_attempts, get_me, get_revert_email_change,
+    get_revoke_session, get_trusted_devices, get_webhook_deliveries, get_webhooks, login, logout,
+    resend_2fa, revert_email_change, revoke_session, revoke_trusted_device, set_two_fa_channel,
+    set_user_status, signup, update_me, verify_2fa, verify_phone_number, verify_token,
+};
 pub use crate::services::email_clients;
+pub use crate::services::sms_clients;
 use app_state::AppState;
+use axum::extract::connect_info::{ConnectInfo, IntoMakeServiceWithConnectInfo};
 use axum::http::Method;
-use axum::{routing::post, serve::Serve, Router};
-pub use domain::data_stores::{LoginAttemptId, TwoFACode};
+use axum::middleware::AddExtension;
+use axum::{
+    routing::{delete, get, post, put},
+    serve::Serve,
+    Router,
+};
+pub use domain::audit;
+pub use domain::data_stores::{
+    AuditEventStore, EmailOutboxStore, LoginAttemptId, ResendPolicy, TokenHash, TwoFACode,
+    UserStore, WebhookStore,
+};
+pub use domain::delivery;
+pub use domain::email_outbox;
 pub use domain::error;
-pub use domain::{email::Email, password::Password, user::User};
+pub use domain::webhook;
+pub use domain::{
+    email::Email,
+    email_client::EmailMessage,
+    locale::Locale,
+    password::{CharacterClass, Password, PasswordPolicy},
+    user::{TwoFAChannel, User, UserId},
+};
 use redis::{Client, RedisResult};
+pub use services::breached_passwords::BreachedPasswords;
+pub use services::data_stores::postgres_audit_event_store::PostgresAuditEventStore;
+pub use services::data_stores::postgres_email_change_store::PostgresEmailChangeStore;
+pub use services::data_stores::postgres_email_outbox_store::PostgresEmailOutboxStore;
+pub use services::data_stores::postgres_known_device_store::PostgresKnownDeviceStore;
+pub use services::data_stores::postgres_trusted_device_store::PostgresTrustedDeviceStore;
 pub use services::data_stores::postgres_user_store::PostgresUserStore;
+pub use services::data_stores::postgres_webhook_store::PostgresWebhookStore;
 pub use services::data_stores::redis_banned_token_store::RedisBannedTokenStore;
+pub use services::email_collisions;
+pub use services::email_dispatcher::EmailDispatcher;
+pub use services::email_domain_policy::{DomainList, DomainListFile, EmailDomainPolicy};
+pub use services::password_hashing::{Pepper, Peppers};
+pub use services::user_import;
+pub use services::webhook_dispatcher::WebhookDispatcher;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
 use std::error::Error;
+use std::net::SocketAddr;
 use tower_http::cors::CorsLayer;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
 use tower_http::services::ServeDir;
 use tower_http::trace::TraceLayer;
 use utils::tracing::*;
 
 // This struct encapsulates our application-related logic.
 pub struct Application {
-    server: Serve<Router, Router>,
+    server: Serve<
+        IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+        AddExtension<Router, ConnectInfo<SocketAddr>>,
+    >,
     // address is exposed as a public field
     // so we have access to it in tests.
     pub address: String,
@@ -39,30 +84,83 @@ impl Application {
         ];
 
         let cors = CorsLayer::new()
-            .allow_methods([Method::POST, Method::GET])
+            .allow_methods([
+                Method::POST,
+                Method::GET,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ])
             .allow_origin(allowed_origins)
             // Allow cookies to be included in requests
             .allow_credentials(true);
 
-        let router = Router::new()
+        let mut router = Router::new()
             .nest_service("/", ServeDir::new("assets"))
             .route("/signup", post(signup))
             .route("/login", post(login))
             .route("/logout", post(logout))
             .route("/verify-2fa", post(verify_2fa))
+            .route("/change-password", post(change_password))
+            .route("/change-email", post(change_email))
+            .route(
+                "/confirm-email-change",
+                get(get_confirm_email_change).post(confirm_email_change),
+            )
+            .route(
+                "/revert-email-change",
+                get(get_revert_email_change).post(revert_email_change),
+            )
+            .route("/resend-2fa", post(resend_2fa))
+            .route("/login-attempts", get(get_login_attempts))
+            .route("/login-attempts/:id", delete(cancel_login_attempt))
+            .route(
+                "/revoke-session",
+                get(get_revoke_session).post(revoke_session),
+            )
+            .route("/trusted-devices", get(get_trusted_devices))
+            .route("/trusted-devices/:id", delete(revoke_trusted_device))
+            .route("/me", get(get_me).patch(update_me))
             .route("/verify-token", post(verify_token))
+            .route(
+                "/phone-number",
+                post(add_phone_number).delete(delete_phone_number),
+            )
+            .route("/phone-number/verify", post(verify_phone_number))
+            .route("/2fa-channel", put(set_two_fa_channel))
+            .route("/admin/audit-events", get(get_audit_events))
+            .route("/admin/webhooks", post(create_webhook).get(get_webhooks))
+            .route("/admin/webhooks/:id", delete(delete_webhook))
+            .route(
+                "/admin/webhooks/:id/deliveries",
+                get(get_webhook_deliveries),
+            )
+            .route("/admin/users/:id", delete(delete_user))
+            .route("/admin/users/:id/status", put(set_user_status));
+        // Only available with the file email provider, which is meant for development
+        if app_state.dev_mailbox.is_some() {
+            router = router.route("/dev/mailbox", get(get_dev_mailbox));
+        }
+        let router = router
             .with_state(app_state)
             .layer(cors)
+            .layer(PropagateRequestIdLayer::x_request_id())
             .layer(
                 TraceLayer::new_for_http()
                     .make_span_with(make_span_with_request_id)
                     .on_request(on_request)
                     .on_response(on_response),
-            );
+            )
+            // Tag every request with an id, shared by the tracing span and the audit log
+            .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid));
 
         let listener = tokio::net::TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        // Keep the peer address around for the audit log
+        let server = axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        );
 
         // Create a new Application instance and return it
         Ok(Application { server, address })
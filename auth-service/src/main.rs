@@ -1,3 +1,4 @@
+use auth_service::app_state::{configure_email_dispatcher, configure_webhook_dispatcher};
 use auth_service::utils::constants::prod;
 use auth_service::utils::tracing::init_tracing;
 use auth_service::Application;
@@ -7,6 +8,10 @@ async fn main() {
     color_eyre::install().expect("Failed to install color_eyre");
     init_tracing().expect("Failed to initialize tracing");
     let state = auth_service::app_state::AppState::new_ps_redis().await;
+    let webhook_dispatcher = configure_webhook_dispatcher(state.webhook_store.clone());
+    tokio::spawn(webhook_dispatcher.run());
+    let email_dispatcher = configure_email_dispatcher(state.email_outbox_store.clone());
+    tokio::spawn(email_dispatcher.run());
     let app = Application::build(state, prod::APP_ADDRESS)
         .await
         .expect("Failed to build the app");
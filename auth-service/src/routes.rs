@@ -1,11 +1,31 @@
+mod admin;
+mod change_email;
+mod change_password;
+mod dev_mailbox;
 mod login;
+mod login_attempts;
 mod logout;
+mod me;
+mod phone_number;
+mod resend_2fa;
+mod revoke_session;
 mod signup;
+mod trusted_devices;
 mod verify_2fa;
 mod verify_token;
 
+pub use admin::*;
+pub use change_email::*;
+pub use change_password::*;
+pub use dev_mailbox::*;
 pub use login::*;
+pub use login_attempts::*;
 pub use logout::*;
+pub use me::*;
+pub use phone_number::*;
+pub use resend_2fa::*;
+pub use revoke_session::*;
 pub use signup::*;
+pub use trusted_devices::*;
 pub use verify_2fa::*;
 pub use verify_token::*;
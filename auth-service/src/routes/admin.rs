@@ -0,0 +1,297 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    domain::audit::{
+        AuditEvent, AuditEventFilter, AuditEventType, AuditOutcome, AuditRecord, ADMIN_ACTOR,
+    },
+    domain::data_stores::{UserStoreError, WebhookStoreError},
+    domain::user::{AccountStatus, UserId},
+    domain::webhook::{WebhookDelivery, WebhookEventType, WebhookSubscription},
+    error::AuthAPIError,
+    utils::{auth::AdminAuth, request_context::RequestContext},
+    AppState,
+};
+
+/// Lists the audit events of a user, optionally restricted to the `[from, to)` time range.
+/// The query itself is recorded in the audit log.
+#[tracing::instrument(name = "get_audit_events", skip_all)]
+pub async fn get_audit_events(
+    State(state): State<AppState>,
+    _admin: AdminAuth,
+    context: RequestContext,
+    Query(filter): Query<AuditEventFilter>,
+) -> Result<Json<Vec<AuditRecord>>, AuthAPIError> {
+    let result = state
+        .audit_event_store
+        .read()
+        .await
+        .get_events(&filter)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()));
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::AdminAuditQuery,
+            filter.user_email.as_deref(),
+            ADMIN_ACTOR,
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result.map(Json)
+}
+
+/// Subscribes a URL to account lifecycle events.
+/// The response is the only place the signing secret is disclosed.
+#[tracing::instrument(name = "create_webhook", skip_all)]
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    _admin: AdminAuth,
+    context: RequestContext,
+    Json(request): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<CreateWebhookResponse>), AuthAPIError> {
+    let result = try_create_webhook(&state, request).await;
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::AdminWebhookSubscribe,
+            None,
+            ADMIN_ACTOR,
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result.map(|response| (StatusCode::CREATED, Json(response)))
+}
+
+async fn try_create_webhook(
+    state: &AppState,
+    request: CreateWebhookRequest,
+) -> Result<CreateWebhookResponse, AuthAPIError> {
+    let url = Url::parse(&request.url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or_else(|| AuthAPIError::InvalidInput("Invalid webhook URL".to_owned()))?;
+    let mut event_types = Vec::new();
+    for event_type in request.event_types {
+        if !event_types.contains(&event_type) {
+            event_types.push(event_type);
+        }
+    }
+    if event_types.is_empty() {
+        return Err(AuthAPIError::InvalidInput(
+            "At least one event type is required".to_owned(),
+        ));
+    }
+
+    let subscription = WebhookSubscription::new(url.to_string(), event_types);
+    state
+        .webhook_store
+        .write()
+        .await
+        .add_subscription(subscription.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(CreateWebhookResponse {
+        secret: subscription.secret.clone(),
+        subscription,
+    })
+}
+
+#[tracing::instrument(name = "get_webhooks", skip_all)]
+pub async fn get_webhooks(
+    State(state): State<AppState>,
+    _admin: AdminAuth,
+) -> Result<Json<Vec<WebhookSubscription>>, AuthAPIError> {
+    let subscriptions = state
+        .webhook_store
+        .read()
+        .await
+        .get_subscriptions()
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(Json(subscriptions))
+}
+
+/// Deactivates a subscription, its delivery log is kept.
+#[tracing::instrument(name = "delete_webhook", skip_all)]
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    _admin: AdminAuth,
+    context: RequestContext,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AuthAPIError> {
+    let result = state
+        .webhook_store
+        .write()
+        .await
+        .deactivate_subscription(&id)
+        .await
+        .map_err(AuthAPIError::from);
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::AdminWebhookUnsubscribe,
+            None,
+            ADMIN_ACTOR,
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result.map(|_| StatusCode::NO_CONTENT)
+}
+
+/// Lists the deliveries of a subscription along with the outcome of their last attempt.
+#[tracing::instrument(name = "get_webhook_deliveries", skip_all)]
+pub async fn get_webhook_deliveries(
+    State(state): State<AppState>,
+    _admin: AdminAuth,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<WebhookDelivery>>, AuthAPIError> {
+    let deliveries = state.webhook_store.read().await.get_deliveries(&id).await?;
+    Ok(Json(deliveries))
+}
+
+/// Sets the status of a user, which revokes all their sessions.
+#[tracing::instrument(name = "set_user_status", skip_all)]
+pub async fn set_user_status(
+    State(state): State<AppState>,
+    _admin: AdminAuth,
+    context: RequestContext,
+    Path(id): Path<String>,
+    Json(request): Json<SetUserStatusRequest>,
+) -> Result<StatusCode, AuthAPIError> {
+    let mut user_email = None;
+    let result = try_set_user_status(&state, &id, request, &mut user_email).await;
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::AdminUserStatusChange,
+            user_email.as_deref(),
+            ADMIN_ACTOR,
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result.map(|_| StatusCode::NO_CONTENT)
+}
+
+/// `user_email` is set as soon as the user is found.
+async fn try_set_user_status(
+    state: &AppState,
+    id: &str,
+    request: SetUserStatusRequest,
+    user_email: &mut Option<String>,
+) -> Result<(), AuthAPIError> {
+    let user_id = UserId::parse(id).map_err(|_| AuthAPIError::NotFound)?;
+    let status = AccountStatus::from_parts(&request.status, request.suspended_until)
+        .map_err(|_| AuthAPIError::InvalidInput("Invalid account status".to_owned()))?;
+    if status
+        .suspended_until()
+        .is_some_and(|until| until <= Utc::now())
+    {
+        return Err(AuthAPIError::InvalidInput(
+            "Suspension must end in the future".to_owned(),
+        ));
+    }
+
+    let mut user_store = state.user_store.write().await;
+    let user = user_store
+        .get_user(&user_id)
+        .await
+        .map_err(map_user_store_error)?;
+    *user_email = Some(user.email.as_ref().to_owned());
+    user_store
+        .set_status(&user_id, status)
+        .await
+        .map_err(map_user_store_error)
+}
+
+/// Deletes a user and everything stored about them, which sends the `user.deleted` webhook event.
+#[tracing::instrument(name = "delete_user", skip_all)]
+pub async fn delete_user(
+    State(state): State<AppState>,
+    _admin: AdminAuth,
+    context: RequestContext,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AuthAPIError> {
+    let mut user_email = None;
+    let result = try_delete_user(&state, &id, &mut user_email).await;
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::AdminUserDelete,
+            user_email.as_deref(),
+            ADMIN_ACTOR,
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result.map(|_| StatusCode::NO_CONTENT)
+}
+
+/// `user_email` is set as soon as the user is found.
+async fn try_delete_user(
+    state: &AppState,
+    id: &str,
+    user_email: &mut Option<String>,
+) -> Result<(), AuthAPIError> {
+    let user_id = UserId::parse(id).map_err(|_| AuthAPIError::NotFound)?;
+    let mut user_store = state.user_store.write().await;
+    let user = user_store
+        .get_user(&user_id)
+        .await
+        .map_err(map_user_store_error)?;
+    *user_email = Some(user.email.as_ref().to_owned());
+    user_store
+        .delete_user(&user_id)
+        .await
+        .map_err(map_user_store_error)
+}
+
+fn map_user_store_error(e: UserStoreError) -> AuthAPIError {
+    match e {
+        UserStoreError::UserNotFound => AuthAPIError::NotFound,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    }
+}
+
+impl From<WebhookStoreError> for AuthAPIError {
+    fn from(err: WebhookStoreError) -> Self {
+        match err {
+            WebhookStoreError::SubscriptionNotFound => AuthAPIError::NotFound,
+            WebhookStoreError::UnexpectedError(e) => AuthAPIError::UnexpectedError(e),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    #[serde(rename = "eventTypes")]
+    pub event_types: Vec<WebhookEventType>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateWebhookResponse {
+    #[serde(flatten)]
+    pub subscription: WebhookSubscription,
+    pub secret: String,
+}
+
+#[derive(Deserialize)]
+pub struct SetUserStatusRequest {
+    pub status: String,
+    /// Required for `suspended`, and only allowed for it
+    #[serde(rename = "suspendedUntil")]
+    pub suspended_until: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,331 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Html,
+    Form, Json,
+};
+use secrecy::Secret;
+use serde::Deserialize;
+
+use crate::{
+    domain::{
+        audit::{AuditEvent, AuditEventType, AuditOutcome, ANONYMOUS_ACTOR},
+        data_stores::{EmailChangeStoreError, UserStoreError},
+        email::Email,
+        email_change::EmailChange,
+        password::Password,
+    },
+    error::AuthAPIError,
+    services::templates::{
+        email::{email_change_confirmation_email, email_change_requested_email},
+        page::{confirm_link_page, EmailLink},
+    },
+    utils::{auth::AuthenticatedUser, constants::PUBLIC_URL, request_context::RequestContext},
+    AppState,
+};
+
+/// Starts a change of the email of the user, who must know their password.
+/// The change only applies once confirmed from the link sent to the new address,
+/// and the old address is sent a link to revert it.
+#[tracing::instrument(name = "change_email", skip_all)]
+pub async fn change_email(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    context: RequestContext,
+    Json(request): Json<ChangeEmailRequest>,
+) -> Result<(StatusCode, Json<&'static str>), AuthAPIError> {
+    let result = try_change_email(&state, &user, request).await;
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::EmailChangeRequest,
+            Some(user.email.as_ref()),
+            user.email.as_ref(),
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result
+}
+
+async fn try_change_email(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    request: ChangeEmailRequest,
+) -> Result<(StatusCode, Json<&'static str>), AuthAPIError> {
+    let password = Password::parse_credential(request.password)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    state
+        .user_store
+        .read()
+        .await
+        .validate_user(&user.email, &password)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::InvalidCredentials => AuthAPIError::AuthenticationFailure,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+
+    let new_email = Email::parse(&request.new_email)
+        .map_err(|_| AuthAPIError::InvalidInput("Invalid email".to_owned()))?;
+    if new_email == user.email {
+        return Err(AuthAPIError::InvalidInput(
+            "New email is the current one".to_owned(),
+        ));
+    }
+    if !state.email_domain_policy.allows_signup(&new_email).await {
+        return Err(AuthAPIError::InvalidInput(
+            "Email domain is not allowed".to_owned(),
+        ));
+    }
+    if is_email_taken(state, &new_email).await? {
+        return Err(AuthAPIError::UserAlreadyExists);
+    }
+
+    let (change, tokens) = EmailChange::new(
+        user.user_id,
+        user.email.clone(),
+        new_email,
+        &state.email_change_policy,
+    );
+    state
+        .email_change_store
+        .write()
+        .await
+        .add_change(change.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let locale = state
+        .user_store
+        .read()
+        .await
+        .get_user(&user.user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+        .locale;
+    let confirm_url = format!(
+        "{}/confirm-email-change?token={}",
+        PUBLIC_URL.as_str(),
+        tokens.confirm
+    );
+    let revert_url = format!(
+        "{}/revert-email-change?token={}",
+        PUBLIC_URL.as_str(),
+        tokens.revert
+    );
+    let confirmation = email_change_confirmation_email(locale.as_ref(), &change, &confirm_url)
+        .map_err(AuthAPIError::UnexpectedError)?;
+    let notice = email_change_requested_email(locale.as_ref(), &change, &revert_url)
+        .map_err(AuthAPIError::UnexpectedError)?;
+    let email_client = state.email_client.read().await;
+    email_client
+        .send_email(&change.new_email, &confirmation)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+    email_client
+        .send_email(&change.old_email, &notice)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json("Confirmation link sent to the new email"),
+    ))
+}
+
+/// Whether `email` belongs to a user, or is kept for the revert of an email change.
+async fn is_email_taken(state: &AppState, email: &Email) -> Result<bool, AuthAPIError> {
+    match state.user_store.read().await.get_user_by_email(email).await {
+        Ok(_) => return Ok(true),
+        Err(UserStoreError::UserNotFound) => {}
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+    state
+        .email_change_store
+        .read()
+        .await
+        .is_reserved(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
+/// Target of the link sent to the new address.
+/// Only shows a page asking to confirm, which posts to `confirm_email_change`.
+#[tracing::instrument(name = "get_confirm_email_change", skip_all)]
+pub async fn get_confirm_email_change(
+    Query(request): Query<EmailChangeTokenRequest>,
+) -> Result<Html<String>, AuthAPIError> {
+    confirm_link_page(EmailLink::ConfirmEmailChange, &request.token)
+        .map(Html)
+        .map_err(AuthAPIError::UnexpectedError)
+}
+
+/// Applies the change, from the page of the link sent to the new address.
+#[tracing::instrument(name = "confirm_email_change", skip_all)]
+pub async fn confirm_email_change(
+    State(state): State<AppState>,
+    context: RequestContext,
+    Form(request): Form<EmailChangeTokenRequest>,
+) -> Result<Json<&'static str>, AuthAPIError> {
+    let mut user_email = None;
+    let result = try_confirm_email_change(&state, &request.token, &mut user_email).await;
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::EmailChangeConfirm,
+            user_email.as_deref(),
+            user_email.as_deref().unwrap_or(ANONYMOUS_ACTOR),
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result
+}
+
+/// `user_email` is set to the new email as soon as the link is matched.
+async fn try_confirm_email_change(
+    state: &AppState,
+    token: &str,
+    user_email: &mut Option<String>,
+) -> Result<Json<&'static str>, AuthAPIError> {
+    let change = state
+        .email_change_store
+        .read()
+        .await
+        .get_pending_change(&EmailChange::hash(token))
+        .await
+        .map_err(map_email_change_store_error)?;
+    *user_email = Some(change.new_email.as_ref().to_owned());
+
+    if state
+        .email_change_store
+        .read()
+        .await
+        .is_reserved(&change.new_email)
+        .await
+        .map_err(map_email_change_store_error)?
+    {
+        return Err(AuthAPIError::UserAlreadyExists);
+    }
+    state
+        .user_store
+        .write()
+        .await
+        .update_email(&change.user_id, &change.old_email, &change.new_email)
+        .await
+        .map_err(map_user_store_error)?;
+    state
+        .email_change_store
+        .write()
+        .await
+        .mark_confirmed(&change.id)
+        .await
+        .map_err(map_email_change_store_error)?;
+    Ok(Json("Email changed"))
+}
+
+/// Target of the link sent to the old address.
+/// Only shows a page asking to confirm, which posts to `revert_email_change`.
+#[tracing::instrument(name = "get_revert_email_change", skip_all)]
+pub async fn get_revert_email_change(
+    Query(request): Query<EmailChangeTokenRequest>,
+) -> Result<Html<String>, AuthAPIError> {
+    confirm_link_page(EmailLink::RevertEmailChange, &request.token)
+        .map(Html)
+        .map_err(AuthAPIError::UnexpectedError)
+}
+
+/// From the page of the link sent to the old address. Cancels the change if it is still pending,
+/// and gives the user their old address back otherwise.
+/// Either way the sessions of the user are revoked, as the change may come from whoever took over the account.
+#[tracing::instrument(name = "revert_email_change", skip_all)]
+pub async fn revert_email_change(
+    State(state): State<AppState>,
+    context: RequestContext,
+    Form(request): Form<EmailChangeTokenRequest>,
+) -> Result<Json<&'static str>, AuthAPIError> {
+    let mut user_email = None;
+    let result = try_revert_email_change(&state, &request.token, &mut user_email).await;
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::EmailChangeRevert,
+            user_email.as_deref(),
+            user_email.as_deref().unwrap_or(ANONYMOUS_ACTOR),
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result
+}
+
+/// `user_email` is set to the old email as soon as the link is matched.
+async fn try_revert_email_change(
+    state: &AppState,
+    token: &str,
+    user_email: &mut Option<String>,
+) -> Result<Json<&'static str>, AuthAPIError> {
+    let change = state
+        .email_change_store
+        .read()
+        .await
+        .get_revertible_change(&EmailChange::hash(token))
+        .await
+        .map_err(map_email_change_store_error)?;
+    *user_email = Some(change.old_email.as_ref().to_owned());
+
+    if change.confirmed_at.is_some() {
+        state
+            .user_store
+            .write()
+            .await
+            .update_email(&change.user_id, &change.new_email, &change.old_email)
+            .await
+            .map_err(map_user_store_error)?;
+    }
+    state
+        .user_store
+        .write()
+        .await
+        .revoke_sessions(&change.user_id)
+        .await
+        .map_err(map_user_store_error)?;
+    state
+        .email_change_store
+        .write()
+        .await
+        .mark_reverted(&change.id)
+        .await
+        .map_err(map_email_change_store_error)?;
+    Ok(Json("Email change reverted"))
+}
+
+fn map_email_change_store_error(e: EmailChangeStoreError) -> AuthAPIError {
+    match e {
+        EmailChangeStoreError::ChangeNotFound => AuthAPIError::NotFound,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    }
+}
+
+/// The email of the user no longer being the one the change started from means that
+/// another change was applied since, the link is stale.
+fn map_user_store_error(e: UserStoreError) -> AuthAPIError {
+    match e {
+        UserStoreError::UserAlreadyExists => AuthAPIError::UserAlreadyExists,
+        UserStoreError::UserNotFound => AuthAPIError::NotFound,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ChangeEmailRequest {
+    #[serde(rename = "newEmail")]
+    pub new_email: String,
+    pub password: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct EmailChangeTokenRequest {
+    pub token: String,
+}
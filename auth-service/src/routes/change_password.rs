@@ -0,0 +1,114 @@
+use axum::{extract::State, http::StatusCode, Json};
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+use serde::Deserialize;
+
+use crate::{
+    domain::{
+        audit::{AuditEvent, AuditEventType, AuditOutcome},
+        data_stores::{TokenHash, UserStoreError},
+        password::{Password, PasswordViolation},
+    },
+    error::AuthAPIError,
+    routes::{parse_new_password, record_login_device},
+    utils::{
+        auth::{generate_auth_cookie, PasswordChangeUser},
+        request_context::RequestContext,
+    },
+    AppState,
+};
+
+/// Changes the password of the user, who must know the current one.
+/// It also completes the login of a user whose password expired: the token of the request,
+/// restricted or not, is banned and replaced by a new session.
+#[tracing::instrument(name = "change_password", skip_all)]
+pub async fn change_password(
+    State(state): State<AppState>,
+    user: PasswordChangeUser,
+    context: RequestContext,
+    jar: CookieJar,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Result<(CookieJar, (StatusCode, Json<&'static str>)), AuthAPIError> {
+    let result = try_change_password(&state, &user, &context, jar, request).await;
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::PasswordChange,
+            Some(user.email.as_ref()),
+            user.email.as_ref(),
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result
+}
+
+async fn try_change_password(
+    state: &AppState,
+    user: &PasswordChangeUser,
+    context: &RequestContext,
+    jar: CookieJar,
+    request: ChangePasswordRequest,
+) -> Result<(CookieJar, (StatusCode, Json<&'static str>)), AuthAPIError> {
+    let current_password = Password::parse_credential(request.current_password)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    state
+        .user_store
+        .read()
+        .await
+        .validate_user(&user.email, &current_password)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::InvalidCredentials => AuthAPIError::AuthenticationFailure,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+
+    let new_password = parse_new_password(state, request.new_password).await?;
+    state
+        .user_store
+        .write()
+        .await
+        .update_password(
+            &user.user_id,
+            new_password,
+            state.password_policy.history_size,
+        )
+        .await
+        .map_err(|e| match e {
+            UserStoreError::PasswordReused => {
+                AuthAPIError::WeakPassword(vec![PasswordViolation::Reused])
+            }
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+
+    state
+        .banned_token_store
+        .write()
+        .await
+        .add_banned_token(&TokenHash::of(&user.token))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    let auth_cookie = generate_auth_cookie(&user.user_id, user.session_version)
+        .map_err(AuthAPIError::UnexpectedError)?;
+    record_login_device(
+        &user.user_id,
+        &user.email,
+        auth_cookie.value(),
+        state,
+        context,
+    )
+    .await?;
+
+    Ok((
+        jar.add(auth_cookie),
+        (StatusCode::OK, Json("Password changed successfully")),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: Secret<String>,
+    #[serde(rename = "newPassword")]
+    pub new_password: Secret<String>,
+}
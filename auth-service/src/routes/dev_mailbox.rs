@@ -0,0 +1,106 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+use crate::{
+    error::AuthAPIError, services::email_clients::file_email_client::MailboxMessage,
+    utils::constants::prod, AppState,
+};
+
+lazy_static! {
+    static ref URL_REGEX: Regex = Regex::new(r"https?://[^\s<>]+").unwrap();
+}
+
+/// Lists the last emails written by the file email provider, the most recent first.
+/// Only routed when that provider is configured, see `Application::build`.
+/// Renders an HTML page with clickable links, or JSON if the client accepts `application/json`.
+#[tracing::instrument(name = "get_dev_mailbox", skip_all)]
+pub async fn get_dev_mailbox(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, AuthAPIError> {
+    let mailbox = state.dev_mailbox.as_ref().ok_or(AuthAPIError::NotFound)?;
+    let messages = mailbox
+        .recent_messages(prod::dev_mailbox::MAX_MESSAGES)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let wants_json = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json"));
+    if wants_json {
+        Ok(Json(messages).into_response())
+    } else {
+        Ok(Html(render_mailbox(&messages)).into_response())
+    }
+}
+
+fn render_mailbox(messages: &[MailboxMessage]) -> String {
+    let mut page = String::from(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Dev mailbox</title></head>\n<body>\n<h1>Dev mailbox</h1>\n",
+    );
+    if messages.is_empty() {
+        page.push_str("<p>No emails sent yet.</p>\n");
+    }
+    for message in messages {
+        page.push_str(&format!(
+            "<article>\n<h2>{}</h2>\n<p>To: {} &middot; {}</p>\n<pre>{}</pre>\n<details><summary>HTML version</summary><iframe sandbox srcdoc=\"{}\" width=\"100%\" height=\"400\"></iframe></details>\n</article>\n<hr>\n",
+            escape_html(&message.subject),
+            escape_html(&message.recipient),
+            message.sent_at.to_rfc3339(),
+            linkify(&escape_html(&message.text_body)),
+            // Rendered in a sandbox, so that scripts in the email cannot run
+            escape_html(&message.html_body),
+        ));
+    }
+    page.push_str("</body>\n</html>\n");
+    page
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+// Expects escaped text, so the links cannot break out of the attribute
+fn linkify(text: &str) -> String {
+    URL_REGEX
+        .replace_all(text, |captures: &Captures| {
+            format!("<a href=\"{0}\">{0}</a>", &captures[0])
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_mailbox_escapes_content_and_links_urls() {
+        let message = MailboxMessage {
+            id: uuid::Uuid::new_v4(),
+            recipient: "foo@bar.com".to_owned(),
+            subject: "<script>alert(1)</script>".to_owned(),
+            html_body: "<p onclick=\"alert(1)\">Verify</p>".to_owned(),
+            text_body: "Verify at https://example.com/verify?token=a&b=\"c\"".to_owned(),
+            sent_at: chrono::Utc::now(),
+        };
+
+        let page = render_mailbox(&[message]);
+        assert!(!page.contains("<script>"));
+        assert!(page.contains("&lt;script&gt;"));
+        assert!(page.contains("srcdoc=\"&lt;p onclick=&quot;alert(1)&quot;&gt;Verify&lt;/p&gt;\""));
+        assert!(
+            page.contains("<a href=\"https://example.com/verify?token=a&amp;b=&quot;c&quot;\">")
+        );
+    }
+}
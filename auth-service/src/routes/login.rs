@@ -1,11 +1,24 @@
-use crate::domain::data_stores::{LoginAttemptId, TwoFACode, UserStoreError};
+use crate::domain::audit::{AuditEvent, AuditEventType, AuditOutcome};
+use crate::domain::data_stores::{
+    LoginAttemptId, TrustedDeviceStoreError, TwoFACode, TwoFACodeHash, UserStoreError,
+};
 use crate::domain::email::Email;
+use crate::domain::known_device::{DeviceSighting, KnownDevice, SessionRevocation};
 use crate::domain::password::Password;
-use crate::utils::auth::generate_auth_cookie;
+use crate::domain::user::{TwoFAChannel, User, UserId};
+use crate::services::templates::email::{new_device_login_email, two_fa_login_email};
+use crate::services::templates::sms::two_fa_login_sms;
+use crate::utils::auth::{
+    check_account_status, generate_auth_cookie, generate_password_change_cookie,
+    get_trusted_device_id,
+};
+use crate::utils::constants::{PUBLIC_URL, TWO_FA_CODE_SECRET};
+use crate::utils::request_context::RequestContext;
 use crate::{error::AuthAPIError, AppState};
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
-use secrecy::Secret;
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize)]
@@ -14,20 +27,44 @@ pub struct LoginRequest {
     pub password: Secret<String>,
 }
 
+type LoginResult = Result<(CookieJar, (StatusCode, Json<LoginResponse>)), AuthAPIError>;
+
 /// This function handles the login request.
 /// It validates the email and password, checks if the user exists,
-/// and if 2FA is required.
+/// and if 2FA is required, which a device trusted by the user skips.
 #[tracing::instrument(name = "Login", skip_all)]
 pub async fn login(
     State(state): State<AppState>,
+    context: RequestContext,
     jar: CookieJar,
     Json(request): Json<LoginRequest>,
 ) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let attempted_email = request.email.clone();
+    let result = try_login(&state, &context, jar, request).await;
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::Login,
+            Some(&attempted_email),
+            &attempted_email,
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result
+}
+
+async fn try_login(
+    state: &AppState,
+    context: &RequestContext,
+    jar: CookieJar,
+    request: LoginRequest,
+) -> LoginResult {
     // Email provided is not valid
     let email = Email::parse(&request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
-    // Password provided is not valid
-    let password =
-        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    // Password provided is empty
+    let password = Password::parse_credential(request.password)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
 
     let validate_login_result = state
         .user_store
@@ -43,16 +80,43 @@ pub async fn login(
         e => AuthAPIError::UnexpectedError(e.into()),
     })?;
 
-    let user_store = state.user_store.read().await;
-
-    let user = user_store
-        .get_user(&email)
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user_by_email(&email)
         .await
         .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
-    if user.requires_2fa {
-        handle_2fa(&email, &state, jar).await
+    // Only told to those who know the password
+    check_account_status(&user)?;
+    // Users who signed up before their domain required 2FA are held to it as well
+    let requires_2fa = user.requires_2fa || state.email_domain_policy.requires_2fa(&email).await;
+    if requires_2fa && !is_trusted_device(state, &jar, &user.id).await? {
+        handle_2fa(&user, state, jar).await
     } else {
-        handle_no_2fa(&email, jar).await
+        handle_no_2fa(&user, state, context, jar).await
+    }
+}
+
+/// Checks whether the trusted device cookie was issued to the user for a device that was neither revoked nor expired.
+async fn is_trusted_device(
+    state: &AppState,
+    jar: &CookieJar,
+    user_id: &UserId,
+) -> Result<bool, AuthAPIError> {
+    let Some(device_id) = get_trusted_device_id(jar, user_id) else {
+        return Ok(false);
+    };
+    match state
+        .trusted_device_store
+        .read()
+        .await
+        .get_device(user_id, &device_id)
+        .await
+    {
+        Ok(_) => Ok(true),
+        Err(TrustedDeviceStoreError::DeviceNotFound) => Ok(false),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
     }
 }
 
@@ -60,46 +124,132 @@ pub async fn login(
 /// It generates a new auth cookie and returns it in the response.
 #[tracing::instrument(name = "Login without 2FA", skip_all)]
 async fn handle_no_2fa(
-    email: &Email,
+    user: &User,
+    state: &AppState,
+    context: &RequestContext,
     jar: CookieJar,
-) -> Result<(CookieJar, (StatusCode, Json<LoginResponse>)), AuthAPIError> {
-    let auth_cookie = generate_auth_cookie(&email).map_err(|e| AuthAPIError::UnexpectedError(e))?;
-    let updated_jar = jar.add(auth_cookie);
-    Ok((updated_jar, (StatusCode::OK, Json(LoginResponse::No2FA))))
+) -> LoginResult {
+    match open_session(user, state, context, jar).await? {
+        (updated_jar, None) => Ok((updated_jar, (StatusCode::OK, Json(LoginResponse::No2FA)))),
+        (updated_jar, Some(response)) => Ok((
+            updated_jar,
+            (
+                StatusCode::FORBIDDEN,
+                Json(LoginResponse::PasswordChangeRequired(response)),
+            ),
+        )),
+    }
 }
 
-/// This function handles the case where 2FA is required for login.
-/// It generates a new 2FA code and stores it in the 2FA code store.
-#[tracing::instrument(name = "Login with 2FA", skip_all)]
-async fn handle_2fa(
-    email: &Email,
+/// Sets the session cookie of a user who completed their login.
+/// When their password expired, the cookie only allows them to change it and its token is returned.
+pub(crate) async fn open_session(
+    user: &User,
     state: &AppState,
+    context: &RequestContext,
     jar: CookieJar,
-) -> Result<(CookieJar, (StatusCode, Json<LoginResponse>)), AuthAPIError> {
-    let two_fa_code = TwoFACode::new();
-    let login_attempt_id = LoginAttemptId::new();
-
-    // Store the 2FA code and the login attempt ID in the 2FA code store
+) -> Result<(CookieJar, Option<PasswordChangeRequiredResponse>), AuthAPIError> {
     state
-        .two_fa_code_store
+        .user_store
         .write()
         .await
-        .add_code(email, two_fa_code.clone(), login_attempt_id.clone())
+        .record_login(&user.id)
         .await
         .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    if state
+        .password_policy
+        .is_expired(user.password_changed_at, Utc::now())
+    {
+        let cookie = generate_password_change_cookie(&user.id, user.session_version)
+            .map_err(AuthAPIError::UnexpectedError)?;
+        let response = PasswordChangeRequiredResponse {
+            message: "Password change required".to_owned(),
+            token: cookie.value().to_owned(),
+        };
+        return Ok((jar.add(cookie), Some(response)));
+    }
 
-    // Send the 2FA code to the user via email
-    let (subject, content) =
-        crate::domain::email_client::two_fa_login_email_template(email, two_fa_code.as_ref());
+    let auth_cookie = generate_auth_cookie(&user.id, user.session_version)
+        .map_err(AuthAPIError::UnexpectedError)?;
+    record_login_device(&user.id, &user.email, auth_cookie.value(), state, context).await?;
+    Ok((jar.add(auth_cookie), None))
+}
 
+/// Records the device of a successful login.
+/// The user is alerted by email of a login from a device they never used, the alert links to the revocation of `session_token`.
+#[tracing::instrument(name = "Record login device", skip_all)]
+pub(crate) async fn record_login_device(
+    user_id: &UserId,
+    email: &Email,
+    session_token: &str,
+    state: &AppState,
+    context: &RequestContext,
+) -> Result<(), AuthAPIError> {
+    let device = KnownDevice::new(
+        *user_id,
+        context.user_agent.clone(),
+        context.ip_address.as_deref(),
+    );
+    let (revocation, revocation_token) = SessionRevocation::new(session_token);
+    let sighting = state
+        .known_device_store
+        .write()
+        .await
+        .record_device(device.clone(), revocation)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    if sighting != DeviceSighting::New {
+        return Ok(());
+    }
+
+    let locale = state
+        .user_store
+        .read()
+        .await
+        .get_user(user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+        .locale;
+    let revoke_url = format!(
+        "{}/revoke-session?token={}",
+        PUBLIC_URL.as_str(),
+        revocation_token
+    );
+    let message = new_device_login_email(locale.as_ref(), email, &device, &revoke_url)
+        .map_err(AuthAPIError::UnexpectedError)?;
     state
         .email_client
         .read()
         .await
-        .send_email(email, &subject, &content)
+        .send_email(email, &message)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)
+}
+
+/// This function handles the case where 2FA is required for login.
+/// It generates a new 2FA code, stores it in the 2FA code store
+/// and sends it on the channel chosen by the user.
+#[tracing::instrument(name = "Login with 2FA", skip_all)]
+async fn handle_2fa(user: &User, state: &AppState, jar: CookieJar) -> LoginResult {
+    let two_fa_code = TwoFACode::new();
+    let login_attempt_id = LoginAttemptId::new();
+
+    // Only a hash of the 2FA code is stored along with the login attempt ID
+    let code_hash = TwoFACodeHash::new(
+        TWO_FA_CODE_SECRET.expose_secret(),
+        &login_attempt_id,
+        &two_fa_code,
+    );
+    state
+        .two_fa_code_store
+        .write()
+        .await
+        .add_code(&user.email, code_hash, login_attempt_id.clone())
         .await
         .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
 
+    let channel = send_2fa_code(user, &two_fa_code, state).await?;
+
     Ok((
         jar,
         (
@@ -107,16 +257,54 @@ async fn handle_2fa(
             Json(LoginResponse::With2FA(TwoFactorLoginResponse {
                 message: "2FA required".to_string(),
                 login_attempt_id: login_attempt_id.as_ref().to_string(),
+                channel,
             })),
         ),
     ))
 }
 
+/// Sends the 2FA code on the channel chosen by the user and returns that channel.
+pub(crate) async fn send_2fa_code(
+    user: &User,
+    two_fa_code: &TwoFACode,
+    state: &AppState,
+) -> Result<TwoFAChannel, AuthAPIError> {
+    let locale = user.locale.as_ref();
+    let channel = user.effective_two_fa_channel();
+    match (channel, &user.phone_number) {
+        (TwoFAChannel::Sms, Some(phone_number)) => {
+            let content =
+                two_fa_login_sms(locale, two_fa_code).map_err(AuthAPIError::UnexpectedError)?;
+            state
+                .sms_client
+                .read()
+                .await
+                .send_sms(phone_number, &content)
+                .await
+                .map_err(AuthAPIError::UnexpectedError)?;
+        }
+        _ => {
+            // In production the email is only queued in the outbox
+            let message = two_fa_login_email(locale, &user.email, two_fa_code)
+                .map_err(AuthAPIError::UnexpectedError)?;
+            state
+                .email_client
+                .read()
+                .await
+                .send_email(&user.email, &message)
+                .await
+                .map_err(AuthAPIError::UnexpectedError)?;
+        }
+    }
+    Ok(channel)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum LoginResponse {
     No2FA,
     With2FA(TwoFactorLoginResponse),
+    PasswordChangeRequired(PasswordChangeRequiredResponse),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -124,4 +312,13 @@ pub struct TwoFactorLoginResponse {
     pub message: String,
     #[serde(rename = "loginAttemptId")]
     pub login_attempt_id: String,
+    /// Where the 2FA code was sent
+    pub channel: TwoFAChannel,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasswordChangeRequiredResponse {
+    pub message: String,
+    /// Only allows `/change-password`, it is also set as the JWT cookie
+    pub token: String,
 }
@@ -0,0 +1,92 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    domain::{
+        audit::{AuditEvent, AuditEventType, AuditOutcome},
+        data_stores::{LoginAttemptId, TwoFACodeStoreError},
+    },
+    error::AuthAPIError,
+    utils::{auth::AuthenticatedUser, request_context::RequestContext},
+    AppState,
+};
+
+/// Lists the login attempts of the user waiting for their 2FA code, e.g. from other devices.
+#[tracing::instrument(name = "get_login_attempts", skip_all)]
+pub async fn get_login_attempts(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<Json<Vec<LoginAttemptResponse>>, AuthAPIError> {
+    let attempts = state
+        .two_fa_code_store
+        .read()
+        .await
+        .get_login_attempts(&user.email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(Json(
+        attempts
+            .into_iter()
+            .map(|attempt| LoginAttemptResponse {
+                login_attempt_id: attempt.login_attempt_id.as_ref().to_owned(),
+                created_at: attempt.created_at,
+            })
+            .collect(),
+    ))
+}
+
+/// Cancels a pending login attempt of the user, its 2FA code can no longer be used.
+#[tracing::instrument(name = "cancel_login_attempt", skip_all)]
+pub async fn cancel_login_attempt(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    context: RequestContext,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AuthAPIError> {
+    let result = try_cancel_login_attempt(&state, &user, &id).await;
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::LoginAttemptCancel,
+            Some(user.email.as_ref()),
+            user.email.as_ref(),
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result
+}
+
+async fn try_cancel_login_attempt(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    id: &str,
+) -> Result<StatusCode, AuthAPIError> {
+    let login_attempt_id = LoginAttemptId::parse(id).map_err(|_| AuthAPIError::NotFound)?;
+    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+    two_fa_code_store
+        .get_code(&user.email, &login_attempt_id)
+        .await
+        .map_err(|e| match e {
+            TwoFACodeStoreError::LoginAttemptIdNotFound => AuthAPIError::NotFound,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+    two_fa_code_store
+        .remove_code(&user.email, &login_attempt_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginAttemptResponse {
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+}
@@ -1,6 +1,12 @@
 use crate::{
+    domain::audit::{AuditEvent, AuditEventType, AuditOutcome, ANONYMOUS_ACTOR},
+    domain::data_stores::TokenHash,
     error::AuthAPIError,
-    utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
+    utils::{
+        auth::{get_token_user, validate_token},
+        constants::JWT_COOKIE_NAME,
+        request_context::RequestContext,
+    },
     AppState,
 };
 use axum::{extract::State, http::StatusCode, response::IntoResponse};
@@ -9,8 +15,30 @@ use axum_extra::extract::CookieJar;
 #[tracing::instrument(name = "logout", skip_all)]
 pub async fn logout(
     State(state): State<AppState>,
+    context: RequestContext,
     jar: CookieJar,
 ) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let mut user_email = None;
+    let result = try_logout(&state, jar, &mut user_email).await;
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::Logout,
+            user_email.as_deref(),
+            user_email.as_deref().unwrap_or(ANONYMOUS_ACTOR),
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result
+}
+
+/// Bans the JWT of the request. `user_email` is set as soon as the user of the token is found.
+async fn try_logout(
+    state: &AppState,
+    jar: CookieJar,
+    user_email: &mut Option<String>,
+) -> Result<(CookieJar, StatusCode), AuthAPIError> {
     let jwt = jar
         .get(JWT_COOKIE_NAME)
         .map_or_else(
@@ -18,9 +46,11 @@ pub async fn logout(
             |cookie| Ok(cookie.value()),
         )?
         .to_owned();
-    validate_token(&jwt)
+    let claims = validate_token(&jwt)
         .await
         .map_err(|_| AuthAPIError::InvalidToken)?;
+    let user = get_token_user(state, &claims).await?;
+    *user_email = Some(user.email.as_ref().to_owned());
 
     // Invalidate the JWT by removing it from the cookie jar
     let jar = jar.remove(JWT_COOKIE_NAME);
@@ -29,9 +59,9 @@ pub async fn logout(
         .banned_token_store
         .write()
         .await
-        .add_banned_token(&jwt)
+        .add_banned_token(&TokenHash::of(&jwt))
         .await
         .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
 
-    Ok((jar, StatusCode::OK.into_response()))
+    Ok((jar, StatusCode::OK))
 }
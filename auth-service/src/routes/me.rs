@@ -0,0 +1,157 @@
+use axum::{extract::State, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{
+    domain::{
+        audit::{AuditEvent, AuditEventType, AuditOutcome},
+        locale::Locale,
+        profile::{DisplayName, ProfileUpdate, TimeZone},
+        user::{TwoFAChannel, User, UserId},
+    },
+    error::AuthAPIError,
+    utils::{auth::AuthenticatedUser, request_context::RequestContext},
+    AppState,
+};
+
+/// Returns the profile and account settings of the user.
+#[tracing::instrument(name = "get_me", skip_all)]
+pub async fn get_me(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<Json<MeResponse>, AuthAPIError> {
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&user.user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(Json(MeResponse::from(&user)))
+}
+
+/// Updates the profile of the user. Attributes missing from the request are left untouched,
+/// and `null` clears them.
+#[tracing::instrument(name = "update_me", skip_all)]
+pub async fn update_me(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    context: RequestContext,
+    Json(request): Json<UpdateMeRequest>,
+) -> Result<Json<MeResponse>, AuthAPIError> {
+    let result = try_update_me(&state, &user, request).await;
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::ProfileUpdate,
+            Some(user.email.as_ref()),
+            user.email.as_ref(),
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result
+}
+
+async fn try_update_me(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    request: UpdateMeRequest,
+) -> Result<Json<MeResponse>, AuthAPIError> {
+    let update = ProfileUpdate {
+        display_name: parse_attribute(request.display_name, DisplayName::parse)
+            .map_err(|_| AuthAPIError::InvalidInput("Invalid display name".to_owned()))?,
+        locale: parse_attribute(request.locale, Locale::parse)
+            .map_err(|_| AuthAPIError::InvalidInput("Invalid locale".to_owned()))?,
+        timezone: parse_attribute(request.timezone, TimeZone::parse)
+            .map_err(|_| AuthAPIError::InvalidInput("Invalid timezone".to_owned()))?,
+    };
+    let user = state
+        .user_store
+        .write()
+        .await
+        .update_profile(&user.user_id, update)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(Json(MeResponse::from(&user)))
+}
+
+fn parse_attribute<T>(
+    value: Option<Option<String>>,
+    parse: impl Fn(&str) -> color_eyre::Result<T>,
+) -> color_eyre::Result<Option<Option<T>>> {
+    value
+        .map(|value| value.as_deref().map(&parse).transpose())
+        .transpose()
+}
+
+/// Tells an attribute set to `null` from a missing one, which `#[serde(default)]` leaves to `None`.
+fn deserialize_nullable<'de, D>(deserializer: D) -> Result<Option<Option<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Deserialize)]
+pub struct UpdateMeRequest {
+    #[serde(
+        rename = "displayName",
+        default,
+        deserialize_with = "deserialize_nullable"
+    )]
+    pub display_name: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    pub locale: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    pub timezone: Option<Option<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MeResponse {
+    pub id: UserId,
+    pub email: String,
+    #[serde(rename = "displayName")]
+    pub display_name: Option<String>,
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: bool,
+    #[serde(rename = "twoFAChannel")]
+    pub two_fa_channel: TwoFAChannel,
+    #[serde(rename = "phoneNumber")]
+    pub phone_number: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename = "lastLoginAt")]
+    pub last_login_at: Option<DateTime<Utc>>,
+}
+
+impl From<&User> for MeResponse {
+    fn from(user: &User) -> Self {
+        MeResponse {
+            id: user.id,
+            email: user.email.as_ref().to_owned(),
+            display_name: user
+                .display_name
+                .as_ref()
+                .map(|name| name.as_ref().to_owned()),
+            locale: user
+                .locale
+                .as_ref()
+                .map(|locale| locale.as_ref().to_owned()),
+            timezone: user
+                .timezone
+                .as_ref()
+                .map(|timezone| timezone.as_ref().to_owned()),
+            requires_2fa: user.requires_2fa,
+            two_fa_channel: user.effective_two_fa_channel(),
+            phone_number: user
+                .phone_number
+                .as_ref()
+                .map(|phone_number| phone_number.as_ref().to_owned()),
+            created_at: user.created_at,
+            last_login_at: user.last_login_at,
+        }
+    }
+}
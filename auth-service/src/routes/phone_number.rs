@@ -0,0 +1,232 @@
+use axum::{extract::State, http::StatusCode, Json};
+use secrecy::ExposeSecret;
+use serde::Deserialize;
+
+use crate::{
+    domain::{
+        audit::{AuditEvent, AuditEventType, AuditOutcome},
+        data_stores::{PhoneVerification, PhoneVerificationStoreError, TwoFACode, TwoFACodeHash},
+        phone_number::PhoneNumber,
+        user::TwoFAChannel,
+    },
+    error::AuthAPIError,
+    services::templates::sms::phone_verification_sms,
+    utils::{
+        auth::AuthenticatedUser,
+        constants::{prod, TWO_FA_CODE_SECRET},
+        request_context::RequestContext,
+    },
+    AppState,
+};
+
+/// Sends a verification code to a phone number.
+/// The number is only saved once the code is confirmed with `verify_phone_number`.
+#[tracing::instrument(name = "add_phone_number", skip_all)]
+pub async fn add_phone_number(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<AddPhoneNumberRequest>,
+) -> Result<(StatusCode, Json<&'static str>), AuthAPIError> {
+    let phone_number = PhoneNumber::parse(&request.phone_number)
+        .map_err(|_| AuthAPIError::InvalidInput("Invalid phone number".to_owned()))?;
+    let locale = state
+        .user_store
+        .read()
+        .await
+        .get_user(&user.user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+        .locale;
+
+    let code = TwoFACode::new();
+    let content =
+        phone_verification_sms(locale.as_ref(), &code).map_err(AuthAPIError::UnexpectedError)?;
+    state
+        .phone_verification_store
+        .write()
+        .await
+        .add_verification(
+            &user.email,
+            PhoneVerification {
+                phone_number: phone_number.clone(),
+                code_hash: TwoFACodeHash::for_phone_number(
+                    TWO_FA_CODE_SECRET.expose_secret(),
+                    &phone_number,
+                    &code,
+                ),
+            },
+        )
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    state
+        .sms_client
+        .read()
+        .await
+        .send_sms(&phone_number, &content)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    Ok((StatusCode::ACCEPTED, Json("Verification code sent")))
+}
+
+/// Saves the phone number pending verification if `code` is the one sent to it.
+#[tracing::instrument(name = "verify_phone_number", skip_all)]
+pub async fn verify_phone_number(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    context: RequestContext,
+    Json(request): Json<VerifyPhoneNumberRequest>,
+) -> Result<StatusCode, AuthAPIError> {
+    let result = try_verify_phone_number(&state, &user, request).await;
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::PhoneNumberVerify,
+            Some(user.email.as_ref()),
+            user.email.as_ref(),
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result
+}
+
+async fn try_verify_phone_number(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    request: VerifyPhoneNumberRequest,
+) -> Result<StatusCode, AuthAPIError> {
+    let invalid_code = || AuthAPIError::InvalidInput("Invalid verification code".to_owned());
+    let code = TwoFACode::parse(request.code).map_err(|_| invalid_code())?;
+    let verification = state
+        .phone_verification_store
+        .read()
+        .await
+        .get_verification(&user.email)
+        .await
+        .map_err(|e| match e {
+            PhoneVerificationStoreError::VerificationNotFound => invalid_code(),
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+    if !verification.code_hash.matches_phone_number(
+        TWO_FA_CODE_SECRET.expose_secret(),
+        &verification.phone_number,
+        &code,
+    ) {
+        // The code must be asked for again after too many wrong guesses
+        state
+            .phone_verification_store
+            .write()
+            .await
+            .record_failed_attempt(&user.email, prod::phone_verification::MAX_ATTEMPTS)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+        return Err(invalid_code());
+    }
+
+    state
+        .user_store
+        .write()
+        .await
+        .set_phone_number(&user.user_id, Some(verification.phone_number))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    state
+        .phone_verification_store
+        .write()
+        .await
+        .remove_verification(&user.email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(StatusCode::OK)
+}
+
+/// Removes the phone number of the user, the 2FA codes are sent by email again.
+#[tracing::instrument(name = "delete_phone_number", skip_all)]
+pub async fn delete_phone_number(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    context: RequestContext,
+) -> Result<StatusCode, AuthAPIError> {
+    let result = state
+        .user_store
+        .write()
+        .await
+        .set_phone_number(&user.user_id, None)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()));
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::PhoneNumberRemove,
+            Some(user.email.as_ref()),
+            user.email.as_ref(),
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result
+}
+
+/// Selects where the 2FA codes of the user are sent. SMS requires a verified phone number.
+#[tracing::instrument(name = "set_two_fa_channel", skip_all)]
+pub async fn set_two_fa_channel(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    context: RequestContext,
+    Json(request): Json<SetTwoFAChannelRequest>,
+) -> Result<StatusCode, AuthAPIError> {
+    let result = try_set_two_fa_channel(&state, &user, request.channel).await;
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::TwoFAChannelChange,
+            Some(user.email.as_ref()),
+            user.email.as_ref(),
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result
+}
+
+async fn try_set_two_fa_channel(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    channel: TwoFAChannel,
+) -> Result<StatusCode, AuthAPIError> {
+    let mut user_store = state.user_store.write().await;
+    if channel == TwoFAChannel::Sms {
+        let stored = user_store
+            .get_user(&user.user_id)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+        if stored.phone_number.is_none() {
+            return Err(AuthAPIError::InvalidInput(
+                "A verified phone number is required".to_owned(),
+            ));
+        }
+    }
+    user_store
+        .set_two_fa_channel(&user.user_id, channel)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+pub struct AddPhoneNumberRequest {
+    #[serde(rename = "phoneNumber")]
+    pub phone_number: String,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyPhoneNumberRequest {
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct SetTwoFAChannelRequest {
+    pub channel: TwoFAChannel,
+}
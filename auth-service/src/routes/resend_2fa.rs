@@ -0,0 +1,95 @@
+use axum::{extract::State, Json};
+use secrecy::ExposeSecret;
+use serde::Deserialize;
+
+use super::login::{send_2fa_code, TwoFactorLoginResponse};
+use crate::{
+    app_state::AppState,
+    domain::{
+        audit::{AuditEvent, AuditEventType, AuditOutcome},
+        data_stores::{TwoFACodeHash, TwoFACodeStoreError},
+    },
+    error::AuthAPIError,
+    utils::{constants::TWO_FA_CODE_SECRET, request_context::RequestContext},
+    Email, LoginAttemptId, TwoFACode,
+};
+
+/// Sends a new 2FA code for a login attempt, e.g. when the first message is delayed.
+/// Only the hash of the codes is stored, so the new code replaces the previous one.
+#[tracing::instrument(name = "resend_2fa", skip_all)]
+pub async fn resend_2fa(
+    State(app): State<AppState>,
+    context: RequestContext,
+    Json(request): Json<Resend2FARequest>,
+) -> Result<Json<TwoFactorLoginResponse>, AuthAPIError> {
+    let attempted_email = request.email.clone();
+    let result = try_resend_2fa(&app, request).await;
+
+    app.record_audit_event(AuditEvent::new(
+        AuditEventType::Resend2FA,
+        Some(&attempted_email),
+        &attempted_email,
+        AuditOutcome::of(&result),
+        &context,
+    ))
+    .await;
+    result
+}
+
+async fn try_resend_2fa(
+    app: &AppState,
+    request: Resend2FARequest,
+) -> Result<Json<TwoFactorLoginResponse>, AuthAPIError> {
+    let email = Email::parse(&request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let login_attempt_id = LoginAttemptId::parse(&request.login_attempt_id)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let two_fa_code = TwoFACode::new();
+    let code_hash = TwoFACodeHash::new(
+        TWO_FA_CODE_SECRET.expose_secret(),
+        &login_attempt_id,
+        &two_fa_code,
+    );
+    app.two_fa_code_store
+        .write()
+        .await
+        .record_resend(
+            &email,
+            &login_attempt_id,
+            code_hash,
+            &app.two_fa_resend_policy,
+        )
+        .await
+        .map_err(|e| match e {
+            TwoFACodeStoreError::LoginAttemptIdNotFound => AuthAPIError::AuthenticationFailure,
+            TwoFACodeStoreError::ResendTooSoon => {
+                AuthAPIError::TooManyRequests("Please wait before requesting a new code".to_owned())
+            }
+            TwoFACodeStoreError::ResendLimitReached => AuthAPIError::TooManyRequests(
+                "Too many codes requested, please log in again".to_owned(),
+            ),
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+
+    let user = app
+        .user_store
+        .read()
+        .await
+        .get_user_by_email(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    let channel = send_2fa_code(&user, &two_fa_code, app).await?;
+
+    Ok(Json(TwoFactorLoginResponse {
+        message: "2FA code sent".to_string(),
+        login_attempt_id: login_attempt_id.as_ref().to_string(),
+        channel,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct Resend2FARequest {
+    email: String,
+    #[serde(rename = "loginAttemptId")]
+    login_attempt_id: String,
+}
@@ -0,0 +1,91 @@
+use axum::{
+    extract::{Query, State},
+    response::Html,
+    Form, Json,
+};
+use serde::Deserialize;
+
+use crate::{
+    domain::{
+        audit::{AuditEvent, AuditEventType, AuditOutcome, ANONYMOUS_ACTOR},
+        data_stores::KnownDeviceStoreError,
+        known_device::SessionRevocation,
+    },
+    error::AuthAPIError,
+    services::templates::page::{confirm_link_page, EmailLink},
+    utils::request_context::RequestContext,
+    AppState,
+};
+
+/// Target of the "this wasn't me" link of the new device alerts.
+/// Only shows a page asking to confirm, which posts to `revoke_session`.
+#[tracing::instrument(name = "get_revoke_session", skip_all)]
+pub async fn get_revoke_session(
+    Query(request): Query<RevokeSessionRequest>,
+) -> Result<Html<String>, AuthAPIError> {
+    confirm_link_page(EmailLink::RevokeSession, &request.token)
+        .map(Html)
+        .map_err(AuthAPIError::UnexpectedError)
+}
+
+/// Logs the new device out, and forgets it so that its next login is alerted again.
+#[tracing::instrument(name = "revoke_session", skip_all)]
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    context: RequestContext,
+    Form(request): Form<RevokeSessionRequest>,
+) -> Result<Json<&'static str>, AuthAPIError> {
+    let mut user_email = None;
+    let result = try_revoke_session(&state, &request.token, &mut user_email).await;
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::SessionRevoke,
+            user_email.as_deref(),
+            user_email.as_deref().unwrap_or(ANONYMOUS_ACTOR),
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result
+}
+
+/// `user_email` is set as soon as the link is matched.
+async fn try_revoke_session(
+    state: &AppState,
+    token: &str,
+    user_email: &mut Option<String>,
+) -> Result<Json<&'static str>, AuthAPIError> {
+    let (user_id, session) = state
+        .known_device_store
+        .write()
+        .await
+        .revoke_session(&SessionRevocation::hash(token))
+        .await
+        .map_err(|e| match e {
+            KnownDeviceStoreError::RevocationNotFound => AuthAPIError::NotFound,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    *user_email = Some(user.email.as_ref().to_owned());
+
+    state
+        .banned_token_store
+        .write()
+        .await
+        .add_banned_token(&session)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(Json("Session revoked"))
+}
+
+#[derive(Deserialize)]
+pub struct RevokeSessionRequest {
+    pub token: String,
+}
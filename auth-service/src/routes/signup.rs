@@ -1,34 +1,123 @@
+use crate::domain::audit::{AuditEvent, AuditEventType, AuditOutcome};
 use crate::domain::data_stores::UserStoreError;
+use crate::domain::email::Email;
+use crate::domain::locale::Locale;
+use crate::domain::password::{Password, PasswordViolation};
+use crate::utils::request_context::RequestContext;
 use crate::{domain::user::User, error::AuthAPIError, AppState};
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
 use secrecy::Secret;
 use serde::Deserialize;
 
 #[tracing::instrument(name = "Signup", skip_all)]
 pub async fn signup(
     State(state): State<AppState>,
+    context: RequestContext,
+    headers: HeaderMap,
     Json(request): Json<SignupRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
-    let user = User::new(request.email, request.password, request.requires_2fa);
-    if user.is_err() {
-        return Err(AuthAPIError::InvalidCredentials);
+    let attempted_email = request.email.clone();
+    let accept_language = headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Locale::from_accept_language);
+    let result = try_signup(&state, request, accept_language).await;
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::Signup,
+            Some(&attempted_email),
+            &attempted_email,
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result
+}
+
+async fn try_signup(
+    state: &AppState,
+    request: SignupRequest,
+    accept_language: Option<Locale>,
+) -> Result<(StatusCode, Json<&'static str>), AuthAPIError> {
+    // An explicit locale takes precedence over the one negotiated by the browser
+    let locale = match request.locale {
+        Some(locale) => Some(
+            Locale::parse(&locale)
+                .map_err(|_| AuthAPIError::InvalidInput("Invalid locale".to_owned()))?,
+        ),
+        None => accept_language,
+    };
+    let email = Email::parse(&request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    if !state.email_domain_policy.allows_signup(&email).await {
+        return Err(AuthAPIError::InvalidInput(
+            "Email domain is not allowed".to_owned(),
+        ));
+    }
+    // The old address of an email change stays with its user until the change can no longer be reverted
+    if state
+        .email_change_store
+        .read()
+        .await
+        .is_reserved(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+    {
+        return Err(AuthAPIError::UserAlreadyExists);
     }
+    let password = parse_new_password(state, request.password).await?;
+    let requires_2fa = request.requires_2fa || state.email_domain_policy.requires_2fa(&email).await;
+    let user = User::from_parts(email, password, requires_2fa).with_locale(locale);
 
-    let res = state.user_store.write().await.add_user(user.unwrap()).await;
+    let res = state.user_store.write().await.add_user(user).await;
     match res {
         Ok(_) => Ok((StatusCode::CREATED, Json("User created successfully"))),
         Err(err) => Err(match err {
             UserStoreError::UserAlreadyExists => AuthAPIError::UserAlreadyExists,
-            UserStoreError::UnexpectedError(e) => AuthAPIError::UnexpectedError(e.into()),
+            UserStoreError::UnexpectedError(e) => AuthAPIError::UnexpectedError(e),
             _ => unreachable!("Unexpected error: {:?}", err),
         }),
     }
 }
 
+/// Checks a password chosen by a user against the policy and the breached password corpus,
+/// reporting every rule it breaks.
+pub(crate) async fn parse_new_password(
+    state: &AppState,
+    password: Secret<String>,
+) -> Result<Password, AuthAPIError> {
+    let breached = match &state.breached_passwords {
+        Some(breached_passwords) => breached_passwords
+            .contains(&password)
+            .await
+            .map_err(AuthAPIError::UnexpectedError)?,
+        None => false,
+    };
+    match Password::parse_with_policy(password, &state.password_policy) {
+        Ok(password) if !breached => Ok(password),
+        Ok(_) => Err(AuthAPIError::WeakPassword(vec![
+            PasswordViolation::Breached,
+        ])),
+        Err(mut violations) => {
+            if breached {
+                violations.push(PasswordViolation::Breached);
+            }
+            Err(AuthAPIError::WeakPassword(violations))
+        }
+    }
+}
+
 #[derive(Deserialize)]
 pub struct SignupRequest {
     pub email: String,
     pub password: Secret<String>,
     #[serde(rename = "requires2FA")]
     pub requires_2fa: bool,
+    /// BCP 47 tag, e.g. `fr-CA`. Defaults to the `Accept-Language` header
+    pub locale: Option<String>,
 }
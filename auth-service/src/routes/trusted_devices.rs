@@ -0,0 +1,96 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    domain::{
+        audit::{AuditEvent, AuditEventType, AuditOutcome},
+        data_stores::TrustedDeviceStoreError,
+    },
+    error::AuthAPIError,
+    utils::{auth::AuthenticatedUser, request_context::RequestContext},
+    AppState,
+};
+
+/// Lists the devices remembered by the user, on which the login skips 2FA.
+#[tracing::instrument(name = "get_trusted_devices", skip_all)]
+pub async fn get_trusted_devices(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<Json<Vec<TrustedDeviceResponse>>, AuthAPIError> {
+    let devices = state
+        .trusted_device_store
+        .read()
+        .await
+        .get_devices(&user.user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(Json(
+        devices
+            .into_iter()
+            .map(|device| TrustedDeviceResponse {
+                id: device.id,
+                user_agent: device.user_agent,
+                created_at: device.created_at,
+                expires_at: device.expires_at,
+            })
+            .collect(),
+    ))
+}
+
+/// Revokes a trusted device of the user, its next login requires 2FA again.
+#[tracing::instrument(name = "revoke_trusted_device", skip_all)]
+pub async fn revoke_trusted_device(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    context: RequestContext,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AuthAPIError> {
+    let result = try_revoke_trusted_device(&state, &user, &id).await;
+
+    state
+        .record_audit_event(AuditEvent::new(
+            AuditEventType::TrustedDeviceRevoke,
+            Some(user.email.as_ref()),
+            user.email.as_ref(),
+            AuditOutcome::of(&result),
+            &context,
+        ))
+        .await;
+    result
+}
+
+async fn try_revoke_trusted_device(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    id: &str,
+) -> Result<StatusCode, AuthAPIError> {
+    let id = Uuid::parse_str(id).map_err(|_| AuthAPIError::NotFound)?;
+    state
+        .trusted_device_store
+        .write()
+        .await
+        .remove_device(&user.user_id, &id)
+        .await
+        .map_err(|e| match e {
+            TrustedDeviceStoreError::DeviceNotFound => AuthAPIError::NotFound,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrustedDeviceResponse {
+    pub id: Uuid,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: DateTime<Utc>,
+}
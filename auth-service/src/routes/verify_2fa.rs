@@ -1,18 +1,57 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
 use axum_extra::extract::CookieJar;
+use secrecy::ExposeSecret;
 use serde::Deserialize;
 
 use crate::{
-    app_state::AppState, error::AuthAPIError, utils::auth::generate_auth_cookie, Email,
-    LoginAttemptId, TwoFACode,
+    app_state::AppState,
+    domain::{
+        audit::{AuditEvent, AuditEventType, AuditOutcome},
+        data_stores::TwoFACodeStoreError,
+        trusted_device::TrustedDevice,
+    },
+    error::AuthAPIError,
+    routes::{open_session, LoginResponse},
+    utils::{
+        auth::{check_account_status, create_trusted_device_cookie},
+        constants::{prod, TWO_FA_CODE_SECRET},
+        request_context::RequestContext,
+    },
+    Email, LoginAttemptId, TwoFACode,
 };
 
 #[tracing::instrument(name = "verify_2fa", skip_all)]
 pub async fn verify_2fa(
     State(app): State<AppState>,
+    context: RequestContext,
     jar: CookieJar,
     Json(request): Json<Verify2FARequest>,
 ) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let attempted_email = request.email.clone();
+    let result = try_verify_2fa(&app, &context, jar, request).await;
+
+    app.record_audit_event(AuditEvent::new(
+        AuditEventType::Verify2FA,
+        Some(&attempted_email),
+        &attempted_email,
+        AuditOutcome::of(&result),
+        &context,
+    ))
+    .await;
+    result
+}
+
+async fn try_verify_2fa(
+    app: &AppState,
+    context: &RequestContext,
+    jar: CookieJar,
+    request: Verify2FARequest,
+) -> Result<(CookieJar, Response), AuthAPIError> {
     // Parse and validate the email, login attempt ID, and 2FA code from the request
     let email = Email::parse(&request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
     let login_attempt_id = LoginAttemptId::parse(&request.login_attempt_id)
@@ -20,32 +59,78 @@ pub async fn verify_2fa(
     let two_fa_code =
         TwoFACode::parse(request.two_fa_code).map_err(|_| AuthAPIError::InvalidCredentials)?;
 
-    // Retrieve the 2FA code and login attempt ID from the store
-    let (code, id) = app
+    // Retrieve the hash of the 2FA code of the login attempt from the store
+    let code_hash = app
         .two_fa_code_store
         .read()
         .await
-        .get_code(&email)
+        .get_code(&email, &login_attempt_id)
         .await
         .map_err(|_| AuthAPIError::AuthenticationFailure)?;
 
-    if login_attempt_id != id || code != two_fa_code {
+    if !code_hash.matches(
+        TWO_FA_CODE_SECRET.expose_secret(),
+        &login_attempt_id,
+        &two_fa_code,
+    ) {
+        // The user must log in again after too many wrong guesses
+        app.two_fa_code_store
+            .write()
+            .await
+            .record_failed_attempt(&email, &login_attempt_id, prod::two_fa::MAX_ATTEMPTS)
+            .await
+            .map_err(|e| match e {
+                TwoFACodeStoreError::LoginAttemptIdNotFound => AuthAPIError::AuthenticationFailure,
+                e => AuthAPIError::UnexpectedError(e.into()),
+            })?;
         return Err(AuthAPIError::AuthenticationFailure);
     }
 
-    // Remove the 2FA code from the store after successful verification
+    // Remove the 2FA code from the store after successful verification,
+    // the other login attempts of the user stay valid
     app.two_fa_code_store
         .write()
         .await
-        .remove_code(&email)
+        .remove_code(&email, &login_attempt_id)
         .await
         .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
 
     // Set jwt cookie in the response
-    let auth_cookie = generate_auth_cookie(&email).map_err(AuthAPIError::UnexpectedError)?;
-    let updated_jar = jar.add(auth_cookie);
+    let user = app
+        .user_store
+        .read()
+        .await
+        .get_user_by_email(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    // The status may have changed since the code was sent
+    check_account_status(&user)?;
+    let (mut updated_jar, password_change_required) =
+        open_session(&user, app, context, jar).await?;
+
+    // The next logins from this browser skip 2FA
+    if request.remember_device {
+        let device =
+            TrustedDevice::new(user.id, context.user_agent.clone(), app.trusted_device_ttl);
+        let trusted_device_cookie = create_trusted_device_cookie(&device);
+        app.trusted_device_store
+            .write()
+            .await
+            .add_device(device)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+        updated_jar = updated_jar.add(trusted_device_cookie);
+    }
 
-    Ok((updated_jar, StatusCode::OK))
+    let response = match password_change_required {
+        None => StatusCode::OK.into_response(),
+        Some(response) => (
+            StatusCode::FORBIDDEN,
+            Json(LoginResponse::PasswordChangeRequired(response)),
+        )
+            .into_response(),
+    };
+    Ok((updated_jar, response))
 }
 
 #[derive(Deserialize)]
@@ -55,4 +140,6 @@ pub struct Verify2FARequest {
     login_attempt_id: String,
     #[serde(rename = "2FACode")]
     two_fa_code: String,
+    #[serde(rename = "rememberDevice", default)]
+    remember_device: bool,
 }
@@ -1,4 +1,8 @@
-use crate::{error::AuthAPIError, utils::auth::validate_token, AppState};
+use crate::{
+    error::AuthAPIError,
+    utils::auth::{check_session, get_token_user, validate_token, TokenScope},
+    AppState,
+};
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use serde::Deserialize;
 
@@ -13,10 +17,13 @@ pub async fn verify_token(
     Json(request): Json<VerifyTokenRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
     let jwt = request.token;
-    // Check if the token is valid
-    validate_token(&jwt)
+    // Check if the token is valid, a token only allowing a password change is not a session
+    let claims = validate_token(&jwt)
         .await
         .map_err(|_| AuthAPIError::InvalidToken)?;
+    if claims.scope != TokenScope::Session {
+        return Err(AuthAPIError::InvalidToken);
+    }
     // Check if the token is banned
     let is_token_banned = app
         .banned_token_store
@@ -28,6 +35,9 @@ pub async fn verify_token(
     if is_token_banned {
         return Err(AuthAPIError::InvalidToken);
     }
+    // The account must still allow its user to log in
+    let user = get_token_user(&app, &claims).await?;
+    check_session(&user, &claims)?;
 
     Ok(StatusCode::OK.into_response())
 }
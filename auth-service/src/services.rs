@@ -1,2 +1,11 @@
+pub mod breached_passwords;
 pub mod data_stores;
 pub mod email_clients;
+pub mod email_collisions;
+pub mod email_dispatcher;
+pub mod email_domain_policy;
+pub mod password_hashing;
+pub mod sms_clients;
+pub mod templates;
+pub mod user_import;
+pub mod webhook_dispatcher;
@@ -0,0 +1,81 @@
+use std::{io::ErrorKind, path::PathBuf};
+
+use color_eyre::eyre::{Context, Result};
+use secrecy::{ExposeSecret, Secret};
+use sha1::{Digest, Sha1};
+
+/// Local copy of a breached password corpus, in the k-anonymity layout of the
+/// Have I Been Pwned range API: the SHA-1 hashes are split on their first 5 hex
+/// characters, `<dir>/<PREFIX>.txt` holding a `<SUFFIX>:<COUNT>` line per hash starting
+/// with `PREFIX`. Only the range of the password is read, nothing leaves the host.
+#[derive(Debug, Clone)]
+pub struct BreachedPasswords {
+    dir: PathBuf,
+}
+
+impl BreachedPasswords {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub async fn contains(&self, password: &Secret<String>) -> Result<bool> {
+        let hash = hex::encode_upper(Sha1::digest(password.expose_secret().as_bytes()));
+        let (prefix, suffix) = hash.split_at(5);
+        let path = self.dir.join(format!("{}.txt", prefix));
+        let range = match tokio::fs::read_to_string(&path).await {
+            Ok(range) => range,
+            // No breached password has this prefix
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e).wrap_err(format!("Failed to read {}", path.display())),
+        };
+        Ok(range.lines().any(|line| match line.trim().split_once(':') {
+            // The downloaded ranges are padded with fake hashes having a count of 0
+            Some((hash_suffix, count)) => {
+                hash_suffix.eq_ignore_ascii_case(suffix) && count.trim() != "0"
+            }
+            None => line.trim().eq_ignore_ascii_case(suffix),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_contains_looks_up_the_range_of_the_password() {
+        let dir = std::env::temp_dir().join(format!("breached-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        // SHA-1 of "password" is 5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8
+        tokio::fs::write(
+            dir.join("5BAA6.txt"),
+            "0018A45C4D1DEF81644B54AB7F969B88D65:0\r\n1E4C9B93F3F0682250B6CF8331B7EE68FD8:9545824\r\n",
+        )
+        .await
+        .unwrap();
+        // SHA-1 of "hunter2" is F3BBBD66A63D4BF1747940578EC3D0103530E21D, a padding entry
+        tokio::fs::write(
+            dir.join("F3BBB.txt"),
+            "d66a63d4bf1747940578ec3d0103530e21d:0\n",
+        )
+        .await
+        .unwrap();
+
+        let corpus = BreachedPasswords::new(&dir);
+        for (password, expected) in [
+            ("password", true),
+            ("hunter2", false),
+            ("correct horse battery staple", false),
+        ] {
+            let secret = Secret::new(password.to_owned());
+            assert_eq!(
+                corpus.contains(&secret).await.unwrap(),
+                expected,
+                "Failed for {}",
+                password
+            );
+        }
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -1,6 +1,20 @@
+pub mod hashmap_email_change_store;
+pub mod hashmap_known_device_store;
+pub mod hashmap_phone_verification_store;
+pub mod hashmap_trusted_device_store;
 pub mod hashmap_two_fa_code_store;
 pub mod hashmap_user_store;
+pub mod hashmap_webhook_store;
 pub mod hashset_banned_token_store;
+pub mod postgres_audit_event_store;
+pub mod postgres_email_change_store;
+pub mod postgres_email_outbox_store;
+pub mod postgres_known_device_store;
+pub mod postgres_trusted_device_store;
 pub mod postgres_user_store;
+pub mod postgres_webhook_store;
 pub mod redis_banned_token_store;
+pub mod redis_phone_verification_store;
 pub mod redis_two_fa_code_store;
+pub mod vec_audit_event_store;
+pub mod vec_email_outbox_store;
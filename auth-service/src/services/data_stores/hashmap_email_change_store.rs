@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::domain::{
+    data_stores::{EmailChangeStore, EmailChangeStoreError},
+    email::Email,
+    email_change::EmailChange,
+};
+
+#[derive(Default, Debug)]
+pub struct HashmapEmailChangeStore {
+    changes: HashMap<Uuid, EmailChange>,
+}
+
+#[async_trait::async_trait]
+impl EmailChangeStore for HashmapEmailChangeStore {
+    async fn add_change(&mut self, change: EmailChange) -> Result<(), EmailChangeStoreError> {
+        self.changes
+            .retain(|_, other| other.user_id != change.user_id || other.confirmed_at.is_some());
+        self.changes.insert(change.id, change);
+        Ok(())
+    }
+
+    async fn get_pending_change(
+        &self,
+        confirm_token_hash: &str,
+    ) -> Result<EmailChange, EmailChangeStoreError> {
+        self.changes
+            .values()
+            .find(|change| {
+                change.confirm_token_hash == confirm_token_hash && change.is_pending(Utc::now())
+            })
+            .cloned()
+            .ok_or(EmailChangeStoreError::ChangeNotFound)
+    }
+
+    async fn get_revertible_change(
+        &self,
+        revert_token_hash: &str,
+    ) -> Result<EmailChange, EmailChangeStoreError> {
+        self.changes
+            .values()
+            .find(|change| {
+                change.revert_token_hash == revert_token_hash && change.is_revertible(Utc::now())
+            })
+            .cloned()
+            .ok_or(EmailChangeStoreError::ChangeNotFound)
+    }
+
+    async fn mark_confirmed(&mut self, id: &Uuid) -> Result<(), EmailChangeStoreError> {
+        let change = self
+            .changes
+            .get_mut(id)
+            .ok_or(EmailChangeStoreError::ChangeNotFound)?;
+        change.confirmed_at = Some(Utc::now());
+        Ok(())
+    }
+
+    async fn mark_reverted(&mut self, id: &Uuid) -> Result<(), EmailChangeStoreError> {
+        let change = self
+            .changes
+            .get_mut(id)
+            .ok_or(EmailChangeStoreError::ChangeNotFound)?;
+        change.reverted_at = Some(Utc::now());
+        Ok(())
+    }
+
+    async fn is_reserved(&self, email: &Email) -> Result<bool, EmailChangeStoreError> {
+        Ok(self.changes.values().any(|change| {
+            &change.old_email == email
+                && change.confirmed_at.is_some()
+                && change.is_revertible(Utc::now())
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::domain::{email_change::EmailChangePolicy, user::UserId};
+
+    fn new_change(user_id: UserId) -> (EmailChange, String, String) {
+        let policy = EmailChangePolicy {
+            confirm_ttl: Duration::from_secs(60),
+            revert_ttl: Duration::from_secs(600),
+        };
+        let (change, tokens) = EmailChange::new(
+            user_id,
+            Email::parse("old@example.com").unwrap(),
+            Email::parse("new@example.com").unwrap(),
+            &policy,
+        );
+        (
+            change,
+            EmailChange::hash(&tokens.confirm),
+            EmailChange::hash(&tokens.revert),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_hashmap_email_change_store() {
+        let mut store = HashmapEmailChangeStore::default();
+        let user_id = UserId::new();
+        let (change, confirm_hash, revert_hash) = new_change(user_id);
+        store.add_change(change.clone()).await.unwrap();
+
+        assert_eq!(
+            store.get_pending_change(&confirm_hash).await.unwrap(),
+            change
+        );
+        assert!(store.get_pending_change(&revert_hash).await.is_err());
+        store.mark_confirmed(&change.id).await.unwrap();
+        assert!(store.get_pending_change(&confirm_hash).await.is_err());
+        assert!(store.is_reserved(&change.old_email).await.unwrap());
+        assert!(!store.is_reserved(&change.new_email).await.unwrap());
+        // A confirmed change can still be reverted, once
+        assert_eq!(
+            store.get_revertible_change(&revert_hash).await.unwrap().id,
+            change.id
+        );
+        store.mark_reverted(&change.id).await.unwrap();
+        assert!(store.get_revertible_change(&revert_hash).await.is_err());
+        assert!(!store.is_reserved(&change.old_email).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_new_change_replaces_the_pending_one() {
+        let mut store = HashmapEmailChangeStore::default();
+        let user_id = UserId::new();
+        let (first, first_confirm_hash, _) = new_change(user_id);
+        store.add_change(first).await.unwrap();
+        let (second, second_confirm_hash, _) = new_change(user_id);
+        store.add_change(second).await.unwrap();
+        let (other_user, other_confirm_hash, _) = new_change(UserId::new());
+        store.add_change(other_user).await.unwrap();
+
+        assert!(store.get_pending_change(&first_confirm_hash).await.is_err());
+        assert!(store.get_pending_change(&second_confirm_hash).await.is_ok());
+        assert!(store.get_pending_change(&other_confirm_hash).await.is_ok());
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{KnownDeviceStore, KnownDeviceStoreError, TokenHash},
+    known_device::{DeviceSighting, KnownDevice, SessionRevocation},
+    user::UserId,
+};
+
+#[derive(Default, Debug)]
+pub struct HashmapKnownDeviceStore {
+    devices: HashMap<(UserId, String), (KnownDevice, Option<SessionRevocation>)>,
+}
+
+#[async_trait::async_trait]
+impl KnownDeviceStore for HashmapKnownDeviceStore {
+    async fn record_device(
+        &mut self,
+        device: KnownDevice,
+        revocation: SessionRevocation,
+    ) -> Result<DeviceSighting, KnownDeviceStoreError> {
+        let has_devices = self
+            .devices
+            .keys()
+            .any(|(user_id, _)| user_id == &device.user_id);
+        let key = (device.user_id, device.fingerprint.clone());
+        if let Some((known, _)) = self.devices.get_mut(&key) {
+            known.last_seen_at = device.last_seen_at;
+            return Ok(DeviceSighting::Known);
+        }
+        // There is no alert, hence no link, for the first device
+        self.devices
+            .insert(key, (device, has_devices.then_some(revocation)));
+        Ok(if has_devices {
+            DeviceSighting::New
+        } else {
+            DeviceSighting::FirstDevice
+        })
+    }
+
+    async fn revoke_session(
+        &mut self,
+        token_hash: &str,
+    ) -> Result<(UserId, TokenHash), KnownDeviceStoreError> {
+        let key = self
+            .devices
+            .iter()
+            .find(|(_, (_, revocation))| {
+                revocation
+                    .as_ref()
+                    .is_some_and(|revocation| revocation.token_hash == token_hash)
+            })
+            .map(|(key, _)| key.clone())
+            .ok_or(KnownDeviceStoreError::RevocationNotFound)?;
+        let (device, revocation) = self.devices.remove(&key).expect("key was just found");
+        let revocation = revocation.expect("revocation was just matched");
+        Ok((device.user_id, revocation.session))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_hashmap_known_device_store() {
+        let mut store = HashmapKnownDeviceStore::default();
+        let user_id = UserId::new();
+        let laptop = KnownDevice::new(user_id, Some("Firefox".to_owned()), Some("10.0.0.1"));
+        let phone = KnownDevice::new(user_id, Some("Safari".to_owned()), Some("10.0.0.1"));
+
+        let (revocation, first_token) = SessionRevocation::new("first");
+        let sighting = store
+            .record_device(laptop.clone(), revocation)
+            .await
+            .unwrap();
+        assert_eq!(sighting, DeviceSighting::FirstDevice);
+        let (revocation, _) = SessionRevocation::new("second");
+        let sighting = store
+            .record_device(laptop.clone(), revocation)
+            .await
+            .unwrap();
+        assert_eq!(sighting, DeviceSighting::Known);
+        let (revocation, token) = SessionRevocation::new("third");
+        let sighting = store
+            .record_device(phone.clone(), revocation)
+            .await
+            .unwrap();
+        assert_eq!(sighting, DeviceSighting::New);
+
+        // The first device had no alert
+        let result = store
+            .revoke_session(&SessionRevocation::hash(&first_token))
+            .await;
+        assert!(matches!(
+            result,
+            Err(KnownDeviceStoreError::RevocationNotFound)
+        ));
+
+        let token_hash = SessionRevocation::hash(&token);
+        let (revoked_user_id, session) = store.revoke_session(&token_hash).await.unwrap();
+        assert_eq!(revoked_user_id, user_id);
+        assert_eq!(session, TokenHash::of("third"));
+        assert!(store.revoke_session(&token_hash).await.is_err());
+
+        // The device is forgotten, its next login is alerted again
+        let (revocation, _) = SessionRevocation::new("fourth");
+        let sighting = store.record_device(phone, revocation).await.unwrap();
+        assert_eq!(sighting, DeviceSighting::New);
+    }
+}
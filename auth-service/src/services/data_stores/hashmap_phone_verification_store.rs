@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{PhoneVerification, PhoneVerificationStore, PhoneVerificationStoreError},
+    email::Email,
+};
+
+#[derive(Default, Debug)]
+pub struct HashmapPhoneVerificationStore {
+    // Each verification along with the number of wrong codes given for it
+    verifications: HashMap<Email, (PhoneVerification, u32)>,
+}
+
+#[async_trait::async_trait]
+impl PhoneVerificationStore for HashmapPhoneVerificationStore {
+    async fn add_verification(
+        &mut self,
+        email: &Email,
+        verification: PhoneVerification,
+    ) -> Result<(), PhoneVerificationStoreError> {
+        self.verifications.insert(email.clone(), (verification, 0));
+        Ok(())
+    }
+
+    async fn get_verification(
+        &self,
+        email: &Email,
+    ) -> Result<PhoneVerification, PhoneVerificationStoreError> {
+        self.verifications
+            .get(email)
+            .map(|(verification, _)| verification.clone())
+            .ok_or(PhoneVerificationStoreError::VerificationNotFound)
+    }
+
+    async fn remove_verification(
+        &mut self,
+        email: &Email,
+    ) -> Result<(), PhoneVerificationStoreError> {
+        self.verifications.remove(email);
+        Ok(())
+    }
+
+    async fn record_failed_attempt(
+        &mut self,
+        email: &Email,
+        max_attempts: u32,
+    ) -> Result<(), PhoneVerificationStoreError> {
+        let (_, failed_attempts) = self
+            .verifications
+            .get_mut(email)
+            .ok_or(PhoneVerificationStoreError::VerificationNotFound)?;
+        *failed_attempts += 1;
+        if *failed_attempts >= max_attempts {
+            self.verifications.remove(email);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::data_stores::{TwoFACode, TwoFACodeHash};
+    use crate::domain::phone_number::PhoneNumber;
+
+    fn verification() -> PhoneVerification {
+        let phone_number = PhoneNumber::parse("+33612345678").unwrap();
+        PhoneVerification {
+            code_hash: TwoFACodeHash::for_phone_number("secret", &phone_number, &TwoFACode::new()),
+            phone_number,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hashmap_phone_verification_store() {
+        let mut store = HashmapPhoneVerificationStore::default();
+        let email = Email::parse("foo@bar.com").unwrap();
+        let verification = verification();
+
+        store
+            .add_verification(&email, verification.clone())
+            .await
+            .unwrap();
+        assert_eq!(store.get_verification(&email).await.unwrap(), verification);
+
+        store.remove_verification(&email).await.unwrap();
+        assert!(matches!(
+            store.get_verification(&email).await,
+            Err(PhoneVerificationStoreError::VerificationNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verification_removed_after_max_failed_attempts() {
+        let mut store = HashmapPhoneVerificationStore::default();
+        let email = Email::parse("foo@bar.com").unwrap();
+        store
+            .add_verification(&email, verification())
+            .await
+            .unwrap();
+
+        store.record_failed_attempt(&email, 2).await.unwrap();
+        assert!(store.get_verification(&email).await.is_ok());
+        store.record_failed_attempt(&email, 2).await.unwrap();
+        assert!(matches!(
+            store.get_verification(&email).await,
+            Err(PhoneVerificationStoreError::VerificationNotFound)
+        ));
+
+        // A new verification starts over
+        store
+            .add_verification(&email, verification())
+            .await
+            .unwrap();
+        store.record_failed_attempt(&email, 2).await.unwrap();
+        assert!(store.get_verification(&email).await.is_ok());
+    }
+}
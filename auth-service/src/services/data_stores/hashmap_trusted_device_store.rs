@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::domain::{
+    data_stores::{TrustedDeviceStore, TrustedDeviceStoreError},
+    trusted_device::TrustedDevice,
+    user::UserId,
+};
+
+#[derive(Default, Debug)]
+pub struct HashmapTrustedDeviceStore {
+    devices: HashMap<Uuid, TrustedDevice>,
+}
+
+#[async_trait::async_trait]
+impl TrustedDeviceStore for HashmapTrustedDeviceStore {
+    async fn add_device(&mut self, device: TrustedDevice) -> Result<(), TrustedDeviceStoreError> {
+        self.devices.insert(device.id, device);
+        Ok(())
+    }
+
+    async fn get_device(
+        &self,
+        user_id: &UserId,
+        id: &Uuid,
+    ) -> Result<TrustedDevice, TrustedDeviceStoreError> {
+        self.devices
+            .get(id)
+            .filter(|device| &device.user_id == user_id && !device.is_expired(Utc::now()))
+            .cloned()
+            .ok_or(TrustedDeviceStoreError::DeviceNotFound)
+    }
+
+    async fn get_devices(
+        &self,
+        user_id: &UserId,
+    ) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError> {
+        let now = Utc::now();
+        let mut devices: Vec<TrustedDevice> = self
+            .devices
+            .values()
+            .filter(|device| &device.user_id == user_id && !device.is_expired(now))
+            .cloned()
+            .collect();
+        devices.sort_by_key(|device| device.created_at);
+        Ok(devices)
+    }
+
+    async fn remove_device(
+        &mut self,
+        user_id: &UserId,
+        id: &Uuid,
+    ) -> Result<(), TrustedDeviceStoreError> {
+        self.get_device(user_id, id).await?;
+        self.devices.remove(id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_hashmap_trusted_device_store() {
+        let mut store = HashmapTrustedDeviceStore::default();
+        let user_id = UserId::new();
+        let other_user_id = UserId::new();
+        let device =
+            TrustedDevice::new(user_id, Some("Firefox".to_owned()), Duration::from_secs(60));
+        store.add_device(device.clone()).await.unwrap();
+
+        assert_eq!(
+            store.get_device(&user_id, &device.id).await.unwrap(),
+            device
+        );
+        assert_eq!(
+            store.get_devices(&user_id).await.unwrap(),
+            vec![device.clone()]
+        );
+        // A device only matches the user it was created for
+        assert!(matches!(
+            store.get_device(&other_user_id, &device.id).await,
+            Err(TrustedDeviceStoreError::DeviceNotFound)
+        ));
+        assert!(store
+            .remove_device(&other_user_id, &device.id)
+            .await
+            .is_err());
+
+        store.remove_device(&user_id, &device.id).await.unwrap();
+        assert!(store.get_device(&user_id, &device.id).await.is_err());
+        assert!(store.get_devices(&user_id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_expired_devices_are_not_returned() {
+        let mut store = HashmapTrustedDeviceStore::default();
+        let user_id = UserId::new();
+        let device = TrustedDevice::new(user_id, None, Duration::ZERO);
+        store.add_device(device.clone()).await.unwrap();
+
+        assert!(store.get_device(&user_id, &device.id).await.is_err());
+        assert!(store.get_devices(&user_id).await.unwrap().is_empty());
+    }
+}
@@ -1,13 +1,41 @@
 use std::collections::HashMap;
 
+use chrono::{DateTime, Utc};
+
 use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+    data_stores::{
+        LoginAttemptId, PendingLoginAttempt, ResendPolicy, TwoFACodeHash, TwoFACodeStore,
+        TwoFACodeStoreError,
+    },
     email::Email,
 };
 
+#[derive(Debug)]
+struct TwoFAEntry {
+    email: Email,
+    code_hash: TwoFACodeHash,
+    created_at: DateTime<Utc>,
+    resend_count: u32,
+    last_sent_at: DateTime<Utc>,
+    failed_attempts: u32,
+}
+
 #[derive(Default, Debug)]
 pub struct HashmapTwoFACodeStore {
-    codes: HashMap<Email, (TwoFACode, LoginAttemptId)>,
+    codes: HashMap<LoginAttemptId, TwoFAEntry>,
+}
+
+impl HashmapTwoFACodeStore {
+    fn get_entry_mut(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<&mut TwoFAEntry, TwoFACodeStoreError> {
+        self.codes
+            .get_mut(login_attempt_id)
+            .filter(|entry| &entry.email == email)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    }
 }
 
 #[async_trait::async_trait]
@@ -15,49 +43,274 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
     async fn add_code(
         &mut self,
         email: &Email,
-        code: TwoFACode,
+        code_hash: TwoFACodeHash,
         login_attempt_id: LoginAttemptId,
     ) -> Result<(), TwoFACodeStoreError> {
-        self.codes.insert(email.clone(), (code, login_attempt_id));
+        let now = Utc::now();
+        self.codes.insert(
+            login_attempt_id,
+            TwoFAEntry {
+                email: email.clone(),
+                code_hash,
+                created_at: now,
+                resend_count: 0,
+                last_sent_at: now,
+                failed_attempts: 0,
+            },
+        );
         Ok(())
     }
 
     async fn get_code(
         &self,
         email: &Email,
-    ) -> Result<(TwoFACode, LoginAttemptId), TwoFACodeStoreError> {
-        if let Some(code) = self.codes.get(email) {
-            Ok(code.clone())
-        } else {
-            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<TwoFACodeHash, TwoFACodeStoreError> {
+        match self.codes.get(login_attempt_id) {
+            Some(entry) if &entry.email == email => Ok(entry.code_hash.clone()),
+            _ => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
     }
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        self.codes.remove(email);
+    async fn remove_code(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        if self.get_entry_mut(email, login_attempt_id).is_ok() {
+            self.codes.remove(login_attempt_id);
+        }
+        Ok(())
+    }
+
+    async fn get_login_attempts(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<PendingLoginAttempt>, TwoFACodeStoreError> {
+        let mut attempts: Vec<PendingLoginAttempt> = self
+            .codes
+            .iter()
+            .filter(|(_, entry)| &entry.email == email)
+            .map(|(login_attempt_id, entry)| PendingLoginAttempt {
+                login_attempt_id: login_attempt_id.clone(),
+                created_at: entry.created_at,
+            })
+            .collect();
+        attempts.sort_by_key(|attempt| attempt.created_at);
+        Ok(attempts)
+    }
+
+    async fn record_resend(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        code_hash: TwoFACodeHash,
+        policy: &ResendPolicy,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let entry = self.get_entry_mut(email, login_attempt_id)?;
+        let now = Utc::now();
+        policy.check(entry.resend_count, entry.last_sent_at, now)?;
+        entry.code_hash = code_hash;
+        entry.resend_count += 1;
+        entry.last_sent_at = now;
+        Ok(())
+    }
+
+    async fn record_failed_attempt(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        max_attempts: u32,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let entry = self.get_entry_mut(email, login_attempt_id)?;
+        entry.failed_attempts += 1;
+        if entry.failed_attempts >= max_attempts {
+            self.codes.remove(login_attempt_id);
+        }
         Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use super::*;
+    use crate::domain::data_stores::TwoFACode;
+
+    fn code_hash(login_attempt_id: &LoginAttemptId) -> TwoFACodeHash {
+        TwoFACodeHash::new("secret", login_attempt_id, &TwoFACode::default())
+    }
 
     #[tokio::test]
     async fn test_hashmap_two_fa_code_store() {
         let mut store = HashmapTwoFACodeStore::default();
         let email = Email::parse("foo@bar.com").unwrap();
-        let code = TwoFACode::default();
         let login_attempt_id = LoginAttemptId::default();
+        let code_hash = code_hash(&login_attempt_id);
         assert!(store
-            .add_code(&email, code.clone(), login_attempt_id.clone())
+            .add_code(&email, code_hash.clone(), login_attempt_id.clone())
             .await
             .is_ok());
-        let retrieved_code = store.get_code(&email).await;
+        let retrieved_code = store.get_code(&email, &login_attempt_id).await;
         assert!(retrieved_code.is_ok());
-        assert_eq!(retrieved_code.unwrap(), (code, login_attempt_id));
-        assert!(store.remove_code(&email).await.is_ok());
-        let retrieved_code = store.get_code(&email).await;
+        assert_eq!(retrieved_code.unwrap(), code_hash);
+        assert!(store.remove_code(&email, &login_attempt_id).await.is_ok());
+        let retrieved_code = store.get_code(&email, &login_attempt_id).await;
         assert!(retrieved_code.is_err());
     }
+
+    #[tokio::test]
+    async fn test_concurrent_login_attempts() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("foo@bar.com").unwrap();
+        let other_email = Email::parse("bar@foo.com").unwrap();
+        let first_id = LoginAttemptId::default();
+        let second_id = LoginAttemptId::default();
+        let first = (first_id.clone(), code_hash(&first_id));
+        let second = (second_id.clone(), code_hash(&second_id));
+        for (login_attempt_id, code_hash) in [&first, &second] {
+            store
+                .add_code(&email, code_hash.clone(), login_attempt_id.clone())
+                .await
+                .unwrap();
+        }
+
+        // Both attempts stay valid
+        for (login_attempt_id, code_hash) in [&first, &second] {
+            assert_eq!(
+                &store.get_code(&email, login_attempt_id).await.unwrap(),
+                code_hash
+            );
+        }
+        let attempts = store.get_login_attempts(&email).await.unwrap();
+        let ids: Vec<&LoginAttemptId> = attempts.iter().map(|a| &a.login_attempt_id).collect();
+        assert_eq!(ids, vec![&first_id, &second_id]);
+
+        // An attempt does not match another email
+        assert!(store.get_code(&other_email, &first_id).await.is_err());
+        store.remove_code(&other_email, &first_id).await.unwrap();
+        assert!(store
+            .get_login_attempts(&other_email)
+            .await
+            .unwrap()
+            .is_empty());
+
+        store.remove_code(&email, &first_id).await.unwrap();
+        assert!(store.get_code(&email, &first_id).await.is_err());
+        assert!(store.get_code(&email, &second_id).await.is_ok());
+        assert_eq!(store.get_login_attempts(&email).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_record_resend() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("foo@bar.com").unwrap();
+        let login_attempt_id = LoginAttemptId::default();
+        let policy = ResendPolicy {
+            cooldown: Duration::from_millis(50),
+            max_resends: 1,
+        };
+        store
+            .add_code(
+                &email,
+                code_hash(&login_attempt_id),
+                login_attempt_id.clone(),
+            )
+            .await
+            .unwrap();
+
+        // The code was just sent
+        let result = store
+            .record_resend(
+                &email,
+                &login_attempt_id,
+                code_hash(&login_attempt_id),
+                &policy,
+            )
+            .await;
+        assert!(matches!(result, Err(TwoFACodeStoreError::ResendTooSoon)));
+
+        tokio::time::sleep(policy.cooldown).await;
+        let other_id = LoginAttemptId::default();
+        let result = store
+            .record_resend(&email, &other_id, code_hash(&other_id), &policy)
+            .await;
+        assert!(matches!(
+            result,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        ));
+        let new_code_hash = code_hash(&login_attempt_id);
+        store
+            .record_resend(&email, &login_attempt_id, new_code_hash.clone(), &policy)
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_code(&email, &login_attempt_id).await.unwrap(),
+            new_code_hash
+        );
+
+        tokio::time::sleep(policy.cooldown).await;
+        let result = store
+            .record_resend(
+                &email,
+                &login_attempt_id,
+                code_hash(&login_attempt_id),
+                &policy,
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(TwoFACodeStoreError::ResendLimitReached)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_record_failed_attempt() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("foo@bar.com").unwrap();
+        let login_attempt_id = LoginAttemptId::default();
+        let policy = ResendPolicy {
+            cooldown: Duration::ZERO,
+            max_resends: 1,
+        };
+        store
+            .add_code(
+                &email,
+                code_hash(&login_attempt_id),
+                login_attempt_id.clone(),
+            )
+            .await
+            .unwrap();
+
+        store
+            .record_failed_attempt(&email, &login_attempt_id, 2)
+            .await
+            .unwrap();
+        assert!(store.get_code(&email, &login_attempt_id).await.is_ok());
+        // A new code does not give more guesses
+        store
+            .record_resend(
+                &email,
+                &login_attempt_id,
+                code_hash(&login_attempt_id),
+                &policy,
+            )
+            .await
+            .unwrap();
+        store
+            .record_failed_attempt(&email, &login_attempt_id, 2)
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.get_code(&email, &login_attempt_id).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        ));
+        assert!(matches!(
+            store
+                .record_failed_attempt(&email, &login_attempt_id, 2)
+                .await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        ));
+    }
 }
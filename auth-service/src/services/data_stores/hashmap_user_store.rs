@@ -2,28 +2,53 @@ use crate::domain::data_stores::UserStore;
 use crate::domain::data_stores::UserStoreError;
 use crate::domain::email::Email;
 use crate::domain::password::Password;
-use crate::domain::user::User;
+use crate::domain::phone_number::PhoneNumber;
+use crate::domain::profile::ProfileUpdate;
+use crate::domain::user::{AccountStatus, TwoFAChannel, User, UserId};
+use chrono::Utc;
 use std::collections::HashMap;
 
 #[derive(Default, Debug)]
 pub struct HashmapUserStore {
-    users: HashMap<Email, User>,
+    users: HashMap<UserId, User>,
+    /// Replaced passwords of each user, most recent last
+    password_history: HashMap<UserId, Vec<Password>>,
 }
 
 #[async_trait::async_trait]
 impl UserStore for HashmapUserStore {
     async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
-        if self.users.contains_key(&user.email) {
+        if self
+            .users
+            .values()
+            .any(|existing| existing.email == user.email || existing.id == user.id)
+        {
             Err(UserStoreError::UserAlreadyExists)
         } else {
-            self.users.insert(user.email.clone(), user);
+            self.users.insert(user.id, user);
             Ok(())
         }
     }
 
-    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+    async fn delete_user(&mut self, user_id: &UserId) -> Result<(), UserStoreError> {
         self.users
-            .get(email)
+            .remove(user_id)
+            .ok_or(UserStoreError::UserNotFound)?;
+        self.password_history.remove(user_id);
+        Ok(())
+    }
+
+    async fn get_user(&self, user_id: &UserId) -> Result<User, UserStoreError> {
+        self.users
+            .get(user_id)
+            .cloned()
+            .ok_or(UserStoreError::UserNotFound)
+    }
+
+    async fn get_user_by_email(&self, email: &Email) -> Result<User, UserStoreError> {
+        self.users
+            .values()
+            .find(|user| &user.email == email)
             .cloned()
             .ok_or(UserStoreError::UserNotFound)
     }
@@ -36,13 +61,136 @@ impl UserStore for HashmapUserStore {
         email: &Email,
         password: &Password,
     ) -> Result<(), UserStoreError> {
-        match self.get_user(email).await {
+        match self.get_user_by_email(email).await {
             Ok(user) if &user.password == password => Ok(()),
             Ok(_) => Err(UserStoreError::InvalidCredentials),
             Err(UserStoreError::UserNotFound) => Err(UserStoreError::UserNotFound),
             Err(_) => unreachable!("Unexpected error while validating user"),
         }
     }
+
+    async fn set_phone_number(
+        &mut self,
+        user_id: &UserId,
+        phone_number: Option<PhoneNumber>,
+    ) -> Result<(), UserStoreError> {
+        let user = self
+            .users
+            .get_mut(user_id)
+            .ok_or(UserStoreError::UserNotFound)?;
+        if phone_number.is_none() {
+            user.two_fa_channel = TwoFAChannel::Email;
+        }
+        user.phone_number = phone_number;
+        Ok(())
+    }
+
+    async fn set_two_fa_channel(
+        &mut self,
+        user_id: &UserId,
+        channel: TwoFAChannel,
+    ) -> Result<(), UserStoreError> {
+        let user = self
+            .users
+            .get_mut(user_id)
+            .ok_or(UserStoreError::UserNotFound)?;
+        user.two_fa_channel = channel;
+        Ok(())
+    }
+
+    async fn update_profile(
+        &mut self,
+        user_id: &UserId,
+        update: ProfileUpdate,
+    ) -> Result<User, UserStoreError> {
+        let user = self
+            .users
+            .get_mut(user_id)
+            .ok_or(UserStoreError::UserNotFound)?;
+        user.apply_profile_update(update);
+        Ok(user.clone())
+    }
+
+    async fn record_login(&mut self, user_id: &UserId) -> Result<(), UserStoreError> {
+        let user = self
+            .users
+            .get_mut(user_id)
+            .ok_or(UserStoreError::UserNotFound)?;
+        user.last_login_at = Some(Utc::now());
+        Ok(())
+    }
+
+    async fn set_status(
+        &mut self,
+        user_id: &UserId,
+        status: AccountStatus,
+    ) -> Result<(), UserStoreError> {
+        let user = self
+            .users
+            .get_mut(user_id)
+            .ok_or(UserStoreError::UserNotFound)?;
+        user.status = status;
+        user.session_version += 1;
+        Ok(())
+    }
+
+    async fn revoke_sessions(&mut self, user_id: &UserId) -> Result<(), UserStoreError> {
+        let user = self
+            .users
+            .get_mut(user_id)
+            .ok_or(UserStoreError::UserNotFound)?;
+        user.session_version += 1;
+        Ok(())
+    }
+
+    async fn update_email(
+        &mut self,
+        user_id: &UserId,
+        current_email: &Email,
+        new_email: &Email,
+    ) -> Result<(), UserStoreError> {
+        if self
+            .users
+            .values()
+            .any(|user| &user.email == new_email && &user.id != user_id)
+        {
+            return Err(UserStoreError::UserAlreadyExists);
+        }
+        let user = self
+            .users
+            .get_mut(user_id)
+            .filter(|user| &user.email == current_email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        user.email = new_email.clone();
+        Ok(())
+    }
+
+    async fn update_password(
+        &mut self,
+        user_id: &UserId,
+        password: Password,
+        history_size: usize,
+    ) -> Result<(), UserStoreError> {
+        let user = self
+            .users
+            .get_mut(user_id)
+            .ok_or(UserStoreError::UserNotFound)?;
+        let history = self.password_history.entry(*user_id).or_default();
+        let reused = std::iter::once(&user.password)
+            .chain(history.iter().rev())
+            .take(history_size)
+            .any(|recent| recent == &password);
+        if reused {
+            return Err(UserStoreError::PasswordReused);
+        }
+
+        let previous = std::mem::replace(&mut user.password, password);
+        user.password_changed_at = Utc::now();
+        history.push(previous);
+        let excess = history.len().saturating_sub(history_size.saturating_sub(1));
+        history.drain(..excess);
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -50,6 +198,8 @@ mod tests {
     use secrecy::Secret;
 
     use super::*;
+    use crate::domain::locale::Locale;
+    use crate::domain::profile::DisplayName;
 
     #[tokio::test]
     async fn test_add_user() {
@@ -64,8 +214,11 @@ mod tests {
         let res = store.add_user(user.clone()).await;
         assert!(res.is_err());
         assert_eq!(res.unwrap_err(), UserStoreError::UserAlreadyExists);
+        // Another user cannot take the email
+        let res = store.add_user(user.clone().with_id(UserId::new())).await;
+        assert_eq!(res.unwrap_err(), UserStoreError::UserAlreadyExists);
         assert!(store.users.keys().len() == 1);
-        assert_eq!(store.users.get(&user.email).unwrap(), &user);
+        assert_eq!(store.users.get(&user.id).unwrap(), &user);
     }
 
     #[tokio::test]
@@ -78,8 +231,12 @@ mod tests {
         )
         .unwrap();
         assert!(store.add_user(user.clone()).await.is_ok());
-        assert!(store.get_user(&user.email).await.is_ok());
-        assert_eq!(store.get_user(&user.email).await.unwrap(), user);
+        assert_eq!(store.get_user(&user.id).await.unwrap(), user);
+        assert_eq!(store.get_user_by_email(&user.email).await.unwrap(), user);
+        assert_eq!(
+            store.get_user(&UserId::new()).await,
+            Err(UserStoreError::UserNotFound)
+        );
     }
 
     #[tokio::test]
@@ -113,4 +270,230 @@ mod tests {
         assert!(res.is_err());
         assert_eq!(res.unwrap_err(), UserStoreError::UserNotFound);
     }
+
+    #[tokio::test]
+    async fn test_set_phone_number_and_channel() {
+        let mut store = HashmapUserStore::default();
+        let user = User::new(
+            "toto@foo.com".to_string(),
+            Secret::new("password123".to_string()),
+            true,
+        )
+        .unwrap();
+        store.add_user(user.clone()).await.unwrap();
+        let phone_number = PhoneNumber::parse("+33612345678").unwrap();
+
+        store
+            .set_phone_number(&user.id, Some(phone_number.clone()))
+            .await
+            .unwrap();
+        store
+            .set_two_fa_channel(&user.id, TwoFAChannel::Sms)
+            .await
+            .unwrap();
+        let stored = store.get_user(&user.id).await.unwrap();
+        assert_eq!(stored.phone_number, Some(phone_number));
+        assert_eq!(stored.effective_two_fa_channel(), TwoFAChannel::Sms);
+
+        // Removing the phone number falls back to email
+        store.set_phone_number(&user.id, None).await.unwrap();
+        let stored = store.get_user(&user.id).await.unwrap();
+        assert_eq!(stored.phone_number, None);
+        assert_eq!(stored.two_fa_channel, TwoFAChannel::Email);
+
+        assert_eq!(
+            store
+                .set_two_fa_channel(&UserId::new(), TwoFAChannel::Sms)
+                .await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_profile() {
+        let mut store = HashmapUserStore::default();
+        let user = User::new(
+            "toto@foo.com".to_string(),
+            Secret::new("password123".to_string()),
+            true,
+        )
+        .unwrap()
+        .with_locale(Some(Locale::parse("fr").unwrap()));
+        store.add_user(user.clone()).await.unwrap();
+        let display_name = DisplayName::parse("Toto").unwrap();
+
+        let updated = store
+            .update_profile(
+                &user.id,
+                ProfileUpdate {
+                    display_name: Some(Some(display_name.clone())),
+                    locale: Some(None),
+                    timezone: None,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(updated.display_name, Some(display_name));
+        assert_eq!(updated.locale, None);
+        assert_eq!(updated.timezone, None);
+        assert_eq!(store.get_user(&user.id).await.unwrap(), updated);
+
+        assert_eq!(store.get_user(&user.id).await.unwrap().last_login_at, None);
+        store.record_login(&user.id).await.unwrap();
+        assert!(store
+            .get_user(&user.id)
+            .await
+            .unwrap()
+            .last_login_at
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_set_status() {
+        let mut store = HashmapUserStore::default();
+        let user = User::new(
+            "toto@foo.com".to_string(),
+            Secret::new("password123".to_string()),
+            true,
+        )
+        .unwrap();
+        store.add_user(user.clone()).await.unwrap();
+
+        store
+            .set_status(&user.id, AccountStatus::Disabled)
+            .await
+            .unwrap();
+        let stored = store.get_user(&user.id).await.unwrap();
+        assert_eq!(stored.status, AccountStatus::Disabled);
+        assert_eq!(stored.session_version, user.session_version + 1);
+        assert_eq!(
+            store
+                .set_status(&UserId::new(), AccountStatus::Active)
+                .await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_user() {
+        let mut store = HashmapUserStore::default();
+        let user = User::new(
+            "toto@foo.com".to_string(),
+            Secret::new("password123".to_string()),
+            true,
+        )
+        .unwrap();
+        store.add_user(user.clone()).await.unwrap();
+
+        store.delete_user(&user.id).await.unwrap();
+        assert_eq!(
+            store.get_user(&user.id).await,
+            Err(UserStoreError::UserNotFound)
+        );
+        assert_eq!(
+            store.delete_user(&user.id).await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revoke_sessions() {
+        let mut store = HashmapUserStore::default();
+        let user = User::new(
+            "toto@foo.com".to_string(),
+            Secret::new("password123".to_string()),
+            true,
+        )
+        .unwrap();
+        store.add_user(user.clone()).await.unwrap();
+
+        store.revoke_sessions(&user.id).await.unwrap();
+        let stored = store.get_user(&user.id).await.unwrap();
+        assert_eq!(stored.session_version, user.session_version + 1);
+        assert_eq!(stored.status, user.status);
+        assert_eq!(
+            store.revoke_sessions(&UserId::new()).await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_email() {
+        let mut store = HashmapUserStore::default();
+        let user = User::new(
+            "toto@foo.com".to_string(),
+            Secret::new("password123".to_string()),
+            true,
+        )
+        .unwrap();
+        let other = User::new(
+            "other@foo.com".to_string(),
+            Secret::new("password123".to_string()),
+            true,
+        )
+        .unwrap();
+        store.add_user(user.clone()).await.unwrap();
+        store.add_user(other.clone()).await.unwrap();
+        let new_email = Email::parse("titi@foo.com").unwrap();
+
+        assert_eq!(
+            store
+                .update_email(&user.id, &user.email, &other.email)
+                .await,
+            Err(UserStoreError::UserAlreadyExists)
+        );
+        store
+            .update_email(&user.id, &user.email, &new_email)
+            .await
+            .unwrap();
+        assert_eq!(store.get_user(&user.id).await.unwrap().email, new_email);
+        assert!(store.get_user_by_email(&user.email).await.is_err());
+        // The email changed in the meantime
+        assert_eq!(
+            store.update_email(&user.id, &user.email, &new_email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_password_rejects_the_last_passwords() {
+        let mut store = HashmapUserStore::default();
+        let user = User::new(
+            "toto@foo.com".to_string(),
+            Secret::new("password1".to_string()),
+            true,
+        )
+        .unwrap();
+        store.add_user(user.clone()).await.unwrap();
+        let password = |p: &str| Password::parse(Secret::new(p.to_string())).unwrap();
+
+        for p in ["password2", "password3"] {
+            store
+                .update_password(&user.id, password(p), 3)
+                .await
+                .unwrap();
+        }
+        for p in ["password1", "password2", "password3"] {
+            assert_eq!(
+                store.update_password(&user.id, password(p), 3).await,
+                Err(UserStoreError::PasswordReused),
+                "Failed for {}",
+                p
+            );
+        }
+        store
+            .update_password(&user.id, password("password4"), 3)
+            .await
+            .unwrap();
+        assert!(store
+            .validate_user(&user.email, &password("password4"))
+            .await
+            .is_ok());
+        // Only the last 3 passwords are remembered
+        assert!(store
+            .update_password(&user.id, password("password1"), 3)
+            .await
+            .is_ok());
+        assert_eq!(store.password_history[&user.id].len(), 2);
+    }
 }
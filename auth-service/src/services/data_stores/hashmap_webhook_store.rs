@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::domain::data_stores::{WebhookStore, WebhookStoreError};
+use crate::domain::webhook::{DeliveryStatus, WebhookDelivery, WebhookSubscription};
+
+#[derive(Default, Debug)]
+pub struct HashmapWebhookStore {
+    subscriptions: HashMap<Uuid, WebhookSubscription>,
+    deliveries: Vec<WebhookDelivery>,
+}
+
+#[cfg(test)]
+impl HashmapWebhookStore {
+    /// Queues `event` for every active subscription to its type,
+    /// like the `users` table triggers do for the Postgres store.
+    pub fn enqueue_event(&mut self, event: crate::domain::webhook::WebhookEvent) {
+        let deliveries: Vec<WebhookDelivery> = self
+            .subscriptions
+            .values()
+            .filter(|subscription| subscription.is_subscribed_to(event.event_type))
+            .map(|subscription| WebhookDelivery::new(subscription.id, event.clone()))
+            .collect();
+        self.deliveries.extend(deliveries);
+    }
+}
+
+#[async_trait::async_trait]
+impl WebhookStore for HashmapWebhookStore {
+    async fn add_subscription(
+        &mut self,
+        subscription: WebhookSubscription,
+    ) -> Result<(), WebhookStoreError> {
+        self.subscriptions.insert(subscription.id, subscription);
+        Ok(())
+    }
+
+    async fn get_subscriptions(&self) -> Result<Vec<WebhookSubscription>, WebhookStoreError> {
+        let mut subscriptions: Vec<WebhookSubscription> =
+            self.subscriptions.values().cloned().collect();
+        subscriptions.sort_by_key(|subscription| subscription.created_at);
+        Ok(subscriptions)
+    }
+
+    async fn deactivate_subscription(&mut self, id: &Uuid) -> Result<(), WebhookStoreError> {
+        let subscription = self
+            .subscriptions
+            .get_mut(id)
+            .ok_or(WebhookStoreError::SubscriptionNotFound)?;
+        subscription.active = false;
+        Ok(())
+    }
+
+    async fn get_deliveries(
+        &self,
+        subscription_id: &Uuid,
+    ) -> Result<Vec<WebhookDelivery>, WebhookStoreError> {
+        if !self.subscriptions.contains_key(subscription_id) {
+            return Err(WebhookStoreError::SubscriptionNotFound);
+        }
+        Ok(self
+            .deliveries
+            .iter()
+            .filter(|delivery| delivery.subscription_id == *subscription_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn claim_due_deliveries(
+        &mut self,
+        limit: i64,
+        lease: Duration,
+    ) -> Result<Vec<(WebhookDelivery, WebhookSubscription)>, WebhookStoreError> {
+        let now = Utc::now();
+        let lease = chrono::Duration::from_std(lease)
+            .map_err(|e| WebhookStoreError::UnexpectedError(e.into()))?;
+        let mut claimed = Vec::new();
+        for delivery in self.deliveries.iter_mut() {
+            if claimed.len() as i64 >= limit {
+                break;
+            }
+            if delivery.status != DeliveryStatus::Pending || delivery.next_attempt_at > now {
+                continue;
+            }
+            let Some(subscription) = self
+                .subscriptions
+                .get(&delivery.subscription_id)
+                .filter(|subscription| subscription.active)
+            else {
+                continue;
+            };
+            delivery.next_attempt_at = now + lease;
+            claimed.push((delivery.clone(), subscription.clone()));
+        }
+        Ok(claimed)
+    }
+
+    async fn update_delivery(
+        &mut self,
+        delivery: &WebhookDelivery,
+    ) -> Result<(), WebhookStoreError> {
+        let stored = self
+            .deliveries
+            .iter_mut()
+            .find(|stored| stored.id == delivery.id)
+            .ok_or_else(|| {
+                WebhookStoreError::UnexpectedError(color_eyre::eyre::eyre!(
+                    "Unknown delivery {}",
+                    delivery.id
+                ))
+            })?;
+        *stored = delivery.clone();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::webhook::{DeliveryAttempt, WebhookEvent, WebhookEventType};
+
+    #[tokio::test]
+    async fn test_hashmap_webhook_store() {
+        let mut store = HashmapWebhookStore::default();
+        let subscription = WebhookSubscription::new(
+            "http://localhost/hook".to_owned(),
+            vec![WebhookEventType::UserCreated],
+        );
+        store.add_subscription(subscription.clone()).await.unwrap();
+
+        let created = WebhookEvent::new(
+            WebhookEventType::UserCreated,
+            serde_json::json!({ "email": "foo@bar.com" }),
+        );
+        let deleted = WebhookEvent::new(
+            WebhookEventType::UserDeleted,
+            serde_json::json!({ "email": "foo@bar.com" }),
+        );
+        store.enqueue_event(created.clone());
+        store.enqueue_event(deleted);
+
+        let deliveries = store.get_deliveries(&subscription.id).await.unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].event, created);
+
+        let claimed = store
+            .claim_due_deliveries(10, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].1, subscription);
+        // Leased deliveries are not claimed twice
+        assert!(store
+            .claim_due_deliveries(10, Duration::from_secs(60))
+            .await
+            .unwrap()
+            .is_empty());
+
+        let mut delivery = claimed[0].0.clone();
+        delivery.record(&DeliveryAttempt {
+            attempted_at: Utc::now(),
+            response_status: Some(200),
+            error: None,
+            next_attempt_at: None,
+        });
+        store.update_delivery(&delivery).await.unwrap();
+        assert_eq!(
+            store.get_deliveries(&subscription.id).await.unwrap()[0].status,
+            DeliveryStatus::Delivered
+        );
+
+        store
+            .deactivate_subscription(&subscription.id)
+            .await
+            .unwrap();
+        store.enqueue_event(WebhookEvent::new(
+            WebhookEventType::UserCreated,
+            serde_json::json!({ "email": "toto@bar.com" }),
+        ));
+        assert_eq!(
+            store.get_deliveries(&subscription.id).await.unwrap().len(),
+            1
+        );
+        assert!(matches!(
+            store.deactivate_subscription(&Uuid::new_v4()).await,
+            Err(WebhookStoreError::SubscriptionNotFound)
+        ));
+    }
+}
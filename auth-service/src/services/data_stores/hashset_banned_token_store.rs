@@ -1,23 +1,26 @@
-use crate::domain::data_stores::{BannedTokenStore, BannedTokenStoreError};
+use crate::domain::data_stores::{BannedTokenStore, BannedTokenStoreError, TokenHash};
 use std::collections::HashSet;
 
 #[derive(Default, Debug, Clone)]
 pub struct HashsetBannedTokenStore {
-    banned_tokens: HashSet<String>,
+    banned_tokens: HashSet<TokenHash>,
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for HashsetBannedTokenStore {
-    async fn add_banned_token(&mut self, token: &str) -> Result<(), BannedTokenStoreError> {
-        self.banned_tokens.insert(token.to_string());
+    async fn add_banned_token(&mut self, token: &TokenHash) -> Result<(), BannedTokenStoreError> {
+        self.banned_tokens.insert(token.clone());
         Ok(())
     }
 
     async fn is_token_banned(&self, token: &str) -> Result<bool, BannedTokenStoreError> {
-        Ok(self.banned_tokens.contains(token))
+        Ok(self.banned_tokens.contains(&TokenHash::of(token)))
     }
 
-    async fn remove_banned_token(&mut self, token: &str) -> Result<(), BannedTokenStoreError> {
+    async fn remove_banned_token(
+        &mut self,
+        token: &TokenHash,
+    ) -> Result<(), BannedTokenStoreError> {
         self.banned_tokens.remove(token);
         Ok(())
     }
@@ -30,11 +33,11 @@ mod tests {
     #[tokio::test]
     async fn test_store_banned_token() {
         let mut store = HashsetBannedTokenStore::default();
-        let token = "token1";
-        store.add_banned_token(token).await.unwrap();
-        assert!(store.is_token_banned(token).await.unwrap());
+        let token = TokenHash::of("token1");
+        store.add_banned_token(&token).await.unwrap();
+        assert!(store.is_token_banned("token1").await.unwrap());
         assert!(!store.is_token_banned("token2").await.unwrap());
-        store.remove_banned_token(token).await.unwrap();
-        assert!(!store.is_token_banned(token).await.unwrap());
+        store.remove_banned_token(&token).await.unwrap();
+        assert!(!store.is_token_banned("token1").await.unwrap());
     }
 }
@@ -0,0 +1,184 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::audit::{
+    AuditChainHead, AuditEvent, AuditEventFilter, AuditOutcome, AuditRecord,
+};
+use crate::domain::data_stores::{AuditEventStore, AuditEventStoreError};
+
+/// Key of the advisory lock serialising writers, so that each record is chained to the latest one
+const AUDIT_CHAIN_LOCK_KEY: i64 = 0x6175_6469_745f_6c6f;
+
+pub struct PostgresAuditEventStore {
+    pool: PgPool,
+}
+
+impl PostgresAuditEventStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditEventStore for PostgresAuditEventStore {
+    #[tracing::instrument(name = "Adding audit event to db", skip_all)]
+    async fn add_event(&mut self, event: AuditEvent) -> Result<AuditRecord, AuditEventStoreError> {
+        let mut transaction = self.pool.begin().await?;
+
+        // The lock is released when the transaction ends
+        sqlx::query!("SELECT pg_advisory_xact_lock($1)", AUDIT_CHAIN_LOCK_KEY)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        let head = sqlx::query_as!(
+            AuditChainHead,
+            r#"
+            SELECT sequence, hash, created_at
+            FROM audit_events
+            ORDER BY sequence DESC
+            LIMIT 1
+            "#
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+
+        let record = AuditRecord::chain(event, head.as_ref());
+        let event = &record.event;
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_events
+                (sequence, prev_hash, hash, id, event_type, user_email, actor, ip_address, user_agent, outcome, failure_reason, request_id, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            "#,
+            record.sequence,
+            record.prev_hash,
+            record.hash,
+            event.id,
+            event.event_type.as_str(),
+            event.user_email,
+            event.actor,
+            event.ip_address,
+            event.user_agent,
+            event.outcome.as_str(),
+            event.outcome.failure_reason(),
+            event.request_id,
+            event.created_at
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(record)
+    }
+
+    #[tracing::instrument(name = "Getting audit events from db", skip_all)]
+    async fn get_events(
+        &self,
+        filter: &AuditEventFilter,
+    ) -> Result<Vec<AuditRecord>, AuditEventStoreError> {
+        let rows = sqlx::query_as!(
+            AuditRecordRow,
+            r#"
+            SELECT *
+            FROM audit_events
+            WHERE ($1::TEXT IS NULL OR user_email = $1)
+              AND ($2::TIMESTAMPTZ IS NULL OR created_at >= $2)
+              AND ($3::TIMESTAMPTZ IS NULL OR created_at < $3)
+            ORDER BY sequence
+            "#,
+            filter.user_email,
+            filter.from,
+            filter.to
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(AuditRecord::try_from).collect()
+    }
+
+    #[tracing::instrument(name = "Getting audit records from db", skip_all)]
+    async fn get_records(
+        &self,
+        after_sequence: i64,
+        limit: i64,
+    ) -> Result<Vec<AuditRecord>, AuditEventStoreError> {
+        let rows = sqlx::query_as!(
+            AuditRecordRow,
+            r#"
+            SELECT *
+            FROM audit_events
+            WHERE sequence > $1
+            ORDER BY sequence
+            LIMIT $2
+            "#,
+            after_sequence,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(AuditRecord::try_from).collect()
+    }
+
+    #[tracing::instrument(name = "Getting audit chain head from db", skip_all)]
+    async fn get_chain_head(&self) -> Result<Option<AuditChainHead>, AuditEventStoreError> {
+        let head = sqlx::query_as!(
+            AuditChainHead,
+            r#"
+            SELECT sequence, hash, created_at
+            FROM audit_events
+            ORDER BY sequence DESC
+            LIMIT 1
+            "#
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(head)
+    }
+}
+
+// Row of the `audit_events` table
+struct AuditRecordRow {
+    id: Uuid,
+    event_type: String,
+    user_email: Option<String>,
+    actor: String,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+    outcome: String,
+    failure_reason: Option<String>,
+    request_id: Option<String>,
+    created_at: DateTime<Utc>,
+    sequence: i64,
+    prev_hash: String,
+    hash: String,
+}
+
+impl TryFrom<AuditRecordRow> for AuditRecord {
+    type Error = AuditEventStoreError;
+
+    fn try_from(row: AuditRecordRow) -> Result<Self, Self::Error> {
+        Ok(AuditRecord {
+            sequence: row.sequence,
+            prev_hash: row.prev_hash,
+            hash: row.hash,
+            event: AuditEvent {
+                id: row.id,
+                event_type: row
+                    .event_type
+                    .parse()
+                    .map_err(AuditEventStoreError::UnexpectedError)?,
+                user_email: row.user_email,
+                actor: row.actor,
+                ip_address: row.ip_address,
+                user_agent: row.user_agent,
+                outcome: AuditOutcome::parse(&row.outcome, row.failure_reason)
+                    .map_err(AuditEventStoreError::UnexpectedError)?,
+                request_id: row.request_id,
+                created_at: row.created_at,
+            },
+        })
+    }
+}
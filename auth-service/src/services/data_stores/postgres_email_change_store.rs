@@ -0,0 +1,192 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::data_stores::{EmailChangeStore, EmailChangeStoreError};
+use crate::domain::email::Email;
+use crate::domain::email_change::EmailChange;
+
+pub struct PostgresEmailChangeStore {
+    pool: PgPool,
+}
+
+impl PostgresEmailChangeStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailChangeStore for PostgresEmailChangeStore {
+    #[tracing::instrument(name = "Adding email change to db", skip_all)]
+    async fn add_change(&mut self, change: EmailChange) -> Result<(), EmailChangeStoreError> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM email_changes
+            WHERE user_id = $1 AND confirmed_at IS NULL
+            "#,
+            change.user_id.as_ref()
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO email_changes (
+                id, user_id, old_email, new_email, confirm_token_hash, revert_token_hash,
+                requested_at, confirm_expires_at, revert_expires_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+            change.id,
+            change.user_id.as_ref(),
+            change.old_email.as_ref(),
+            change.new_email.as_ref(),
+            change.confirm_token_hash,
+            change.revert_token_hash,
+            change.requested_at,
+            change.confirm_expires_at,
+            change.revert_expires_at
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Getting pending email change from db", skip_all)]
+    async fn get_pending_change(
+        &self,
+        confirm_token_hash: &str,
+    ) -> Result<EmailChange, EmailChangeStoreError> {
+        let row = sqlx::query_as!(
+            EmailChangeRow,
+            r#"
+            SELECT id, user_id, old_email, new_email, confirm_token_hash, revert_token_hash,
+                   requested_at, confirm_expires_at, revert_expires_at, confirmed_at, reverted_at
+            FROM email_changes
+            WHERE confirm_token_hash = $1
+              AND confirmed_at IS NULL AND reverted_at IS NULL AND confirm_expires_at > NOW()
+            "#,
+            confirm_token_hash
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        EmailChange::try_from(row)
+    }
+
+    #[tracing::instrument(name = "Getting revertible email change from db", skip_all)]
+    async fn get_revertible_change(
+        &self,
+        revert_token_hash: &str,
+    ) -> Result<EmailChange, EmailChangeStoreError> {
+        let row = sqlx::query_as!(
+            EmailChangeRow,
+            r#"
+            SELECT id, user_id, old_email, new_email, confirm_token_hash, revert_token_hash,
+                   requested_at, confirm_expires_at, revert_expires_at, confirmed_at, reverted_at
+            FROM email_changes
+            WHERE revert_token_hash = $1 AND reverted_at IS NULL AND revert_expires_at > NOW()
+            "#,
+            revert_token_hash
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        EmailChange::try_from(row)
+    }
+
+    #[tracing::instrument(name = "Confirming email change in db", skip_all)]
+    async fn mark_confirmed(&mut self, id: &Uuid) -> Result<(), EmailChangeStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_changes
+            SET confirmed_at = NOW()
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(EmailChangeStoreError::ChangeNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Reverting email change in db", skip_all)]
+    async fn mark_reverted(&mut self, id: &Uuid) -> Result<(), EmailChangeStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_changes
+            SET reverted_at = NOW()
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(EmailChangeStoreError::ChangeNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Checking reserved email in db", skip_all)]
+    async fn is_reserved(&self, email: &Email) -> Result<bool, EmailChangeStoreError> {
+        let reserved = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM email_changes
+                WHERE old_email = $1 AND confirmed_at IS NOT NULL
+                  AND reverted_at IS NULL AND revert_expires_at > NOW()
+            ) AS "reserved!"
+            "#,
+            email.as_ref()
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(reserved)
+    }
+}
+
+struct EmailChangeRow {
+    id: Uuid,
+    user_id: Uuid,
+    old_email: String,
+    new_email: String,
+    confirm_token_hash: String,
+    revert_token_hash: String,
+    requested_at: DateTime<Utc>,
+    confirm_expires_at: DateTime<Utc>,
+    revert_expires_at: DateTime<Utc>,
+    confirmed_at: Option<DateTime<Utc>>,
+    reverted_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<EmailChangeRow> for EmailChange {
+    type Error = EmailChangeStoreError;
+
+    fn try_from(row: EmailChangeRow) -> Result<Self, Self::Error> {
+        let parse =
+            |email: &str| Email::parse(email).map_err(EmailChangeStoreError::UnexpectedError);
+        Ok(EmailChange {
+            id: row.id,
+            user_id: row.user_id.into(),
+            old_email: parse(&row.old_email)?,
+            new_email: parse(&row.new_email)?,
+            confirm_token_hash: row.confirm_token_hash,
+            revert_token_hash: row.revert_token_hash,
+            requested_at: row.requested_at,
+            confirm_expires_at: row.confirm_expires_at,
+            revert_expires_at: row.revert_expires_at,
+            confirmed_at: row.confirmed_at,
+            reverted_at: row.reverted_at,
+        })
+    }
+}
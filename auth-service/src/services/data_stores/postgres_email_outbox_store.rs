@@ -0,0 +1,176 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::data_stores::{EmailOutboxStore, EmailOutboxStoreError};
+use crate::domain::email::Email;
+use crate::domain::email_client::EmailMessage;
+use crate::domain::email_outbox::OutboxEmail;
+
+pub struct PostgresEmailOutboxStore {
+    pool: PgPool,
+}
+
+impl PostgresEmailOutboxStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailOutboxStore for PostgresEmailOutboxStore {
+    #[tracing::instrument(name = "Adding email to outbox in db", skip_all)]
+    async fn add_email(&mut self, email: OutboxEmail) -> Result<(), EmailOutboxStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO email_outbox (id, recipient, subject, html_body, text_body, status, attempts, next_attempt_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+            email.id,
+            email.recipient.as_ref(),
+            email.message.subject,
+            email.message.html_body,
+            email.message.text_body,
+            email.status.as_str(),
+            email.attempts,
+            email.next_attempt_at,
+            email.created_at
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Getting email from outbox in db", skip_all)]
+    async fn get_email(&self, id: &Uuid) -> Result<OutboxEmail, EmailOutboxStoreError> {
+        let row = sqlx::query_as!(
+            OutboxEmailRow,
+            r#"
+            SELECT *
+            FROM email_outbox
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        row.try_into()
+    }
+
+    #[tracing::instrument(name = "Claiming due emails in db", skip_all)]
+    async fn claim_due_emails(
+        &mut self,
+        limit: i64,
+        lease: Duration,
+    ) -> Result<Vec<OutboxEmail>, EmailOutboxStoreError> {
+        // Rows locked by another dispatcher are skipped rather than waited for
+        let rows = sqlx::query_as!(
+            OutboxEmailRow,
+            r#"
+            UPDATE email_outbox
+            SET next_attempt_at = NOW() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id
+                FROM email_outbox
+                WHERE status = 'pending' AND next_attempt_at <= NOW()
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *
+            "#,
+            limit,
+            lease.as_secs_f64()
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut emails = rows
+            .into_iter()
+            .map(OutboxEmail::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        emails.sort_by_key(|email| email.created_at);
+        Ok(emails)
+    }
+
+    #[tracing::instrument(name = "Updating email in outbox in db", skip_all)]
+    async fn update_email(&mut self, email: &OutboxEmail) -> Result<(), EmailOutboxStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET status = $2,
+                attempts = $3,
+                next_attempt_at = $4,
+                last_attempt_at = $5,
+                last_error = $6,
+                sent_at = $7,
+                html_body = $8,
+                text_body = $9
+            WHERE id = $1
+            "#,
+            email.id,
+            email.status.as_str(),
+            email.attempts,
+            email.next_attempt_at,
+            email.last_attempt_at,
+            email.last_error,
+            email.sent_at,
+            email.message.html_body,
+            email.message.text_body
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(EmailOutboxStoreError::EmailNotFound);
+        }
+        Ok(())
+    }
+}
+
+// Row of the `email_outbox` table
+struct OutboxEmailRow {
+    id: Uuid,
+    recipient: String,
+    subject: String,
+    html_body: String,
+    text_body: String,
+    status: String,
+    attempts: i32,
+    next_attempt_at: DateTime<Utc>,
+    last_attempt_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    sent_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+}
+
+impl TryFrom<OutboxEmailRow> for OutboxEmail {
+    type Error = EmailOutboxStoreError;
+
+    fn try_from(row: OutboxEmailRow) -> Result<Self, Self::Error> {
+        Ok(OutboxEmail {
+            id: row.id,
+            recipient: Email::parse(&row.recipient)
+                .map_err(EmailOutboxStoreError::UnexpectedError)?,
+            message: EmailMessage {
+                subject: row.subject,
+                html_body: row.html_body,
+                text_body: row.text_body,
+            },
+            status: row
+                .status
+                .parse()
+                .map_err(EmailOutboxStoreError::UnexpectedError)?,
+            attempts: row.attempts,
+            next_attempt_at: row.next_attempt_at,
+            last_attempt_at: row.last_attempt_at,
+            last_error: row.last_error,
+            sent_at: row.sent_at,
+            created_at: row.created_at,
+        })
+    }
+}
@@ -0,0 +1,85 @@
+use sqlx::PgPool;
+
+use crate::domain::data_stores::{KnownDeviceStore, KnownDeviceStoreError, TokenHash};
+use crate::domain::known_device::{DeviceSighting, KnownDevice, SessionRevocation};
+use crate::domain::user::UserId;
+
+pub struct PostgresKnownDeviceStore {
+    pool: PgPool,
+}
+
+impl PostgresKnownDeviceStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl KnownDeviceStore for PostgresKnownDeviceStore {
+    #[tracing::instrument(name = "Recording known device in db", skip_all)]
+    async fn record_device(
+        &mut self,
+        device: KnownDevice,
+        revocation: SessionRevocation,
+    ) -> Result<DeviceSighting, KnownDeviceStoreError> {
+        let mut transaction = self.pool.begin().await?;
+        let has_devices = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(SELECT 1 FROM known_devices WHERE user_id = $1) AS "exists!"
+            "#,
+            device.user_id.as_ref()
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        // There is no alert, hence no link, for the first device
+        let revocation = has_devices.then_some(revocation);
+
+        // `xmax` is only set when the row already existed
+        let inserted = sqlx::query_scalar!(
+            r#"
+            INSERT INTO known_devices (user_id, fingerprint, user_agent, ip_prefix, first_seen_at, last_seen_at, revocation_token_hash, session_token_hash)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (user_id, fingerprint) DO UPDATE SET last_seen_at = EXCLUDED.last_seen_at
+            RETURNING (xmax = 0) AS "inserted!"
+            "#,
+            device.user_id.as_ref(),
+            device.fingerprint,
+            device.user_agent,
+            device.ip_prefix,
+            device.first_seen_at,
+            device.last_seen_at,
+            revocation.as_ref().map(|r| r.token_hash.as_str()),
+            revocation.as_ref().map(|r| r.session.as_ref())
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+
+        Ok(match (has_devices, inserted) {
+            (false, _) => DeviceSighting::FirstDevice,
+            (true, false) => DeviceSighting::Known,
+            (true, true) => DeviceSighting::New,
+        })
+    }
+
+    #[tracing::instrument(name = "Revoking session of known device in db", skip_all)]
+    async fn revoke_session(
+        &mut self,
+        token_hash: &str,
+    ) -> Result<(UserId, TokenHash), KnownDeviceStoreError> {
+        let row = sqlx::query!(
+            r#"
+            DELETE FROM known_devices
+            WHERE revocation_token_hash = $1
+            RETURNING user_id, session_token_hash AS "session_token_hash!"
+            "#,
+            token_hash
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let session = TokenHash::parse(row.session_token_hash)
+            .map_err(KnownDeviceStoreError::UnexpectedError)?;
+        Ok((row.user_id.into(), session))
+    }
+}
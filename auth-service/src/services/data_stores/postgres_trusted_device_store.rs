@@ -0,0 +1,127 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::data_stores::{TrustedDeviceStore, TrustedDeviceStoreError};
+use crate::domain::trusted_device::TrustedDevice;
+use crate::domain::user::UserId;
+
+pub struct PostgresTrustedDeviceStore {
+    pool: PgPool,
+}
+
+impl PostgresTrustedDeviceStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl TrustedDeviceStore for PostgresTrustedDeviceStore {
+    #[tracing::instrument(name = "Adding trusted device to db", skip_all)]
+    async fn add_device(&mut self, device: TrustedDevice) -> Result<(), TrustedDeviceStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO trusted_devices (id, user_id, user_agent, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            device.id,
+            device.user_id.as_ref(),
+            device.user_agent,
+            device.created_at,
+            device.expires_at
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Getting trusted device from db", skip_all)]
+    async fn get_device(
+        &self,
+        user_id: &UserId,
+        id: &Uuid,
+    ) -> Result<TrustedDevice, TrustedDeviceStoreError> {
+        let row = sqlx::query_as!(
+            TrustedDeviceRow,
+            r#"
+            SELECT id, user_id, user_agent, created_at, expires_at
+            FROM trusted_devices
+            WHERE id = $1 AND user_id = $2 AND expires_at > NOW()
+            "#,
+            id,
+            user_id.as_ref()
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(TrustedDevice::from(row))
+    }
+
+    #[tracing::instrument(name = "Getting trusted devices from db", skip_all)]
+    async fn get_devices(
+        &self,
+        user_id: &UserId,
+    ) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError> {
+        let rows = sqlx::query_as!(
+            TrustedDeviceRow,
+            r#"
+            SELECT id, user_id, user_agent, created_at, expires_at
+            FROM trusted_devices
+            WHERE user_id = $1 AND expires_at > NOW()
+            ORDER BY created_at
+            "#,
+            user_id.as_ref()
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(TrustedDevice::from).collect())
+    }
+
+    #[tracing::instrument(name = "Removing trusted device from db", skip_all)]
+    async fn remove_device(
+        &mut self,
+        user_id: &UserId,
+        id: &Uuid,
+    ) -> Result<(), TrustedDeviceStoreError> {
+        // Expired devices are cleaned up along the way
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM trusted_devices
+            WHERE user_id = $1 AND (id = $2 OR expires_at <= NOW())
+            RETURNING id
+            "#,
+            user_id.as_ref(),
+            id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        if !result.iter().any(|row| &row.id == id) {
+            return Err(TrustedDeviceStoreError::DeviceNotFound);
+        }
+        Ok(())
+    }
+}
+
+struct TrustedDeviceRow {
+    id: Uuid,
+    user_id: Uuid,
+    user_agent: Option<String>,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+impl From<TrustedDeviceRow> for TrustedDevice {
+    fn from(row: TrustedDeviceRow) -> Self {
+        TrustedDevice {
+            id: row.id,
+            user_id: row.user_id.into(),
+            user_agent: row.user_agent,
+            created_at: row.created_at,
+            expires_at: row.expires_at,
+        }
+    }
+}
@@ -1,23 +1,68 @@
-use argon2::{
-    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
-    PasswordVerifier, Version,
-};
+use argon2::Params;
 
-use color_eyre::eyre::Result;
-use secrecy::ExposeSecret;
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Result};
 use secrecy::Secret;
 use sqlx::PgPool;
+use tracing::Instrument;
+use uuid::Uuid;
 
 use crate::domain::data_stores::{UserStore, UserStoreError};
-use crate::{Email, Password, User};
+use crate::domain::phone_number::PhoneNumber;
+use crate::domain::profile::{DisplayName, ProfileUpdate, TimeZone};
+use crate::domain::user::{AccountStatus, TwoFAChannel, UserId};
+use crate::services::password_hashing::{
+    compute_password_hash, is_supported_password_hash, needs_rehash, verify_password_hash, Peppers,
+};
+use crate::{Email, Locale, Password, User};
 
 pub struct PostgresUserStore {
     pool: PgPool,
+    /// Argon2id costs of the new hashes, see `needs_rehash`
+    hash_params: Params,
+    peppers: Peppers,
 }
 
 impl PostgresUserStore {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub fn new(pool: PgPool, hash_params: Params, peppers: Peppers) -> Self {
+        Self {
+            pool,
+            hash_params,
+            peppers,
+        }
+    }
+
+    /// Adds a user exported from another system along with the hash of their password,
+    /// which is upgraded to Argon2id at their first login, see `is_supported_password_hash`.
+    #[tracing::instrument(name = "Importing user to db", skip_all)]
+    pub async fn import_user(
+        &self,
+        email: &Email,
+        password_hash: &str,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError> {
+        if !is_supported_password_hash(password_hash) {
+            return Err(UserStoreError::UnexpectedError(eyre!(
+                "Unsupported password hash format"
+            )));
+        }
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO users (email, password_hash, requires_2fa)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (email) DO NOTHING
+            "#,
+            email.as_ref(),
+            password_hash,
+            requires_2fa
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserAlreadyExists);
+        }
+        Ok(())
     }
 }
 
@@ -40,19 +85,34 @@ impl UserStore for PostgresUserStore {
         }
 
         // Hash the password before storing it
-        let password_hash = compute_password_hash(user.password.as_ref().clone())
-            .await
-            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        let password_hash = compute_password_hash(
+            user.password.as_ref().clone(),
+            self.hash_params.clone(),
+            self.peppers.clone(),
+        )
+        .await
+        .map_err(UserStoreError::UnexpectedError)?;
 
         // Store the user in the database
         sqlx::query!(
             r#"
-            INSERT INTO users (email, password_hash, requires_2fa)
-            VALUES ($1, $2, $3)
+            INSERT INTO users (
+                id, email, password_hash, requires_2fa, locale, password_changed_at,
+                display_name, timezone, created_at, status, suspended_until
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
             "#,
+            user.id.as_ref(),
             user.email.0,
             password_hash,
-            user.requires_2fa
+            user.requires_2fa,
+            user.locale.as_ref().map(AsRef::as_ref),
+            user.password_changed_at,
+            user.display_name.as_ref().map(AsRef::as_ref),
+            user.timezone.as_ref().map(AsRef::as_ref),
+            user.created_at,
+            user.status.as_str(),
+            user.status.suspended_until()
         )
         .execute(&self.pool)
         .await?;
@@ -60,28 +120,61 @@ impl UserStore for PostgresUserStore {
         Ok(())
     }
 
+    #[tracing::instrument(name = "Deleting user from db", skip_all)]
+    async fn delete_user(&mut self, user_id: &UserId) -> Result<(), UserStoreError> {
+        // Their devices and password history are deleted in cascade,
+        // and the `users` table trigger queues the `user.deleted` webhook event
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM users WHERE id = $1
+            "#,
+            user_id.as_ref()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
     #[tracing::instrument(name = "Getting user from db", skip_all)]
-    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
-        // Retrieve the user from the database
-        let user = sqlx::query!(
+    async fn get_user(&self, user_id: &UserId) -> Result<User, UserStoreError> {
+        sqlx::query_as!(
+            UserRow,
             r#"
-            SELECT *
+            SELECT id, email, password_hash, requires_2fa, locale, phone_number,
+                   two_fa_channel, password_changed_at, display_name, timezone,
+                   created_at, last_login_at, status, suspended_until, session_version
             FROM users
-            WHERE email = $1
+            WHERE id = $1
             "#,
-            email.0
+            user_id.as_ref()
         )
         .fetch_optional(&self.pool)
-        .await?;
+        .await?
+        .ok_or(UserStoreError::UserNotFound)?
+        .try_into()
+    }
 
-        match user {
-            Some(user) => Ok(User::new_with_fake_password(
-                user.email,
-                Secret::new(user.password_hash),
-                user.requires_2fa,
-            )?),
-            None => Err(UserStoreError::UserNotFound),
-        }
+    #[tracing::instrument(name = "Getting user by email from db", skip_all)]
+    async fn get_user_by_email(&self, email: &Email) -> Result<User, UserStoreError> {
+        sqlx::query_as!(
+            UserRow,
+            r#"
+            SELECT id, email, password_hash, requires_2fa, locale, phone_number,
+                   two_fa_channel, password_changed_at, display_name, timezone,
+                   created_at, last_login_at, status, suspended_until, session_version
+            FROM users
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(UserStoreError::UserNotFound)?
+        .try_into()
     }
 
     #[tracing::instrument(name = "Validating user", skip_all)]
@@ -105,55 +198,411 @@ impl UserStore for PostgresUserStore {
         match record {
             Some(record) => {
                 // Verify the password hash
-                verify_password_hash(record.password_hash, password.as_ref().clone())
-                    .await
-                    .map_err(|_| UserStoreError::InvalidCredentials)?;
+                verify_password_hash(
+                    record.password_hash.clone(),
+                    password.as_ref().clone(),
+                    self.peppers.clone(),
+                )
+                .await
+                .map_err(|_| UserStoreError::InvalidCredentials)?;
+
+                // Upgrade a hash computed with older costs while the password is at hand,
+                // without delaying the login
+                if needs_rehash(&record.password_hash, &self.hash_params, &self.peppers) {
+                    let pool = self.pool.clone();
+                    let hash_params = self.hash_params.clone();
+                    let peppers = self.peppers.clone();
+                    let email = email.clone();
+                    let password = password.as_ref().clone();
+                    tokio::spawn(
+                        async move {
+                            let result = rehash_password(
+                                &pool,
+                                &email,
+                                &record.password_hash,
+                                password,
+                                hash_params,
+                                peppers,
+                            )
+                            .await;
+                            if let Err(e) = result {
+                                tracing::error!(error = ?e, "Failed to rehash password");
+                            }
+                        }
+                        .in_current_span(),
+                    );
+                }
                 Ok(())
             }
             None => Err(UserStoreError::UserNotFound),
         }
     }
+
+    #[tracing::instrument(name = "Setting phone number in db", skip_all)]
+    async fn set_phone_number(
+        &mut self,
+        user_id: &UserId,
+        phone_number: Option<PhoneNumber>,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET phone_number = $2,
+                two_fa_channel = CASE WHEN $2::TEXT IS NULL THEN 'email' ELSE two_fa_channel END
+            WHERE id = $1
+            "#,
+            user_id.as_ref(),
+            phone_number.as_ref().map(AsRef::as_ref)
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Setting 2FA channel in db", skip_all)]
+    async fn set_two_fa_channel(
+        &mut self,
+        user_id: &UserId,
+        channel: TwoFAChannel,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET two_fa_channel = $2
+            WHERE id = $1
+            "#,
+            user_id.as_ref(),
+            channel.as_str()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating profile in db", skip_all)]
+    async fn update_profile(
+        &mut self,
+        user_id: &UserId,
+        update: ProfileUpdate,
+    ) -> Result<User, UserStoreError> {
+        // Each attribute is only replaced when its flag is set, so that NULL can clear it
+        let display_name = update.display_name.as_ref();
+        let locale = update.locale.as_ref();
+        let timezone = update.timezone.as_ref();
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET display_name = CASE WHEN $2 THEN $3 ELSE display_name END,
+                locale = CASE WHEN $4 THEN $5 ELSE locale END,
+                timezone = CASE WHEN $6 THEN $7 ELSE timezone END
+            WHERE id = $1
+            "#,
+            user_id.as_ref(),
+            display_name.is_some(),
+            display_name.and_then(Option::as_ref).map(AsRef::as_ref),
+            locale.is_some(),
+            locale.and_then(Option::as_ref).map(AsRef::as_ref),
+            timezone.is_some(),
+            timezone.and_then(Option::as_ref).map(AsRef::as_ref)
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        self.get_user(user_id).await
+    }
+
+    #[tracing::instrument(name = "Recording login in db", skip_all)]
+    async fn record_login(&mut self, user_id: &UserId) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET last_login_at = NOW()
+            WHERE id = $1
+            "#,
+            user_id.as_ref()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Setting status in db", skip_all)]
+    async fn set_status(
+        &mut self,
+        user_id: &UserId,
+        status: AccountStatus,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET status = $2, suspended_until = $3, session_version = session_version + 1
+            WHERE id = $1
+            "#,
+            user_id.as_ref(),
+            status.as_str(),
+            status.suspended_until()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Revoking sessions of user in db", skip_all)]
+    async fn revoke_sessions(&mut self, user_id: &UserId) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET session_version = session_version + 1
+            WHERE id = $1
+            "#,
+            user_id.as_ref()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating email in db", skip_all)]
+    async fn update_email(
+        &mut self,
+        user_id: &UserId,
+        current_email: &Email,
+        new_email: &Email,
+    ) -> Result<(), UserStoreError> {
+        // The devices and password history follow through their ON UPDATE CASCADE foreign keys
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET email = $3
+            WHERE id = $1 AND email = $2
+            "#,
+            user_id.as_ref(),
+            current_email.as_ref(),
+            new_email.as_ref()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating password in db", skip_all)]
+    async fn update_password(
+        &mut self,
+        user_id: &UserId,
+        password: Password,
+        history_size: usize,
+    ) -> Result<(), UserStoreError> {
+        let mut transaction = self.pool.begin().await?;
+        // Locks the user until the new password is stored, so concurrent changes cannot both pass the history check
+        let current_hash = sqlx::query_scalar!(
+            r#"
+            SELECT password_hash
+            FROM users
+            WHERE id = $1
+            FOR UPDATE
+            "#,
+            user_id.as_ref()
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        .ok_or(UserStoreError::UserNotFound)?;
+        // The current password counts as the first of the history
+        let history_limit = history_size.saturating_sub(1) as i64;
+        let previous_hashes = sqlx::query_scalar!(
+            r#"
+            SELECT password_hash
+            FROM password_history
+            WHERE user_id = $1
+            ORDER BY id DESC
+            LIMIT $2
+            "#,
+            user_id.as_ref(),
+            history_limit
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+
+        let recent_hashes = std::iter::once(current_hash.clone())
+            .chain(previous_hashes)
+            .take(history_size);
+        for recent_hash in recent_hashes {
+            if verify_password_hash(recent_hash, password.as_ref().clone(), self.peppers.clone())
+                .await
+                .is_ok()
+            {
+                return Err(UserStoreError::PasswordReused);
+            }
+        }
+
+        let password_hash = compute_password_hash(
+            password.as_ref().clone(),
+            self.hash_params.clone(),
+            self.peppers.clone(),
+        )
+        .await
+        .map_err(UserStoreError::UnexpectedError)?;
+        sqlx::query!(
+            r#"
+            INSERT INTO password_history (user_id, password_hash)
+            VALUES ($1, $2)
+            "#,
+            user_id.as_ref(),
+            current_hash
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $2, password_changed_at = NOW()
+            WHERE id = $1
+            "#,
+            user_id.as_ref(),
+            password_hash
+        )
+        .execute(&mut *transaction)
+        .await?;
+        // Older hashes are of no use, and would only leak more if the database did
+        sqlx::query!(
+            r#"
+            DELETE FROM password_history
+            WHERE user_id = $1
+              AND id NOT IN (
+                SELECT id FROM password_history WHERE user_id = $1 ORDER BY id DESC LIMIT $2
+              )
+            "#,
+            user_id.as_ref(),
+            history_limit
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
 }
 
-// Helper function to verify if a given password matches an expected hash
-#[tracing::instrument(name = "Verifying password hash", skip_all)]
-async fn verify_password_hash(
-    expected_password_hash: String,
-    password_candidate: Secret<String>,
-) -> color_eyre::eyre::Result<()> {
-    let current_span = tracing::Span::current();
-    tokio::task::spawn_blocking(move || {
-        current_span.in_scope(|| {
-            let expected_password_hash: PasswordHash<'_> =
-                PasswordHash::new(&expected_password_hash)?;
-            Argon2::default()
-                .verify_password(
-                    password_candidate.expose_secret().as_bytes(),
-                    &expected_password_hash,
-                )
-                .map_err(|e| e.into())
-        })
-    })
-    .await?
+struct UserRow {
+    id: Uuid,
+    email: String,
+    password_hash: String,
+    requires_2fa: bool,
+    locale: Option<String>,
+    phone_number: Option<String>,
+    two_fa_channel: String,
+    password_changed_at: DateTime<Utc>,
+    display_name: Option<String>,
+    timezone: Option<String>,
+    created_at: DateTime<Utc>,
+    last_login_at: Option<DateTime<Utc>>,
+    status: String,
+    suspended_until: Option<DateTime<Utc>>,
+    session_version: i32,
 }
 
-// Helper function to hash passwords before persisting them in the database.
-#[tracing::instrument(name = "Computing password hash", skip_all)]
-async fn compute_password_hash(password: Secret<String>) -> color_eyre::eyre::Result<String> {
-    let current_span = tracing::Span::current();
-    tokio::task::spawn_blocking(move || {
-        current_span.in_scope(|| {
-            let salt: SaltString = SaltString::generate(&mut rand::thread_rng());
-            let password_hash = Argon2::new(
-                Algorithm::Argon2id,
-                Version::V0x13,
-                Params::new(15000, 2, 1, None)?,
-            )
-            .hash_password(password.expose_secret().as_bytes(), &salt)?
-            .to_string();
+impl TryFrom<UserRow> for User {
+    type Error = UserStoreError;
 
-            Ok(password_hash)
-        })
-    })
-    .await?
+    fn try_from(row: UserRow) -> Result<Self, Self::Error> {
+        let locale = row
+            .locale
+            .map(|locale| Locale::parse(&locale))
+            .transpose()
+            .map_err(UserStoreError::UnexpectedError)?;
+        let phone_number = row
+            .phone_number
+            .map(|phone_number| PhoneNumber::parse(&phone_number))
+            .transpose()
+            .map_err(UserStoreError::UnexpectedError)?;
+        let two_fa_channel = row
+            .two_fa_channel
+            .parse()
+            .map_err(UserStoreError::UnexpectedError)?;
+        let status = AccountStatus::from_parts(&row.status, row.suspended_until)
+            .map_err(UserStoreError::UnexpectedError)?;
+        let display_name = row
+            .display_name
+            .map(|display_name| DisplayName::parse(&display_name))
+            .transpose()
+            .map_err(UserStoreError::UnexpectedError)?;
+        let timezone = row
+            .timezone
+            .map(|timezone| TimeZone::parse(&timezone))
+            .transpose()
+            .map_err(UserStoreError::UnexpectedError)?;
+        Ok(User::new_with_fake_password(
+            row.email,
+            Secret::new(row.password_hash),
+            row.requires_2fa,
+        )?
+        .with_id(row.id.into())
+        .with_locale(locale)
+        .with_phone_number(phone_number, two_fa_channel)
+        .with_password_changed_at(row.password_changed_at)
+        .with_profile(display_name, timezone)
+        .with_activity(row.created_at, row.last_login_at)
+        .with_status(status)
+        .with_session_version(row.session_version))
+    }
+}
+
+// Replaces the hash of the password of the user by one with the current costs and pepper.
+// The password stays the same, so `password_changed_at` is left untouched and the
+// `user.password_changed` webhook is not sent.
+#[tracing::instrument(name = "Rehashing password", skip_all)]
+async fn rehash_password(
+    pool: &PgPool,
+    email: &Email,
+    old_hash: &str,
+    password: Secret<String>,
+    hash_params: Params,
+    peppers: Peppers,
+) -> color_eyre::eyre::Result<()> {
+    let password_hash = compute_password_hash(password, hash_params, peppers).await?;
+    let mut transaction = pool.begin().await?;
+    // Read by the webhook trigger of the users table, only for this transaction
+    sqlx::query!("SELECT set_config('auth.password_rehash', 'on', true)")
+        .fetch_one(&mut *transaction)
+        .await?;
+    // Only if the password was not changed in the meantime
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET password_hash = $3
+        WHERE email = $1 AND password_hash = $2
+        "#,
+        email.as_ref(),
+        old_hash,
+        password_hash
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
 }
@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::data_stores::{WebhookStore, WebhookStoreError};
+use crate::domain::webhook::{
+    WebhookDelivery, WebhookEvent, WebhookEventType, WebhookSubscription,
+};
+
+/// Account lifecycle events are queued by triggers on the `users` table,
+/// in the same transaction as the change, see the `create_webhooks_tables` migration.
+pub struct PostgresWebhookStore {
+    pool: PgPool,
+}
+
+impl PostgresWebhookStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl WebhookStore for PostgresWebhookStore {
+    #[tracing::instrument(name = "Adding webhook subscription to db", skip_all)]
+    async fn add_subscription(
+        &mut self,
+        subscription: WebhookSubscription,
+    ) -> Result<(), WebhookStoreError> {
+        let event_types: Vec<String> = subscription
+            .event_types
+            .iter()
+            .map(|event_type| event_type.as_str().to_owned())
+            .collect();
+        sqlx::query!(
+            r#"
+            INSERT INTO webhook_subscriptions (id, url, secret, event_types, active, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            subscription.id,
+            subscription.url,
+            subscription.secret,
+            &event_types,
+            subscription.active,
+            subscription.created_at
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Getting webhook subscriptions from db", skip_all)]
+    async fn get_subscriptions(&self) -> Result<Vec<WebhookSubscription>, WebhookStoreError> {
+        let rows = sqlx::query_as!(
+            WebhookSubscriptionRow,
+            r#"
+            SELECT *
+            FROM webhook_subscriptions
+            ORDER BY created_at
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(WebhookSubscription::try_from)
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Deactivating webhook subscription in db", skip_all)]
+    async fn deactivate_subscription(&mut self, id: &Uuid) -> Result<(), WebhookStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE webhook_subscriptions
+            SET active = FALSE
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(WebhookStoreError::SubscriptionNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Getting webhook deliveries from db", skip_all)]
+    async fn get_deliveries(
+        &self,
+        subscription_id: &Uuid,
+    ) -> Result<Vec<WebhookDelivery>, WebhookStoreError> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(SELECT 1 FROM webhook_subscriptions WHERE id = $1) AS "exists!"
+            "#,
+            subscription_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if !exists {
+            return Err(WebhookStoreError::SubscriptionNotFound);
+        }
+
+        let rows = sqlx::query_as!(
+            WebhookDeliveryRow,
+            r#"
+            SELECT *
+            FROM webhook_deliveries
+            WHERE subscription_id = $1
+            ORDER BY occurred_at, id
+            "#,
+            subscription_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(WebhookDelivery::try_from).collect()
+    }
+
+    #[tracing::instrument(name = "Claiming due webhook deliveries in db", skip_all)]
+    async fn claim_due_deliveries(
+        &mut self,
+        limit: i64,
+        lease: Duration,
+    ) -> Result<Vec<(WebhookDelivery, WebhookSubscription)>, WebhookStoreError> {
+        // Rows locked by another dispatcher are skipped rather than waited for
+        let rows = sqlx::query_as!(
+            WebhookDeliveryRow,
+            r#"
+            UPDATE webhook_deliveries
+            SET next_attempt_at = NOW() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT d.id
+                FROM webhook_deliveries d
+                JOIN webhook_subscriptions s ON s.id = d.subscription_id
+                WHERE d.status = 'pending' AND d.next_attempt_at <= NOW() AND s.active
+                ORDER BY d.next_attempt_at
+                LIMIT $1
+                FOR UPDATE OF d SKIP LOCKED
+            )
+            RETURNING *
+            "#,
+            limit,
+            lease.as_secs_f64()
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        if rows.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let subscription_ids: Vec<Uuid> = rows.iter().map(|row| row.subscription_id).collect();
+        let subscriptions = sqlx::query_as!(
+            WebhookSubscriptionRow,
+            r#"
+            SELECT *
+            FROM webhook_subscriptions
+            WHERE id = ANY($1)
+            "#,
+            &subscription_ids
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| WebhookSubscription::try_from(row).map(|s| (s.id, s)))
+        .collect::<Result<HashMap<Uuid, WebhookSubscription>, _>>()?;
+
+        let mut claimed = Vec::with_capacity(rows.len());
+        for row in rows {
+            let delivery = WebhookDelivery::try_from(row)?;
+            if let Some(subscription) = subscriptions.get(&delivery.subscription_id) {
+                claimed.push((delivery, subscription.clone()));
+            }
+        }
+        claimed.sort_by_key(|(delivery, _)| delivery.event.occurred_at);
+        Ok(claimed)
+    }
+
+    #[tracing::instrument(name = "Updating webhook delivery in db", skip_all)]
+    async fn update_delivery(
+        &mut self,
+        delivery: &WebhookDelivery,
+    ) -> Result<(), WebhookStoreError> {
+        sqlx::query!(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = $2,
+                attempts = $3,
+                next_attempt_at = $4,
+                last_attempt_at = $5,
+                last_response_status = $6,
+                last_error = $7,
+                delivered_at = $8
+            WHERE id = $1
+            "#,
+            delivery.id,
+            delivery.status.as_str(),
+            delivery.attempts,
+            delivery.next_attempt_at,
+            delivery.last_attempt_at,
+            delivery.last_response_status,
+            delivery.last_error,
+            delivery.delivered_at
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+// Row of the `webhook_subscriptions` table
+struct WebhookSubscriptionRow {
+    id: Uuid,
+    url: String,
+    secret: String,
+    event_types: Vec<String>,
+    active: bool,
+    created_at: DateTime<Utc>,
+}
+
+impl TryFrom<WebhookSubscriptionRow> for WebhookSubscription {
+    type Error = WebhookStoreError;
+
+    fn try_from(row: WebhookSubscriptionRow) -> Result<Self, Self::Error> {
+        Ok(WebhookSubscription {
+            id: row.id,
+            url: row.url,
+            secret: row.secret,
+            event_types: row
+                .event_types
+                .iter()
+                .map(|event_type| event_type.parse::<WebhookEventType>())
+                .collect::<Result<_, _>>()
+                .map_err(WebhookStoreError::UnexpectedError)?,
+            active: row.active,
+            created_at: row.created_at,
+        })
+    }
+}
+
+// Row of the `webhook_deliveries` table
+struct WebhookDeliveryRow {
+    id: Uuid,
+    subscription_id: Uuid,
+    event_id: Uuid,
+    event_type: String,
+    data: serde_json::Value,
+    occurred_at: DateTime<Utc>,
+    status: String,
+    attempts: i32,
+    next_attempt_at: DateTime<Utc>,
+    last_attempt_at: Option<DateTime<Utc>>,
+    last_response_status: Option<i32>,
+    last_error: Option<String>,
+    delivered_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<WebhookDeliveryRow> for WebhookDelivery {
+    type Error = WebhookStoreError;
+
+    fn try_from(row: WebhookDeliveryRow) -> Result<Self, Self::Error> {
+        Ok(WebhookDelivery {
+            id: row.id,
+            subscription_id: row.subscription_id,
+            event: WebhookEvent {
+                id: row.event_id,
+                event_type: row
+                    .event_type
+                    .parse()
+                    .map_err(WebhookStoreError::UnexpectedError)?,
+                occurred_at: row.occurred_at,
+                data: row.data,
+            },
+            status: row
+                .status
+                .parse()
+                .map_err(WebhookStoreError::UnexpectedError)?,
+            attempts: row.attempts,
+            next_attempt_at: row.next_attempt_at,
+            last_attempt_at: row.last_attempt_at,
+            last_response_status: row.last_response_status,
+            last_error: row.last_error,
+            delivered_at: row.delivered_at,
+        })
+    }
+}
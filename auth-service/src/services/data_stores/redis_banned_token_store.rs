@@ -5,7 +5,7 @@ use redis::{Commands, Connection};
 use tokio::sync::RwLock;
 
 use crate::{
-    domain::data_stores::{BannedTokenStore, BannedTokenStoreError},
+    domain::data_stores::{BannedTokenStore, BannedTokenStoreError, TokenHash},
     utils::auth::TOKEN_TTL_SECONDS,
 };
 
@@ -22,7 +22,7 @@ impl RedisBannedTokenStore {
 #[async_trait::async_trait]
 impl BannedTokenStore for RedisBannedTokenStore {
     #[tracing::instrument(name = "add_banned_token", skip_all)]
-    async fn add_banned_token(&mut self, token: &str) -> Result<(), BannedTokenStoreError> {
+    async fn add_banned_token(&mut self, token: &TokenHash) -> Result<(), BannedTokenStoreError> {
         let mut conn = self.conn.write().await;
         let key = get_key(token);
         let ttl: u64 = TOKEN_TTL_SECONDS
@@ -39,16 +39,19 @@ impl BannedTokenStore for RedisBannedTokenStore {
     #[tracing::instrument(name = "is_token_banned", skip_all)]
     async fn is_token_banned(&self, token: &str) -> Result<bool, BannedTokenStoreError> {
         let mut conn = self.conn.write().await;
-        let key = get_key(token);
-        let result: Option<bool> = conn
-            .get(key)
+        let keys = [get_key(&TokenHash::of(token)), get_legacy_key(token)];
+        let count: u64 = conn
+            .exists(&keys)
             .wrap_err("Failed to check if token exists in Redis")
             .map_err(BannedTokenStoreError::UnexpectedError)?;
-        Ok(result.unwrap_or(false))
+        Ok(count > 0)
     }
 
     #[tracing::instrument(name = "remove_banned_token", skip_all)]
-    async fn remove_banned_token(&mut self, token: &str) -> Result<(), BannedTokenStoreError> {
+    async fn remove_banned_token(
+        &mut self,
+        token: &TokenHash,
+    ) -> Result<(), BannedTokenStoreError> {
         let mut conn = self.conn.write().await;
         let key = get_key(token);
         let _: () = conn
@@ -62,6 +65,12 @@ impl BannedTokenStore for RedisBannedTokenStore {
 // We are using a key prefix to prevent collisions and organize data!
 const BANNED_TOKEN_KEY_PREFIX: &str = "banned_token:";
 
-fn get_key(token: &str) -> String {
+fn get_key(token: &TokenHash) -> String {
+    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, token.as_ref())
+}
+
+// Tokens banned before they were identified by their hash are keyed by the JWT itself.
+// These keys expire at most TOKEN_TTL_SECONDS after the upgrade, the lookup can be dropped then.
+fn get_legacy_key(token: &str) -> String {
     format!("{}{}", BANNED_TOKEN_KEY_PREFIX, token)
 }
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::domain::data_stores::{
+    PhoneVerification, PhoneVerificationStore, PhoneVerificationStoreError, TwoFACodeHash,
+};
+use crate::domain::phone_number::PhoneNumber;
+use crate::Email;
+
+pub struct RedisPhoneVerificationStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisPhoneVerificationStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl PhoneVerificationStore for RedisPhoneVerificationStore {
+    #[tracing::instrument(name = "RedisPhoneVerificationStore::add_verification", skip_all)]
+    async fn add_verification(
+        &mut self,
+        email: &Email,
+        verification: PhoneVerification,
+    ) -> Result<(), PhoneVerificationStoreError> {
+        let key = get_key(email);
+        let stored = StoredVerification(
+            verification.phone_number.as_ref().to_string(),
+            verification.code_hash.as_ref().to_string(),
+        );
+        let json = serde_json::to_string(&stored)
+            .wrap_err("Failed to serialize StoredVerification")
+            .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+
+        let mut conn = self.conn.write().await;
+        conn.set_ex::<_, _, ()>(key, json, TEN_MINUTES_IN_SECONDS)
+            .wrap_err("Failed to set phone verification in Redis")
+            .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+        // The wrong codes given for a previous verification do not count against this one
+        conn.del(get_attempts_key(email))
+            .wrap_err("Failed to reset phone verification attempts in Redis")
+            .map_err(PhoneVerificationStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "RedisPhoneVerificationStore::get_verification", skip_all)]
+    async fn get_verification(
+        &self,
+        email: &Email,
+    ) -> Result<PhoneVerification, PhoneVerificationStoreError> {
+        let key = get_key(email);
+        let json = self
+            .conn
+            .write()
+            .await
+            .get::<String, String>(key)
+            .map_err(|_| PhoneVerificationStoreError::VerificationNotFound)?;
+        let StoredVerification(phone_number, code_hash) = serde_json::from_str(&json)
+            .wrap_err("Failed to deserialize StoredVerification")
+            .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+        Ok(PhoneVerification {
+            phone_number: PhoneNumber::parse(&phone_number)
+                .map_err(PhoneVerificationStoreError::UnexpectedError)?,
+            code_hash: TwoFACodeHash::parse(code_hash)
+                .map_err(PhoneVerificationStoreError::UnexpectedError)?,
+        })
+    }
+
+    #[tracing::instrument(name = "RedisPhoneVerificationStore::remove_verification", skip_all)]
+    async fn remove_verification(
+        &mut self,
+        email: &Email,
+    ) -> Result<(), PhoneVerificationStoreError> {
+        self.conn
+            .write()
+            .await
+            .del(&[get_key(email), get_attempts_key(email)])
+            .wrap_err("Failed to delete phone verification from Redis")
+            .map_err(PhoneVerificationStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "RedisPhoneVerificationStore::record_failed_attempt", skip_all)]
+    async fn record_failed_attempt(
+        &mut self,
+        email: &Email,
+        max_attempts: u32,
+    ) -> Result<(), PhoneVerificationStoreError> {
+        let attempts_key = get_attempts_key(email);
+        let mut conn = self.conn.write().await;
+        // Counted atomically, so that concurrent guesses cannot go over the limit
+        let failed_attempts: u32 = conn
+            .incr(&attempts_key, 1)
+            .wrap_err("Failed to count phone verification attempt in Redis")
+            .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+        conn.expire::<_, ()>(&attempts_key, TEN_MINUTES_IN_SECONDS as i64)
+            .wrap_err("Failed to set expiry of phone verification attempts in Redis")
+            .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+        if failed_attempts >= max_attempts {
+            conn.del::<_, ()>(&[get_key(email), attempts_key])
+                .wrap_err("Failed to delete phone verification from Redis")
+                .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+        }
+        Ok(())
+    }
+}
+
+// Tuple struct to hold the phone number and the hash of the code sent to it
+#[derive(Serialize, Deserialize)]
+struct StoredVerification(pub String, pub String);
+
+const TEN_MINUTES_IN_SECONDS: u64 = 600;
+const PHONE_VERIFICATION_PREFIX: &str = "phone_verification:";
+const PHONE_VERIFICATION_ATTEMPTS_PREFIX: &str = "phone_verification_attempts:";
+
+fn get_key(email: &Email) -> String {
+    format!("{}{}", PHONE_VERIFICATION_PREFIX, email.as_ref())
+}
+
+fn get_attempts_key(email: &Email) -> String {
+    format!("{}{}", PHONE_VERIFICATION_ATTEMPTS_PREFIX, email.as_ref())
+}
@@ -1,13 +1,19 @@
 use std::sync::Arc;
 
-use color_eyre::eyre::Context;
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Context, Report};
 use redis::{Commands, Connection};
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
-use crate::domain::data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
+use crate::domain::data_stores::{
+    LoginAttemptId, PendingLoginAttempt, ResendPolicy, TwoFACodeHash, TwoFACodeStore,
+    TwoFACodeStoreError,
+};
 use crate::Email;
 
+/// Each login attempt is stored under its own key, expiring with its code.
+/// A set per email indexes the attempts of the user, its members are pruned once their key expired.
 pub struct RedisTwoFACodeStore {
     conn: Arc<RwLock<Connection>>,
 }
@@ -16,6 +22,21 @@ impl RedisTwoFACodeStore {
     pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
         Self { conn }
     }
+
+    async fn get_entry(
+        &self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<TwoFAEntry, TwoFACodeStoreError> {
+        let json: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get(get_key(login_attempt_id))
+            .wrap_err("Failed to get 2FA code from Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        parse_entry(json, email)
+    }
 }
 
 #[async_trait::async_trait]
@@ -24,34 +45,55 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
     async fn add_code(
         &mut self,
         email: &Email,
-        code: TwoFACode,
+        code_hash: TwoFACodeHash,
         login_attempt_id: LoginAttemptId,
     ) -> Result<(), TwoFACodeStoreError> {
-        let key = get_key(&email);
-        let two_fa = TwoFATuple(
-            code.as_ref().to_string(),
-            login_attempt_id.as_ref().to_string(),
-        );
-        let json = serde_json::to_string(&two_fa)
-            .wrap_err("Failed to serialize TwoFATuple")
+        let now = Utc::now();
+        let entry = TwoFAEntry {
+            email: email.as_ref().to_string(),
+            code_hash: code_hash.as_ref().to_string(),
+            created_at: now,
+            resend_count: 0,
+            last_sent_at: now,
+        };
+        let json = serde_json::to_string(&entry)
+            .wrap_err("Failed to serialize TwoFAEntry")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-        self.conn
-            .write()
-            .await
-            .set_ex(key, json, TEN_MINUTES_IN_SECONDS)
+        let mut conn = self.conn.write().await;
+        conn.set_ex::<_, _, ()>(get_key(&login_attempt_id), json, TEN_MINUTES_IN_SECONDS)
             .wrap_err("failed to set 2FA code in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        // The index lives as long as the most recent attempt
+        let index_key = get_index_key(email);
+        conn.sadd::<_, _, ()>(&index_key, login_attempt_id.as_ref())
+            .wrap_err("failed to index 2FA code in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        conn.expire(&index_key, TEN_MINUTES_IN_SECONDS as i64)
+            .wrap_err("failed to set expiry of 2FA code index in Redis")
             .map_err(TwoFACodeStoreError::UnexpectedError)
     }
 
     #[tracing::instrument(name = "RedisTwoFACodeStore::remove_code", skip_all)]
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        let key = get_key(email);
-        self.conn
-            .write()
-            .await
-            .del(key)
-            .wrap_err("Failed to delete 2FA code from Redis")
+    async fn remove_code(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        match self.get_entry(email, login_attempt_id).await {
+            Ok(_) => {}
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => return Ok(()),
+            Err(e) => return Err(e),
+        }
+        let mut conn = self.conn.write().await;
+        conn.del::<_, ()>(&[
+            get_key(login_attempt_id),
+            get_attempts_key(login_attempt_id),
+        ])
+        .wrap_err("Failed to delete 2FA code from Redis")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        conn.srem(get_index_key(email), login_attempt_id.as_ref())
+            .wrap_err("Failed to delete 2FA code from its Redis index")
             .map_err(TwoFACodeStoreError::UnexpectedError)
     }
 
@@ -59,32 +101,169 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
     async fn get_code(
         &self,
         email: &Email,
-    ) -> Result<(TwoFACode, LoginAttemptId), TwoFACodeStoreError> {
-        let key = get_key(email);
-        let json = self
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<TwoFACodeHash, TwoFACodeStoreError> {
+        let entry = self.get_entry(email, login_attempt_id).await?;
+        TwoFACodeHash::parse(entry.code_hash).map_err(TwoFACodeStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "RedisTwoFACodeStore::get_login_attempts", skip_all)]
+    async fn get_login_attempts(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<PendingLoginAttempt>, TwoFACodeStoreError> {
+        let index_key = get_index_key(email);
+        let ids: Vec<String> = self
             .conn
             .write()
             .await
-            .get::<String, String>(key)
-            .map_err(|_| TwoFACodeStoreError::LoginAttemptIdNotFound)?;
-        let TwoFATuple(two_fa_code, login_attempt_id): TwoFATuple = serde_json::from_str(&json)
-            .wrap_err("Failed to deserialize TwoFATuple")
+            .smembers(&index_key)
+            .wrap_err("Failed to get 2FA code index from Redis")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
-        let login_attempt_id = LoginAttemptId::parse(&login_attempt_id)
+
+        let mut attempts = Vec::with_capacity(ids.len());
+        for id in ids {
+            let login_attempt_id =
+                LoginAttemptId::parse(&id).map_err(TwoFACodeStoreError::UnexpectedError)?;
+            match self.get_entry(email, &login_attempt_id).await {
+                Ok(entry) => attempts.push(PendingLoginAttempt {
+                    login_attempt_id,
+                    created_at: entry.created_at,
+                }),
+                // The code expired
+                Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => self
+                    .conn
+                    .write()
+                    .await
+                    .srem(&index_key, &id)
+                    .wrap_err("Failed to prune 2FA code index in Redis")
+                    .map_err(TwoFACodeStoreError::UnexpectedError)?,
+                Err(e) => return Err(e),
+            }
+        }
+        attempts.sort_by_key(|attempt| attempt.created_at);
+        Ok(attempts)
+    }
+
+    #[tracing::instrument(name = "RedisTwoFACodeStore::record_resend", skip_all)]
+    async fn record_resend(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        code_hash: TwoFACodeHash,
+        policy: &ResendPolicy,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let key = get_key(login_attempt_id);
+        let mut conn = self.conn.write().await;
+        // The entry is watched, so that the update is dropped and tried again if the entry
+        // changed or expired since it was read, instead of overwriting it
+        redis::transaction(&mut *conn, &[&key], |conn, pipe| {
+            let json: Option<String> = conn.get(&key)?;
+            let mut entry = match parse_entry(json, email) {
+                Ok(entry) => entry,
+                Err(e) => return Ok(Some(Err(e))),
+            };
+            let now = Utc::now();
+            if let Err(e) = policy.check(entry.resend_count, entry.last_sent_at, now) {
+                return Ok(Some(Err(e)));
+            }
+            entry.code_hash = code_hash.as_ref().to_string();
+            entry.resend_count += 1;
+            entry.last_sent_at = now;
+            let json = match serde_json::to_string(&entry) {
+                Ok(json) => json,
+                Err(e) => {
+                    return Ok(Some(Err(TwoFACodeStoreError::UnexpectedError(
+                        Report::new(e).wrap_err("Failed to serialize TwoFAEntry"),
+                    ))))
+                }
+            };
+            // Resending does not extend the validity of the login attempt
+            let reply: Option<(Option<String>,)> = pipe
+                .cmd("SET")
+                .arg(&key)
+                .arg(json)
+                .arg("XX")
+                .arg("KEEPTTL")
+                .query(conn)?;
+            // `None` when the entry was modified, `Some((None,))` when it no longer exists
+            Ok(reply.map(|(set,)| {
+                set.map(|_| ())
+                    .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
+            }))
+        })
+        .wrap_err("Failed to update 2FA code in Redis")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?
+    }
+
+    #[tracing::instrument(name = "RedisTwoFACodeStore::record_failed_attempt", skip_all)]
+    async fn record_failed_attempt(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        max_attempts: u32,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let attempts_key = get_attempts_key(login_attempt_id);
+        let mut conn = self.conn.write().await;
+        // Counted atomically, so that concurrent guesses cannot go over the limit
+        let failed_attempts: u32 = conn
+            .incr(&attempts_key, 1)
+            .wrap_err("Failed to count 2FA attempt in Redis")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
-        let two_fa_code =
-            TwoFACode::parse(two_fa_code).map_err(TwoFACodeStoreError::UnexpectedError)?;
-        Ok((two_fa_code, login_attempt_id))
+        conn.expire::<_, ()>(&attempts_key, TEN_MINUTES_IN_SECONDS as i64)
+            .wrap_err("Failed to set expiry of 2FA attempts in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        if failed_attempts >= max_attempts {
+            conn.del::<_, ()>(&[get_key(login_attempt_id), attempts_key])
+                .wrap_err("Failed to delete 2FA code from Redis")
+                .map_err(TwoFACodeStoreError::UnexpectedError)?;
+            conn.srem::<_, _, ()>(get_index_key(email), login_attempt_id.as_ref())
+                .wrap_err("Failed to delete 2FA code from its Redis index")
+                .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        }
+        Ok(())
     }
 }
 
-// Tuple struct to hold the 2FA code and login attempt ID
+// A pending 2FA challenge, with how many times a new code was sent
 #[derive(Serialize, Deserialize)]
-struct TwoFATuple(pub String, pub String);
+struct TwoFAEntry {
+    email: String,
+    code_hash: String,
+    created_at: DateTime<Utc>,
+    resend_count: u32,
+    last_sent_at: DateTime<Utc>,
+}
+
+fn parse_entry(json: Option<String>, email: &Email) -> Result<TwoFAEntry, TwoFACodeStoreError> {
+    let entry: TwoFAEntry =
+        serde_json::from_str(&json.ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?)
+            .wrap_err("Failed to deserialize TwoFAEntry")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+    // Login attempt ids are only valid for the email they were created for
+    if entry.email != email.as_ref() {
+        return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+    }
+    Ok(entry)
+}
 
 const TEN_MINUTES_IN_SECONDS: u64 = 600;
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
+const TWO_FA_INDEX_PREFIX: &str = "two_fa_attempts:";
+const TWO_FA_FAILED_ATTEMPTS_PREFIX: &str = "two_fa_failed_attempts:";
+
+fn get_key(login_attempt_id: &LoginAttemptId) -> String {
+    format!("{}{}", TWO_FA_CODE_PREFIX, login_attempt_id.as_ref())
+}
+
+fn get_index_key(email: &Email) -> String {
+    format!("{}{}", TWO_FA_INDEX_PREFIX, email.as_ref())
+}
 
-fn get_key(email: &Email) -> String {
-    format!("{}{}", TWO_FA_CODE_PREFIX, email.as_ref())
+fn get_attempts_key(login_attempt_id: &LoginAttemptId) -> String {
+    format!(
+        "{}{}",
+        TWO_FA_FAILED_ATTEMPTS_PREFIX,
+        login_attempt_id.as_ref()
+    )
 }
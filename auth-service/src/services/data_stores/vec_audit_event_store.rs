@@ -0,0 +1,132 @@
+use crate::domain::audit::{AuditChainHead, AuditEvent, AuditEventFilter, AuditRecord};
+use crate::domain::data_stores::{AuditEventStore, AuditEventStoreError};
+
+#[derive(Default, Debug)]
+pub struct VecAuditEventStore {
+    records: Vec<AuditRecord>,
+}
+
+#[async_trait::async_trait]
+impl AuditEventStore for VecAuditEventStore {
+    async fn add_event(&mut self, event: AuditEvent) -> Result<AuditRecord, AuditEventStoreError> {
+        let head = self.records.last().map(AuditRecord::head);
+        let record = AuditRecord::chain(event, head.as_ref());
+        self.records.push(record.clone());
+        Ok(record)
+    }
+
+    async fn get_events(
+        &self,
+        filter: &AuditEventFilter,
+    ) -> Result<Vec<AuditRecord>, AuditEventStoreError> {
+        Ok(self
+            .records
+            .iter()
+            .filter(|record| filter.matches(&record.event))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_records(
+        &self,
+        after_sequence: i64,
+        limit: i64,
+    ) -> Result<Vec<AuditRecord>, AuditEventStoreError> {
+        Ok(self
+            .records
+            .iter()
+            .filter(|record| record.sequence > after_sequence)
+            .take(limit.try_into().unwrap_or_default())
+            .cloned()
+            .collect())
+    }
+
+    async fn get_chain_head(&self) -> Result<Option<AuditChainHead>, AuditEventStoreError> {
+        Ok(self.records.last().map(AuditRecord::head))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::audit::{
+        verify_audit_chain, AuditChainVerification, AuditEventType, AuditOutcome,
+    };
+    use crate::utils::request_context::RequestContext;
+
+    #[tokio::test]
+    async fn test_vec_audit_event_store() {
+        let mut store = VecAuditEventStore::default();
+        let context = RequestContext::default();
+        let login = AuditEvent::new(
+            AuditEventType::Login,
+            Some("foo@bar.com"),
+            "foo@bar.com",
+            AuditOutcome::Success,
+            &context,
+        );
+        let logout = AuditEvent::new(
+            AuditEventType::Logout,
+            Some("toto@bar.com"),
+            "toto@bar.com",
+            AuditOutcome::Failure("Invalid token".to_owned()),
+            &context,
+        );
+        let login = store.add_event(login).await.unwrap();
+        let logout = store.add_event(logout).await.unwrap();
+        assert_eq!(logout.prev_hash, login.hash);
+
+        let all = store
+            .get_events(&AuditEventFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(all, vec![login.clone(), logout.clone()]);
+
+        let filter = AuditEventFilter {
+            user_email: Some("foo@bar.com".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(
+            store.get_events(&filter).await.unwrap(),
+            vec![login.clone()]
+        );
+
+        assert_eq!(
+            store.get_records(1, 10).await.unwrap(),
+            vec![logout.clone()]
+        );
+        assert_eq!(store.get_records(0, 1).await.unwrap(), vec![login]);
+        assert_eq!(store.get_chain_head().await.unwrap(), Some(logout.head()));
+    }
+
+    #[tokio::test]
+    async fn test_verify_audit_chain() {
+        let mut store = VecAuditEventStore::default();
+        assert_eq!(
+            verify_audit_chain(&store).await.unwrap(),
+            AuditChainVerification::Valid(None)
+        );
+
+        for _ in 0..3 {
+            let event = AuditEvent::new(
+                AuditEventType::Signup,
+                Some("foo@bar.com"),
+                "foo@bar.com",
+                AuditOutcome::Success,
+                &RequestContext::default(),
+            );
+            store.add_event(event).await.unwrap();
+        }
+        let head = store.get_chain_head().await.unwrap();
+        assert_eq!(
+            verify_audit_chain(&store).await.unwrap(),
+            AuditChainVerification::Valid(head)
+        );
+
+        store.records[1].event.actor = "admin".to_owned();
+        match verify_audit_chain(&store).await.unwrap() {
+            AuditChainVerification::Broken(broken_link) => assert_eq!(broken_link.sequence, 2),
+            verification => panic!("Expected a broken chain, got {:?}", verification),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::domain::data_stores::{EmailOutboxStore, EmailOutboxStoreError};
+use crate::domain::delivery::DeliveryStatus;
+use crate::domain::email_outbox::OutboxEmail;
+
+#[derive(Default, Debug)]
+pub struct VecEmailOutboxStore {
+    emails: Vec<OutboxEmail>,
+}
+
+#[async_trait::async_trait]
+impl EmailOutboxStore for VecEmailOutboxStore {
+    async fn add_email(&mut self, email: OutboxEmail) -> Result<(), EmailOutboxStoreError> {
+        self.emails.push(email);
+        Ok(())
+    }
+
+    async fn get_email(&self, id: &Uuid) -> Result<OutboxEmail, EmailOutboxStoreError> {
+        self.emails
+            .iter()
+            .find(|email| email.id == *id)
+            .cloned()
+            .ok_or(EmailOutboxStoreError::EmailNotFound)
+    }
+
+    async fn claim_due_emails(
+        &mut self,
+        limit: i64,
+        lease: Duration,
+    ) -> Result<Vec<OutboxEmail>, EmailOutboxStoreError> {
+        let now = Utc::now();
+        let lease = chrono::Duration::from_std(lease)
+            .map_err(|e| EmailOutboxStoreError::UnexpectedError(e.into()))?;
+        let claimed = self
+            .emails
+            .iter_mut()
+            .filter(|email| email.status == DeliveryStatus::Pending && email.next_attempt_at <= now)
+            .take(limit.try_into().unwrap_or_default())
+            .map(|email| {
+                email.next_attempt_at = now + lease;
+                email.clone()
+            })
+            .collect();
+        Ok(claimed)
+    }
+
+    async fn update_email(&mut self, email: &OutboxEmail) -> Result<(), EmailOutboxStoreError> {
+        let stored = self
+            .emails
+            .iter_mut()
+            .find(|stored| stored.id == email.id)
+            .ok_or(EmailOutboxStoreError::EmailNotFound)?;
+        *stored = email.clone();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::email::Email;
+    use crate::domain::email_client::EmailMessage;
+
+    #[tokio::test]
+    async fn test_vec_email_outbox_store() {
+        let mut store = VecEmailOutboxStore::default();
+        let email = OutboxEmail::new(
+            Email::parse("foo@bar.com").unwrap(),
+            EmailMessage {
+                subject: "subject".to_owned(),
+                html_body: "<p>content</p>".to_owned(),
+                text_body: "content".to_owned(),
+            },
+        );
+        store.add_email(email.clone()).await.unwrap();
+        assert_eq!(store.get_email(&email.id).await.unwrap(), email);
+
+        let claimed = store
+            .claim_due_emails(10, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(claimed.len(), 1);
+        // Leased emails are not claimed twice
+        assert!(store
+            .claim_due_emails(10, Duration::from_secs(60))
+            .await
+            .unwrap()
+            .is_empty());
+
+        let mut sent = claimed[0].clone();
+        sent.record(Utc::now(), None, None);
+        store.update_email(&sent).await.unwrap();
+        assert_eq!(
+            store.get_email(&email.id).await.unwrap().status,
+            DeliveryStatus::Delivered
+        );
+        assert!(matches!(
+            store.get_email(&Uuid::new_v4()).await,
+            Err(EmailOutboxStoreError::EmailNotFound)
+        ));
+    }
+}
@@ -1,2 +1,5 @@
+pub mod file_email_client;
 pub mod mock_email_client;
+pub mod outbox_email_client;
 pub mod postmark_email_client;
+pub mod smtp_email_client;
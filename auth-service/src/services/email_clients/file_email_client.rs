@@ -0,0 +1,144 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::{
+    domain::{EmailClient, EmailMessage},
+    Email,
+};
+
+/// An email as written to the mailbox file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MailboxMessage {
+    pub id: Uuid,
+    pub recipient: String,
+    pub subject: String,
+    #[serde(rename = "htmlBody")]
+    pub html_body: String,
+    #[serde(rename = "textBody")]
+    pub text_body: String,
+    #[serde(rename = "sentAt")]
+    pub sent_at: DateTime<Utc>,
+}
+
+/// Appends emails to a local JSONL file instead of sending them, for development.
+/// The messages can be browsed at `/dev/mailbox`.
+pub struct FileEmailClient {
+    path: PathBuf,
+    // Serializes the writes, so that lines are never interleaved
+    lock: Mutex<()>,
+}
+
+impl FileEmailClient {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Returns the last `limit` messages, the most recent first.
+    pub async fn recent_messages(&self, limit: usize) -> Result<Vec<MailboxMessage>> {
+        let contents = match tokio::fs::read_to_string(&self.path).await {
+            Ok(contents) => contents,
+            // Nothing was sent yet
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).wrap_err("Failed to read the mailbox"),
+        };
+        contents
+            .lines()
+            .rev()
+            .filter(|line| !line.trim().is_empty())
+            .take(limit)
+            .map(|line| serde_json::from_str(line).wrap_err("Invalid message in the mailbox"))
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for FileEmailClient {
+    #[tracing::instrument(name = "Writing email to the mailbox", skip_all)]
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<()> {
+        let message = MailboxMessage {
+            id: Uuid::new_v4(),
+            recipient: recipient.as_ref().to_owned(),
+            subject: message.subject.clone(),
+            html_body: message.html_body.clone(),
+            text_body: message.text_body.clone(),
+            sent_at: Utc::now(),
+        };
+        let mut line = serde_json::to_string(&message)?;
+        line.push('\n');
+
+        let _guard = self.lock.lock().await;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .wrap_err("Failed to open the mailbox")?;
+        file.write_all(line.as_bytes())
+            .await
+            .wrap_err("Failed to write to the mailbox")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mailbox_path() -> PathBuf {
+        std::env::temp_dir().join(format!("mailbox-{}.jsonl", Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn recent_messages_returns_the_latest_messages_first() {
+        let path = mailbox_path();
+        let email_client = FileEmailClient::new(&path);
+        let recipient = Email::parse("foo@bar.com").unwrap();
+
+        for i in 0..3 {
+            email_client
+                .send_email(
+                    &recipient,
+                    &EmailMessage {
+                        subject: format!("subject {}", i),
+                        html_body: "<p>content</p>".to_owned(),
+                        text_body: "content".to_owned(),
+                    },
+                )
+                .await
+                .unwrap();
+        }
+
+        let messages = email_client.recent_messages(2).await.unwrap();
+        let subjects: Vec<&str> = messages.iter().map(|m| m.subject.as_str()).collect();
+        assert_eq!(subjects, vec!["subject 2", "subject 1"]);
+        assert_eq!(messages[0].recipient, "foo@bar.com");
+        assert_eq!(messages[0].html_body, "<p>content</p>");
+        assert_eq!(messages[0].text_body, "content");
+
+        // The file can be read back by another client, e.g. after a restart
+        assert_eq!(
+            FileEmailClient::new(&path)
+                .recent_messages(10)
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn recent_messages_is_empty_if_nothing_was_sent() {
+        let email_client = FileEmailClient::new(mailbox_path());
+        assert!(email_client.recent_messages(10).await.unwrap().is_empty());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::domain::{email::Email, EmailClient};
+use crate::domain::{email::Email, EmailClient, EmailMessage};
 use color_eyre::eyre::Result;
 
 #[derive(Debug, Clone, Default)]
@@ -6,13 +6,13 @@ pub struct MockEmailClient;
 
 #[async_trait::async_trait]
 impl EmailClient for MockEmailClient {
-    async fn send_email(&self, recipient: &Email, subject: &str, content: &str) -> Result<()> {
-        // Our mock email client will simply log the recipient, subject, and content to standard output
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<()> {
+        // Our mock email client will simply log the recipient, subject, and text body to standard output
         tracing::debug!(
             "Sending email to {} with\n\n[SUBJECT]: {}\n[CONTENT]: {}",
             recipient.as_ref(),
-            subject,
-            content
+            message.subject,
+            message.text_body
         );
 
         Ok(())
@@ -0,0 +1,66 @@
+use color_eyre::eyre::Result;
+
+use crate::app_state::EmailOutboxStoreType;
+use crate::domain::email_outbox::OutboxEmail;
+use crate::domain::{email::Email, EmailClient, EmailMessage};
+
+/// Queues emails in the outbox instead of sending them, `EmailDispatcher` sends them in the background.
+/// Use the provider's client directly to send emails synchronously, e.g. in tests.
+pub struct OutboxEmailClient {
+    email_outbox_store: EmailOutboxStoreType,
+}
+
+impl OutboxEmailClient {
+    pub fn new(email_outbox_store: EmailOutboxStoreType) -> Self {
+        Self { email_outbox_store }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for OutboxEmailClient {
+    #[tracing::instrument(name = "Queuing email", skip_all)]
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<()> {
+        let email = OutboxEmail::new(recipient.clone(), message.clone());
+        self.email_outbox_store
+            .write()
+            .await
+            .add_email(email)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::sync::RwLock;
+
+    use super::*;
+    use crate::services::data_stores::vec_email_outbox_store::VecEmailOutboxStore;
+
+    #[tokio::test]
+    async fn send_email_queues_the_email() {
+        let store: EmailOutboxStoreType = Arc::new(RwLock::new(VecEmailOutboxStore::default()));
+        let email_client = OutboxEmailClient::new(store.clone());
+        let recipient = Email::parse("foo@bar.com").unwrap();
+        let message = EmailMessage {
+            subject: "subject".to_owned(),
+            html_body: "<p>content</p>".to_owned(),
+            text_body: "content".to_owned(),
+        };
+
+        email_client.send_email(&recipient, &message).await.unwrap();
+
+        let queued = store
+            .write()
+            .await
+            .claim_due_emails(10, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].recipient, recipient);
+        assert_eq!(queued[0].message, message);
+    }
+}
@@ -0,0 +1,111 @@
+use color_eyre::eyre::{bail, Result};
+use serde::Serialize;
+use sqlx::PgPool;
+
+/// Accounts whose emails only differ by case, left un-normalised by the migration normalising
+/// emails. They are listed in the `email_collisions` table until resolved.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct EmailCollision {
+    pub normalized_email: String,
+    /// The emails of the accounts, as stored
+    pub emails: Vec<String>,
+}
+
+/// Lists the unresolved collisions, ordered by normalised email.
+pub async fn get_email_collisions(pool: &PgPool) -> Result<Vec<EmailCollision>> {
+    let collisions = sqlx::query_as!(
+        EmailCollision,
+        r#"
+        SELECT normalized_email, array_agg(email ORDER BY email) AS "emails!"
+        FROM email_collisions
+        GROUP BY normalized_email
+        ORDER BY normalized_email
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(collisions)
+}
+
+/// Resolves the collision of `email` by keeping its account, under the normalised email,
+/// and deleting the other accounts of the collision along with their devices and password history.
+/// Once no collision is left, the constraint keeping the emails normalised is validated.
+/// Returns the emails of the deleted accounts.
+pub async fn keep_colliding_account(pool: &PgPool, email: &str) -> Result<Vec<String>> {
+    let mut transaction = pool.begin().await?;
+    let Some(normalized_email) = sqlx::query_scalar!(
+        r#"
+        SELECT normalized_email FROM email_collisions WHERE email = $1
+        "#,
+        email
+    )
+    .fetch_optional(&mut *transaction)
+    .await?
+    else {
+        bail!("{} is not listed in email_collisions", email);
+    };
+
+    let deleted = sqlx::query_scalar!(
+        r#"
+        DELETE FROM users
+        WHERE email IN (
+            SELECT email FROM email_collisions WHERE normalized_email = $1 AND email <> $2
+        )
+        RETURNING email
+        "#,
+        normalized_email,
+        email
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        r#"
+        UPDATE users SET email = $2 WHERE email = $1
+        "#,
+        email,
+        normalized_email
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM email_collisions WHERE normalized_email = $1
+        "#,
+        normalized_email
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    validate_normalized_emails(&mut transaction).await?;
+    transaction.commit().await?;
+    Ok(deleted)
+}
+
+/// Validates the constraint keeping the emails normalised once no collision is left,
+/// for collisions resolved by hand. Returns whether it was validated.
+pub async fn validate_email_normalization(pool: &PgPool) -> Result<bool> {
+    let mut transaction = pool.begin().await?;
+    let validated = validate_normalized_emails(&mut transaction).await?;
+    transaction.commit().await?;
+    Ok(validated)
+}
+
+async fn validate_normalized_emails(
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<bool> {
+    let remaining = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) AS "count!" FROM email_collisions
+        "#
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    if remaining > 0 {
+        return Ok(false);
+    }
+    // Fails if an email was left un-normalised outside of the collisions
+    sqlx::query("ALTER TABLE users VALIDATE CONSTRAINT users_email_normalized")
+        .execute(&mut **transaction)
+        .await?;
+    Ok(true)
+}
@@ -0,0 +1,229 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use color_eyre::eyre::Result;
+
+use crate::app_state::{EmailClientType, EmailOutboxStoreType};
+use crate::domain::delivery::RetryPolicy;
+use crate::domain::email_outbox::OutboxEmail;
+
+/// Sends the emails queued in the outbox, retrying failed ones with exponential back-off.
+pub struct EmailDispatcher {
+    email_outbox_store: EmailOutboxStoreType,
+    /// Client of the email provider, must not be an `OutboxEmailClient`
+    email_client: EmailClientType,
+    retry_policy: RetryPolicy,
+    poll_interval: Duration,
+    batch_size: i64,
+    /// How long a claimed email is hidden from other dispatchers, must exceed the request timeout
+    lease: Duration,
+}
+
+impl EmailDispatcher {
+    pub fn new(
+        email_outbox_store: EmailOutboxStoreType,
+        email_client: EmailClientType,
+        retry_policy: RetryPolicy,
+        poll_interval: Duration,
+        batch_size: i64,
+        lease: Duration,
+    ) -> Self {
+        Self {
+            email_outbox_store,
+            email_client,
+            retry_policy,
+            poll_interval,
+            batch_size,
+            lease,
+        }
+    }
+
+    /// Polls the outbox forever, meant to be spawned next to the server.
+    pub async fn run(self) {
+        loop {
+            match self.dispatch_due().await {
+                // A full batch probably means more emails are due
+                Ok(dispatched) if dispatched as i64 >= self.batch_size => continue,
+                Ok(_) => {}
+                Err(e) => tracing::error!(error = ?e, "Failed to dispatch emails"),
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Sends one batch of due emails, returning how many were attempted.
+    #[tracing::instrument(name = "Dispatching emails", skip_all)]
+    pub async fn dispatch_due(&self) -> Result<usize> {
+        let claimed = self
+            .email_outbox_store
+            .write()
+            .await
+            .claim_due_emails(self.batch_size, self.lease)
+            .await?;
+
+        let dispatched = claimed.len();
+        for mut email in claimed {
+            self.send(&mut email).await;
+            self.email_outbox_store
+                .write()
+                .await
+                .update_email(&email)
+                .await?;
+        }
+        Ok(dispatched)
+    }
+
+    #[tracing::instrument(name = "Sending queued email", skip_all, fields(email_id = %email.id))]
+    async fn send(&self, email: &mut OutboxEmail) {
+        let attempted_at = Utc::now();
+        let result = self
+            .email_client
+            .read()
+            .await
+            .send_email(&email.recipient, &email.message)
+            .await;
+
+        match result {
+            Ok(()) => email.record(attempted_at, None, None),
+            Err(e) => {
+                tracing::warn!(
+                    attempts = email.attempts + 1,
+                    error = ?e,
+                    "Failed to send email"
+                );
+                let next_attempt_at = self
+                    .retry_policy
+                    .delay_after(email.attempts + 1)
+                    .and_then(|delay| chrono::Duration::from_std(delay).ok())
+                    .map(|delay| attempted_at + delay);
+                email.record(attempted_at, Some(e.to_string()), next_attempt_at);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use reqwest::Client;
+    use secrecy::Secret;
+    use tokio::sync::RwLock;
+    use uuid::Uuid;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::domain::data_stores::EmailOutboxStore;
+    use crate::domain::delivery::DeliveryStatus;
+    use crate::domain::email::Email;
+    use crate::domain::EmailMessage;
+    use crate::services::data_stores::vec_email_outbox_store::VecEmailOutboxStore;
+    use crate::services::email_clients::postmark_email_client::PostmarkEmailClient;
+
+    async fn setup(server: &MockServer) -> (EmailDispatcher, EmailOutboxStoreType, Uuid) {
+        let email = OutboxEmail::new(
+            Email::parse("foo@bar.com").unwrap(),
+            EmailMessage {
+                subject: "subject".to_owned(),
+                html_body: "<p>content</p>".to_owned(),
+                text_body: "content".to_owned(),
+            },
+        );
+        let id = email.id;
+        let mut store = VecEmailOutboxStore::default();
+        store.add_email(email).await.unwrap();
+        let store: EmailOutboxStoreType = Arc::new(RwLock::new(store));
+
+        let email_client = PostmarkEmailClient::new(
+            server.uri(),
+            Email::parse("sender@bar.com").unwrap(),
+            Secret::new("token".to_owned()),
+            Client::builder()
+                .timeout(Duration::from_millis(200))
+                .build()
+                .unwrap(),
+        );
+        let dispatcher = EmailDispatcher::new(
+            store.clone(),
+            Arc::new(RwLock::new(email_client)),
+            RetryPolicy {
+                max_attempts: 3,
+                base_delay: Duration::ZERO,
+                max_delay: Duration::ZERO,
+            },
+            Duration::from_millis(10),
+            10,
+            Duration::from_secs(1),
+        );
+        (dispatcher, store, id)
+    }
+
+    #[tokio::test]
+    async fn dispatch_sends_queued_email() {
+        let server = MockServer::start().await;
+        let (dispatcher, store, id) = setup(&server).await;
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 1);
+        let email = store.read().await.get_email(&id).await.unwrap();
+        assert_eq!(email.status, DeliveryStatus::Delivered);
+        assert_eq!(email.attempts, 1);
+        assert!(email.sent_at.is_some());
+        // Sent emails are not sent again
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn dispatch_retries_until_max_attempts() {
+        let server = MockServer::start().await;
+        let (dispatcher, store, id) = setup(&server).await;
+        Mock::given(path("/email"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        for attempts in 1..=3 {
+            assert_eq!(dispatcher.dispatch_due().await.unwrap(), 1);
+            let email = store.read().await.get_email(&id).await.unwrap();
+            assert_eq!(email.attempts, attempts);
+            assert!(email.last_error.is_some());
+        }
+        let email = store.read().await.get_email(&id).await.unwrap();
+        assert_eq!(email.status, DeliveryStatus::Failed);
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn dispatch_recovers_after_a_failure() {
+        let server = MockServer::start().await;
+        let (dispatcher, store, id) = setup(&server).await;
+        Mock::given(path("/email"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/email"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 1);
+        assert_eq!(
+            store.read().await.get_email(&id).await.unwrap().status,
+            DeliveryStatus::Pending
+        );
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 1);
+        let email = store.read().await.get_email(&id).await.unwrap();
+        assert_eq!(email.status, DeliveryStatus::Delivered);
+        assert_eq!(email.attempts, 2);
+    }
+}
@@ -0,0 +1,259 @@
+use std::collections::HashSet;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use color_eyre::eyre::{eyre, Context, Result};
+
+use crate::Email;
+
+/// Domains an email address can be signed up with, and those whose users must log in with 2FA.
+/// Each list is a file read again whenever it changes, so it can be updated without a restart.
+#[derive(Debug, Clone, Default)]
+pub struct EmailDomainPolicy {
+    /// Disposable and other unwanted domains
+    pub blocklist: Option<DomainListFile>,
+    /// When set, only its domains can sign up
+    pub allowlist: Option<DomainListFile>,
+    pub require_2fa: Option<DomainListFile>,
+}
+
+impl EmailDomainPolicy {
+    /// Whether a user can sign up with `email`, a blocked domain taking precedence over an allowed one.
+    pub async fn allows_signup(&self, email: &Email) -> bool {
+        if let Some(blocklist) = &self.blocklist {
+            if blocklist.get().await.contains(&email.ascii_domain()) {
+                return false;
+            }
+        }
+        match &self.allowlist {
+            Some(allowlist) => allowlist.get().await.contains(&email.ascii_domain()),
+            None => true,
+        }
+    }
+
+    /// Whether the user of `email` must log in with 2FA, whatever they chose.
+    pub async fn requires_2fa(&self, email: &Email) -> bool {
+        match &self.require_2fa {
+            Some(require_2fa) => require_2fa.get().await.contains(&email.ascii_domain()),
+            None => false,
+        }
+    }
+}
+
+/// A set of domains, each also covering its subdomains.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DomainList(HashSet<String>);
+
+impl DomainList {
+    /// Parses a domain per line, in Unicode or punycode. Blank lines and `#` comments are ignored.
+    pub fn parse(input: &str) -> Result<Self> {
+        input
+            .lines()
+            .zip(1..)
+            .filter_map(|(line, number)| {
+                let domain = line.split('#').next().unwrap_or_default().trim();
+                (!domain.is_empty()).then_some((domain, number))
+            })
+            .map(|(domain, number)| {
+                idna::domain_to_ascii_strict(domain.trim_end_matches('.'))
+                    .map_err(|_| eyre!("Invalid domain on line {}: {}", number, domain))
+            })
+            .collect::<Result<_>>()
+            .map(DomainList)
+    }
+
+    /// Whether the lowercased punycode `domain` or one of its parents is in the list.
+    pub fn contains(&self, domain: &str) -> bool {
+        let mut domain = domain;
+        loop {
+            if self.0.contains(domain) {
+                return true;
+            }
+            match domain.split_once('.') {
+                Some((_, parent)) => domain = parent,
+                None => return false,
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// A `DomainList` read from a file, and read again when the modification time or size of the file changes.
+#[derive(Debug, Clone)]
+pub struct DomainListFile {
+    path: PathBuf,
+    loaded: Arc<RwLock<LoadedDomainList>>,
+}
+
+#[derive(Debug)]
+struct LoadedDomainList {
+    version: FileVersion,
+    domains: Arc<DomainList>,
+}
+
+type FileVersion = (Option<SystemTime>, u64);
+
+impl DomainListFile {
+    /// Reads the list, failing if the file is missing or holds an invalid domain.
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let version = std::fs::metadata(&path)
+            .map(|metadata| file_version(&metadata))
+            .wrap_err(format!("Failed to read {}", path.display()))?;
+        let input = std::fs::read_to_string(&path)
+            .wrap_err(format!("Failed to read {}", path.display()))?;
+        let domains = parse(&path, &input)?;
+        Ok(Self {
+            path,
+            loaded: Arc::new(RwLock::new(LoadedDomainList {
+                version,
+                domains: Arc::new(domains),
+            })),
+        })
+    }
+
+    /// The current list. If the file can no longer be read, the last list read is kept.
+    pub async fn get(&self) -> Arc<DomainList> {
+        let current = {
+            let loaded = self.loaded.read().expect("Domain list lock poisoned");
+            (loaded.version, loaded.domains.clone())
+        };
+        let version = match tokio::fs::metadata(&self.path).await {
+            Ok(metadata) if file_version(&metadata) == current.0 => return current.1,
+            Ok(metadata) => file_version(&metadata),
+            Err(e) => {
+                tracing::error!(error = ?e, path = %self.path.display(), "Failed to check email domain list");
+                return current.1;
+            }
+        };
+        // The version is taken before reading, so that a change made meanwhile is picked up next time
+        let domains = tokio::fs::read_to_string(&self.path)
+            .await
+            .wrap_err(format!("Failed to read {}", self.path.display()))
+            .and_then(|input| parse(&self.path, &input));
+        match domains {
+            Ok(domains) => {
+                tracing::info!(
+                    path = %self.path.display(),
+                    domains = domains.len(),
+                    "Reloaded email domain list"
+                );
+                let domains = Arc::new(domains);
+                *self.loaded.write().expect("Domain list lock poisoned") = LoadedDomainList {
+                    version,
+                    domains: domains.clone(),
+                };
+                domains
+            }
+            Err(e) => {
+                tracing::error!(error = ?e, "Failed to reload email domain list");
+                current.1
+            }
+        }
+    }
+}
+
+fn file_version(metadata: &Metadata) -> FileVersion {
+    (metadata.modified().ok(), metadata.len())
+}
+
+fn parse(path: &Path, input: &str) -> Result<DomainList> {
+    DomainList::parse(input).wrap_err(format!("Invalid {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_domain_list() {
+        let input = "# Disposable domains\nMailinator.com\n\n  yopmail.fr  # and its aliases\nbücher.example.\n";
+        let domains = DomainList::parse(input).unwrap();
+        assert_eq!(domains.len(), 3);
+        assert!(domains.contains("mailinator.com"));
+        assert!(domains.contains("yopmail.fr"));
+        assert!(domains.contains("xn--bcher-kva.example"));
+
+        let error = DomainList::parse("example.com\nnot a domain\n").unwrap_err();
+        assert!(error.to_string().contains("line 2"), "{}", error);
+    }
+
+    #[test]
+    fn test_contains_subdomains() {
+        let domains = DomainList::parse("mailinator.com").unwrap();
+        assert!(domains.contains("mailinator.com"));
+        assert!(domains.contains("eu.mailinator.com"));
+        assert!(!domains.contains("notmailinator.com"));
+        assert!(!domains.contains("com"));
+    }
+
+    #[tokio::test]
+    async fn test_policy() {
+        let dir = std::env::temp_dir().join(format!("domains-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        for (name, domains) in [
+            ("blocked.txt", "corp.example"),
+            ("allowed.txt", "example\nbücher.example"),
+            ("2fa.txt", "xn--bcher-kva.example"),
+        ] {
+            tokio::fs::write(dir.join(name), domains).await.unwrap();
+        }
+        let policy = EmailDomainPolicy {
+            blocklist: Some(DomainListFile::load(dir.join("blocked.txt")).unwrap()),
+            allowlist: Some(DomainListFile::load(dir.join("allowed.txt")).unwrap()),
+            require_2fa: Some(DomainListFile::load(dir.join("2fa.txt")).unwrap()),
+        };
+
+        for (email, allowed, requires_2fa) in [
+            ("foo@bar.example", true, false),
+            ("foo@corp.example", false, false),
+            ("foo@eu.corp.example", false, false),
+            ("foo@bücher.example", true, true),
+            ("foo@example.com", false, false),
+        ] {
+            let email = Email::parse(email).unwrap();
+            assert_eq!(policy.allows_signup(&email).await, allowed, "{:?}", email);
+            assert_eq!(
+                policy.requires_2fa(&email).await,
+                requires_2fa,
+                "{:?}",
+                email
+            );
+        }
+        let everything = EmailDomainPolicy::default();
+        let email = Email::parse("foo@corp.example").unwrap();
+        assert!(everything.allows_signup(&email).await);
+        assert!(!everything.requires_2fa(&email).await);
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reloads_a_changed_file() {
+        let path = std::env::temp_dir().join(format!("domains-{}.txt", uuid::Uuid::new_v4()));
+        tokio::fs::write(&path, "mailinator.com\n").await.unwrap();
+        assert!(DomainListFile::load(path.with_extension("missing")).is_err());
+        let list = DomainListFile::load(&path).unwrap();
+        assert!(list.get().await.contains("mailinator.com"));
+
+        tokio::fs::write(&path, "mailinator.com\nyopmail.fr\n")
+            .await
+            .unwrap();
+        assert!(list.get().await.contains("yopmail.fr"));
+
+        // An invalid or missing file keeps the last list read
+        tokio::fs::write(&path, "not a domain\n").await.unwrap();
+        assert!(list.get().await.contains("yopmail.fr"));
+        tokio::fs::remove_file(&path).await.unwrap();
+        assert!(list.get().await.contains("yopmail.fr"));
+    }
+}
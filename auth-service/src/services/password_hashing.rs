@@ -0,0 +1,420 @@
+use std::str::FromStr;
+
+use argon2::{
+    password_hash::{Ident, SaltString},
+    Algorithm, Argon2, KeyId, Params, ParamsBuilder, PasswordHash, PasswordHasher, Version,
+};
+use color_eyre::eyre::{eyre, Result};
+use pbkdf2::Pbkdf2;
+use scrypt::Scrypt;
+use secrecy::{ExposeSecret, Secret};
+
+// Modular crypt prefixes of the bcrypt variants, none of which is a PHC string
+const BCRYPT_PREFIXES: [&str; 4] = ["$2a$", "$2b$", "$2x$", "$2y$"];
+
+/// Whether the passwords of users imported from another system can be verified with
+/// `password_hash`: an Argon2, scrypt or PBKDF2 PHC string, or a bcrypt modular crypt hash.
+pub fn is_supported_password_hash(password_hash: &str) -> bool {
+    if is_bcrypt(password_hash) {
+        return bcrypt::HashParts::from_str(password_hash).is_ok();
+    }
+    match PasswordHash::new(password_hash) {
+        Ok(password_hash) => {
+            let algorithm = password_hash.algorithm;
+            Algorithm::try_from(algorithm).is_ok()
+                || algorithm == scrypt::ALG_ID
+                || pbkdf2::Algorithm::try_from(algorithm).is_ok()
+        }
+        Err(_) => false,
+    }
+}
+
+fn is_bcrypt(password_hash: &str) -> bool {
+    BCRYPT_PREFIXES
+        .iter()
+        .any(|prefix| password_hash.starts_with(prefix))
+}
+
+/// Server-side secret given to Argon2 along with each password, so that a leaked `password_hash`
+/// column cannot be cracked without it. Its version is stored as the `keyid` of the hashes.
+#[derive(Debug, Clone)]
+pub struct Pepper {
+    version: String,
+    secret: Secret<String>,
+}
+
+impl Pepper {
+    pub fn new(version: &str, secret: Secret<String>) -> Result<Self> {
+        // Argon2 key ids are at most 8 bytes
+        if version.is_empty()
+            || version.len() > 8
+            || !version.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            return Err(eyre!(
+                "Pepper version must be 1 to 8 letters or digits: {}",
+                version
+            ));
+        }
+        if secret.expose_secret().is_empty() {
+            return Err(eyre!("Pepper {} is empty", version));
+        }
+        Ok(Self {
+            version: version.to_owned(),
+            secret,
+        })
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+}
+
+/// The peppers the stored hashes may have been computed with. The first one peppers the new
+/// hashes, the others are only kept to verify the hashes not upgraded since a rotation.
+#[derive(Debug, Clone, Default)]
+pub struct Peppers(Vec<Pepper>);
+
+impl Peppers {
+    pub fn new(peppers: Vec<Pepper>) -> Self {
+        Self(peppers)
+    }
+
+    /// Parses `version:secret` entries separated by commas or new lines, the current one first.
+    pub fn parse(peppers: &Secret<String>) -> Result<Self> {
+        let peppers = peppers
+            .expose_secret()
+            .split([',', '\n'])
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (version, secret) = entry
+                    .split_once(':')
+                    .ok_or_else(|| eyre!("Pepper must be given as version:secret"))?;
+                Pepper::new(version.trim(), Secret::new(secret.trim().to_owned()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self(peppers))
+    }
+
+    pub fn current(&self) -> Option<&Pepper> {
+        self.0.first()
+    }
+
+    fn get(&self, version: &[u8]) -> Option<&Pepper> {
+        self.0
+            .iter()
+            .find(|pepper| pepper.version.as_bytes() == version)
+    }
+}
+
+// Helper function to verify if a given password matches an expected hash,
+// in any of the formats accepted by `is_supported_password_hash`
+#[tracing::instrument(name = "Verifying password hash", skip_all)]
+pub(crate) async fn verify_password_hash(
+    expected_password_hash: String,
+    password_candidate: Secret<String>,
+    peppers: Peppers,
+) -> Result<()> {
+    let current_span = tracing::Span::current();
+    tokio::task::spawn_blocking(move || {
+        current_span.in_scope(|| {
+            let password_candidate = password_candidate.expose_secret().as_bytes();
+            if is_bcrypt(&expected_password_hash) {
+                return match bcrypt::verify(password_candidate, &expected_password_hash)? {
+                    true => Ok(()),
+                    false => Err(eyre!("Password does not match")),
+                };
+            }
+            let expected_password_hash: PasswordHash<'_> =
+                PasswordHash::new(&expected_password_hash)?;
+            // Hashes without a key id were computed before a pepper was configured
+            let pepper = match Params::try_from(&expected_password_hash) {
+                Ok(params) if !params.keyid().is_empty() => {
+                    let pepper = peppers.get(params.keyid()).ok_or_else(|| {
+                        eyre!(
+                            "Unknown pepper version {}",
+                            String::from_utf8_lossy(params.keyid())
+                        )
+                    })?;
+                    Some(pepper.secret.expose_secret().as_bytes())
+                }
+                _ => None,
+            };
+            let argon2 = match pepper {
+                Some(pepper) => Argon2::new_with_secret(
+                    pepper,
+                    Algorithm::default(),
+                    Version::default(),
+                    Params::default(),
+                )?,
+                None => Argon2::default(),
+            };
+            expected_password_hash
+                .verify_password(&[&argon2, &Scrypt, &Pbkdf2], password_candidate)
+                .map_err(|e| e.into())
+        })
+    })
+    .await?
+}
+
+// Helper function to hash passwords before persisting them in the database.
+#[tracing::instrument(name = "Computing password hash", skip_all)]
+pub(crate) async fn compute_password_hash(
+    password: Secret<String>,
+    hash_params: Params,
+    peppers: Peppers,
+) -> Result<String> {
+    let current_span = tracing::Span::current();
+    tokio::task::spawn_blocking(move || {
+        current_span.in_scope(|| {
+            let salt: SaltString = SaltString::generate(&mut rand::thread_rng());
+            let argon2 = match peppers.current() {
+                Some(pepper) => {
+                    let mut params = ParamsBuilder::new();
+                    params
+                        .m_cost(hash_params.m_cost())
+                        .t_cost(hash_params.t_cost())
+                        .p_cost(hash_params.p_cost())
+                        .keyid(KeyId::new(pepper.version.as_bytes())?);
+                    if let Some(output_len) = hash_params.output_len() {
+                        params.output_len(output_len);
+                    }
+                    Argon2::new_with_secret(
+                        pepper.secret.expose_secret().as_bytes(),
+                        Algorithm::Argon2id,
+                        Version::V0x13,
+                        params.build()?,
+                    )?
+                }
+                None => Argon2::new(Algorithm::Argon2id, Version::V0x13, hash_params),
+            };
+            let password_hash = argon2
+                .hash_password(password.expose_secret().as_bytes(), &salt)?
+                .to_string();
+
+            Ok(password_hash)
+        })
+    })
+    .await?
+}
+
+// Whether a hash that was just verified should be replaced by a `compute_password_hash` one:
+// it comes from another system, or was computed with another algorithm, version, costs or pepper
+pub(crate) fn needs_rehash(password_hash: &str, hash_params: &Params, peppers: &Peppers) -> bool {
+    let Ok(password_hash) = PasswordHash::new(password_hash) else {
+        return true;
+    };
+    if password_hash.algorithm != Ident::from(Algorithm::Argon2id)
+        || password_hash.version != Some(Version::V0x13.into())
+    {
+        return true;
+    }
+    let Ok(params) = Params::try_from(&password_hash) else {
+        return true;
+    };
+    let current_pepper = peppers
+        .current()
+        .map_or(&[][..], |pepper| pepper.version.as_bytes());
+    params.m_cost() != hash_params.m_cost()
+        || params.t_cost() != hash_params.t_cost()
+        || params.p_cost() != hash_params.p_cost()
+        || params.keyid() != current_pepper
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secret(password: &str) -> Secret<String> {
+        Secret::new(password.to_owned())
+    }
+
+    fn peppers(peppers: &str) -> Peppers {
+        Peppers::parse(&secret(peppers)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_pepper_is_required_to_verify_and_rotates() {
+        let params = Params::new(64, 1, 1, None).unwrap();
+        let v1 = peppers("v1:first-pepper");
+        let password_hash =
+            compute_password_hash(secret("password123"), params.clone(), v1.clone())
+                .await
+                .unwrap();
+        let password_hash_params =
+            Params::try_from(&PasswordHash::new(&password_hash).unwrap()).unwrap();
+        assert_eq!(password_hash_params.keyid(), b"v1");
+        assert!(
+            verify_password_hash(password_hash.clone(), secret("password123"), v1.clone())
+                .await
+                .is_ok()
+        );
+        assert!(!needs_rehash(&password_hash, &params, &v1));
+
+        // Without the pepper, or with another one under the same version, the hash is useless
+        for wrong_peppers in [
+            Peppers::default(),
+            peppers("v2:second-pepper"),
+            peppers("v1:other-pepper"),
+        ] {
+            assert!(verify_password_hash(
+                password_hash.clone(),
+                secret("password123"),
+                wrong_peppers
+            )
+            .await
+            .is_err());
+        }
+
+        // After a rotation the hash is still verified, then upgraded to the new pepper
+        let rotated = peppers("v2:second-pepper\nv1:first-pepper\n");
+        assert!(verify_password_hash(
+            password_hash.clone(),
+            secret("password123"),
+            rotated.clone()
+        )
+        .await
+        .is_ok());
+        assert!(needs_rehash(&password_hash, &params, &rotated));
+        let unpeppered_hash =
+            compute_password_hash(secret("password123"), params.clone(), Peppers::default())
+                .await
+                .unwrap();
+        assert!(verify_password_hash(
+            unpeppered_hash.clone(),
+            secret("password123"),
+            rotated.clone()
+        )
+        .await
+        .is_ok());
+        assert!(needs_rehash(&unpeppered_hash, &params, &rotated));
+    }
+
+    #[test]
+    fn test_parse_peppers() {
+        let parsed = peppers(" 2:second, 1:first:with:colons ");
+        assert_eq!(parsed.current().unwrap().version(), "2");
+        assert_eq!(
+            parsed.get(b"1").unwrap().secret.expose_secret(),
+            "first:with:colons"
+        );
+        assert!(peppers("").current().is_none());
+
+        for invalid in [
+            "no-version",
+            "toolongversion:pepper",
+            "v-1:pepper",
+            "1:",
+            ":pepper",
+        ] {
+            assert!(
+                Peppers::parse(&secret(invalid)).is_err(),
+                "Expected {} to be invalid",
+                invalid
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_needs_rehash_when_costs_change() {
+        let params = Params::new(64, 1, 1, None).unwrap();
+        let peppers = Peppers::default();
+        let password_hash =
+            compute_password_hash(secret("password123"), params.clone(), peppers.clone())
+                .await
+                .unwrap();
+        assert!(!needs_rehash(&password_hash, &params, &peppers));
+
+        for other_params in [
+            Params::new(128, 1, 1, None).unwrap(),
+            Params::new(64, 2, 1, None).unwrap(),
+            Params::new(64, 1, 2, None).unwrap(),
+        ] {
+            assert!(needs_rehash(&password_hash, &other_params, &peppers));
+        }
+
+        let argon2i_hash = Argon2::new(Algorithm::Argon2i, Version::V0x13, params.clone())
+            .hash_password(
+                b"password123",
+                &SaltString::generate(&mut rand::thread_rng()),
+            )
+            .unwrap()
+            .to_string();
+        assert!(needs_rehash(&argon2i_hash, &params, &peppers));
+    }
+
+    #[tokio::test]
+    async fn test_verifies_and_upgrades_legacy_hashes() {
+        let salt = SaltString::generate(&mut rand::thread_rng());
+        let legacy_hashes = [
+            bcrypt::hash("password123", 4).unwrap(),
+            Scrypt
+                .hash_password_customized(
+                    b"password123",
+                    None,
+                    None,
+                    scrypt::Params::new(4, 8, 1, 32).unwrap(),
+                    &salt,
+                )
+                .unwrap()
+                .to_string(),
+            Pbkdf2
+                .hash_password_customized(
+                    b"password123",
+                    Some(pbkdf2::Algorithm::Pbkdf2Sha512.ident()),
+                    None,
+                    pbkdf2::Params {
+                        rounds: 1000,
+                        output_length: 64,
+                    },
+                    &salt,
+                )
+                .unwrap()
+                .to_string(),
+        ];
+
+        let params = Params::new(64, 1, 1, None).unwrap();
+        let peppers = peppers("1:pepper");
+        for legacy_hash in legacy_hashes {
+            assert!(is_supported_password_hash(&legacy_hash), "{}", legacy_hash);
+            assert!(
+                verify_password_hash(legacy_hash.clone(), secret("password123"), peppers.clone())
+                    .await
+                    .is_ok(),
+                "Failed to verify {}",
+                legacy_hash
+            );
+            assert!(
+                verify_password_hash(legacy_hash.clone(), secret("password124"), peppers.clone())
+                    .await
+                    .is_err(),
+                "Wrongly verified {}",
+                legacy_hash
+            );
+            assert!(
+                needs_rehash(&legacy_hash, &params, &peppers),
+                "{}",
+                legacy_hash
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejects_unsupported_hashes() {
+        for password_hash in [
+            "",
+            "password123",
+            // MD5 crypt and SHA-512 crypt
+            "$1$saltsalt$qjXMvbEw8oaL.CzflDugX/",
+            "$6$saltsalt$qFmFH.bQmmtXzyBY0s9v7Oicd2z4XSIecDzlB5KiA2/jctKu9YterLp8wwnSq.qc.eoxqOmSuNp2xS0ktL3nh/",
+            "$2b$04$tooshort",
+            "$md5$salt$hash",
+        ] {
+            assert!(
+                !is_supported_password_hash(password_hash),
+                "Expected {} to be rejected",
+                password_hash
+            );
+        }
+    }
+}
@@ -0,0 +1,2 @@
+pub mod http_sms_client;
+pub mod mock_sms_client;
@@ -0,0 +1,142 @@
+use color_eyre::eyre::Result;
+use reqwest::Client;
+use secrecy::{ExposeSecret, Secret};
+
+use crate::domain::{phone_number::PhoneNumber, SmsClient};
+
+/// Sends text messages through an HTTP API.
+/// Most providers accept, or can be proxied to accept, a JSON `POST` with bearer authentication:
+/// `{"from": "<sender>", "to": "<E.164 number>", "body": "<text>"}`
+pub struct HttpSmsClient {
+    http_client: Client,
+    url: String,
+    sender: String,
+    authorization_token: Secret<String>,
+}
+
+impl HttpSmsClient {
+    pub fn new(
+        url: String,
+        sender: String,
+        authorization_token: Secret<String>,
+        http_client: Client,
+    ) -> Self {
+        Self {
+            http_client,
+            url,
+            sender,
+            authorization_token,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SmsClient for HttpSmsClient {
+    #[tracing::instrument(name = "Sending SMS", skip_all)]
+    async fn send_sms(&self, recipient: &PhoneNumber, content: &str) -> Result<()> {
+        let request_body = SendSmsRequest {
+            from: &self.sender,
+            to: recipient.as_ref(),
+            body: content,
+        };
+
+        self.http_client
+            .post(&self.url)
+            .bearer_auth(self.authorization_token.expose_secret())
+            .json(&request_body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[derive(serde::Serialize, Debug)]
+struct SendSmsRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    body: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wiremock::matchers::{any, body_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    fn sms_client(base_url: String) -> HttpSmsClient {
+        let http_client = Client::builder()
+            .timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        HttpSmsClient::new(
+            format!("{}/messages", base_url),
+            "AuthService".to_owned(),
+            Secret::new("token".to_owned()),
+            http_client,
+        )
+    }
+
+    fn phone_number() -> PhoneNumber {
+        PhoneNumber::parse("+33612345678").unwrap()
+    }
+
+    #[tokio::test]
+    async fn send_sms_sends_the_expected_request() {
+        let mock_server = MockServer::start().await;
+        let sms_client = sms_client(mock_server.uri());
+
+        Mock::given(path("/messages"))
+            .and(method("POST"))
+            .and(header("Authorization", "Bearer token"))
+            .and(header("Content-Type", "application/json"))
+            .and(body_json(serde_json::json!({
+                "from": "AuthService",
+                "to": "+33612345678",
+                "body": "Your 2FA code is: 123456",
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = sms_client
+            .send_sms(&phone_number(), "Your 2FA code is: 123456")
+            .await;
+        assert!(outcome.is_ok());
+    }
+
+    #[tokio::test]
+    async fn send_sms_fails_if_the_server_returns_500() {
+        let mock_server = MockServer::start().await;
+        let sms_client = sms_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = sms_client.send_sms(&phone_number(), "content").await;
+        assert!(outcome.is_err());
+    }
+
+    #[tokio::test]
+    async fn send_sms_times_out_if_the_server_takes_too_long() {
+        let mock_server = MockServer::start().await;
+        let sms_client = sms_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(180)))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = sms_client.send_sms(&phone_number(), "content").await;
+        assert!(outcome.is_err());
+    }
+}
@@ -0,0 +1,19 @@
+use crate::domain::{phone_number::PhoneNumber, SmsClient};
+use color_eyre::eyre::Result;
+
+#[derive(Debug, Clone, Default)]
+pub struct MockSmsClient;
+
+#[async_trait::async_trait]
+impl SmsClient for MockSmsClient {
+    async fn send_sms(&self, recipient: &PhoneNumber, content: &str) -> Result<()> {
+        // Our mock SMS client will simply log the recipient and content to standard output
+        tracing::debug!(
+            "Sending SMS to {} with\n\n[CONTENT]: {}",
+            recipient.as_ref(),
+            content
+        );
+
+        Ok(())
+    }
+}
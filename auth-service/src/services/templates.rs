@@ -0,0 +1,52 @@
+//! Localised messages sent to the users, rendered from the askama templates in `templates/`.
+//! Each message kind has a template per language, under `templates/<channel>/<language>/`.
+//! The pages opened by the links of the emails are under `templates/pages/`.
+
+use crate::domain::locale::Locale;
+
+pub mod email;
+pub mod page;
+pub mod sms;
+
+/// Languages the messages are translated to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    French,
+}
+
+impl Language {
+    /// Picks the language of a locale, ignoring its region (`fr-ca` is sent in French).
+    /// Falls back to English when the user has no locale or it is not translated.
+    pub fn negotiate(locale: Option<&Locale>) -> Self {
+        match locale.map(Locale::language) {
+            Some("fr") => Language::French,
+            _ => Language::English,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_falls_back_to_english() {
+        let cases = [
+            (None, Language::English),
+            (Some("en-GB"), Language::English),
+            (Some("fr"), Language::French),
+            (Some("fr-CA"), Language::French),
+            (Some("de"), Language::English),
+        ];
+        for (tag, expected) in cases {
+            let locale = tag.map(|tag| Locale::parse(tag).unwrap());
+            assert_eq!(
+                Language::negotiate(locale.as_ref()),
+                expected,
+                "Failed for locale: {:?}",
+                tag
+            );
+        }
+    }
+}
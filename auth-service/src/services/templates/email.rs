@@ -0,0 +1,385 @@
+use askama::Template;
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Context, Result};
+
+use super::Language;
+use crate::domain::{
+    data_stores::TwoFACode, email::Email, email_change::EmailChange, known_device::KnownDevice,
+    locale::Locale, EmailMessage,
+};
+
+#[derive(Template)]
+#[template(path = "emails/en/two_fa_login.html")]
+struct TwoFALoginHtmlEn<'a> {
+    email: &'a str,
+    code: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/en/two_fa_login.txt")]
+struct TwoFALoginTextEn<'a> {
+    email: &'a str,
+    code: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/fr/two_fa_login.html")]
+struct TwoFALoginHtmlFr<'a> {
+    email: &'a str,
+    code: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/fr/two_fa_login.txt")]
+struct TwoFALoginTextFr<'a> {
+    email: &'a str,
+    code: &'a str,
+}
+
+/// The email sending the 2FA code of a login attempt.
+pub fn two_fa_login_email(
+    locale: Option<&Locale>,
+    email: &Email,
+    code: &TwoFACode,
+) -> Result<EmailMessage> {
+    let (email, code) = (email.as_ref(), code.as_ref());
+    match Language::negotiate(locale) {
+        Language::English => render(
+            "Your 2FA Code",
+            TwoFALoginHtmlEn { email, code },
+            TwoFALoginTextEn { email, code },
+        ),
+        Language::French => render(
+            "Votre code de vérification",
+            TwoFALoginHtmlFr { email, code },
+            TwoFALoginTextFr { email, code },
+        ),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "emails/en/new_device_login.html")]
+struct NewDeviceLoginHtmlEn<'a> {
+    email: &'a str,
+    user_agent: &'a str,
+    network: &'a str,
+    revoke_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/en/new_device_login.txt")]
+struct NewDeviceLoginTextEn<'a> {
+    email: &'a str,
+    user_agent: &'a str,
+    network: &'a str,
+    revoke_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/fr/new_device_login.html")]
+struct NewDeviceLoginHtmlFr<'a> {
+    email: &'a str,
+    user_agent: &'a str,
+    network: &'a str,
+    revoke_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/fr/new_device_login.txt")]
+struct NewDeviceLoginTextFr<'a> {
+    email: &'a str,
+    user_agent: &'a str,
+    network: &'a str,
+    revoke_url: &'a str,
+}
+
+/// The alert sent to `email` after a login from a device the user never logged in from.
+/// `revoke_url` logs the device out.
+pub fn new_device_login_email(
+    locale: Option<&Locale>,
+    email: &Email,
+    device: &KnownDevice,
+    revoke_url: &str,
+) -> Result<EmailMessage> {
+    let email = email.as_ref();
+    let network = device.ip_prefix.as_deref().unwrap_or("?");
+    let user_agent = device.user_agent.as_deref().unwrap_or("?");
+    match Language::negotiate(locale) {
+        Language::English => render(
+            "New login to your account",
+            NewDeviceLoginHtmlEn {
+                email,
+                user_agent,
+                network,
+                revoke_url,
+            },
+            NewDeviceLoginTextEn {
+                email,
+                user_agent,
+                network,
+                revoke_url,
+            },
+        ),
+        Language::French => render(
+            "Nouvelle connexion à votre compte",
+            NewDeviceLoginHtmlFr {
+                email,
+                user_agent,
+                network,
+                revoke_url,
+            },
+            NewDeviceLoginTextFr {
+                email,
+                user_agent,
+                network,
+                revoke_url,
+            },
+        ),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "emails/en/email_change_confirmation.html")]
+struct EmailChangeConfirmationHtmlEn<'a> {
+    email: &'a str,
+    confirm_url: &'a str,
+    valid_until: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/en/email_change_confirmation.txt")]
+struct EmailChangeConfirmationTextEn<'a> {
+    email: &'a str,
+    confirm_url: &'a str,
+    valid_until: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/fr/email_change_confirmation.html")]
+struct EmailChangeConfirmationHtmlFr<'a> {
+    email: &'a str,
+    confirm_url: &'a str,
+    valid_until: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/fr/email_change_confirmation.txt")]
+struct EmailChangeConfirmationTextFr<'a> {
+    email: &'a str,
+    confirm_url: &'a str,
+    valid_until: &'a str,
+}
+
+/// The email sent to the new address of an email change, whose link applies the change.
+pub fn email_change_confirmation_email(
+    locale: Option<&Locale>,
+    change: &EmailChange,
+    confirm_url: &str,
+) -> Result<EmailMessage> {
+    let email = change.new_email.as_ref();
+    let valid_until = &format_expiry(&change.confirm_expires_at);
+    match Language::negotiate(locale) {
+        Language::English => render(
+            "Confirm your new email address",
+            EmailChangeConfirmationHtmlEn {
+                email,
+                confirm_url,
+                valid_until,
+            },
+            EmailChangeConfirmationTextEn {
+                email,
+                confirm_url,
+                valid_until,
+            },
+        ),
+        Language::French => render(
+            "Confirmez votre nouvelle adresse email",
+            EmailChangeConfirmationHtmlFr {
+                email,
+                confirm_url,
+                valid_until,
+            },
+            EmailChangeConfirmationTextFr {
+                email,
+                confirm_url,
+                valid_until,
+            },
+        ),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "emails/en/email_change_requested.html")]
+struct EmailChangeRequestedHtmlEn<'a> {
+    email: &'a str,
+    new_email: &'a str,
+    revert_url: &'a str,
+    valid_until: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/en/email_change_requested.txt")]
+struct EmailChangeRequestedTextEn<'a> {
+    email: &'a str,
+    new_email: &'a str,
+    revert_url: &'a str,
+    valid_until: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/fr/email_change_requested.html")]
+struct EmailChangeRequestedHtmlFr<'a> {
+    email: &'a str,
+    new_email: &'a str,
+    revert_url: &'a str,
+    valid_until: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/fr/email_change_requested.txt")]
+struct EmailChangeRequestedTextFr<'a> {
+    email: &'a str,
+    new_email: &'a str,
+    revert_url: &'a str,
+    valid_until: &'a str,
+}
+
+/// The notice sent to the old address of an email change. `revert_url` keeps the old address.
+pub fn email_change_requested_email(
+    locale: Option<&Locale>,
+    change: &EmailChange,
+    revert_url: &str,
+) -> Result<EmailMessage> {
+    let (email, new_email) = (change.old_email.as_ref(), change.new_email.as_ref());
+    let valid_until = &format_expiry(&change.revert_expires_at);
+    match Language::negotiate(locale) {
+        Language::English => render(
+            "Change of your email address",
+            EmailChangeRequestedHtmlEn {
+                email,
+                new_email,
+                revert_url,
+                valid_until,
+            },
+            EmailChangeRequestedTextEn {
+                email,
+                new_email,
+                revert_url,
+                valid_until,
+            },
+        ),
+        Language::French => render(
+            "Changement de votre adresse email",
+            EmailChangeRequestedHtmlFr {
+                email,
+                new_email,
+                revert_url,
+                valid_until,
+            },
+            EmailChangeRequestedTextFr {
+                email,
+                new_email,
+                revert_url,
+                valid_until,
+            },
+        ),
+    }
+}
+
+fn format_expiry(expires_at: &DateTime<Utc>) -> String {
+    expires_at.format("%Y-%m-%d %H:%M UTC").to_string()
+}
+
+fn render(subject: &str, html: impl Template, text: impl Template) -> Result<EmailMessage> {
+    Ok(EmailMessage {
+        subject: subject.to_owned(),
+        html_body: html.render().wrap_err("Failed to render HTML email")?,
+        text_body: text.render().wrap_err("Failed to render text email")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::user::UserId;
+
+    #[test]
+    fn test_two_fa_login_email() {
+        let email = Email::parse("foo@bar.com").unwrap();
+        let code = TwoFACode::parse("123456".to_owned()).unwrap();
+
+        let message = two_fa_login_email(None, &email, &code).unwrap();
+        assert_eq!(message.subject, "Your 2FA Code");
+        assert!(message.text_body.contains("Your 2FA code is: 123456"));
+        assert!(!message.text_body.contains('<'));
+        assert!(message.html_body.starts_with("<!DOCTYPE html>"));
+        assert!(message.html_body.contains("<html lang=\"en\">"));
+        assert!(message.html_body.contains("123456"));
+
+        let locale = Locale::parse("fr-FR").unwrap();
+        let message = two_fa_login_email(Some(&locale), &email, &code).unwrap();
+        assert_eq!(message.subject, "Votre code de vérification");
+        assert!(message
+            .text_body
+            .contains("Votre code de vérification est : 123456"));
+        assert!(message.html_body.contains("<html lang=\"fr\">"));
+    }
+
+    #[test]
+    fn test_new_device_login_email() {
+        let email = Email::parse("foo@bar.com").unwrap();
+        let device = KnownDevice::new(UserId::new(), Some("Firefox".to_owned()), Some("10.0.0.1"));
+        let revoke_url = "http://localhost:3000/revoke-session?token=abc&x=1";
+
+        let message = new_device_login_email(None, &email, &device, revoke_url).unwrap();
+        assert_eq!(message.subject, "New login to your account");
+        assert!(message.text_body.contains("Browser: Firefox"));
+        assert!(message.text_body.contains("Network: 10.0.0.0/24"));
+        assert!(message.text_body.contains(revoke_url));
+        // The link is escaped in the HTML body
+        assert!(message
+            .html_body
+            .contains("http://localhost:3000/revoke-session?token=abc&amp;x=1"));
+
+        let locale = Locale::parse("fr").unwrap();
+        let message = new_device_login_email(Some(&locale), &email, &device, revoke_url).unwrap();
+        assert_eq!(message.subject, "Nouvelle connexion à votre compte");
+        assert!(message.text_body.contains("Navigateur : Firefox"));
+    }
+
+    #[test]
+    fn test_email_change_emails() {
+        let policy = crate::domain::email_change::EmailChangePolicy {
+            confirm_ttl: std::time::Duration::from_secs(60 * 60),
+            revert_ttl: std::time::Duration::from_secs(24 * 60 * 60),
+        };
+        let (change, _) = EmailChange::new(
+            crate::domain::user::UserId::new(),
+            Email::parse("old@bar.com").unwrap(),
+            Email::parse("new@bar.com").unwrap(),
+            &policy,
+        );
+        let url = "http://localhost:3000/x?token=abc";
+
+        let message = email_change_confirmation_email(None, &change, url).unwrap();
+        assert_eq!(message.subject, "Confirm your new email address");
+        assert!(message.text_body.contains("to new@bar.com"));
+        assert!(message.text_body.contains(url));
+        assert!(message
+            .text_body
+            .contains(&format_expiry(&change.confirm_expires_at)));
+
+        let message = email_change_requested_email(None, &change, url).unwrap();
+        assert_eq!(message.subject, "Change of your email address");
+        assert!(message.text_body.starts_with("Hello old@bar.com,"));
+        assert!(message.text_body.contains("to new@bar.com"));
+        assert!(message
+            .text_body
+            .contains(&format_expiry(&change.revert_expires_at)));
+
+        let locale = Locale::parse("fr").unwrap();
+        let message = email_change_requested_email(Some(&locale), &change, url).unwrap();
+        assert_eq!(message.subject, "Changement de votre adresse email");
+        assert!(message.html_body.contains("<html lang=\"fr\">"));
+    }
+}
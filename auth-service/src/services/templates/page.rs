@@ -0,0 +1,67 @@
+use askama::Template;
+use color_eyre::eyre::{Context, Result};
+
+#[derive(Template)]
+#[template(path = "pages/confirm_link.html")]
+struct ConfirmLinkPage<'a> {
+    title: &'a str,
+    message: &'a str,
+    action: &'a str,
+    token: &'a str,
+    button: &'a str,
+}
+
+/// The links sent by email whose target changes the account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailLink {
+    RevokeSession,
+    ConfirmEmailChange,
+    RevertEmailChange,
+}
+
+/// The page a link of an email opens, which only acts once its button is pressed.
+/// Following the link must not change anything, as mail scanners and link previews fetch it.
+pub fn confirm_link_page(link: EmailLink, token: &str) -> Result<String> {
+    // Relative to the link, which may be served under a path prefix of `PUBLIC_URL`
+    let (title, message, action, button) = match link {
+        EmailLink::RevokeSession => (
+            "Log this device out",
+            "The device that just logged in to your account will be logged out. Remember to change your password.",
+            "revoke-session",
+            "Log the device out",
+        ),
+        EmailLink::ConfirmEmailChange => (
+            "Confirm your new email",
+            "This address will replace the current email of your account.",
+            "confirm-email-change",
+            "Confirm the change",
+        ),
+        EmailLink::RevertEmailChange => (
+            "Undo the email change",
+            "Your account will get its previous email back, and all its devices will be logged out. Remember to change your password.",
+            "revert-email-change",
+            "Undo the change",
+        ),
+    };
+    ConfirmLinkPage {
+        title,
+        message,
+        action,
+        token,
+        button,
+    }
+    .render()
+    .wrap_err("Failed to render the confirmation page")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirm_link_page_posts_the_escaped_token() {
+        let page = confirm_link_page(EmailLink::RevokeSession, "abc\"><script>").unwrap();
+        assert!(page.contains(r#"<form method="post" action="revoke-session">"#));
+        assert!(!page.contains("<script>"));
+    }
+}
@@ -0,0 +1,77 @@
+use askama::Template;
+use color_eyre::eyre::{Context, Result};
+
+use super::Language;
+use crate::domain::{data_stores::TwoFACode, locale::Locale};
+
+#[derive(Template)]
+#[template(path = "sms/en/two_fa_login.txt")]
+struct TwoFALoginEn<'a> {
+    code: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "sms/fr/two_fa_login.txt")]
+struct TwoFALoginFr<'a> {
+    code: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "sms/en/phone_verification.txt")]
+struct PhoneVerificationEn<'a> {
+    code: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "sms/fr/phone_verification.txt")]
+struct PhoneVerificationFr<'a> {
+    code: &'a str,
+}
+
+/// The text message sending the 2FA code of a login attempt.
+pub fn two_fa_login_sms(locale: Option<&Locale>, code: &TwoFACode) -> Result<String> {
+    let code = code.as_ref();
+    match Language::negotiate(locale) {
+        Language::English => render(TwoFALoginEn { code }),
+        Language::French => render(TwoFALoginFr { code }),
+    }
+}
+
+/// The text message sending the code proving the user receives messages on a phone number.
+pub fn phone_verification_sms(locale: Option<&Locale>, code: &TwoFACode) -> Result<String> {
+    let code = code.as_ref();
+    match Language::negotiate(locale) {
+        Language::English => render(PhoneVerificationEn { code }),
+        Language::French => render(PhoneVerificationFr { code }),
+    }
+}
+
+fn render(template: impl Template) -> Result<String> {
+    template.render().wrap_err("Failed to render text message")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_messages() {
+        let code = TwoFACode::parse("123456".to_owned()).unwrap();
+        let french = Locale::parse("fr").unwrap();
+
+        assert_eq!(
+            two_fa_login_sms(None, &code).unwrap().trim_end(),
+            "Your 2FA code is: 123456"
+        );
+        assert_eq!(
+            two_fa_login_sms(Some(&french), &code).unwrap().trim_end(),
+            "Votre code de vérification est : 123456"
+        );
+        assert!(phone_verification_sms(None, &code)
+            .unwrap()
+            .contains("verify this phone number is: 123456"));
+        assert!(phone_verification_sms(Some(&french), &code)
+            .unwrap()
+            .contains("vérifier ce numéro de téléphone est : 123456"));
+    }
+}
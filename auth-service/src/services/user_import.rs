@@ -0,0 +1,201 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::domain::data_stores::UserStoreError;
+use crate::{Email, PostgresUserStore};
+
+/// Layout of a file of users exported from another system, with an `email`, `hash` and optional
+/// `requires_2fa` field per user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// With a header row, hashes containing commas must be quoted
+    Csv,
+    /// A JSON object per line
+    Jsonl,
+}
+
+impl ImportFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(ImportFormat::Csv),
+            "jsonl" | "ndjson" => Some(ImportFormat::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct ImportedUser {
+    email: String,
+    hash: String,
+    #[serde(default)]
+    requires_2fa: bool,
+}
+
+/// Outcome of `import_users`, a user is only rejected for its own record.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    /// Users whose email is already taken, left untouched so that an import can be resumed
+    pub existing: usize,
+    pub rejected: Vec<RejectedRecord>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RejectedRecord {
+    pub line: u64,
+    pub reason: String,
+}
+
+/// Adds the users of `input` who do not exist yet, each with the password hash of their former system.
+pub async fn import_users(
+    store: &PostgresUserStore,
+    format: ImportFormat,
+    input: &str,
+) -> ImportReport {
+    let mut report = ImportReport::default();
+    for (line, record) in parse_records(format, input) {
+        let result = match record {
+            Ok(user) => import_user(store, user).await,
+            Err(reason) => Err(reason),
+        };
+        match result {
+            Ok(true) => report.imported += 1,
+            Ok(false) => report.existing += 1,
+            Err(reason) => report.rejected.push(RejectedRecord { line, reason }),
+        }
+    }
+    report
+}
+
+// Whether the user was added, rather than already existing
+async fn import_user(store: &PostgresUserStore, user: ImportedUser) -> Result<bool, String> {
+    let email = Email::parse(user.email.trim()).map_err(|e| e.to_string())?;
+    match store
+        .import_user(&email, user.hash.trim(), user.requires_2fa)
+        .await
+    {
+        Ok(()) => Ok(true),
+        Err(UserStoreError::UserAlreadyExists) => Ok(false),
+        Err(e) => Err(format!("{:#}", color_eyre::eyre::Report::from(e))),
+    }
+}
+
+// Records of `input` along with their line number
+fn parse_records(format: ImportFormat, input: &str) -> Vec<(u64, Result<ImportedUser, String>)> {
+    match format {
+        ImportFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(input.as_bytes());
+            let headers = match reader.headers() {
+                Ok(headers) => headers.clone(),
+                Err(e) => return vec![(1, Err(e.to_string()))],
+            };
+            reader
+                .records()
+                .map(|record| match record {
+                    Ok(record) => {
+                        let line = record.position().map_or(0, |position| position.line());
+                        let user = record
+                            .deserialize::<ImportedUser>(Some(&headers))
+                            .map_err(|e| e.to_string());
+                        (line, user)
+                    }
+                    Err(e) => {
+                        let line = e.position().map_or(0, |position| position.line());
+                        (line, Err(e.to_string()))
+                    }
+                })
+                .collect()
+        }
+        ImportFormat::Jsonl => input
+            .lines()
+            .zip(1..)
+            .filter(|(line, _)| !line.trim().is_empty())
+            .map(|(line, number)| {
+                let user = serde_json::from_str::<ImportedUser>(line).map_err(|e| e.to_string());
+                (number, user)
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(email: &str, hash: &str, requires_2fa: bool) -> ImportedUser {
+        ImportedUser {
+            email: email.to_owned(),
+            hash: hash.to_owned(),
+            requires_2fa,
+        }
+    }
+
+    #[test]
+    fn test_parse_csv_records() {
+        let input = "email,hash,requires_2fa\n\
+            foo@bar.com,$2b$04$abc,true\n\
+            baz@bar.com,\"$pbkdf2-sha256$i=1000,l=32$c2FsdA$aGFzaA\",false\n\
+            broken@bar.com,$2b$04$abc,maybe\n";
+        let records = parse_records(ImportFormat::Csv, input);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0], (2, Ok(user("foo@bar.com", "$2b$04$abc", true))));
+        assert_eq!(
+            records[1],
+            (
+                3,
+                Ok(user(
+                    "baz@bar.com",
+                    "$pbkdf2-sha256$i=1000,l=32$c2FsdA$aGFzaA",
+                    false
+                ))
+            )
+        );
+        assert_eq!(records[2].0, 4);
+        assert!(records[2].1.is_err());
+
+        // The 2FA column is optional
+        let records = parse_records(ImportFormat::Csv, "hash,email\n$2b$04$abc,foo@bar.com\n");
+        assert_eq!(
+            records,
+            vec![(2, Ok(user("foo@bar.com", "$2b$04$abc", false)))]
+        );
+    }
+
+    #[test]
+    fn test_parse_jsonl_records() {
+        let input = "{\"email\":\"foo@bar.com\",\"hash\":\"$2b$04$abc\",\"requires_2fa\":true}\n\
+            \n\
+            {\"email\":\"baz@bar.com\",\"hash\":\"$2b$04$abc\"}\n\
+            not json\n";
+        let records = parse_records(ImportFormat::Jsonl, input);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0], (1, Ok(user("foo@bar.com", "$2b$04$abc", true))));
+        assert_eq!(
+            records[1],
+            (3, Ok(user("baz@bar.com", "$2b$04$abc", false)))
+        );
+        assert_eq!(records[2].0, 4);
+        assert!(records[2].1.is_err());
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        for (path, expected) in [
+            ("users.csv", Some(ImportFormat::Csv)),
+            ("export/users.jsonl", Some(ImportFormat::Jsonl)),
+            ("users.json", None),
+            ("users", None),
+        ] {
+            assert_eq!(
+                ImportFormat::from_path(Path::new(path)),
+                expected,
+                "{}",
+                path
+            );
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use color_eyre::eyre::Result;
+use reqwest::{redirect::Policy, Client};
+
+use crate::app_state::WebhookStoreType;
+use crate::domain::webhook::{
+    sign_webhook_payload, DeliveryAttempt, RetryPolicy, WebhookDelivery, WebhookSubscription,
+    WEBHOOK_ID_HEADER, WEBHOOK_SIGNATURE_HEADER, WEBHOOK_TIMESTAMP_HEADER,
+};
+
+/// Sends the deliveries queued in the webhook outbox, retrying failed ones with exponential back-off.
+pub struct WebhookDispatcher {
+    webhook_store: WebhookStoreType,
+    http_client: Client,
+    retry_policy: RetryPolicy,
+    poll_interval: Duration,
+    batch_size: i64,
+    /// How long a claimed delivery is hidden from other dispatchers, must exceed the request timeout
+    lease: Duration,
+}
+
+impl WebhookDispatcher {
+    /// Requests time out after `timeout`.
+    pub fn new(
+        webhook_store: WebhookStoreType,
+        timeout: Duration,
+        retry_policy: RetryPolicy,
+        poll_interval: Duration,
+        batch_size: i64,
+        lease: Duration,
+    ) -> Result<Self> {
+        // The URLs are supplied by admins but the responses by the subscribers,
+        // who must not be able to redirect the signed requests to internal hosts
+        let http_client = Client::builder()
+            .timeout(timeout)
+            .redirect(Policy::none())
+            .build()?;
+        Ok(Self {
+            webhook_store,
+            http_client,
+            retry_policy,
+            poll_interval,
+            batch_size,
+            lease,
+        })
+    }
+
+    /// Polls the outbox forever, meant to be spawned next to the server.
+    pub async fn run(self) {
+        loop {
+            match self.dispatch_due().await {
+                // A full batch probably means more deliveries are due
+                Ok(dispatched) if dispatched as i64 >= self.batch_size => continue,
+                Ok(_) => {}
+                Err(e) => tracing::error!(error = ?e, "Failed to dispatch webhook deliveries"),
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Sends one batch of due deliveries, returning how many were attempted.
+    #[tracing::instrument(name = "Dispatching webhook deliveries", skip_all)]
+    pub async fn dispatch_due(&self) -> Result<usize> {
+        let claimed = self
+            .webhook_store
+            .write()
+            .await
+            .claim_due_deliveries(self.batch_size, self.lease)
+            .await?;
+
+        let dispatched = claimed.len();
+        for (mut delivery, subscription) in claimed {
+            let attempt = self.deliver(&delivery, &subscription).await;
+            if let Some(error) = &attempt.error {
+                tracing::warn!(
+                    delivery_id = %delivery.id,
+                    attempts = delivery.attempts + 1,
+                    error,
+                    "Webhook delivery failed"
+                );
+            }
+            delivery.record(&attempt);
+            self.webhook_store
+                .write()
+                .await
+                .update_delivery(&delivery)
+                .await?;
+        }
+        Ok(dispatched)
+    }
+
+    /// Sends a delivery once, never fails: errors are reported in the returned attempt.
+    #[tracing::instrument(name = "Sending webhook", skip_all, fields(delivery_id = %delivery.id))]
+    async fn deliver(
+        &self,
+        delivery: &WebhookDelivery,
+        subscription: &WebhookSubscription,
+    ) -> DeliveryAttempt {
+        let attempted_at = Utc::now();
+        let (response_status, error) = match self.send(delivery, subscription).await {
+            Ok(status) if status.is_success() => (Some(status.as_u16() as i32), None),
+            Ok(status) if status.is_redirection() => (
+                Some(status.as_u16() as i32),
+                Some(format!("Redirects are not followed, got status {}", status)),
+            ),
+            Ok(status) => (
+                Some(status.as_u16() as i32),
+                Some(format!("Unexpected response status {}", status)),
+            ),
+            Err(e) => (None, Some(e.to_string())),
+        };
+
+        let next_attempt_at = match error {
+            Some(_) => self
+                .retry_policy
+                .delay_after(delivery.attempts + 1)
+                .and_then(|delay| chrono::Duration::from_std(delay).ok())
+                .map(|delay| attempted_at + delay),
+            None => None,
+        };
+        DeliveryAttempt {
+            attempted_at,
+            response_status,
+            error,
+            next_attempt_at,
+        }
+    }
+
+    async fn send(
+        &self,
+        delivery: &WebhookDelivery,
+        subscription: &WebhookSubscription,
+    ) -> Result<reqwest::StatusCode> {
+        let body = serde_json::to_string(&delivery.event)?;
+        let timestamp = Utc::now().timestamp();
+        let signature =
+            sign_webhook_payload(&subscription.secret, &delivery.event.id, timestamp, &body);
+
+        let response = self
+            .http_client
+            .post(&subscription.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(WEBHOOK_ID_HEADER, delivery.event.id.to_string())
+            .header(WEBHOOK_TIMESTAMP_HEADER, timestamp.to_string())
+            .header(WEBHOOK_SIGNATURE_HEADER, signature)
+            .body(body)
+            .send()
+            .await?;
+        Ok(response.status())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::sync::RwLock;
+    use wiremock::matchers::{header, header_exists, method, path};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    use super::*;
+    use crate::domain::data_stores::WebhookStore;
+    use crate::domain::webhook::{DeliveryStatus, WebhookEvent, WebhookEventType};
+    use crate::services::data_stores::hashmap_webhook_store::HashmapWebhookStore;
+
+    fn retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        }
+    }
+
+    async fn setup(
+        server: &MockServer,
+    ) -> (WebhookDispatcher, WebhookStoreType, WebhookSubscription) {
+        let mut store = HashmapWebhookStore::default();
+        let subscription = WebhookSubscription::new(
+            format!("{}/hook", server.uri()),
+            vec![WebhookEventType::UserCreated],
+        );
+        store.add_subscription(subscription.clone()).await.unwrap();
+        store.enqueue_event(WebhookEvent::new(
+            WebhookEventType::UserCreated,
+            serde_json::json!({ "email": "foo@bar.com" }),
+        ));
+
+        let store: WebhookStoreType = Arc::new(RwLock::new(store));
+        let dispatcher = WebhookDispatcher::new(
+            store.clone(),
+            Duration::from_millis(200),
+            retry_policy(),
+            Duration::from_millis(10),
+            10,
+            Duration::from_secs(1),
+        )
+        .unwrap();
+        (dispatcher, store, subscription)
+    }
+
+    struct ValidSignature(String);
+
+    impl wiremock::Match for ValidSignature {
+        fn matches(&self, request: &Request) -> bool {
+            let header = |name: &str| request.headers.get(name)?.to_str().ok();
+            let (Some(id), Some(timestamp), Some(signature)) = (
+                header(WEBHOOK_ID_HEADER),
+                header(WEBHOOK_TIMESTAMP_HEADER),
+                header(WEBHOOK_SIGNATURE_HEADER),
+            ) else {
+                return false;
+            };
+            let (Ok(id), Ok(timestamp), Ok(body)) = (
+                id.parse(),
+                timestamp.parse(),
+                std::str::from_utf8(&request.body),
+            ) else {
+                return false;
+            };
+            sign_webhook_payload(&self.0, &id, timestamp, body) == signature
+        }
+    }
+
+    #[tokio::test]
+    async fn dispatch_sends_signed_event() {
+        let server = MockServer::start().await;
+        let (dispatcher, store, subscription) = setup(&server).await;
+
+        Mock::given(path("/hook"))
+            .and(method("POST"))
+            .and(header("Content-Type", "application/json"))
+            .and(header_exists(WEBHOOK_ID_HEADER))
+            .and(ValidSignature(subscription.secret.clone()))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 1);
+
+        let deliveries = store
+            .read()
+            .await
+            .get_deliveries(&subscription.id)
+            .await
+            .unwrap();
+        assert_eq!(deliveries[0].status, DeliveryStatus::Delivered);
+        assert_eq!(deliveries[0].attempts, 1);
+        assert_eq!(deliveries[0].last_response_status, Some(204));
+
+        let body: WebhookEvent =
+            serde_json::from_slice(&server.received_requests().await.unwrap()[0].body).unwrap();
+        assert_eq!(body, deliveries[0].event);
+        // Delivered events are not sent again
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn dispatch_retries_until_max_attempts() {
+        let server = MockServer::start().await;
+        let (dispatcher, store, subscription) = setup(&server).await;
+
+        Mock::given(path("/hook"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        for attempts in 1..=3 {
+            assert_eq!(dispatcher.dispatch_due().await.unwrap(), 1);
+            let delivery = store
+                .read()
+                .await
+                .get_deliveries(&subscription.id)
+                .await
+                .unwrap()
+                .remove(0);
+            assert_eq!(delivery.attempts, attempts);
+            assert_eq!(delivery.last_response_status, Some(500));
+        }
+
+        let delivery = store
+            .read()
+            .await
+            .get_deliveries(&subscription.id)
+            .await
+            .unwrap()
+            .remove(0);
+        assert_eq!(delivery.status, DeliveryStatus::Failed);
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn dispatch_backs_off_after_failure() {
+        let server = MockServer::start().await;
+        let (mut dispatcher, store, subscription) = setup(&server).await;
+        dispatcher.retry_policy.base_delay = Duration::from_secs(60);
+        dispatcher.retry_policy.max_delay = Duration::from_secs(60);
+
+        Mock::given(path("/hook"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 1);
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 0);
+
+        let delivery = store
+            .read()
+            .await
+            .get_deliveries(&subscription.id)
+            .await
+            .unwrap()
+            .remove(0);
+        assert_eq!(delivery.status, DeliveryStatus::Pending);
+        assert!(delivery.next_attempt_at > Utc::now() + chrono::Duration::seconds(50));
+    }
+
+    #[tokio::test]
+    async fn dispatch_does_not_follow_redirects() {
+        let server = MockServer::start().await;
+        let (dispatcher, store, subscription) = setup(&server).await;
+
+        Mock::given(path("/hook"))
+            .respond_with(
+                ResponseTemplate::new(307).insert_header("Location", "/internal".to_owned()),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/internal"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        assert_eq!(dispatcher.dispatch_due().await.unwrap(), 1);
+
+        let delivery = store
+            .read()
+            .await
+            .get_deliveries(&subscription.id)
+            .await
+            .unwrap()
+            .remove(0);
+        assert_eq!(delivery.status, DeliveryStatus::Pending);
+        assert_eq!(delivery.last_response_status, Some(307));
+        assert!(delivery.last_error.is_some());
+    }
+}
@@ -1,3 +1,4 @@
 pub mod auth;
 pub mod constants;
+pub mod request_context;
 pub mod tracing;
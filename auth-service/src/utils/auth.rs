@@ -1,18 +1,46 @@
-use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts},
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
 use chrono::Utc;
 use color_eyre::eyre::{eyre, Context, Result};
+use hmac::{Hmac, Mac};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
+use secrecy::ExposeSecret;
 use serde::{Deserialize, Serialize};
+use sha2::Sha256;
 use thiserror::Error;
 
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::domain::data_stores::UserStoreError;
 use crate::domain::email::Email;
+use crate::domain::error::AuthAPIError;
+use crate::domain::trusted_device::{TrustedDevice, TrustedDeviceToken};
+use crate::domain::user::{AccountStatus, User, UserId};
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+use super::constants::{
+    ADMIN_TOKEN, JWT_COOKIE_NAME, JWT_SECRET, TRUSTED_DEVICE_COOKIE_NAME, TRUSTED_DEVICE_SECRET,
+};
 
-// Create cookie with a new JWT auth token
+// Create cookie with a new JWT auth token, valid until the session version of the user changes
 #[tracing::instrument(name = "generate_auth_cookie", skip_all)]
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>> {
-    let token = generate_auth_token(email)?;
+pub fn generate_auth_cookie(user_id: &UserId, session_version: i32) -> Result<Cookie<'static>> {
+    let token = generate_auth_token(user_id, session_version, TokenScope::Session)?;
+
+    Ok(create_auth_cookie(token))
+}
+
+// Create cookie with a JWT only allowing the user to change their expired password
+#[tracing::instrument(name = "generate_password_change_cookie", skip_all)]
+pub fn generate_password_change_cookie(
+    user_id: &UserId,
+    session_version: i32,
+) -> Result<Cookie<'static>> {
+    let token = generate_auth_token(user_id, session_version, TokenScope::PasswordChange)?;
 
     Ok(create_auth_cookie(token))
 }
@@ -29,6 +57,34 @@ fn create_auth_cookie(token: String) -> Cookie<'static> {
     cookie
 }
 
+// Create the cookie remembering a trusted device, it expires along with the device
+#[tracing::instrument(name = "create_trusted_device_cookie", skip_all)]
+pub fn create_trusted_device_cookie(device: &TrustedDevice) -> Cookie<'static> {
+    let token = TrustedDeviceToken::new(
+        TRUSTED_DEVICE_SECRET.expose_secret(),
+        &device.user_id,
+        &device.id,
+    );
+    let max_age = (device.expires_at - device.created_at).num_seconds();
+    Cookie::build((TRUSTED_DEVICE_COOKIE_NAME, token.as_ref().to_owned()))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(max_age))
+        .build()
+}
+
+// Get the id of the device remembered by the cookie, if it was issued to `user_id`.
+// The device may have been revoked since, see `TrustedDeviceStore`.
+pub fn get_trusted_device_id(jar: &CookieJar, user_id: &UserId) -> Option<Uuid> {
+    let cookie = jar.get(TRUSTED_DEVICE_COOKIE_NAME)?;
+    TrustedDeviceToken::verify(
+        cookie.value(),
+        TRUSTED_DEVICE_SECRET.expose_secret(),
+        user_id,
+    )
+}
+
 #[derive(Debug, Error)]
 pub enum GenerateTokenError {
     #[error("Error generating token")]
@@ -42,7 +98,11 @@ pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
 
 // Create JWT auth token
 #[tracing::instrument(name = "generate_auth_token", skip_all)]
-fn generate_auth_token(email: &Email) -> Result<String> {
+fn generate_auth_token(
+    user_id: &UserId,
+    session_version: i32,
+    scope: TokenScope,
+) -> Result<String> {
     let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS).ok_or(
         GenerateTokenError::UnexpectedError(eyre!("failed to create 10mins delta")),
     )?;
@@ -60,9 +120,14 @@ fn generate_auth_token(email: &Email) -> Result<String> {
         .try_into()
         .map_err(|_| GenerateTokenError::UnexpectedError(eyre!("failed cast from i64 to usize")))?;
 
-    let sub = email.as_ref().to_owned();
+    let sub = user_id.to_string();
 
-    let claims = Claims { sub, exp };
+    let claims = Claims {
+        sub,
+        exp,
+        scope,
+        session_version,
+    };
 
     create_token(&claims)
 }
@@ -91,18 +156,210 @@ fn create_token(claims: &Claims) -> Result<String> {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
+    /// `UserId` of the user, or their email in tokens issued before user ids existed
     pub sub: String,
     pub exp: usize,
+    /// Tokens issued before scopes existed are sessions
+    #[serde(default)]
+    pub scope: TokenScope,
+    /// `session_version` of the user when the token was issued.
+    /// Tokens issued before session versions existed have the initial one
+    #[serde(default)]
+    pub session_version: i32,
+}
+
+/// What a JWT allows its holder to do.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenScope {
+    /// A regular session
+    #[default]
+    Session,
+    /// Only changing the password, issued at the login of a user whose password expired
+    PasswordChange,
+}
+
+/// Extractor guarding the admin routes.
+/// The request must carry `Authorization: Bearer <ADMIN_TOKEN>`.
+#[derive(Debug)]
+pub struct AdminAuth;
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AdminAuth
+where
+    S: Send + Sync,
+{
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(AuthAPIError::MissingToken)?;
+
+        let admin_token = ADMIN_TOKEN.expose_secret();
+        if admin_token.is_empty() || !is_admin_token(admin_token, token) {
+            return Err(AuthAPIError::InvalidToken);
+        }
+        Ok(AdminAuth)
+    }
+}
+
+/// Compares the digests of the tokens in constant time, so that the time taken
+/// tells nothing of how much of `token` matches.
+fn is_admin_token(admin_token: &str, token: &str) -> bool {
+    let mac = |value: &str| {
+        let mut mac = Hmac::<Sha256>::new_from_slice(admin_token.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(value.as_bytes());
+        mac
+    };
+    mac(token)
+        .verify_slice(&mac(admin_token).finalize().into_bytes())
+        .is_ok()
+}
+
+/// Extractor guarding the routes of a logged in user.
+/// The request must carry a valid session JWT cookie, which has not been banned by a logout.
+#[derive(Debug)]
+pub struct AuthenticatedUser {
+    pub user_id: UserId,
+    pub email: Email,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthenticatedUser {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let (user, _, scope) = authenticate(parts, state).await?;
+        if scope != TokenScope::Session {
+            return Err(AuthAPIError::InvalidToken);
+        }
+        Ok(AuthenticatedUser {
+            user_id: user.id,
+            email: user.email,
+        })
+    }
+}
+
+/// Extractor guarding the password change.
+/// Unlike `AuthenticatedUser`, it also accepts the token of a user whose password expired.
+#[derive(Debug)]
+pub struct PasswordChangeUser {
+    pub user_id: UserId,
+    pub email: Email,
+    pub session_version: i32,
+    /// JWT of the request, replaced by a session once the password is changed
+    pub token: String,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for PasswordChangeUser {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let (user, token, _) = authenticate(parts, state).await?;
+        Ok(PasswordChangeUser {
+            user_id: user.id,
+            email: user.email,
+            session_version: user.session_version,
+            token,
+        })
+    }
+}
+
+// Get the user of the JWT cookie of the request, if valid and not banned
+async fn authenticate(
+    parts: &Parts,
+    state: &AppState,
+) -> Result<(User, String, TokenScope), AuthAPIError> {
+    let jar = CookieJar::from_headers(&parts.headers);
+    let token = jar
+        .get(JWT_COOKIE_NAME)
+        .ok_or(AuthAPIError::MissingToken)?
+        .value();
+    let claims = validate_token(token)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+    let is_token_banned = state
+        .banned_token_store
+        .read()
+        .await
+        .is_token_banned(token)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    if is_token_banned {
+        return Err(AuthAPIError::InvalidToken);
+    }
+    let user = get_token_user(state, &claims).await?;
+    check_session(&user, &claims)?;
+    Ok((user, token.to_owned(), claims.scope))
+}
+
+/// Gets the user a token was issued to, the token being invalid if they no longer exist.
+pub async fn get_token_user(state: &AppState, claims: &Claims) -> Result<User, AuthAPIError> {
+    let user_store = state.user_store.read().await;
+    let user = match UserId::parse(&claims.sub) {
+        Ok(user_id) => user_store.get_user(&user_id).await,
+        Err(_) => {
+            let email = Email::parse(&claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+            user_store.get_user_by_email(&email).await
+        }
+    };
+    user.map_err(|e| match e {
+        UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    })
+}
+
+/// Checks that a token of `user` is still valid: their account must allow them to log in,
+/// and their sessions must not have been revoked, e.g. by a status change, since the token was issued.
+pub fn check_session(user: &User, claims: &Claims) -> Result<(), AuthAPIError> {
+    check_account_status(user)?;
+    if claims.session_version != user.session_version {
+        return Err(AuthAPIError::InvalidToken);
+    }
+    Ok(())
+}
+
+/// Fails with the error of the status of the account unless the user can log in.
+pub fn check_account_status(user: &User) -> Result<(), AuthAPIError> {
+    match user.status.at(Utc::now()) {
+        AccountStatus::Active => Ok(()),
+        AccountStatus::Disabled => Err(AuthAPIError::AccountDisabled),
+        AccountStatus::Suspended { until } => Err(AuthAPIError::AccountSuspended(until)),
+        AccountStatus::PendingVerification => Err(AuthAPIError::AccountPendingVerification),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_is_admin_token() {
+        assert!(is_admin_token("s3cret-admin-token", "s3cret-admin-token"));
+        for token in ["", "s3cret", "s3cret-admin-token ", "S3CRET-ADMIN-TOKEN"] {
+            assert!(
+                !is_admin_token("s3cret-admin-token", token),
+                "Failed for {:?}",
+                token
+            );
+        }
+    }
+
     #[tokio::test]
     async fn test_generate_auth_cookie() {
-        let email = Email::parse("test@example.com").unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let cookie = generate_auth_cookie(&UserId::new(), 0).unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -121,19 +378,35 @@ mod tests {
         assert_eq!(cookie.same_site(), Some(SameSite::Lax));
     }
 
+    #[tokio::test]
+    async fn test_trusted_device_cookie_is_only_valid_for_its_user() {
+        let user_id = UserId::new();
+        let device = TrustedDevice::new(user_id, None, std::time::Duration::from_secs(60));
+        let cookie = create_trusted_device_cookie(&device);
+        assert_eq!(cookie.name(), TRUSTED_DEVICE_COOKIE_NAME);
+        assert_eq!(cookie.max_age(), Some(time::Duration::seconds(60)));
+        assert_eq!(cookie.http_only(), Some(true));
+
+        let jar = CookieJar::new().add(cookie);
+        assert_eq!(get_trusted_device_id(&jar, &user_id), Some(device.id));
+        assert_eq!(get_trusted_device_id(&jar, &UserId::new()), None);
+        assert_eq!(get_trusted_device_id(&CookieJar::new(), &user_id), None);
+    }
+
     #[tokio::test]
     async fn test_generate_auth_token() {
-        let email = Email::parse("test@example.com").unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let result = generate_auth_token(&UserId::new(), 0, TokenScope::Session).unwrap();
         assert_eq!(result.split('.').count(), 3);
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
-        let email = Email::parse("test@example.com").unwrap();
-        let token = generate_auth_token(&email).unwrap();
+        let user_id = UserId::new();
+        let token = generate_auth_token(&user_id, 3, TokenScope::Session).unwrap();
         let result = validate_token(&token).await.unwrap();
-        assert_eq!(result.sub, "test@example.com");
+        assert_eq!(result.sub, user_id.to_string());
+        assert_eq!(result.scope, TokenScope::Session);
+        assert_eq!(result.session_version, 3);
 
         let exp = Utc::now()
             .checked_add_signed(chrono::Duration::try_minutes(9).expect("valid duration"))
@@ -143,6 +416,24 @@ mod tests {
         assert!(result.exp > exp as usize);
     }
 
+    #[tokio::test]
+    async fn test_legacy_token_is_a_session_of_the_initial_version() {
+        let claims = serde_json::json!({ "sub": "test@example.com", "exp": usize::MAX });
+        let token = encode(
+            &jsonwebtoken::Header::default(),
+            &claims,
+            &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+        )
+        .unwrap();
+        let result = validate_token(&token).await.unwrap();
+        assert_eq!(result.scope, TokenScope::Session);
+        assert_eq!(result.session_version, 0);
+
+        let token = generate_auth_token(&UserId::new(), 0, TokenScope::PasswordChange).unwrap();
+        let result = validate_token(&token).await.unwrap();
+        assert_eq!(result.scope, TokenScope::PasswordChange);
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
         let token = "invalid_token".to_owned();
@@ -0,0 +1,47 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header::USER_AGENT, request::Parts},
+};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Information about the client that issued a request, used to enrich audit events.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestContext {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RequestContext
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // The peer address is only available when the server is started with connect info
+        let ip_address = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string());
+        let header = |name| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+
+        Ok(RequestContext {
+            ip_address,
+            user_agent: header(USER_AGENT.as_str()),
+            request_id: header(REQUEST_ID_HEADER),
+        })
+    }
+}
@@ -4,6 +4,8 @@ use tracing_error::ErrorLayer;
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::EnvFilter;
 
+use super::request_context::REQUEST_ID_HEADER;
+
 pub fn init_tracing() -> color_eyre::eyre::Result<()> {
     // Create a layer that logs to stdout
     let fmt_layer = tracing_subscriber::fmt::layer().compact();
@@ -22,7 +24,13 @@ pub fn init_tracing() -> color_eyre::eyre::Result<()> {
 }
 
 pub fn make_span_with_request_id(request: &Request<Body>) -> Span {
-    let request_id = uuid::Uuid::new_v4();
+    // The id is set by `SetRequestIdLayer`, fall back to a fresh one if it is missing
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
     tracing::span!(
         tracing::Level::INFO,
         "[REQUEST]",
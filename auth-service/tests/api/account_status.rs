@@ -0,0 +1,142 @@
+use crate::helpers::{app_signup_and_login, TestApp};
+use auth_service::routes::MeResponse;
+use chrono::{DateTime, Duration, Utc};
+
+/// The id of the logged in user
+async fn user_id(app: &TestApp) -> String {
+    let me: MeResponse = app.get_me().await.json().await.unwrap();
+    me.id.to_string()
+}
+
+async fn login(app: &TestApp, email: &str, password: &str) -> reqwest::Response {
+    app.post_login(&serde_json::json!({ "email": email, "password": password }))
+        .await
+}
+
+#[tokio::test]
+async fn should_refuse_login_according_to_the_status() {
+    let (mut app, email, password, _, _) = app_signup_and_login(false).await;
+    let id = user_id(&app).await;
+
+    let test_cases = [
+        (serde_json::json!({ "status": "disabled" }), 403),
+        (serde_json::json!({ "status": "pending_verification" }), 403),
+        (serde_json::json!({ "status": "active" }), 200),
+    ];
+    for (status, expected_status) in test_cases {
+        let response = app.put_user_status(&id, &status).await;
+        assert_eq!(response.status().as_u16(), 204);
+        let response = login(&app, &email, &password).await;
+        assert_eq!(
+            response.status().as_u16(),
+            expected_status,
+            "Failed for {}",
+            status
+        );
+    }
+    // The status is only disclosed to those who know the password
+    app.put_user_status(&id, &serde_json::json!({ "status": "disabled" }))
+        .await;
+    let response = login(&app, &email, "wrong-password").await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_423_with_the_end_of_the_suspension() {
+    let (mut app, email, password, _, _) = app_signup_and_login(false).await;
+    let id = user_id(&app).await;
+    let until = Utc::now() + Duration::hours(1);
+
+    let response = app
+        .put_user_status(
+            &id,
+            &serde_json::json!({ "status": "suspended", "suspendedUntil": until }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = login(&app, &email, &password).await;
+    assert_eq!(response.status().as_u16(), 423);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let suspended_until: DateTime<Utc> =
+        serde_json::from_value(body["suspendedUntil"].clone()).unwrap();
+    // Stored to the microsecond
+    assert_eq!(suspended_until.timestamp_micros(), until.timestamp_micros());
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_revoke_sessions_on_status_change() {
+    let (mut app, email, password, jwt, _) = app_signup_and_login(false).await;
+    let id = user_id(&app).await;
+
+    let response = app
+        .put_user_status(&id, &serde_json::json!({ "status": "disabled" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": jwt }))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    // Enabling the account again does not bring the old sessions back
+    let response = app
+        .put_user_status(&id, &serde_json::json!({ "status": "active" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": jwt }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = login(&app, &email, &password).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_reject_invalid_status_changes() {
+    let (mut app, _, _, _, _) = app_signup_and_login(false).await;
+    let id = user_id(&app).await;
+    let past = Utc::now() - Duration::hours(1);
+
+    let test_cases = [
+        (id.as_str(), serde_json::json!({ "status": "deleted" }), 400),
+        (
+            id.as_str(),
+            serde_json::json!({ "status": "suspended" }),
+            400,
+        ),
+        (
+            id.as_str(),
+            serde_json::json!({ "status": "suspended", "suspendedUntil": past }),
+            400,
+        ),
+        (
+            id.as_str(),
+            serde_json::json!({ "status": "disabled", "suspendedUntil": Utc::now() }),
+            400,
+        ),
+        (
+            "not-an-id",
+            serde_json::json!({ "status": "disabled" }),
+            404,
+        ),
+        (
+            "8e5e4ab4-7c25-4f8b-9d35-5a6d5e2cf1e0",
+            serde_json::json!({ "status": "disabled" }),
+            404,
+        ),
+    ];
+    for (id, body, expected_status) in test_cases {
+        let response = app.put_user_status(id, &body).await;
+        assert_eq!(
+            response.status().as_u16(),
+            expected_status,
+            "Failed for {} {}",
+            id,
+            body
+        );
+    }
+    app.cleanup().await;
+}
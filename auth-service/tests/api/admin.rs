@@ -0,0 +1,153 @@
+use crate::helpers::{app_signup_and_login, get_random_email, TestApp};
+use auth_service::audit::{AuditEvent, AuditEventType, AuditOutcome};
+
+#[tokio::test]
+async fn should_record_signup_login_and_logout() {
+    let (mut app, email, _, _, _) = app_signup_and_login(false).await;
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.get_audit_events(&[("userEmail", &email)]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let events: Vec<AuditEvent> = response.json().await.unwrap();
+    let event_types: Vec<AuditEventType> = events.iter().map(|e| e.event_type).collect();
+    assert_eq!(
+        event_types,
+        vec![
+            AuditEventType::Signup,
+            AuditEventType::Login,
+            AuditEventType::Logout
+        ]
+    );
+    for event in events.iter() {
+        assert_eq!(event.actor, email);
+        assert_eq!(event.outcome, AuditOutcome::Success);
+        assert_eq!(event.ip_address.as_deref(), Some("127.0.0.1"));
+        assert!(event.request_id.is_some(), "missing request id");
+    }
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_record_failed_login() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+    let response = app
+        .http_client
+        .post(format!("{}/login", &app.address))
+        .header("User-Agent", "audit-test")
+        .json(&login_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    let request_id = response
+        .headers()
+        .get("x-request-id")
+        .expect("request id is not propagated")
+        .to_str()
+        .unwrap()
+        .to_owned();
+
+    let events: Vec<AuditEvent> = app
+        .get_audit_events(&[("userEmail", &email)])
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event.event_type, AuditEventType::Login);
+    assert_eq!(
+        event.outcome,
+        AuditOutcome::Failure("Authentication failure".to_owned())
+    );
+    assert_eq!(event.user_agent.as_deref(), Some("audit-test"));
+    assert_eq!(event.request_id.as_deref(), Some(request_id.as_str()));
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_filter_by_time_range() {
+    let (mut app, email, _, _, _) = app_signup_and_login(false).await;
+
+    let events: Vec<AuditEvent> = app
+        .get_audit_events(&[("userEmail", &email)])
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(events.len(), 2);
+    let login_time = events[1].created_at.to_rfc3339();
+
+    let events: Vec<AuditEvent> = app
+        .get_audit_events(&[("userEmail", &email), ("from", &login_time)])
+        .await
+        .json()
+        .await
+        .unwrap();
+    // The previous admin query is recorded against the user as well
+    let event_types: Vec<AuditEventType> = events.iter().map(|e| e.event_type).collect();
+    assert_eq!(
+        event_types,
+        vec![AuditEventType::Login, AuditEventType::AdminAuditQuery]
+    );
+
+    let events: Vec<AuditEvent> = app
+        .get_audit_events(&[("userEmail", &email), ("to", &login_time)])
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].event_type, AuditEventType::Signup);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_record_admin_queries() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    let response = app.get_audit_events(&[("userEmail", &email)]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let events: Vec<AuditEvent> = app
+        .get_audit_events(&[("userEmail", &email)])
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].event_type, AuditEventType::AdminAuditQuery);
+    assert_eq!(events[0].actor, "admin");
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_admin_token() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_audit_events_with_token(&[], "wrong_token").await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_admin_token_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .get(format!("{}/admin/audit-events", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+    app.cleanup().await;
+}
@@ -0,0 +1,66 @@
+use crate::helpers::app_signup_and_login;
+use auth_service::audit::{export_audit_chain, verify_audit_chain, AuditChainVerification};
+use auth_service::{AuditEventStore, PostgresAuditEventStore};
+use sqlx::Executor;
+
+#[tokio::test]
+async fn should_chain_recorded_events() {
+    let (mut app, _, _, _, _) = app_signup_and_login(false).await;
+    let store = PostgresAuditEventStore::new(app.db_pool().await);
+
+    let head = store.get_chain_head().await.unwrap().expect("empty chain");
+    assert_eq!(head.sequence, 2);
+    assert_eq!(
+        verify_audit_chain(&store).await.unwrap(),
+        AuditChainVerification::Valid(Some(head.clone()))
+    );
+
+    let export = export_audit_chain(&store).await.unwrap();
+    assert_eq!(export.head, Some(head));
+    assert_eq!(export.records.len(), 2);
+    assert_eq!(export.records[1].prev_hash, export.records[0].hash);
+    assert_eq!(
+        export.verify(),
+        AuditChainVerification::Valid(export.head.clone())
+    );
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_reject_modification_of_events() {
+    let (mut app, _, _, _, _) = app_signup_and_login(false).await;
+    let pool = app.db_pool().await;
+
+    let result = pool
+        .execute("UPDATE audit_events SET outcome = 'failure' WHERE sequence = 1")
+        .await;
+    assert!(result.is_err(), "audit events should be append-only");
+    let result = pool.execute("DELETE FROM audit_events").await;
+    assert!(result.is_err(), "audit events should be append-only");
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_report_first_broken_link() {
+    let (mut app, _, _, _, _) = app_signup_and_login(false).await;
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let pool = app.db_pool().await;
+
+    // Someone with direct access to the database bypasses the trigger
+    pool.execute(
+        r#"
+        ALTER TABLE audit_events DISABLE TRIGGER audit_events_append_only;
+        UPDATE audit_events SET ip_address = '10.0.0.1' WHERE sequence = 2;
+        "#,
+    )
+    .await
+    .unwrap();
+
+    let store = PostgresAuditEventStore::new(pool);
+    match verify_audit_chain(&store).await.unwrap() {
+        AuditChainVerification::Broken(broken_link) => assert_eq!(broken_link.sequence, 2),
+        verification => panic!("Expected a broken chain, got {:?}", verification),
+    }
+    app.cleanup().await;
+}
@@ -0,0 +1,195 @@
+use crate::helpers::{app_signup_and_login, get_random_email, TestApp};
+use regex::Regex;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+/// Replaces the expectation of `app_signup_and_login` on the emails
+async fn expect_emails(app: &TestApp, count: u64) {
+    app.email_server.reset().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(count)
+        .mount(&app.email_server)
+        .await;
+}
+
+/// The token of the `link` found in the email sent to `recipient`
+async fn link_token(app: &TestApp, recipient: &str, link: &str) -> String {
+    let requests = app.email_server.received_requests().await.unwrap();
+    let link = Regex::new(&format!(r"/{}\?token=([0-9a-f]+)", link)).unwrap();
+    requests
+        .iter()
+        .map(|request| request.body_json::<serde_json::Value>().unwrap())
+        .filter(|body| body["To"] == recipient)
+        .find_map(|body| {
+            link.captures(body["TextBody"].as_str().unwrap())
+                .map(|captures| captures[1].to_owned())
+        })
+        .expect("No link in the emails")
+}
+
+/// Requests the change and returns the tokens of the confirmation and revert links
+async fn request_change(
+    app: &TestApp,
+    email: &str,
+    new_email: &str,
+    password: &str,
+) -> (String, String) {
+    let response = app
+        .post_change_email(&serde_json::json!({ "newEmail": new_email, "password": password }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    (
+        link_token(app, new_email, "confirm-email-change").await,
+        link_token(app, email, "revert-email-change").await,
+    )
+}
+
+async fn login_status(app: &TestApp, email: &str, password: &str) -> u16 {
+    app.post_login(&serde_json::json!({ "email": email, "password": password }))
+        .await
+        .status()
+        .as_u16()
+}
+
+#[tokio::test]
+async fn should_change_the_email_once_confirmed_from_the_new_address() {
+    let (mut app, email, password, _, _) = app_signup_and_login(false).await;
+    expect_emails(&app, 2).await;
+    let new_email = get_random_email();
+
+    let (confirm_token, _) = request_change(&app, &email, &new_email, &password).await;
+    // Nothing changes until the confirmation
+    assert_eq!(login_status(&app, &new_email, &password).await, 401);
+    assert_eq!(login_status(&app, &email, &password).await, 200);
+    // Following the link only opens a page asking to confirm
+    let response = app.get_confirm_email_change(&confirm_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains(&confirm_token));
+    assert_eq!(login_status(&app, &new_email, &password).await, 401);
+
+    let response = app.post_confirm_email_change(&confirm_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(login_status(&app, &new_email, &password).await, 200);
+    assert_eq!(login_status(&app, &email, &password).await, 401);
+
+    // A link can only be used once
+    let response = app.post_confirm_email_change(&confirm_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_revert_a_confirmed_change_from_the_old_address() {
+    let (mut app, email, password, jwt, _) = app_signup_and_login(false).await;
+    expect_emails(&app, 2).await;
+    let new_email = get_random_email();
+
+    let (confirm_token, revert_token) = request_change(&app, &email, &new_email, &password).await;
+    let response = app.post_confirm_email_change(&confirm_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    // The old address cannot be taken while the change can be reverted
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "Another-passw0rd",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+    let response = app.get_revert_email_change(&revert_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(login_status(&app, &new_email, &password).await, 200);
+
+    let response = app.post_revert_email_change(&revert_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(login_status(&app, &email, &password).await, 200);
+    assert_eq!(login_status(&app, &new_email, &password).await, 401);
+    // The sessions opened before, possibly by whoever changed the email, are revoked
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": jwt }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_revert_email_change(&revert_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_cancel_a_pending_change_from_the_old_address() {
+    let (mut app, email, password, _, _) = app_signup_and_login(false).await;
+    expect_emails(&app, 2).await;
+    let new_email = get_random_email();
+
+    let (confirm_token, revert_token) = request_change(&app, &email, &new_email, &password).await;
+    let response = app.post_revert_email_change(&revert_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_confirm_email_change(&confirm_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(login_status(&app, &email, &password).await, 200);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_keep_trusting_the_devices_of_the_user() {
+    let (mut app, email, password, _, login_attempt_id) = app_signup_and_login(true).await;
+    let code = app.last_email_2fa_code().await;
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id.unwrap(),
+            "2FACode": code,
+            "rememberDevice": true,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    // No 2FA code is sent after the change
+    expect_emails(&app, 2).await;
+    let new_email = get_random_email();
+
+    let (confirm_token, _) = request_change(&app, &email, &new_email, &password).await;
+    let response = app.post_confirm_email_change(&confirm_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(login_status(&app, &new_email, &password).await, 200);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_reject_invalid_email_change_requests() {
+    let (mut app, email, password, _, _) = app_signup_and_login(false).await;
+    expect_emails(&app, 0).await;
+    let other_email = get_random_email();
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": other_email,
+            "password": "Another-passw0rd",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let test_cases = [
+        (get_random_email(), "wrong-password", 401),
+        (other_email, password.as_str(), 409),
+        (email.clone(), password.as_str(), 400),
+        ("not an email".to_owned(), password.as_str(), 400),
+        ("foo@mailinator.com".to_owned(), password.as_str(), 400),
+    ];
+    for (new_email, password, expected_status) in test_cases {
+        let response = app
+            .post_change_email(&serde_json::json!({ "newEmail": new_email, "password": password }))
+            .await;
+        assert_eq!(
+            response.status().as_u16(),
+            expected_status,
+            "Failed for {}",
+            new_email
+        );
+    }
+    app.cleanup().await;
+}
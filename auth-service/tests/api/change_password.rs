@@ -0,0 +1,202 @@
+use crate::helpers::{app_signup, app_signup_and_login, TestApp};
+use auth_service::error::ErrorResponse;
+use auth_service::routes::LoginResponse;
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+
+/// Makes the password of the user older than the max age of the test policy
+async fn expire_password(app: &TestApp, email: &str) {
+    sqlx::query(
+        "UPDATE users SET password_changed_at = NOW() - INTERVAL '91 days' WHERE email = $1",
+    )
+    .bind(email)
+    .execute(&app.db_pool().await)
+    .await
+    .expect("Failed to expire the password");
+}
+
+async fn violated_rules(response: reqwest::Response) -> Vec<String> {
+    response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse")
+        .violations
+        .into_iter()
+        .map(|violation| violation.rule)
+        .collect()
+}
+
+#[tokio::test]
+async fn should_change_password_and_open_a_new_session() {
+    let (mut app, email, password, jwt, _) = app_signup_and_login(false).await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": password,
+            "newPassword": "newpassword456",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let new_jwt = response
+        .cookies()
+        .find(|c| c.name() == JWT_COOKIE_NAME)
+        .expect("No new session cookie")
+        .value()
+        .to_owned();
+
+    // The token of the request is replaced
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": jwt }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": new_jwt }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": password }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "newpassword456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_reject_the_last_passwords() {
+    let (mut app, _, password, _, _) = app_signup_and_login(false).await;
+
+    // The test policy remembers the last 3 passwords, the current one included
+    let passwords = [password.as_str(), "password2", "password3", "password4"];
+    for pair in passwords.windows(2) {
+        let response = app
+            .post_change_password(&serde_json::json!({
+                "currentPassword": pair[0],
+                "newPassword": pair[1],
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 200, "failed for {}", pair[1]);
+    }
+
+    for reused in ["password2", "password3", "password4"] {
+        let response = app
+            .post_change_password(&serde_json::json!({
+                "currentPassword": "password4",
+                "newPassword": reused,
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 400, "failed for {}", reused);
+        assert_eq!(violated_rules(response).await, vec!["history"]);
+    }
+
+    // Old enough to be forgotten
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password4",
+            "newPassword": password,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_reject_a_wrong_current_password_or_a_weak_new_one() {
+    let (mut app, _, password, _, _) = app_signup_and_login(false).await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "wrongpassword123",
+            "newPassword": "newpassword456",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": password,
+            "newPassword": "short",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(violated_rules(response).await, vec!["min_length", "digit"]);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_not_logged_in() {
+    let (mut app, _, password) = app_signup(false).await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": password,
+            "newPassword": "newpassword456",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_require_a_password_change_at_login_once_expired() {
+    let (mut app, email, password) = app_signup(false).await;
+    expire_password(&app, &email).await;
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": password }))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+    let token = match response.json::<LoginResponse>().await.unwrap() {
+        LoginResponse::PasswordChangeRequired(response) => response.token,
+        response => panic!("Unexpected login response: {:?}", response),
+    };
+
+    // The token only allows the password change
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app.get_trusted_devices().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": password,
+            "newPassword": "newpassword456",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app.get_trusted_devices().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The new password is fresh
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "newpassword456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_only_require_the_password_change_after_2fa() {
+    let (mut app, email, _, _, login_attempt_id) = app_signup_and_login(true).await;
+    expire_password(&app, &email).await;
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": app.last_email_2fa_code().await,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(matches!(
+        response.json::<LoginResponse>().await.unwrap(),
+        LoginResponse::PasswordChangeRequired(_)
+    ));
+    let response = app.get_trusted_devices().await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.cleanup().await;
+}
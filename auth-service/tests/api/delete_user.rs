@@ -0,0 +1,44 @@
+use crate::helpers::app_signup_and_login;
+use auth_service::routes::MeResponse;
+
+#[tokio::test]
+async fn should_delete_the_user_and_revoke_their_sessions() {
+    let (mut app, email, password, jwt, _) = app_signup_and_login(false).await;
+    let me: MeResponse = app.get_me().await.json().await.unwrap();
+    let id = me.id.to_string();
+
+    let response = app.delete_user(&id).await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": jwt }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": password }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(app.delete_user(&id).await.status().as_u16(), 404);
+
+    // The address can be used by a new account
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": password,
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_404_if_unknown_user() {
+    let (mut app, _, _, _, _) = app_signup_and_login(false).await;
+
+    for id in ["not-an-id", "8e5e4ab4-7c25-4f8b-9d35-5a6d5e2cf1e0"] {
+        let response = app.delete_user(id).await;
+        assert_eq!(response.status().as_u16(), 404, "Failed for {}", id);
+    }
+    app.cleanup().await;
+}
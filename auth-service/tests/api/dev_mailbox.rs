@@ -0,0 +1,65 @@
+use crate::helpers::{extract_2fa_code, get_random_email, TestApp};
+use auth_service::email_clients::file_email_client::MailboxMessage;
+use auth_service::routes::TwoFactorLoginResponse;
+
+#[tokio::test]
+async fn should_list_the_2fa_code_sent_at_login() {
+    let mut app = TestApp::new_with_dev_mailbox().await;
+    let email = get_random_email();
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    let login_response: TwoFactorLoginResponse = response.json().await.unwrap();
+
+    let response = app.get_dev_mailbox("application/json").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let messages: Vec<MailboxMessage> = response.json().await.unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].recipient, email);
+    let code = extract_2fa_code(&messages[0].text_body);
+    assert!(messages[0].html_body.contains(&code));
+
+    let response = app.get_dev_mailbox("text/html").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .headers()
+        .get("content-type")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    assert!(response.text().await.unwrap().contains(&code));
+
+    // The listed code completes the login
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_response.login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_404_without_the_file_email_provider() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_dev_mailbox("application/json").await;
+    assert_eq!(response.status().as_u16(), 404);
+    app.cleanup().await;
+}
@@ -0,0 +1,157 @@
+use crate::helpers::{configure_postgresql_before, delete_database, get_random_email, TestApp};
+use auth_service::email_collisions::{
+    get_email_collisions, keep_colliding_account, validate_email_normalization,
+};
+
+#[tokio::test]
+async fn should_treat_emails_differing_by_case_as_the_same_user() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    let signup_body = |email: String| {
+        serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        })
+    };
+
+    let response = app.post_signup(&signup_body(email.to_uppercase())).await;
+    assert_eq!(response.status().as_u16(), 201);
+    let response = app.post_signup(&signup_body(format!(" {} ", email))).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_treat_punycode_and_unicode_domains_as_the_same_user() {
+    let mut app = TestApp::new().await;
+    let local_part = uuid::Uuid::new_v4();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": format!("{}@xn--bcher-kva.example", local_part),
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": format!("{}@B\u{fc}cher.example", local_part),
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": format!("{}@toto..example", local_part),
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_normalize_existing_emails_and_report_collisions() {
+    let (pool, db_name) = configure_postgresql_before(20250810090000).await;
+    for email in [
+        "Alice@Example.com",
+        "Bob@Example.com",
+        "bob@example.com",
+        "carol@example.com",
+    ] {
+        sqlx::query("INSERT INTO users (email, password_hash) VALUES ($1, 'hash')")
+            .bind(email)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    sqlx::query("INSERT INTO known_devices (email, fingerprint) VALUES ('Alice@Example.com', 'f')")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    sqlx::migrate!().run(&pool).await.unwrap();
+
+    let emails: Vec<String> = sqlx::query_scalar("SELECT email FROM users ORDER BY email")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        emails,
+        vec![
+            "Bob@Example.com",
+            "alice@example.com",
+            "bob@example.com",
+            "carol@example.com"
+        ]
+    );
+    let device_email: String = sqlx::query_scalar(
+        "SELECT users.email FROM known_devices JOIN users ON users.id = known_devices.user_id",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(device_email, "alice@example.com");
+
+    let collisions: Vec<(String, String)> =
+        sqlx::query_as("SELECT email, normalized_email FROM email_collisions ORDER BY email")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        collisions,
+        vec![
+            ("Bob@Example.com".to_owned(), "bob@example.com".to_owned()),
+            ("bob@example.com".to_owned(), "bob@example.com".to_owned()),
+        ]
+    );
+
+    // New emails must be normalised
+    let result =
+        sqlx::query("INSERT INTO users (email, password_hash) VALUES ('Dave@example.com', 'hash')")
+            .execute(&pool)
+            .await;
+    assert!(result.is_err());
+
+    // Resolving the collision validates the constraint
+    assert!(keep_colliding_account(&pool, "carol@example.com")
+        .await
+        .is_err());
+    assert!(!validate_email_normalization(&pool).await.unwrap());
+    let deleted = keep_colliding_account(&pool, "Bob@Example.com")
+        .await
+        .unwrap();
+    assert_eq!(deleted, vec!["bob@example.com"]);
+    let emails: Vec<String> = sqlx::query_scalar("SELECT email FROM users ORDER BY email")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        emails,
+        vec!["alice@example.com", "bob@example.com", "carol@example.com"]
+    );
+    assert!(get_email_collisions(&pool).await.unwrap().is_empty());
+    let validated: bool = sqlx::query_scalar(
+        "SELECT convalidated FROM pg_constraint WHERE conname = 'users_email_normalized'",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert!(validated);
+
+    pool.close().await;
+    delete_database(&db_name).await;
+}
@@ -1,23 +1,46 @@
 use auth_service::app_state::*;
+use auth_service::delivery::RetryPolicy;
+use auth_service::email_clients::file_email_client::FileEmailClient;
+use auth_service::email_clients::outbox_email_client::OutboxEmailClient;
 use auth_service::email_clients::postmark_email_client::PostmarkEmailClient;
 use auth_service::get_postgres_pool;
 use auth_service::routes::LoginResponse;
+use auth_service::sms_clients::http_sms_client::HttpSmsClient;
 use auth_service::utils::constants::*;
 use auth_service::Application;
+use auth_service::BreachedPasswords;
+use auth_service::DomainListFile;
 use auth_service::Email;
+use auth_service::EmailDispatcher;
+use auth_service::EmailDomainPolicy;
+use auth_service::PasswordPolicy;
+use auth_service::Peppers;
+use auth_service::PostgresAuditEventStore;
+use auth_service::PostgresEmailChangeStore;
+use auth_service::PostgresEmailOutboxStore;
+use auth_service::PostgresKnownDeviceStore;
+use auth_service::PostgresTrustedDeviceStore;
 use auth_service::PostgresUserStore;
+use auth_service::PostgresWebhookStore;
+use auth_service::ResendPolicy;
+use auth_service::WebhookDispatcher;
+use regex::Regex;
 use reqwest::cookie::Jar;
 use reqwest::Client;
+use secrecy::ExposeSecret;
 use secrecy::Secret;
+use sqlx::migrate::Migrate;
 use sqlx::postgres::PgConnectOptions;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::Connection;
 use sqlx::Executor;
 use sqlx::PgConnection;
 use sqlx::PgPool;
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
 use uuid::Uuid;
 use wiremock::matchers::method;
 use wiremock::matchers::path;
@@ -32,26 +55,120 @@ pub struct TestApp {
     pub banned_tokens: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
     pub email_server: MockServer,
+    pub sms_server: MockServer,
     pub db_name: String,
+    background_tasks: Vec<JoinHandle<()>>,
+    mailbox_path: Option<PathBuf>,
     cleanup_called: bool,
 }
 
+enum EmailSetup {
+    Postmark,
+    Outbox,
+    DevMailbox,
+}
+
 impl TestApp {
+    /// Emails are sent synchronously, as part of the request
     pub async fn new() -> Self {
+        Self::build(EmailSetup::Postmark).await
+    }
+
+    /// Emails are queued in the outbox and sent by a background dispatcher, as in production
+    pub async fn new_with_email_outbox() -> Self {
+        Self::build(EmailSetup::Outbox).await
+    }
+
+    /// Emails are written to a temporary mailbox file, browsable at `/dev/mailbox`
+    pub async fn new_with_dev_mailbox() -> Self {
+        Self::build(EmailSetup::DevMailbox).await
+    }
+
+    async fn build(email_setup: EmailSetup) -> Self {
+        // Enable the admin API unless a token is already configured
+        if std::env::var(env::ADMIN_TOKEN_ENV_VAR).is_err() {
+            std::env::set_var(env::ADMIN_TOKEN_ENV_VAR, Uuid::new_v4().to_string());
+        }
+
         let mut app_state = AppState::new_ps_redis().await;
 
         // Reconfigure the PostgreSQL database for testing and get db name
         let (db_pool, db_name) = configure_postgresql_test().await;
-        app_state.user_store = Arc::new(tokio::sync::RwLock::new(PostgresUserStore::new(db_pool)));
+        app_state.user_store = Arc::new(tokio::sync::RwLock::new(PostgresUserStore::new(
+            db_pool.clone(),
+            password_hash_params(),
+            test_peppers(),
+        )));
+        app_state.audit_event_store =
+            Arc::new(RwLock::new(PostgresAuditEventStore::new(db_pool.clone())));
+        app_state.webhook_store = Arc::new(RwLock::new(PostgresWebhookStore::new(db_pool.clone())));
+        app_state.trusted_device_store = Arc::new(RwLock::new(PostgresTrustedDeviceStore::new(
+            db_pool.clone(),
+        )));
+        app_state.known_device_store =
+            Arc::new(RwLock::new(PostgresKnownDeviceStore::new(db_pool.clone())));
+        app_state.email_change_store =
+            Arc::new(RwLock::new(PostgresEmailChangeStore::new(db_pool.clone())));
+        app_state.email_outbox_store =
+            Arc::new(RwLock::new(PostgresEmailOutboxStore::new(db_pool)));
 
         // Configure the email server
         let email_server = MockServer::start().await;
         let base_url = email_server.uri();
-        let email_client = Arc::new(RwLock::new(configure_postmark_email_client(base_url)));
-        app_state.email_client = email_client;
+        let email_client: EmailClientType =
+            Arc::new(RwLock::new(configure_postmark_email_client(base_url)));
+        let mut background_tasks = Vec::new();
+        let mut mailbox_path = None;
+        match email_setup {
+            EmailSetup::Postmark => app_state.email_client = email_client,
+            EmailSetup::Outbox => {
+                app_state.email_client = Arc::new(RwLock::new(OutboxEmailClient::new(
+                    app_state.email_outbox_store.clone(),
+                )));
+                let email_dispatcher =
+                    configure_email_dispatcher(app_state.email_outbox_store.clone(), email_client);
+                background_tasks.push(tokio::spawn(email_dispatcher.run()));
+            }
+            EmailSetup::DevMailbox => {
+                let path = std::env::temp_dir().join(format!("mailbox-{}.jsonl", Uuid::new_v4()));
+                app_state.email_client = Arc::new(RwLock::new(FileEmailClient::new(&path)));
+                app_state.dev_mailbox = Some(Arc::new(FileEmailClient::new(&path)));
+                mailbox_path = Some(path);
+            }
+        }
+
+        // Configure the SMS server
+        let sms_server = MockServer::start().await;
+        app_state.sms_client = Arc::new(RwLock::new(configure_http_sms_client(format!(
+            "{}/messages",
+            sms_server.uri()
+        ))));
+
+        app_state.two_fa_resend_policy = ResendPolicy {
+            cooldown: test::two_fa::RESEND_COOLDOWN,
+            max_resends: test::two_fa::MAX_RESENDS,
+        };
+        app_state.trusted_device_ttl = test::trusted_devices::TTL;
+        app_state.password_policy = PasswordPolicy {
+            history_size: test::passwords::HISTORY_SIZE,
+            max_age: Some(test::passwords::MAX_AGE),
+            ..PasswordPolicy::default()
+        };
+        // Holds the range of "Sup3rSecret!", see `BREACHED_PASSWORD`
+        app_state.breached_passwords = Some(BreachedPasswords::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/breached_passwords"
+        )));
+        // Blocks `mailinator.com` and requires 2FA for `secure.example`
+        app_state.email_domain_policy = EmailDomainPolicy {
+            blocklist: Some(email_domain_list("blocklist.txt")),
+            allowlist: None,
+            require_2fa: Some(email_domain_list("require_2fa.txt")),
+        };
 
         let banned_tokens = app_state.banned_token_store.clone();
         let two_fa_code_store = app_state.two_fa_code_store.clone();
+        let webhook_store = app_state.webhook_store.clone();
         let app = Application::build(app_state, test::APP_ADDRESS)
             .await
             .expect("Failed to build app");
@@ -62,6 +179,9 @@ impl TestApp {
         // to avoid blocking the main test thread.
         #[allow(clippy::let_underscore_future)]
         let _ = tokio::spawn(app.run());
+        background_tasks.push(tokio::spawn(
+            configure_webhook_dispatcher(webhook_store).run(),
+        ));
 
         let cookie_jar = Arc::new(Jar::default());
         // Create a Reqwest http client instance
@@ -70,7 +190,7 @@ impl TestApp {
             .build()
             .expect("Failed to build HTTP client");
 
-        let test_app = TestApp {
+        TestApp {
             address,
             cookie_jar,
             http_client,
@@ -78,15 +198,16 @@ impl TestApp {
             two_fa_code_store,
             db_name,
             email_server,
+            sms_server,
+            background_tasks,
+            mailbox_path,
             cleanup_called: false,
-        };
-
-        test_app
+        }
     }
 
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -97,7 +218,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/signup", &self.address))
+            .post(format!("{}/signup", &self.address))
             .json(body)
             .send()
             .await
@@ -109,16 +230,52 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .json(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    /// Logs in from another browser than the one of `post_login`
+    pub async fn post_login_with_user_agent<Body>(
+        &self,
+        body: &Body,
+        user_agent: &str,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/login", &self.address))
+            .header(reqwest::header::USER_AGENT, user_agent)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_revoke_session(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/revoke-session", &self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_session(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/revoke-session", &self.address))
+            .form(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_logout(&self) -> reqwest::Response {
         self.http_client
-            .post(&format!("{}/logout", &self.address))
+            .post(format!("{}/logout", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -129,28 +286,320 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/verify-2fa", &self.address))
+            .post(format!("{}/verify-2fa", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/change-password", &self.address))
             .json(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_change_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/change-email", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_confirm_email_change(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/confirm-email-change", &self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_confirm_email_change(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/confirm-email-change", &self.address))
+            .form(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_revert_email_change(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/revert-email-change", &self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revert_email_change(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/revert-email-change", &self.address))
+            .form(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/resend-2fa", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_login_attempts(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/login-attempts", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_login_attempt(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/login-attempts/{}", &self.address, id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_trusted_devices(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/trusted-devices", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_trusted_device(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/trusted-devices/{}", &self.address, id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_me(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/me", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn patch_me<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .patch(format!("{}/me", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn options_me_preflight(&self, origin: &str, method: &str) -> reqwest::Response {
+        self.http_client
+            .request(reqwest::Method::OPTIONS, format!("{}/me", &self.address))
+            .header("Origin", origin)
+            .header("Access-Control-Request-Method", method)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_verify_token<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/verify-token", &self.address))
+            .post(format!("{}/verify-token", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_phone_number<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/phone-number", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_phone_number<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/phone-number/verify", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_phone_number(&self) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/phone-number", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn put_2fa_channel<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .put(format!("{}/2fa-channel", &self.address))
             .json(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_dev_mailbox(&self, accept: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/dev/mailbox", &self.address))
+            .header("Accept", accept)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_audit_events(&self, query: &[(&str, &str)]) -> reqwest::Response {
+        self.get_audit_events_with_token(query, ADMIN_TOKEN.expose_secret())
+            .await
+    }
+
+    pub async fn get_audit_events_with_token(
+        &self,
+        query: &[(&str, &str)],
+        token: &str,
+    ) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/audit-events", &self.address))
+            .query(query)
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webhook<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/admin/webhooks", &self.address))
+            .bearer_auth(ADMIN_TOKEN.expose_secret())
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_webhooks(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/webhooks", &self.address))
+            .bearer_auth(ADMIN_TOKEN.expose_secret())
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_webhook(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/admin/webhooks/{}", &self.address, id))
+            .bearer_auth(ADMIN_TOKEN.expose_secret())
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_webhook_deliveries(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!(
+                "{}/admin/webhooks/{}/deliveries",
+                &self.address, id
+            ))
+            .bearer_auth(ADMIN_TOKEN.expose_secret())
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn put_user_status<Body>(&self, id: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .put(format!("{}/admin/users/{}/status", &self.address, id))
+            .bearer_auth(ADMIN_TOKEN.expose_secret())
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_user(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/admin/users/{}", &self.address, id))
+            .bearer_auth(ADMIN_TOKEN.expose_secret())
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// The 2FA code sent in the last email received by the email server
+    pub async fn last_email_2fa_code(&self) -> String {
+        let requests = self.email_server.received_requests().await.unwrap();
+        let body: serde_json::Value = requests.last().expect("No email sent").body_json().unwrap();
+        extract_2fa_code(body["TextBody"].as_str().unwrap())
+    }
+
+    /// Opens a new connection pool to the database of this test app
+    pub async fn db_pool(&self) -> PgPool {
+        get_postgres_pool(&format!("{}/{}", DATABASE_URL.to_owned(), self.db_name))
+            .await
+            .expect("Failed to create Postgres connection pool!")
+    }
+
     pub async fn cleanup(&mut self) {
+        // Stop polling the database before dropping it
+        for task in self.background_tasks.iter() {
+            task.abort();
+        }
         // Cleanup the database
         delete_database(&self.db_name).await;
+        if let Some(path) = &self.mailbox_path {
+            let _ = tokio::fs::remove_file(path).await;
+        }
         self.cleanup_called = true;
     }
 }
@@ -166,6 +615,30 @@ impl Drop for TestApp {
     }
 }
 
+/// The 2FA codes are only stored hashed, tests read them from the messages sent
+pub fn extract_2fa_code(text: &str) -> String {
+    let code_regex = Regex::new(r"\b\d{6}\b").unwrap();
+    code_regex
+        .find_iter(text)
+        .last()
+        .expect("No 2FA code in the message")
+        .as_str()
+        .to_owned()
+}
+
+/// Listed in the breached password corpus of the test app, but valid for the default policy
+pub const BREACHED_PASSWORD: &str = "Sup3rSecret!";
+
+/// The test pepper of the hashes, along with a rotated one
+pub fn test_peppers() -> Peppers {
+    Peppers::parse(&Secret::new(test::passwords::PEPPERS.to_owned())).expect("Invalid test peppers")
+}
+
+fn email_domain_list(name: &str) -> DomainListFile {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/email_domains");
+    DomainListFile::load(PathBuf::from(dir).join(name)).expect("Failed to load email domain list")
+}
+
 pub fn get_random_email() -> String {
     format!("{}@example.com", Uuid::new_v4())
 }
@@ -227,7 +700,7 @@ pub async fn app_signup_and_login(
             .cookies()
             .find(|c| c.name() == JWT_COOKIE_NAME)
             .expect("auth_cookie not found in response cookies");
-        assert!(auth_cookie.value().len() > 0, "auth_cookie is empty");
+        assert!(!auth_cookie.value().is_empty(), "auth_cookie is empty");
 
         app.cookie_jar.add_cookie_str(
             &format!(
@@ -303,7 +776,39 @@ async fn configure_database(db_conn_string: &str, db_name: &str) {
         .expect("Failed to migrate the database");
 }
 
-async fn delete_database(db_name: &str) {
+/// Creates a database with the migrations older than `version` only, to test a migration on existing data.
+/// Running `sqlx::migrate!()` on the pool then applies the remaining ones.
+pub async fn configure_postgresql_before(version: i64) -> (PgPool, String) {
+    let db_name = Uuid::new_v4().to_string();
+    let connection = PgPoolOptions::new()
+        .connect(&DATABASE_URL)
+        .await
+        .expect("Failed to create Postgres connection pool.");
+    connection
+        .execute(format!(r#"CREATE DATABASE "{}";"#, db_name).as_str())
+        .await
+        .expect("Failed to create database.");
+
+    let pool = get_postgres_pool(&format!("{}/{}", DATABASE_URL.to_owned(), db_name))
+        .await
+        .expect("Failed to create Postgres connection pool!");
+    let mut connection = pool.acquire().await.expect("Failed to connect to Postgres");
+    connection
+        .ensure_migrations_table()
+        .await
+        .expect("Failed to create the migrations table");
+    for migration in sqlx::migrate!().iter() {
+        if migration.version < version && !migration.migration_type.is_down_migration() {
+            connection
+                .apply(migration)
+                .await
+                .expect("Failed to migrate the database");
+        }
+    }
+    (pool, db_name)
+}
+
+pub async fn delete_database(db_name: &str) {
     let postgresql_conn_url: String = DATABASE_URL.to_owned();
 
     let connection_options = PgConnectOptions::from_str(&postgresql_conn_url)
@@ -340,7 +845,7 @@ async fn delete_database(db_name: &str) {
 fn configure_postmark_email_client(base_url: String) -> PostmarkEmailClient {
     let postmark_auth_token = Secret::new("auth_token".to_owned());
 
-    let sender = Email::parse(&test::email_client::SENDER.to_owned()).unwrap();
+    let sender = Email::parse(test::email_client::SENDER).unwrap();
 
     let http_client = Client::builder()
         .timeout(test::email_client::TIMEOUT)
@@ -349,3 +854,51 @@ fn configure_postmark_email_client(base_url: String) -> PostmarkEmailClient {
 
     PostmarkEmailClient::new(base_url, sender, postmark_auth_token, http_client)
 }
+
+fn configure_http_sms_client(url: String) -> HttpSmsClient {
+    let http_client = Client::builder()
+        .timeout(test::sms_client::TIMEOUT)
+        .build()
+        .expect("Failed to build HTTP client");
+
+    HttpSmsClient::new(
+        url,
+        test::sms_client::SENDER.to_owned(),
+        Secret::new("auth_token".to_owned()),
+        http_client,
+    )
+}
+
+fn configure_webhook_dispatcher(webhook_store: WebhookStoreType) -> WebhookDispatcher {
+    WebhookDispatcher::new(
+        webhook_store,
+        test::webhooks::TIMEOUT,
+        RetryPolicy {
+            max_attempts: test::webhooks::MAX_ATTEMPTS,
+            base_delay: test::webhooks::BASE_DELAY,
+            max_delay: test::webhooks::MAX_DELAY,
+        },
+        test::webhooks::POLL_INTERVAL,
+        test::webhooks::BATCH_SIZE,
+        test::webhooks::LEASE,
+    )
+    .expect("Failed to build webhook dispatcher")
+}
+
+fn configure_email_dispatcher(
+    email_outbox_store: EmailOutboxStoreType,
+    email_client: EmailClientType,
+) -> EmailDispatcher {
+    EmailDispatcher::new(
+        email_outbox_store,
+        email_client,
+        RetryPolicy {
+            max_attempts: test::email_outbox::MAX_ATTEMPTS,
+            base_delay: test::email_outbox::BASE_DELAY,
+            max_delay: test::email_outbox::MAX_DELAY,
+        },
+        test::email_outbox::POLL_INTERVAL,
+        test::email_outbox::BATCH_SIZE,
+        test::email_outbox::LEASE,
+    )
+}
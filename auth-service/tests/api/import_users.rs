@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use crate::helpers::{get_random_email, test_peppers, TestApp};
+use argon2::PasswordHash;
+use auth_service::app_state::password_hash_params;
+use auth_service::user_import::{import_users, ImportFormat, RejectedRecord};
+use auth_service::PostgresUserStore;
+
+// Hashes of "password123" from systems this service replaces
+const BCRYPT_HASH: &str = "$2b$04$hIO7K3jhaJV9CHylqOmRUeec7/Q.McdjukoOY3lInPBZLbUVzq4ZK";
+const PBKDF2_HASH: &str =
+    "$pbkdf2-sha256$i=1000,l=32$c2FsdHNhbHQ$Dl199yZQRQiBSpxYBJo6qcXlI52pY2Gh4qg7BbBgK/c";
+
+#[tokio::test]
+async fn should_login_with_imported_hashes_and_upgrade_them() {
+    let mut app = TestApp::new().await;
+    let pool = app.db_pool().await;
+    let store = PostgresUserStore::new(pool.clone(), password_hash_params(), test_peppers());
+    let emails = [get_random_email(), get_random_email()];
+    let input = format!(
+        "email,hash,requires_2fa\n{},{},false\n{},\"{}\",false\nunknown@example.com,$1$md5crypt,false\n",
+        emails[0], BCRYPT_HASH, emails[1], PBKDF2_HASH
+    );
+
+    let report = import_users(&store, ImportFormat::Csv, &input).await;
+    assert_eq!(report.imported, 2);
+    assert_eq!(report.existing, 0);
+    assert_eq!(
+        report.rejected,
+        vec![RejectedRecord {
+            line: 4,
+            reason: "Unexpected error: Unsupported password hash format".to_owned(),
+        }]
+    );
+    // Importing again leaves the users as they are
+    let report = import_users(&store, ImportFormat::Csv, &input).await;
+    assert_eq!((report.imported, report.existing), (0, 2));
+
+    for email in &emails {
+        let response = app
+            .post_login(&serde_json::json!({ "email": email, "password": "password124" }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401, "failed for {}", email);
+        let response = app
+            .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+            .await;
+        assert_eq!(response.status().as_u16(), 200, "failed for {}", email);
+    }
+
+    // The legacy hashes are replaced in the background of the login
+    for email in &emails {
+        let mut upgraded = false;
+        for _ in 0..100 {
+            let hash: String =
+                sqlx::query_scalar("SELECT password_hash FROM users WHERE email = $1")
+                    .bind(email)
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap();
+            if PasswordHash::new(&hash).is_ok_and(|hash| hash.algorithm.as_str() == "argon2id") {
+                upgraded = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(upgraded, "The hash of {} was not upgraded in time", email);
+        let response = app
+            .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+            .await;
+        assert_eq!(response.status().as_u16(), 200, "failed for {}", email);
+    }
+    app.cleanup().await;
+}
@@ -0,0 +1,161 @@
+use crate::helpers::{app_signup_and_login, TestApp};
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+use regex::Regex;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+const OTHER_BROWSER: &str = "Mozilla/5.0 (X11; Linux x86_64) Firefox/128.0";
+
+/// Replaces the expectation of `app_signup_and_login` on the emails
+async fn expect_emails(app: &TestApp, count: u64) {
+    app.email_server.reset().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(count)
+        .mount(&app.email_server)
+        .await;
+}
+
+/// The token of the "this wasn't me" link of the last email
+async fn last_revocation_token(app: &TestApp) -> String {
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests.last().expect("No email sent").body_json().unwrap();
+    let link = Regex::new(r"/revoke-session\?token=([0-9a-f]+)").unwrap();
+    link.captures(body["TextBody"].as_str().unwrap())
+        .expect("No revocation link in the email")[1]
+        .to_owned()
+}
+
+fn jwt(response: &reqwest::Response) -> String {
+    response
+        .cookies()
+        .find(|c| c.name() == JWT_COOKIE_NAME)
+        .expect("auth_cookie not found in response cookies")
+        .value()
+        .to_owned()
+}
+
+#[tokio::test]
+async fn should_alert_on_login_from_a_new_device_and_revoke_its_session() {
+    let (mut app, email, password, _, _) = app_signup_and_login(false).await;
+    expect_emails(&app, 1).await;
+
+    let response = app
+        .post_login_with_user_agent(
+            &serde_json::json!({ "email": email, "password": password }),
+            OTHER_BROWSER,
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let new_session = jwt(&response);
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests[0].body_json().unwrap();
+    assert_eq!(body["To"], email);
+    assert!(body["TextBody"].as_str().unwrap().contains(OTHER_BROWSER));
+    // Reading the database is not enough to use the session
+    let pool = app.db_pool().await;
+    let stored: Vec<(Option<String>,)> =
+        sqlx::query_as("SELECT session_token_hash FROM known_devices")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    pool.close().await;
+    assert!(stored.iter().any(|(hash,)| hash.is_some()));
+    assert!(stored
+        .iter()
+        .all(|(hash,)| hash.as_deref() != Some(new_session.as_str())));
+
+    let token = last_revocation_token(&app).await;
+    // Following the link only opens a page asking to confirm
+    let response = app.get_revoke_session(&token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains(&token));
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": new_session }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_revoke_session(&token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": new_session }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // A link can only be used once
+    let response = app.post_revoke_session(&token).await;
+    assert_eq!(response.status().as_u16(), 404);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_not_alert_on_login_from_a_known_device() {
+    let (mut app, email, password, _, _) = app_signup_and_login(false).await;
+    expect_emails(&app, 0).await;
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": password }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_alert_once_2fa_is_verified_from_a_new_device() {
+    let (mut app, email, password, _, login_attempt_id) = app_signup_and_login(true).await;
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id.unwrap(),
+            "2FACode": app.last_email_2fa_code().await,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    // The 2FA code, then the alert
+    expect_emails(&app, 2).await;
+
+    let response = app
+        .post_login_with_user_agent(
+            &serde_json::json!({ "email": email, "password": password }),
+            OTHER_BROWSER,
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    let body: serde_json::Value = response.json().await.unwrap();
+    // The code is entered in the same browser
+    let response = app
+        .http_client
+        .post(format!("{}/verify-2fa", &app.address))
+        .header(reqwest::header::USER_AGENT, OTHER_BROWSER)
+        .json(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": body["loginAttemptId"],
+            "2FACode": app.last_email_2fa_code().await,
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    let new_session = jwt(&response);
+
+    let token = last_revocation_token(&app).await;
+    let response = app.post_revoke_session(&token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": new_session }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_404_if_unknown_revocation_token() {
+    let (mut app, _, _, _, _) = app_signup_and_login(false).await;
+
+    let response = app.post_revoke_session(&"0".repeat(64)).await;
+    assert_eq!(response.status().as_u16(), 404);
+    app.cleanup().await;
+}
@@ -1,7 +1,15 @@
-use crate::helpers::{app_signup, get_random_email};
+use std::time::Duration;
+
+use crate::helpers::{app_signup, get_random_email, TestApp};
+use argon2::{Params, PasswordHash};
+use auth_service::app_state::password_hash_params;
 use auth_service::utils::constants::JWT_COOKIE_NAME;
-use auth_service::{routes::TwoFactorLoginResponse, Email};
+use auth_service::{
+    routes::TwoFactorLoginResponse, Email, LoginAttemptId, Pepper, Peppers, PostgresUserStore,
+    User, UserStore,
+};
 use reqwest::{cookie::CookieStore, Url};
+use secrecy::Secret;
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
 
@@ -25,7 +33,7 @@ async fn valid_login_without_2fa_returns_200() {
         .cookies()
         .find(|c| c.name() == JWT_COOKIE_NAME)
         .expect("auth_cookie not found in response cookies");
-    assert!(auth_cookie.value().len() > 0, "auth_cookie is empty");
+    assert!(!auth_cookie.value().is_empty(), "auth_cookie is empty");
 
     // Check if the cookie jar is updated with the auth cookie
     let jar_state = (*app.cookie_jar).cookies(&Url::parse(&app.address).unwrap());
@@ -72,7 +80,7 @@ async fn invalid_login_returns_400() {
         }),
         serde_json::json!({
             "email": email,
-            "password": "wrong_password",
+            "password": "",
         }),
         serde_json::json!({
             "email": "wrong_email",
@@ -156,18 +164,238 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
         response_body
     );
     let response_body = response_body.unwrap();
-    let attempt_id_from_response = response_body.login_attempt_id;
-    let attempt_id_from_store = app
+    let attempt_id_from_response = LoginAttemptId::parse(&response_body.login_attempt_id).unwrap();
+    let code_from_store = app
         .two_fa_code_store
         .read()
         .await
-        .get_code(&Email::parse(&email).unwrap())
+        .get_code(&Email::parse(&email).unwrap(), &attempt_id_from_response)
+        .await;
+    assert!(
+        code_from_store.is_ok(),
+        "login_attempt_id from response not found in the store"
+    );
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_206_and_retry_email_if_email_provider_fails() {
+    let mut app = TestApp::new_with_email_outbox().await;
+    let email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    // The first attempt fails, the retry succeeds
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let pool = app.db_pool().await;
+    let mut status = String::new();
+    for _ in 0..100 {
+        let row: (String, i32) = sqlx::query_as("SELECT status, attempts FROM email_outbox")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        if row.0 == "delivered" {
+            assert_eq!(row.1, 2);
+        }
+        status = row.0;
+        if status != "pending" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert_eq!(status, "delivered");
+    pool.close().await;
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_not_keep_the_2fa_code_once_the_email_is_delivered() {
+    let mut app = TestApp::new_with_email_outbox().await;
+    let email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
         .await;
-    let attempt_id_from_store = attempt_id_from_store.unwrap().1;
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let pool = app.db_pool().await;
+    let mut row = (String::new(), String::new(), String::new());
+    for _ in 0..100 {
+        row = sqlx::query_as("SELECT status, html_body, text_body FROM email_outbox")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        if row.0 != "pending" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    let (status, html_body, text_body) = row;
+    assert_eq!(status, "delivered");
+    let code = app.last_email_2fa_code().await;
+    assert!(!html_body.contains(&code));
+    assert!(!text_body.contains(&code));
+    pool.close().await;
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_send_2fa_email_in_the_user_locale() {
+    let mut app = TestApp::new().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    // Explicit locale, negotiated locale, and a locale without translations
+    let french = get_random_email();
+    let negotiated = get_random_email();
+    let german = get_random_email();
+    for (email, locale) in [(&french, Some("fr-CA")), (&german, Some("de"))] {
+        let response = app
+            .post_signup(&serde_json::json!({
+                "email": email,
+                "password": "password123",
+                "requires2FA": true,
+                "locale": locale,
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 201);
+    }
+    let response = app
+        .http_client
+        .post(format!("{}/signup", &app.address))
+        .header("Accept-Language", "fr;q=0.9, en;q=0.8")
+        .json(&serde_json::json!({
+            "email": negotiated,
+            "password": "password123",
+            "requires2FA": true,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+
+    for email in [&french, &negotiated, &german] {
+        let response = app
+            .post_login(&serde_json::json!({
+                "email": email,
+                "password": "password123",
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 206);
+    }
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let bodies: Vec<serde_json::Value> = requests
+        .iter()
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect();
+    let subjects: Vec<&str> = bodies
+        .iter()
+        .map(|body| body["Subject"].as_str().unwrap())
+        .collect();
     assert_eq!(
-        attempt_id_from_response,
-        attempt_id_from_store.as_ref(),
-        "login_attempt_id code from response and store do not match"
+        subjects,
+        vec![
+            "Votre code de vérification",
+            "Votre code de vérification",
+            "Your 2FA Code"
+        ]
     );
+    for body in bodies.iter() {
+        assert!(body["HtmlBody"]
+            .as_str()
+            .unwrap()
+            .starts_with("<!DOCTYPE html>"));
+        assert!(!body["TextBody"].as_str().unwrap().contains('<'));
+    }
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_move_the_password_hash_to_the_current_pepper_at_login() {
+    let mut app = TestApp::new().await;
+    let pool = app.db_pool().await;
+    // A user signed up before the rotation to the current test pepper
+    let previous_pepper = Pepper::new("test1", Secret::new("previous-test-pepper".to_owned()))
+        .expect("Invalid pepper");
+    let mut store = PostgresUserStore::new(
+        pool.clone(),
+        password_hash_params(),
+        Peppers::new(vec![previous_pepper]),
+    );
+    let email = get_random_email();
+    store
+        .add_user(User::new(email.clone(), Secret::new("password123".to_owned()), false).unwrap())
+        .await
+        .unwrap();
+
+    let login_body = serde_json::json!({ "email": email, "password": "password123" });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let mut keyid = Vec::new();
+    for _ in 0..100 {
+        let hash: String = sqlx::query_scalar("SELECT password_hash FROM users WHERE email = $1")
+            .bind(&email)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        keyid = Params::try_from(&PasswordHash::new(&hash).unwrap())
+            .unwrap()
+            .keyid()
+            .to_vec();
+        if keyid == b"test2" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(
+        keyid, b"test2",
+        "The hash was not moved to the current pepper in time"
+    );
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
     app.cleanup().await;
 }
@@ -0,0 +1,142 @@
+use crate::helpers::{app_signup, TestApp};
+use auth_service::{
+    routes::{LoginAttemptResponse, TwoFactorLoginResponse},
+    LoginAttemptId,
+};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+async fn login_with_2fa(app: &TestApp, email: &str, password: &str) -> (String, String) {
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": password }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    let login_attempt_id = response
+        .json::<TwoFactorLoginResponse>()
+        .await
+        .unwrap()
+        .login_attempt_id;
+    let code = app.last_email_2fa_code().await;
+    (login_attempt_id, code)
+}
+
+async fn verify_2fa(
+    app: &TestApp,
+    email: &str,
+    login_attempt_id: &str,
+    code: &str,
+) -> reqwest::Response {
+    app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code,
+    }))
+    .await
+}
+
+async fn expect_emails(app: &TestApp, count: u64) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(count)
+        .mount(&app.email_server)
+        .await;
+}
+
+#[tokio::test]
+async fn should_keep_concurrent_login_attempts_valid() {
+    let (mut app, email, password) = app_signup(true).await;
+    expect_emails(&app, 2).await;
+
+    let (first_id, first_code) = login_with_2fa(&app, &email, &password).await;
+    let (second_id, second_code) = login_with_2fa(&app, &email, &password).await;
+    assert_ne!(first_id, second_id);
+
+    // A code only completes its own login attempt
+    let response = verify_2fa(&app, &email, &second_id, &first_code).await;
+    if first_code != second_code {
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = verify_2fa(&app, &email, &first_id, &first_code).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = verify_2fa(&app, &email, &second_id, &second_code).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_list_and_cancel_pending_login_attempts() {
+    let (mut app, email, password) = app_signup(true).await;
+    expect_emails(&app, 3).await;
+
+    // Log in on this device
+    let (id, code) = login_with_2fa(&app, &email, &password).await;
+    let response = verify_2fa(&app, &email, &id, &code).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Two other devices are waiting for their 2FA code
+    let (first_id, first_code) = login_with_2fa(&app, &email, &password).await;
+    let (second_id, _) = login_with_2fa(&app, &email, &password).await;
+
+    let response = app.get_login_attempts().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let attempts: Vec<LoginAttemptResponse> = response.json().await.unwrap();
+    let ids: Vec<&str> = attempts
+        .iter()
+        .map(|a| a.login_attempt_id.as_str())
+        .collect();
+    assert_eq!(ids, vec![first_id.as_str(), second_id.as_str()]);
+
+    let response = app.delete_login_attempt(&first_id).await;
+    assert_eq!(response.status().as_u16(), 204);
+    let response = verify_2fa(&app, &email, &first_id, &first_code).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let attempts: Vec<LoginAttemptResponse> = app.get_login_attempts().await.json().await.unwrap();
+    assert_eq!(attempts.len(), 1);
+    assert_eq!(attempts[0].login_attempt_id, second_id);
+
+    let response = app.delete_login_attempt(&first_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_404_if_login_attempt_of_another_user() {
+    let (mut app, email, password) = app_signup(true).await;
+    expect_emails(&app, 2).await;
+    let (id, code) = login_with_2fa(&app, &email, &password).await;
+    verify_2fa(&app, &email, &id, &code).await;
+
+    // Another user is waiting for their 2FA code
+    let other_email = crate::helpers::get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": other_email,
+        "password": password,
+        "requires2FA": true
+    }))
+    .await;
+    let (other_id, other_code) = login_with_2fa(&app, &other_email, &password).await;
+
+    let response = app.delete_login_attempt(&other_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+    let response = verify_2fa(&app, &other_email, &other_id, &other_code).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let (mut app, _, _) = app_signup(true).await;
+
+    let response = app.get_login_attempts().await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app
+        .delete_login_attempt(LoginAttemptId::new().as_ref())
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.cleanup().await;
+}
@@ -1,7 +1,24 @@
+mod account_status;
+mod admin;
+mod audit_chain;
+mod change_email;
+mod change_password;
+mod delete_user;
+mod dev_mailbox;
+mod email_normalization;
 mod helpers;
+mod import_users;
+mod known_devices;
 mod login;
+mod login_attempts;
 mod logout;
+mod me;
+mod phone_number;
+mod resend_2fa;
+mod root;
 mod signup;
+mod trusted_devices;
+mod user_ids;
 mod verify_2fa;
 mod verify_token;
-mod root;
\ No newline at end of file
+mod webhooks;
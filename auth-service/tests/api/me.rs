@@ -0,0 +1,102 @@
+use crate::helpers::{app_signup, app_signup_and_login};
+use auth_service::routes::MeResponse;
+
+#[tokio::test]
+async fn should_return_the_profile_of_the_logged_in_user() {
+    let (mut app, email, _, _, _) = app_signup_and_login(false).await;
+
+    let response = app.get_me().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let me: MeResponse = response.json().await.unwrap();
+    assert_eq!(me.email, email);
+    assert_eq!(me.display_name, None);
+    assert!(!me.requires_2fa);
+    assert!(me.created_at <= me.last_login_at.expect("Login not recorded"));
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_not_logged_in() {
+    let (mut app, _, _) = app_signup(false).await;
+
+    let response = app.get_me().await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app
+        .patch_me(&serde_json::json!({ "displayName": "Toto" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_update_only_the_given_attributes() {
+    let (mut app, _, _, _, _) = app_signup_and_login(false).await;
+
+    let response = app
+        .patch_me(&serde_json::json!({
+            "displayName": "  Zoé Martin ",
+            "locale": "fr-CA",
+            "timezone": "Europe/Paris",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let me: MeResponse = response.json().await.unwrap();
+    assert_eq!(me.display_name.as_deref(), Some("Zoé Martin"));
+    assert_eq!(me.locale.as_deref(), Some("fr-ca"));
+    assert_eq!(me.timezone.as_deref(), Some("Europe/Paris"));
+
+    // Missing attributes are kept, null ones are cleared
+    let response = app.patch_me(&serde_json::json!({ "timezone": null })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let me: MeResponse = app.get_me().await.json().await.unwrap();
+    assert_eq!(me.display_name.as_deref(), Some("Zoé Martin"));
+    assert_eq!(me.locale.as_deref(), Some("fr-ca"));
+    assert_eq!(me.timezone, None);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_profile() {
+    let (mut app, _, _, _, _) = app_signup_and_login(false).await;
+
+    let test_cases = [
+        serde_json::json!({ "displayName": "   " }),
+        serde_json::json!({ "displayName": "a".repeat(101) }),
+        serde_json::json!({ "locale": "not a locale" }),
+        serde_json::json!({ "timezone": "Mars/Olympus_Mons" }),
+    ];
+    for test_case in test_cases {
+        let response = app.patch_me(&test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+    let me: MeResponse = app.get_me().await.json().await.unwrap();
+    assert_eq!(me.display_name, None);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_allow_cross_origin_patch_from_the_app_service() {
+    let (mut app, _, _) = app_signup(false).await;
+
+    let response = app
+        .options_me_preflight("http://localhost:8000", "PATCH")
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let headers = response.headers();
+    assert_eq!(
+        headers["access-control-allow-origin"],
+        "http://localhost:8000"
+    );
+    assert!(headers["access-control-allow-methods"]
+        .to_str()
+        .unwrap()
+        .split(',')
+        .any(|method| method.trim() == "PATCH"));
+    assert_eq!(headers["access-control-allow-credentials"], "true");
+    app.cleanup().await;
+}
@@ -0,0 +1,220 @@
+use crate::helpers::{app_signup, app_signup_and_login, TestApp};
+use auth_service::{routes::LoginResponse, TwoFAChannel};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+/// Completes the email 2FA of the login started by `app_signup_and_login`, so that the auth cookie is set
+async fn complete_2fa(app: &TestApp, email: &str, login_attempt_id: Option<String>) {
+    let code = app.last_email_2fa_code().await;
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id.unwrap(),
+            "2FACode": code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+/// Extracts the 6 digits code ending the last text message received by the SMS server
+async fn last_sms_code(app: &TestApp) -> String {
+    let requests = app.sms_server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests.last().expect("No SMS sent").body_json().unwrap();
+    let content = body["body"].as_str().unwrap().trim_end();
+    content[content.len() - 6..].to_owned()
+}
+
+async fn mount_sms_server(app: &TestApp) {
+    Mock::given(path("/messages"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.sms_server)
+        .await;
+}
+
+#[tokio::test]
+async fn should_send_2fa_codes_by_sms_once_the_phone_number_is_verified() {
+    let (mut app, email, password, _, login_attempt_id) = app_signup_and_login(true).await;
+    complete_2fa(&app, &email, login_attempt_id).await;
+    mount_sms_server(&app).await;
+
+    let response = app
+        .post_phone_number(&serde_json::json!({ "phoneNumber": "+33 6 12 34 56 78" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let requests = app.sms_server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests[0].body_json().unwrap();
+    assert_eq!(body["to"], "+33612345678");
+
+    let code = last_sms_code(&app).await;
+    let response = app
+        .post_verify_phone_number(&serde_json::json!({ "code": code }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .put_2fa_channel(&serde_json::json!({ "channel": "sms" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": password }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    let LoginResponse::With2FA(login_response) = response.json().await.unwrap() else {
+        panic!("2FA response expected");
+    };
+    assert_eq!(login_response.channel, TwoFAChannel::Sms);
+
+    assert_eq!(app.sms_server.received_requests().await.unwrap().len(), 2);
+
+    // The code sent by SMS completes the login
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_response.login_attempt_id,
+            "2FACode": last_sms_code(&app).await,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_send_2fa_codes_by_email_again_once_the_phone_number_is_removed() {
+    let (mut app, email, password, _, login_attempt_id) = app_signup_and_login(true).await;
+    complete_2fa(&app, &email, login_attempt_id).await;
+    mount_sms_server(&app).await;
+
+    app.post_phone_number(&serde_json::json!({ "phoneNumber": "+14155550123" }))
+        .await;
+    let code = last_sms_code(&app).await;
+    app.post_verify_phone_number(&serde_json::json!({ "code": code }))
+        .await;
+    app.put_2fa_channel(&serde_json::json!({ "channel": "sms" }))
+        .await;
+
+    let response = app.delete_phone_number().await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    // The login email of app_signup_and_login was already received
+    app.email_server.reset().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": password }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    let LoginResponse::With2FA(login_response) = response.json().await.unwrap() else {
+        panic!("2FA response expected");
+    };
+    assert_eq!(login_response.channel, TwoFAChannel::Email);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_verification_code() {
+    let (mut app, email, _, _, login_attempt_id) = app_signup_and_login(true).await;
+    complete_2fa(&app, &email, login_attempt_id).await;
+    mount_sms_server(&app).await;
+
+    // No code was sent yet
+    let response = app
+        .post_verify_phone_number(&serde_json::json!({ "code": "123456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.post_phone_number(&serde_json::json!({ "phoneNumber": "+14155550123" }))
+        .await;
+    let code = last_sms_code(&app).await;
+    let wrong_code = if code == "000000" { "111111" } else { "000000" };
+    let response = app
+        .post_verify_phone_number(&serde_json::json!({ "code": wrong_code }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_drop_the_verification_after_too_many_wrong_codes() {
+    let (mut app, email, _, _, login_attempt_id) = app_signup_and_login(true).await;
+    complete_2fa(&app, &email, login_attempt_id).await;
+    mount_sms_server(&app).await;
+
+    app.post_phone_number(&serde_json::json!({ "phoneNumber": "+14155550123" }))
+        .await;
+    let code = last_sms_code(&app).await;
+    let wrong_code = if code == "000000" { "111111" } else { "000000" };
+    for _ in 0..5 {
+        let response = app
+            .post_verify_phone_number(&serde_json::json!({ "code": wrong_code }))
+            .await;
+        assert_eq!(response.status().as_u16(), 400);
+    }
+    // Even the right code is refused now, a new one must be asked for
+    let response = app
+        .post_verify_phone_number(&serde_json::json!({ "code": code }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.post_phone_number(&serde_json::json!({ "phoneNumber": "+14155550123" }))
+        .await;
+    let code = last_sms_code(&app).await;
+    let response = app
+        .post_verify_phone_number(&serde_json::json!({ "code": code }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_phone_number() {
+    let (mut app, email, _, _, login_attempt_id) = app_signup_and_login(true).await;
+    complete_2fa(&app, &email, login_attempt_id).await;
+
+    for phone_number in ["", "0612345678", "+33 abc", "+1234"] {
+        let response = app
+            .post_phone_number(&serde_json::json!({ "phoneNumber": phone_number }))
+            .await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for phone number: {}",
+            phone_number
+        );
+    }
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_sms_channel_without_phone_number() {
+    let (mut app, _, _, _, _) = app_signup_and_login(false).await;
+
+    let response = app
+        .put_2fa_channel(&serde_json::json!({ "channel": "sms" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let (mut app, _, _) = app_signup(true).await;
+
+    let response = app
+        .post_phone_number(&serde_json::json!({ "phoneNumber": "+14155550123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app
+        .put_2fa_channel(&serde_json::json!({ "channel": "email" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.cleanup().await;
+}
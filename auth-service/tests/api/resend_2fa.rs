@@ -0,0 +1,143 @@
+use crate::helpers::{app_signup_and_login, TestApp};
+use auth_service::{routes::TwoFactorLoginResponse, utils::constants::test, LoginAttemptId};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+/// Replaces the expectation of `app_signup_and_login` on the login email
+async fn expect_emails(app: &TestApp, count: u64) {
+    app.email_server.reset().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(count)
+        .mount(&app.email_server)
+        .await;
+}
+
+#[tokio::test]
+async fn should_send_a_new_code_replacing_the_previous_one_after_the_cooldown() {
+    let (mut app, email, _, _, login_attempt_id) = app_signup_and_login(true).await;
+    let login_attempt_id = login_attempt_id.unwrap();
+    let previous_code = app.last_email_2fa_code().await;
+    expect_emails(&app, 1).await;
+
+    tokio::time::sleep(test::two_fa::RESEND_COOLDOWN).await;
+    let response = app
+        .post_resend_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: TwoFactorLoginResponse = response.json().await.unwrap();
+    assert_eq!(body.login_attempt_id, login_attempt_id);
+    let code = app.last_email_2fa_code().await;
+
+    if previous_code != code {
+        let response = app
+            .post_verify_2fa(&serde_json::json!({
+                "email": email,
+                "loginAttemptId": login_attempt_id,
+                "2FACode": previous_code,
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // The new code completes the login
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_429_if_resent_during_the_cooldown() {
+    let (mut app, email, _, _, login_attempt_id) = app_signup_and_login(true).await;
+    let body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id.unwrap(),
+    });
+    expect_emails(&app, 1).await;
+
+    // The code was just sent by the login
+    let response = app.post_resend_2fa(&body).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    tokio::time::sleep(test::two_fa::RESEND_COOLDOWN).await;
+    let response = app.post_resend_2fa(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app.post_resend_2fa(&body).await;
+    assert_eq!(response.status().as_u16(), 429);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_429_once_the_maximum_number_of_resends_is_reached() {
+    let (mut app, email, _, _, login_attempt_id) = app_signup_and_login(true).await;
+    let body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id.unwrap(),
+    });
+    expect_emails(&app, test::two_fa::MAX_RESENDS as u64).await;
+
+    for _ in 0..test::two_fa::MAX_RESENDS {
+        tokio::time::sleep(test::two_fa::RESEND_COOLDOWN).await;
+        let response = app.post_resend_2fa(&body).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+    tokio::time::sleep(test::two_fa::RESEND_COOLDOWN).await;
+    let response = app.post_resend_2fa(&body).await;
+    assert_eq!(response.status().as_u16(), 429);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_unknown_login_attempt() {
+    let (mut app, email, _, _, _) = app_signup_and_login(true).await;
+    expect_emails(&app, 0).await;
+
+    tokio::time::sleep(test::two_fa::RESEND_COOLDOWN).await;
+    let response = app
+        .post_resend_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": LoginAttemptId::new().as_ref(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let (mut app, email, _, _, login_attempt_id) = app_signup_and_login(true).await;
+    let login_attempt_id = login_attempt_id.unwrap();
+
+    let test_cases = [
+        serde_json::json!({
+            "email": "invalid_email",
+            "loginAttemptId": login_attempt_id,
+        }),
+        serde_json::json!({
+            "email": email,
+            "loginAttemptId": "invalid_id",
+        }),
+    ];
+    for body in test_cases {
+        let response = app.post_resend_2fa(&body).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "failed for input: {:?}",
+            body
+        );
+    }
+    app.cleanup().await;
+}
@@ -1,5 +1,7 @@
-use crate::helpers::{get_random_email, TestApp};
+use crate::helpers::{get_random_email, TestApp, BREACHED_PASSWORD};
 use auth_service::error::ErrorResponse;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
 
 #[tokio::test]
 async fn should_return_422_if_malformed_input() {
@@ -79,11 +81,6 @@ async fn should_return_400_if_invalid_input() {
             "password": "password123",
             "requires2FA": false
         }),
-        serde_json::json!({
-            "email": "foo@toto.com",
-            "password": "5char",
-            "requires2FA": false
-        }),
     ];
 
     for test_case in test_cases.iter() {
@@ -107,6 +104,58 @@ async fn should_return_400_if_invalid_input() {
     app.cleanup().await;
 }
 
+#[tokio::test]
+async fn should_return_400_with_every_violation_if_weak_password() {
+    let mut app = TestApp::new().await;
+    let test_cases = [
+        ("5char", vec!["min_length"]),
+        ("abc", vec!["min_length", "digit"]),
+        (&"a1".repeat(65), vec!["max_length"]),
+        (BREACHED_PASSWORD, vec!["breached"]),
+    ];
+
+    for (password, expected_rules) in test_cases {
+        let response = app
+            .post_signup(&serde_json::json!({
+                "email": get_random_email(),
+                "password": password,
+                "requires2FA": false
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 400, "failed for {}", password);
+
+        let body = response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse");
+        assert_eq!(body.error, "Password does not meet the requirements");
+        let rules: Vec<_> = body.violations.iter().map(|v| v.rule.as_str()).collect();
+        assert_eq!(rules, expected_rules, "failed for {}", password);
+        assert!(body.violations.iter().all(|v| !v.message.is_empty()));
+    }
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_locale() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": get_random_email(),
+            "password": "password123",
+            "requires2FA": false,
+            "locale": "not a locale"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Invalid locale".to_owned()
+    );
+    app.cleanup().await;
+}
+
 #[tokio::test]
 async fn should_return_409_if_email_already_exists() {
     let mut app: TestApp = TestApp::new().await;
@@ -142,3 +191,58 @@ async fn should_return_409_if_email_already_exists() {
     );
     app.cleanup().await;
 }
+
+#[tokio::test]
+async fn should_return_400_if_email_domain_is_blocked() {
+    let mut app = TestApp::new().await;
+
+    for email in ["foo@mailinator.com", "foo@eu.mailinator.com"] {
+        let response = app
+            .post_signup(&serde_json::json!({
+                "email": email,
+                "password": "password123",
+                "requires2FA": false
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 400, "failed for {}", email);
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Email domain is not allowed".to_owned()
+        );
+    }
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_require_2fa_for_the_domains_of_the_policy() {
+    let mut app = TestApp::new().await;
+    let email = format!("{}@secure.example", uuid::Uuid::new_v4());
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    app.cleanup().await;
+}
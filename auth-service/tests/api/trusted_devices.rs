@@ -0,0 +1,160 @@
+use crate::helpers::{app_signup, app_signup_and_login, TestApp};
+use auth_service::{
+    routes::TrustedDeviceResponse,
+    utils::constants::{test, TRUSTED_DEVICE_COOKIE_NAME},
+};
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+/// Completes the 2FA login started by `app_signup_and_login`
+async fn verify_2fa(
+    app: &TestApp,
+    email: &str,
+    login_attempt_id: Option<String>,
+    remember_device: bool,
+) -> reqwest::Response {
+    let code = app.last_email_2fa_code().await;
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id.unwrap(),
+            "2FACode": code,
+            "rememberDevice": remember_device,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+}
+
+/// Replaces the expectation of `app_signup_and_login` on the login email
+async fn expect_emails(app: &TestApp, count: u64) {
+    app.email_server.reset().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(count)
+        .mount(&app.email_server)
+        .await;
+}
+
+#[tokio::test]
+async fn should_skip_2fa_on_a_remembered_device() {
+    let (mut app, email, password, _, login_attempt_id) = app_signup_and_login(true).await;
+    let response = verify_2fa(&app, &email, login_attempt_id, true).await;
+    let cookie = response
+        .cookies()
+        .find(|c| c.name() == TRUSTED_DEVICE_COOKIE_NAME)
+        .expect("trusted device cookie not found in response cookies");
+    assert!(cookie.http_only());
+    expect_emails(&app, 0).await;
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": password }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.get_trusted_devices().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let devices: Vec<TrustedDeviceResponse> = response.json().await.unwrap();
+    assert_eq!(devices.len(), 1);
+    assert!(devices[0].expires_at > devices[0].created_at);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_require_2fa_without_remember_device() {
+    let (mut app, email, password, _, login_attempt_id) = app_signup_and_login(true).await;
+    let response = verify_2fa(&app, &email, login_attempt_id, false).await;
+    assert!(response
+        .cookies()
+        .all(|c| c.name() != TRUSTED_DEVICE_COOKIE_NAME));
+    expect_emails(&app, 1).await;
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": password }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    let devices: Vec<TrustedDeviceResponse> = app.get_trusted_devices().await.json().await.unwrap();
+    assert!(devices.is_empty());
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_require_2fa_once_the_device_is_revoked() {
+    let (mut app, email, password, _, login_attempt_id) = app_signup_and_login(true).await;
+    verify_2fa(&app, &email, login_attempt_id, true).await;
+    expect_emails(&app, 1).await;
+
+    let devices: Vec<TrustedDeviceResponse> = app.get_trusted_devices().await.json().await.unwrap();
+    let id = devices[0].id.to_string();
+    let response = app.delete_trusted_device(&id).await;
+    assert_eq!(response.status().as_u16(), 204);
+    let response = app.delete_trusted_device(&id).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": password }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_require_2fa_once_the_device_expired() {
+    let (mut app, email, password, _, login_attempt_id) = app_signup_and_login(true).await;
+    verify_2fa(&app, &email, login_attempt_id, true).await;
+    expect_emails(&app, 1).await;
+
+    tokio::time::sleep(test::trusted_devices::TTL).await;
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": password }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_not_skip_2fa_for_another_user() {
+    let (mut app, email, _, _, login_attempt_id) = app_signup_and_login(true).await;
+    verify_2fa(&app, &email, login_attempt_id, true).await;
+    expect_emails(&app, 1).await;
+
+    // Same browser, another account with 2FA
+    let other_email = format!("other-{}", email);
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": other_email,
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let response = app
+        .post_login(&serde_json::json!({ "email": other_email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_404_if_unknown_device() {
+    let (mut app, _, _, _, _) = app_signup_and_login(false).await;
+
+    for id in [Uuid::new_v4().to_string(), "invalid".to_owned()] {
+        let response = app.delete_trusted_device(&id).await;
+        assert_eq!(response.status().as_u16(), 404, "failed for id: {}", id);
+    }
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let (mut app, _, _) = app_signup(true).await;
+
+    let response = app.get_trusted_devices().await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.cleanup().await;
+}
@@ -0,0 +1,105 @@
+use crate::helpers::{app_signup_and_login, configure_postgresql_before, delete_database};
+use auth_service::utils::auth::validate_token;
+use auth_service::utils::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn should_identify_the_user_of_a_token_by_id() {
+    let (mut app, email, _, jwt, _) = app_signup_and_login(false).await;
+
+    let claims = validate_token(&jwt.unwrap()).await.unwrap();
+    let user_id: Uuid = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+        .bind(&email)
+        .fetch_one(&app.db_pool().await)
+        .await
+        .unwrap();
+    assert_eq!(claims.sub, user_id.to_string());
+
+    let response = app.get_trusted_devices().await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_accept_the_tokens_issued_with_the_email() {
+    let (mut app, email, _, _, _) = app_signup_and_login(false).await;
+
+    let claims = serde_json::json!({ "sub": email, "exp": usize::MAX });
+    let token = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+    )
+    .unwrap();
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; Path=/", JWT_COOKIE_NAME, token),
+        &reqwest::Url::parse(&app.address).unwrap(),
+    );
+    let response = app.get_trusted_devices().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Unless the user no longer exists
+    let claims = serde_json::json!({ "sub": "unknown@example.com", "exp": usize::MAX });
+    let token = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+    )
+    .unwrap();
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; Path=/", JWT_COOKIE_NAME, token),
+        &reqwest::Url::parse(&app.address).unwrap(),
+    );
+    let response = app.get_trusted_devices().await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_backfill_the_ids_of_existing_users() {
+    let (pool, db_name) = configure_postgresql_before(20250817090000).await;
+    for email in ["alice@example.com", "bob@example.com"] {
+        sqlx::query("INSERT INTO users (email, password_hash) VALUES ($1, 'hash')")
+            .bind(email)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    sqlx::query(
+        "INSERT INTO password_history (email, password_hash) VALUES ('alice@example.com', 'old')",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    sqlx::migrate!().run(&pool).await.unwrap();
+
+    let ids: Vec<Uuid> = sqlx::query_scalar("SELECT DISTINCT id FROM users")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(ids.len(), 2);
+
+    // The email stays unique, and the password history follows the user across email changes
+    let result = sqlx::query(
+        "INSERT INTO users (email, password_hash) VALUES ('alice@example.com', 'hash')",
+    )
+    .execute(&pool)
+    .await;
+    assert!(result.is_err());
+    sqlx::query("UPDATE users SET email = 'alice@example.org' WHERE email = 'alice@example.com'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let history_email: String = sqlx::query_scalar(
+        "SELECT users.email FROM password_history JOIN users ON users.id = password_history.user_id",
+    )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(history_email, "alice@example.org");
+
+    pool.close().await;
+    delete_database(&db_name).await;
+}
@@ -1,31 +1,24 @@
 use crate::helpers::app_signup_and_login;
-use auth_service::{utils::constants::JWT_COOKIE_NAME, Email, LoginAttemptId, TwoFACode};
+use auth_service::{
+    utils::constants::{prod, JWT_COOKIE_NAME},
+    LoginAttemptId, TwoFACode,
+};
 use reqwest::{cookie::CookieStore, Url};
 
 #[tokio::test]
 async fn should_return_200_if_valid_credentials_and_2fa_enabled() {
     let (mut app, email, _, _, login_attempt_id_from_response) = app_signup_and_login(true).await;
-    let (two_fa_code, login_attempt_id) = app
-        .two_fa_code_store
-        .read()
-        .await
-        .get_code(&Email::parse(&email).unwrap())
-        .await
-        .unwrap();
     assert!(
         login_attempt_id_from_response.is_some(),
         "login attempt id is None"
     );
-    assert_eq!(
-        login_attempt_id_from_response.unwrap(),
-        login_attempt_id.as_ref(),
-        "login attempt id from response does not match the one in the store"
-    );
+    let login_attempt_id = LoginAttemptId::parse(&login_attempt_id_from_response.unwrap()).unwrap();
+    let two_fa_code = app.last_email_2fa_code().await;
 
     let body = serde_json::json!({
         "email": email,
         "loginAttemptId": login_attempt_id.as_ref(),
-        "2FACode": two_fa_code.as_ref(),
+        "2FACode": two_fa_code,
     });
     println!("body: {:?}", body);
 
@@ -41,7 +34,7 @@ async fn should_return_200_if_valid_credentials_and_2fa_enabled() {
         .cookies()
         .find(|c| c.name() == JWT_COOKIE_NAME)
         .expect("auth_cookie not found in response cookies");
-    assert!(auth_cookie.value().len() > 0, "auth_cookie is empty");
+    assert!(!auth_cookie.value().is_empty(), "auth_cookie is empty");
 
     // Check if the cookie jar is updated with the auth cookie
     let jar_state = (*app.cookie_jar).cookies(&Url::parse(&app.address).unwrap());
@@ -147,27 +140,17 @@ async fn return_401_if_invalid_credentials() {
 async fn return_401_if_same_code_twice() {
     let (mut app, email, _, _, login_attempt_id_from_response) = app_signup_and_login(true).await;
 
-    let (two_fa_code, login_attempt_id) = app
-        .two_fa_code_store
-        .read()
-        .await
-        .get_code(&Email::parse(&email).unwrap())
-        .await
-        .unwrap();
     assert!(
         login_attempt_id_from_response.is_some(),
         "login attempt id is None"
     );
-    assert_eq!(
-        login_attempt_id_from_response.unwrap(),
-        login_attempt_id.as_ref(),
-        "login attempt id from response does not match the one in the store"
-    );
+    let login_attempt_id = LoginAttemptId::parse(&login_attempt_id_from_response.unwrap()).unwrap();
+    let two_fa_code = app.last_email_2fa_code().await;
 
     let body = serde_json::json!({
         "email": email,
         "loginAttemptId": login_attempt_id.as_ref(),
-        "2FACode": two_fa_code.as_ref(),
+        "2FACode": two_fa_code,
     });
     println!("body: {:?}", body);
 
@@ -189,3 +172,36 @@ async fn return_401_if_same_code_twice() {
     );
     app.cleanup().await;
 }
+
+#[tokio::test]
+async fn return_401_once_too_many_wrong_codes_were_given() {
+    let (mut app, email, _, _, login_attempt_id) = app_signup_and_login(true).await;
+    let login_attempt_id = login_attempt_id.expect("login attempt id is None");
+    let two_fa_code = app.last_email_2fa_code().await;
+    let wrong_code = format!(
+        "{:06}",
+        (two_fa_code.parse::<u32>().unwrap() + 1) % 1_000_000
+    );
+
+    for _ in 0..prod::two_fa::MAX_ATTEMPTS {
+        let response = app
+            .post_verify_2fa(&serde_json::json!({
+                "email": email,
+                "loginAttemptId": login_attempt_id,
+                "2FACode": wrong_code,
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // The login attempt was dropped
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": two_fa_code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.cleanup().await;
+}
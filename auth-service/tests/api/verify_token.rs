@@ -1,6 +1,8 @@
 use crate::helpers::app_signup_and_login;
-use auth_service::utils::auth::generate_auth_cookie;
-use auth_service::Email;
+use auth_service::utils::auth::{generate_auth_cookie, TOKEN_TTL_SECONDS};
+use auth_service::utils::constants::REDIS_HOST_NAME;
+use auth_service::{get_redis_client, UserId};
+use redis::Commands;
 
 #[tokio::test]
 async fn should_return_200_if_jwt_is_valid() {
@@ -45,7 +47,7 @@ async fn should_return_422_if_jwt_cookie_missing() {
 #[tokio::test]
 async fn should_return_401_if_invalid_token() {
     // Signup but do not login
-    let (mut app, email, _, jwt, _) = app_signup_and_login(false).await;
+    let (mut app, _, _, jwt, _) = app_signup_and_login(false).await;
 
     // Ban the jwt token
     let response = app.post_logout().await;
@@ -61,7 +63,7 @@ async fn should_return_401_if_invalid_token() {
             "token": "invalid_token",
         }),
         serde_json::json!({
-            "token": generate_auth_cookie(&Email::parse(&email).unwrap()).unwrap().to_string(),
+            "token": generate_auth_cookie(&UserId::new(), 0).unwrap().to_string(),
         }),
         // jwt that was banned
         serde_json::json!({
@@ -80,3 +82,27 @@ async fn should_return_401_if_invalid_token() {
     }
     app.cleanup().await;
 }
+
+#[tokio::test]
+async fn should_return_401_if_jwt_was_banned_before_tokens_were_hashed() {
+    let (mut app, _, _, jwt, _) = app_signup_and_login(false).await;
+    let jwt = jwt.unwrap();
+    // Tokens used to be banned under the JWT itself
+    let mut conn = get_redis_client(REDIS_HOST_NAME.to_owned())
+        .unwrap()
+        .get_connection()
+        .unwrap();
+    let _: () = conn
+        .set_ex(
+            format!("banned_token:{}", jwt),
+            true,
+            TOKEN_TTL_SECONDS as u64,
+        )
+        .unwrap();
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": jwt }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.cleanup().await;
+}
@@ -0,0 +1,364 @@
+use std::time::Duration;
+
+use crate::helpers::{get_random_email, TestApp};
+use argon2::{
+    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher, Version,
+};
+use auth_service::app_state::password_hash_params;
+use auth_service::routes::CreateWebhookResponse;
+use auth_service::webhook::{
+    sign_webhook_payload, DeliveryStatus, WebhookDelivery, WebhookEvent, WebhookEventType,
+    WebhookSubscription, WEBHOOK_ID_HEADER, WEBHOOK_SIGNATURE_HEADER, WEBHOOK_TIMESTAMP_HEADER,
+};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+async fn subscribe(
+    app: &TestApp,
+    server: &MockServer,
+    event_types: &[&str],
+) -> CreateWebhookResponse {
+    let response = app
+        .post_webhook(&serde_json::json!({
+            "url": format!("{}/hook", server.uri()),
+            "eventTypes": event_types,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    response.json().await.unwrap()
+}
+
+async fn signup(app: &TestApp, email: &str) {
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+/// Polls the delivery log until every delivery satisfies `done`
+async fn wait_for_deliveries(
+    app: &TestApp,
+    subscription: &WebhookSubscription,
+    count: usize,
+    done: impl Fn(&WebhookDelivery) -> bool,
+) -> Vec<WebhookDelivery> {
+    for _ in 0..100 {
+        let deliveries: Vec<WebhookDelivery> = app
+            .get_webhook_deliveries(&subscription.id.to_string())
+            .await
+            .json()
+            .await
+            .unwrap();
+        if deliveries.len() == count && deliveries.iter().all(&done) {
+            return deliveries;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("Deliveries did not complete in time");
+}
+
+#[tokio::test]
+async fn should_deliver_signed_event_on_signup() {
+    let mut app = TestApp::new().await;
+    let server = MockServer::start().await;
+    Mock::given(path("/hook"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let created = subscribe(&app, &server, &["user.created"]).await;
+    assert_eq!(created.secret.len(), 64);
+
+    let email = get_random_email();
+    signup(&app, &email).await;
+
+    let deliveries = wait_for_deliveries(&app, &created.subscription, 1, |d| {
+        d.status == DeliveryStatus::Delivered
+    })
+    .await;
+    assert_eq!(deliveries[0].attempts, 1);
+    assert_eq!(deliveries[0].last_response_status, Some(200));
+
+    let request = &server.received_requests().await.unwrap()[0];
+    let header = |name: &str| request.headers.get(name).unwrap().to_str().unwrap();
+    let body = std::str::from_utf8(&request.body).unwrap();
+    let event: WebhookEvent = serde_json::from_str(body).unwrap();
+    assert_eq!(event.event_type, WebhookEventType::UserCreated);
+    let user_id: uuid::Uuid = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+        .bind(&email)
+        .fetch_one(&app.db_pool().await)
+        .await
+        .unwrap();
+    assert_eq!(
+        event.data,
+        serde_json::json!({ "user_id": user_id, "email": email })
+    );
+    assert_eq!(header(WEBHOOK_ID_HEADER), event.id.to_string());
+    assert_eq!(
+        header(WEBHOOK_SIGNATURE_HEADER),
+        sign_webhook_payload(
+            &created.secret,
+            &event.id,
+            header(WEBHOOK_TIMESTAMP_HEADER).parse().unwrap(),
+            body
+        )
+    );
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_queue_events_with_the_account_change() {
+    let mut app = TestApp::new().await;
+    let server = MockServer::start().await;
+    Mock::given(path("/hook"))
+        .respond_with(ResponseTemplate::new(204))
+        .mount(&server)
+        .await;
+    let created = subscribe(&app, &server, &["user.password_changed", "user.deleted"]).await;
+
+    let email = get_random_email();
+    signup(&app, &email).await;
+
+    let pool = app.db_pool().await;
+    // A rolled back change emits no event
+    let mut transaction = pool.begin().await.unwrap();
+    sqlx::query("UPDATE users SET password_hash = 'rolled back' WHERE email = $1")
+        .bind(&email)
+        .execute(&mut *transaction)
+        .await
+        .unwrap();
+    transaction.rollback().await.unwrap();
+
+    sqlx::query("UPDATE users SET password_hash = 'changed' WHERE email = $1")
+        .bind(&email)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let user_id: uuid::Uuid = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+        .bind(&email)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    pool.close().await;
+    let response = app.delete_user(&user_id.to_string()).await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let deliveries = wait_for_deliveries(&app, &created.subscription, 2, |d| {
+        d.status == DeliveryStatus::Delivered
+    })
+    .await;
+    let event_types: Vec<WebhookEventType> =
+        deliveries.iter().map(|d| d.event.event_type).collect();
+    assert_eq!(
+        event_types,
+        vec![
+            WebhookEventType::PasswordChanged,
+            WebhookEventType::UserDeleted
+        ]
+    );
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_give_up_after_max_attempts() {
+    let mut app = TestApp::new().await;
+    let server = MockServer::start().await;
+    Mock::given(path("/hook"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(3)
+        .mount(&server)
+        .await;
+    let created = subscribe(&app, &server, &["user.created"]).await;
+
+    signup(&app, &get_random_email()).await;
+
+    let deliveries = wait_for_deliveries(&app, &created.subscription, 1, |d| {
+        d.status == DeliveryStatus::Failed
+    })
+    .await;
+    assert_eq!(deliveries[0].attempts, 3);
+    assert_eq!(deliveries[0].last_response_status, Some(500));
+    assert!(deliveries[0].last_error.is_some());
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_stop_delivering_after_unsubscribe() {
+    let mut app = TestApp::new().await;
+    let server = MockServer::start().await;
+    Mock::given(path("/hook"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&server)
+        .await;
+    let created = subscribe(&app, &server, &["user.created"]).await;
+
+    let response = app
+        .delete_webhook(&created.subscription.id.to_string())
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let subscriptions: Vec<WebhookSubscription> = app.get_webhooks().await.json().await.unwrap();
+    assert_eq!(subscriptions.len(), 1);
+    assert!(!subscriptions[0].active);
+    // The secret is only disclosed on creation
+    assert!(subscriptions[0].secret.is_empty());
+
+    signup(&app, &get_random_email()).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let deliveries: Vec<WebhookDelivery> = app
+        .get_webhook_deliveries(&created.subscription.id.to_string())
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert!(deliveries.is_empty());
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let mut app = TestApp::new().await;
+
+    let test_cases = [
+        serde_json::json!({ "url": "not a url", "eventTypes": ["user.created"] }),
+        serde_json::json!({ "url": "ftp://example.com/hook", "eventTypes": ["user.created"] }),
+        serde_json::json!({ "url": "https://example.com/hook", "eventTypes": [] }),
+    ];
+    for test_case in test_cases.iter() {
+        let response = app.post_webhook(test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_unknown_event_type() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_webhook(&serde_json::json!({
+            "url": "https://example.com/hook",
+            "eventTypes": ["user.unknown"],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_404_if_unknown_subscription() {
+    let mut app = TestApp::new().await;
+    let id = uuid::Uuid::new_v4().to_string();
+
+    assert_eq!(app.delete_webhook(&id).await.status().as_u16(), 404);
+    assert_eq!(app.get_webhook_deliveries(&id).await.status().as_u16(), 404);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_admin_token() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .get(format!("{}/admin/webhooks", &app.address))
+        .bearer_auth("wrong_token")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_deliver_password_changed_only_for_a_new_password() {
+    let mut app = TestApp::new().await;
+    let server = MockServer::start().await;
+    Mock::given(path("/hook"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let created = subscribe(&app, &server, &["user.password_changed"]).await;
+    let email = get_random_email();
+    signup(&app, &email).await;
+
+    // A hash with outdated costs is upgraded in the background of the login
+    let pool = app.db_pool().await;
+    let weak_hash = Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(8, 1, 1, None).unwrap(),
+    )
+    .hash_password(
+        b"password123",
+        &SaltString::generate(&mut rand::thread_rng()),
+    )
+    .unwrap()
+    .to_string();
+    // Like a hash left by older costs, not a password change
+    let mut transaction = pool.begin().await.unwrap();
+    sqlx::query("SELECT set_config('auth.password_rehash', 'on', true)")
+        .execute(&mut *transaction)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE users SET password_hash = $2 WHERE email = $1")
+        .bind(&email)
+        .bind(&weak_hash)
+        .execute(&mut *transaction)
+        .await
+        .unwrap();
+    transaction.commit().await.unwrap();
+    let login_body = serde_json::json!({ "email": email, "password": "password123" });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let mut rehashed = None;
+    for _ in 0..100 {
+        let hash: String = sqlx::query_scalar("SELECT password_hash FROM users WHERE email = $1")
+            .bind(&email)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        if hash != weak_hash {
+            rehashed = Some(hash);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let rehashed = rehashed.expect("The password was not rehashed in time");
+    let params = Params::try_from(&PasswordHash::new(&rehashed).unwrap()).unwrap();
+    assert_eq!(params.m_cost(), password_hash_params().m_cost());
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Only the actual change is delivered
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "newpassword456",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let deliveries = wait_for_deliveries(&app, &created.subscription, 1, |d| {
+        d.status == DeliveryStatus::Delivered
+    })
+    .await;
+    assert_eq!(
+        deliveries[0].event.event_type,
+        WebhookEventType::PasswordChanged
+    );
+    app.cleanup().await;
+}